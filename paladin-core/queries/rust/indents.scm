@@ -0,0 +1,26 @@
+; Nodes whose contents are indented a level deeper than their first line.
+[
+  (use_list)
+  (block)
+  (match_block)
+  (arguments)
+  (parameters)
+  (declaration_list)
+  (field_declaration_list)
+  (field_initializer_list)
+  (enum_variant_list)
+  (struct_pattern)
+  (tuple_pattern)
+  (tuple_expression)
+  (array_expression)
+  (where_clause)
+  (token_tree)
+  (macro_definition)
+] @indent
+
+; Closing delimiters line up with the line that opened them.
+[
+  "}"
+  "]"
+  ")"
+] @outdent
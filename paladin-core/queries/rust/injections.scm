@@ -0,0 +1,15 @@
+((macro_invocation
+  (token_tree) @injection.content)
+ (#set! injection.language "rust")
+ (#set! injection.include-children))
+
+((macro_rule
+  (token_tree) @injection.content)
+ (#set! injection.language "rust")
+ (#set! injection.include-children))
+
+; Doc comments are markdown
+((line_comment) @injection.content
+ (#match? @injection.content "^//(/[^/]|!)")
+ (#set! injection.language "markdown_inline")
+ (#set! injection.include-children))
@@ -1,22 +1,40 @@
-use std::{fmt::Display, path::PathBuf};
+use std::{
+    fmt::Display,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
+use bevy_reflect::Reflect;
 use crop::RopeSlice;
 
 use miette::IntoDiagnostic;
 use strum::EnumString;
 use tree_sitter::Tree;
 
+pub mod block;
 pub mod buffer;
+pub mod caret;
+pub mod fold;
+mod history;
+pub mod word;
 
 use crate::{
-    lsp::{LspRequest, LspRequestData, LspResponseTransmitter},
+    lsp::{LspConfig, LspRequest, LspRequestData, LspResponseTransmitter},
     ts::{
         self,
         highlight::{self, LineHighlights},
+        injection::{self, Layer},
+        Language, Queries, QueryKind, Theme,
     },
 };
 
+pub use self::block::BlockSelection;
 pub use self::buffer::SimpleBuffer;
+pub use self::caret::{Caret, CaretShape, CaretStyle};
+pub use self::fold::{Fold, Folds};
+pub use self::history::Coalescing;
+pub use self::word::WordHighlights;
 
 // #[derive(Debug)]
 // pub struct Editor {
@@ -30,50 +48,87 @@ pub use self::buffer::SimpleBuffer;
 #[derive(Debug)]
 pub struct Buffer {
     lsp: Option<lsp::Lsp>,
+    language: Language,
     tree: Option<Tree>,
+    layers: Vec<Layer>,
+    queries: Queries,
+    folds: Folds,
+    /// What the characters of positions count, the language server's once it picked, see [lsp::Lsp::position_encoding].
+    position_encoding: Arc<Mutex<lsp_types::PositionEncodingKind>>,
+    /// The language server's latest diagnostics, ordered by where they start.
+    diagnostics: Vec<lsp_types::Diagnostic>,
     pub buffer: SimpleBuffer,
 }
 
 impl Buffer {
     fn new(buffer: SimpleBuffer, lsp: Option<lsp::Lsp>) -> Self {
-        let tree = ts::tree(&buffer.rope, None);
+        let first_line = buffer.line(0).to_string();
+        let language = Language::detect(buffer.path().unwrap_or(Path::new("")), &first_line);
+        let tree = ts::tree(&buffer.rope, language, None);
 
-        Self {
+        let mut this = Self {
             lsp,
-            tree: Some(tree),
+            language,
+            tree,
+            layers: vec![],
+            queries: Queries::default(),
+            folds: Folds::default(),
+            position_encoding: lsp.as_ref().map_or_else(
+                || Arc::new(Mutex::new(lsp_types::PositionEncodingKind::UTF16)),
+                lsp::Lsp::position_encoding,
+            ),
+            diagnostics: vec![],
             buffer,
-        }
+        };
+
+        this.refresh_layers();
+
+        this
+    }
+
+    /// Open `buffer` without a language server, like a scratch buffer or text that isn't on disk.
+    pub fn scratch(buffer: SimpleBuffer) -> Self {
+        Self::new(buffer, None)
     }
 
+    /// Open `buffer`, starting the language server in `lsp` if it serves the buffer's language.
+    /// Scratch buffers don't start it, the server only knows files.
     pub fn create(
         buffer: SimpleBuffer,
         workspace: PathBuf,
+        lsp: Option<LspConfig>,
         receiver: impl LspResponseTransmitter,
     ) -> crate::Result<Self> {
         let workspace = workspace.canonicalize().into_diagnostic()?;
 
-        let lsp = if buffer
-            .path()
-            .to_str()
-            .map(|it| it.contains(".rs"))
-            .unwrap_or(false)
-        {
-            Some(lsp::Lsp::new(
-                workspace,
-                buffer.path().to_owned(),
-                receiver,
-            )?)
-        } else {
-            None
-        };
+        let mut this = Self::new(buffer, None);
+
+        let config = lsp.filter(|it| it.language_id == this.language.lsp_id());
+
+        if let (Some(config), Some(path)) = (config, this.buffer.path()) {
+            let lsp = lsp::Lsp::new(&config, workspace, path.to_owned(), receiver)?;
+
+            this.position_encoding = lsp.position_encoding();
+            this.lsp = Some(lsp);
+        }
 
-        Ok(Self::new(buffer, lsp))
+        Ok(this)
     }
 
     pub fn text(&self) -> String {
         self.buffer.text()
     }
 
+    /// See [SimpleBuffer::lines_in_range].
+    pub fn lines_in_range(&self, lines: Range<usize>) -> impl Iterator<Item = RopeSlice<'_>> {
+        self.buffer.lines_in_range(lines)
+    }
+
+    /// See [SimpleBuffer::chunks].
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.buffer.chunks()
+    }
+
     pub fn line_len(&self) -> usize {
         self.buffer.line_len()
     }
@@ -86,6 +141,242 @@ impl Buffer {
         self.buffer.cursor()
     }
 
+    pub fn save(&self) -> crate::Result<()> {
+        self.buffer.save()
+    }
+
+    /// Save to `path` from now on, see [SimpleBuffer::save_as].
+    /// A scratch buffer gets the language its new path suggests.
+    pub fn save_as(&mut self, path: PathBuf) -> crate::Result<()> {
+        let scratch = self.buffer.path().is_none();
+
+        self.buffer.save_as(path)?;
+
+        if scratch {
+            let first_line = self.buffer.line(0).to_string();
+            let language = Language::detect(self.buffer.path().unwrap(), &first_line);
+
+            self.set_language(language);
+        }
+
+        Ok(())
+    }
+
+    /// Ranges of lines that can be folded, like function bodies and imports.
+    pub fn foldable_ranges(&self) -> Vec<Fold> {
+        self.tree.as_ref().map(fold::foldable).unwrap_or_default()
+    }
+
+    pub fn folds(&self) -> &Folds {
+        &self.folds
+    }
+
+    /// Expand the fold starting at `line`, or collapse the innermost one around it.
+    pub fn toggle_fold(&mut self, line: usize) {
+        let foldable = self.foldable_ranges();
+
+        self.folds.toggle(line, &foldable);
+    }
+
+    pub fn language(&self) -> Language {
+        self.language
+    }
+
+    /// The buffer language's `kind` query, like the indents for auto-indent. `None` if it has none.
+    pub fn query(&mut self, kind: QueryKind) -> Option<&tree_sitter::Query> {
+        self.queries.load(self.language, kind)
+    }
+
+    /// Override the detected language, re-parsing the buffer.
+    /// The language server only has the document while it serves the language,
+    /// so switching away closes it there and switching back opens it again.
+    pub fn set_language(&mut self, language: Language) {
+        if self.language == language {
+            return;
+        }
+
+        if self.lsp_serves_language() {
+            self.lsp_event(LspRequestData::DidClose);
+            self.diagnostics.clear();
+        }
+
+        self.language = language;
+        self.tree = ts::tree(&self.buffer.rope, language, None);
+        self.refresh_layers();
+
+        self.lsp_event(LspRequestData::DidOpen {
+            language_id: language.lsp_id().into(),
+            text: self.buffer.text(),
+        });
+    }
+
+    /// Use the position encoding the language server picked, for positions it sends and those sent to it, UTF-16 until set.
+    /// Buffers with a language server set it when the server is initialized.
+    pub fn set_position_encoding(&mut self, encoding: lsp_types::PositionEncodingKind) {
+        *self.position_encoding.lock().unwrap() = encoding;
+    }
+
+    /// Apply the `TextEdit`s of a rename, code action or formatting as one undo step.
+    ///
+    /// Their ranges are all in the text before any of them is applied, as LSP specifies,
+    /// and inserts at the same position keep their order. Overlapping edits are an error, and nothing is applied.
+    pub fn apply_text_edits(&mut self, edits: &[lsp_types::TextEdit]) -> crate::Result<()> {
+        let mut replacements: Vec<_> = edits
+            .iter()
+            .map(|edit| {
+                let start = self
+                    .buffer
+                    .position_to_byte(edit.range.start, &self.position_encoding.lock().unwrap());
+                let end = self
+                    .buffer
+                    .position_to_byte(edit.range.end, &self.position_encoding.lock().unwrap());
+
+                (start..end.max(start), edit)
+            })
+            .collect();
+
+        // Sorting is stable, so inserts at one position stay in order until reversed
+        replacements.sort_by_key(|(range, _)| range.start);
+
+        if let Some([(first, _), (second, _)]) = replacements
+            .windows(2)
+            .find(|pair| pair[1].0.start < pair[0].0.end)
+        {
+            return Err(miette::miette!(
+                "text edits at bytes {first:?} and {second:?} overlap"
+            ));
+        }
+
+        // From the last edit to the first, so the ranges of those before stay valid
+        replacements.reverse();
+
+        let lsp_edits = replacements
+            .iter()
+            .map(|(_, edit)| crate::lsp::LspEdit {
+                range: edit.range,
+                text: edit.new_text.clone(),
+            })
+            .collect();
+
+        let edits = self.buffer.replace_ranges(
+            replacements
+                .into_iter()
+                .map(|(range, edit)| (range, edit.new_text.clone()))
+                .collect(),
+        );
+
+        if let Some(tree) = &mut self.tree {
+            for (edit, _) in &edits {
+                tree.edit(&edit.to_ts());
+            }
+        }
+        self.reparse();
+
+        if !edits.is_empty() {
+            self.lsp_event(LspRequestData::DidChange { edits: lsp_edits });
+        }
+
+        Ok(())
+    }
+
+    /// Replace the buffer's diagnostics with those the language server published.
+    pub fn set_diagnostics(&mut self, mut diagnostics: Vec<lsp_types::Diagnostic>) {
+        diagnostics.sort_by_key(|it| it.range.start);
+
+        self.diagnostics = diagnostics;
+    }
+
+    pub fn diagnostics(&self) -> &[lsp_types::Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Diagnostics starting on `line`.
+    pub fn line_diagnostics(
+        &self,
+        line: usize,
+    ) -> impl Iterator<Item = &lsp_types::Diagnostic> + '_ {
+        self.diagnostics
+            .iter()
+            .filter(move |it| it.range.start.line as usize == line)
+    }
+
+    /// The worst severity of the diagnostics starting on `line`, if there are any.
+    /// Diagnostics without one count as errors.
+    pub fn line_severity(&self, line: usize) -> Option<lsp_types::DiagnosticSeverity> {
+        // The most severe has the lowest value
+        self.line_diagnostics(line)
+            .map(|it| it.severity.unwrap_or(lsp_types::DiagnosticSeverity::ERROR))
+            .min()
+    }
+
+    /// Move the cursor to an LSP `position`, like where a diagnostic starts.
+    pub fn goto_position(&mut self, position: lsp_types::Position) {
+        let byte = self
+            .buffer
+            .position_to_byte(position, &self.position_encoding.lock().unwrap());
+
+        self.buffer.cursor = self.buffer.cursor_at_byte(byte).into();
+    }
+
+    /// Move the cursor to the start of the next diagnostic after it, or the previous one before it,
+    /// wrapping around at the end of the buffer.
+    pub(super) fn goto_diagnostic(&mut self, forward: bool) {
+        let cursor = self.buffer.global_cursor_to_byte();
+        let starts: Vec<_> = self
+            .diagnostics
+            .iter()
+            .map(|it| {
+                self.buffer
+                    .position_to_byte(it.range.start, &self.position_encoding.lock().unwrap())
+            })
+            .collect();
+
+        let target = if forward {
+            starts.iter().find(|it| **it > cursor).or(starts.first())
+        } else {
+            starts
+                .iter()
+                .rev()
+                .find(|it| **it < cursor)
+                .or(starts.last())
+        };
+
+        if let Some(byte) = target {
+            self.buffer.cursor = self.buffer.cursor_at_byte(*byte).into();
+        }
+    }
+
+    /// Ask the language server for the buffer's diagnostics, for servers that don't push them.
+    /// They arrive like pushed ones, only when they changed.
+    pub fn pull_diagnostics(&self) {
+        self.lsp_event(LspRequestData::Diagnostics);
+    }
+
+    /// Revert the last edit, or the last group of edits made by one action.
+    pub fn undo(&mut self) {
+        for (edit, text) in self.buffer.undo() {
+            self.tree_refresh(edit);
+            self.lsp_for_edit(edit, text);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        for (edit, text) in self.buffer.redo() {
+            self.tree_refresh(edit);
+            self.lsp_for_edit(edit, text);
+        }
+    }
+
+    /// Delete the selected text, see [SimpleBuffer::set_anchor].
+    pub fn delete_selection(&mut self) {
+        if let Some(edit) = self.buffer.delete_selection() {
+            self.tree_refresh(edit);
+            self.lsp_for_edit(edit, String::new());
+        }
+    }
+
+    /// Delete the character before the cursor, or the line break before the line at its start.
+    /// Recorded for undo like [Buffer::insert], typed deletes joining one step.
     pub(super) fn back(&mut self) -> Option<Edit> {
         let edit = self.buffer.back()?;
 
@@ -96,43 +387,34 @@ impl Buffer {
     }
 
     fn lsp_for_edit(&mut self, edit: Edit, text: String) {
-        match edit {
+        let encoding = self.position_encoding.lock().unwrap().clone();
+
+        let (range, text) = match edit {
             Edit::Insert { start, .. } => {
-                let range = lsp_types::Range {
-                    start: lsp_types::Position {
-                        line: start.line as u32,
-                        character: start.character as u32,
-                    },
-                    end: lsp_types::Position {
-                        line: start.line as u32,
-                        character: start.character as u32,
-                    },
-                };
+                let start = start.to_position(&encoding);
 
-                self.lsp_event(LspRequestData::DidChange {
-                    edit: crate::lsp::LspEdit { range, text },
-                });
+                (lsp_types::Range { start, end: start }, text)
             }
             Edit::Delete { from, to, .. } => {
                 let range = lsp_types::Range {
-                    start: lsp_types::Position {
-                        line: from.line as u32,
-                        character: from.character as u32,
-                    },
-                    end: lsp_types::Position {
-                        line: to.line as u32,
-                        character: to.character as u32,
-                    },
+                    start: from.to_position(&encoding),
+                    end: to.to_position(&encoding),
                 };
 
-                self.lsp_event(LspRequestData::DidChange {
-                    edit: crate::lsp::LspEdit {
-                        range,
-                        text: String::new(),
-                    },
-                });
+                (range, String::new())
             }
-        }
+        };
+
+        self.lsp_event(LspRequestData::DidChange {
+            edits: vec![crate::lsp::LspEdit { range, text }],
+        });
+    }
+
+    /// The cursor as an LSP position, in the encoding the language server picked.
+    fn cursor_position(&self) -> lsp_types::Position {
+        self.buffer
+            .with_character(self.buffer.cursor)
+            .to_position(&self.position_encoding.lock().unwrap())
     }
 
     pub(super) fn cursor_up(&mut self) {
@@ -143,6 +425,30 @@ impl Buffer {
         self.buffer.cursor_right()
     }
 
+    pub(super) fn goto_line(&mut self, line: usize) {
+        self.buffer.goto_line(line)
+    }
+
+    /// Replace the block on each of its lines with `text`. An empty `text` deletes the block.
+    pub fn insert_block(&mut self, block: &BlockSelection, text: &str) {
+        for (edit, text) in self.buffer.insert_block(block, text) {
+            self.tree_refresh(edit);
+            self.lsp_for_edit(edit, text);
+        }
+    }
+
+    /// Toggle line comments on `lines`. Does nothing for languages without line comments.
+    pub fn toggle_comment(&mut self, lines: std::ops::Range<usize>) {
+        let Some(prefix) = self.language.line_comment() else {
+            return;
+        };
+
+        for (edit, text) in self.buffer.toggle_comment(lines, prefix) {
+            self.tree_refresh(edit);
+            self.lsp_for_edit(edit, text);
+        }
+    }
+
     pub(super) fn cursor_down(&mut self) {
         self.buffer.cursor_down()
     }
@@ -163,23 +469,56 @@ impl Buffer {
         edit
     }
 
-    pub(super) fn line_current_char_idx(&self) -> usize {
-        self.buffer.line_current_char_idx()
-    }
-
     fn tree_refresh(&mut self, edit: Edit) {
         let Some(tree) = &mut self.tree else {
             return;
         };
 
         tree.edit(&edit.to_ts());
-        *tree = ts::tree(&self.buffer.rope, Some(tree));
+        self.reparse();
+    }
+
+    /// Parse again after the tree was told about edits.
+    fn reparse(&mut self) {
+        let Some(tree) = &self.tree else {
+            return;
+        };
+
+        self.tree = ts::tree(&self.buffer.rope, self.language, Some(tree));
+        self.refresh_layers();
+
+        let foldable = self.foldable_ranges();
+        self.folds.retain(&foldable);
+    }
+
+    // Injections are parsed from scratch, they are usually small.
+    fn refresh_layers(&mut self) {
+        self.layers = match &self.tree {
+            Some(tree) => {
+                injection::layers(tree, self.language, &self.buffer.rope, &mut self.queries)
+            }
+            None => vec![],
+        };
+    }
+
+    /// Whether the language server has the document, it only gets buffers in the language it serves.
+    fn lsp_serves_language(&self) -> bool {
+        self.lsp
+            .as_ref()
+            .is_some_and(|lsp| lsp.serves(self.language))
     }
 
     fn lsp_event(&self, event: LspRequestData) {
-        let Some(lsp) = &self.lsp else { return };
+        let (Some(lsp), Some(file)) = (&self.lsp, &self.buffer.path) else {
+            return;
+        };
+
+        if !lsp.serves(self.language) {
+            return;
+        }
+
         lsp.send(LspRequest {
-            file: self.buffer.path.clone(),
+            file: file.clone(),
             data: event,
         });
     }
@@ -188,19 +527,30 @@ impl Buffer {
         &'sel self,
         cursor: &'query mut tree_sitter::QueryCursor,
         query: &'query tree_sitter::Query,
+        theme: &'query Theme,
         range: std::ops::Range<usize>,
-    ) -> LineHighlights<'query, 'tree, 'sel>
+    ) -> Option<LineHighlights<'query, 'tree, 'sel>>
     where
         'tree: 'query,
         'sel: 'tree,
     {
-        highlight::syntax_highlight(
-            self.tree.as_ref().unwrap(),
+        let injected = injection::line_spans(
+            &self.layers,
+            &self.queries,
+            theme,
+            &self.buffer.rope,
+            range.clone(),
+        );
+
+        Some(highlight::syntax_highlight(
+            self.tree.as_ref()?,
             cursor,
             query,
+            theme,
             &self.buffer.rope,
             range,
-        )
+            injected,
+        ))
     }
 }
 
@@ -210,20 +560,40 @@ pub fn action(buffer: &mut Buffer, action: Action) {
         Action::Down => buffer.cursor_down(),
         Action::Left => buffer.cursor_left(),
         Action::Right => buffer.cursor_right(),
+        Action::GotoLine(line) => buffer.goto_line(line),
+        Action::ToggleComment => {
+            let line = buffer.cursor().line;
+
+            buffer.toggle_comment(line..line + 1);
+        }
+        Action::ToggleFold => buffer.toggle_fold(buffer.cursor().line),
+        Action::NextDiagnostic => buffer.goto_diagnostic(true),
+        Action::PrevDiagnostic => buffer.goto_diagnostic(false),
         // Action::InsertMode => self.mode = Mode::Insert,
         // Action::NormalMode => self.mode = Mode::Normal,
         Action::Hover => {
+            let position = buffer.cursor_position();
             let event = LspRequestData::Hover {
-                line: buffer.cursor().line as u32,
-                character: buffer.line_current_char_idx() as u32,
+                line: position.line,
+                character: position.character,
             };
 
             buffer.lsp_event(event)
         }
         Action::Complete => {
+            let position = buffer.cursor_position();
             let event = LspRequestData::Completion {
-                line: buffer.cursor().line as u32,
-                character: buffer.line_current_char_idx() as u32,
+                line: position.line,
+                character: position.character,
+            };
+
+            buffer.lsp_event(event)
+        }
+        Action::GotoDefinition => {
+            let position = buffer.cursor_position();
+            let event = LspRequestData::GotoDefinition {
+                line: position.line,
+                character: position.character,
             };
 
             buffer.lsp_event(event)
@@ -231,14 +601,21 @@ pub fn action(buffer: &mut Buffer, action: Action) {
         Action::Back => {
             buffer.back();
         }
+        Action::Undo => buffer.undo(),
+        Action::Redo => buffer.redo(),
         Action::NewLine => {
             buffer.insert("\n");
         }
+        Action::Save => {
+            if let Err(err) = buffer.save() {
+                dbg!("WARN: ", err);
+            }
+        }
         _ => todo!(),
     }
 }
 
-#[derive(Debug, Clone, Copy, EnumString)]
+#[derive(Debug, Clone, Copy, EnumString, Reflect)]
 #[strum(serialize_all = "snake_case")]
 pub enum Action {
     Up,
@@ -251,9 +628,31 @@ pub enum Action {
     NewLine,
     Hover,
     Complete,
+    GotoDefinition,
+    Undo,
+    Redo,
+    Save,
+    /// Move the cursor to the start of a zero-based line, clamped to the buffer.
+    GotoLine(usize),
+    ToggleComment,
+    /// Collapse or expand the fold at the cursor.
+    ToggleFold,
+    /// Move the cursor to the next diagnostic, from the last one around to the first.
+    NextDiagnostic,
+    /// Move the cursor to the previous diagnostic, from the first one around to the last.
+    PrevDiagnostic,
 }
 
-#[derive(Debug, Copy, Clone)]
+impl Action {
+    /// Parse a one-based line number as typed by the user. Invalid input gives `None`.
+    pub fn goto_line(input: &str) -> Option<Self> {
+        let line: usize = input.trim().parse().ok()?;
+
+        Some(Action::GotoLine(line.saturating_sub(1)))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Cursor {
     pub byte: usize,
     pub line: usize,
@@ -262,10 +661,30 @@ pub struct Cursor {
 #[derive(Debug, Copy, Clone)]
 pub struct CursorWithCharacter {
     pub byte: usize,
+    /// Code points before the cursor on its line.
     pub character: usize,
+    /// UTF-16 code units before the cursor on its line.
+    pub utf16: usize,
     pub line: usize,
 }
 
+impl CursorWithCharacter {
+    /// The LSP position of the cursor, its character counted in code units of `encoding`.
+    /// The reverse of [Buffer::goto_position], in the text the cursor was taken from, like the one before a deletion.
+    pub fn to_position(self, encoding: &lsp_types::PositionEncodingKind) -> lsp_types::Position {
+        let character = match encoding.as_str() {
+            "utf-8" => self.byte,
+            "utf-16" => self.utf16,
+            _ => self.character,
+        };
+
+        lsp_types::Position {
+            line: self.line as u32,
+            character: character as u32,
+        }
+    }
+}
+
 impl Cursor {
     pub fn new() -> Self {
         Self { byte: 0, line: 0 }
@@ -274,14 +693,6 @@ impl Cursor {
     pub fn from_line_byte(line: usize, byte: usize) -> Self {
         Self { byte, line }
     }
-
-    pub fn with_character(self, character: usize) -> CursorWithCharacter {
-        CursorWithCharacter {
-            byte: self.byte,
-            character,
-            line: self.line,
-        }
-    }
 }
 
 impl Default for Cursor {
@@ -363,7 +774,7 @@ mod workspace {
 
     use slotmap::new_key_type;
 
-    use crate::lsp::LspResponseTransmitter;
+    use crate::lsp::{LspConfig, LspResponseTransmitter};
 
     new_key_type! {
         pub struct WorkspaceId;
@@ -382,9 +793,10 @@ mod workspace {
             id: WorkspaceId,
             path: PathBuf,
             initial_file: PathBuf,
+            config: &LspConfig,
             sync: impl LspResponseTransmitter,
         ) -> Self {
-            let lsp = { super::lsp::Lsp::new(path.clone(), initial_file, sync).ok() };
+            let lsp = { super::lsp::Lsp::new(config, path.clone(), initial_file, sync).ok() };
 
             Self {
                 id,
@@ -397,41 +809,96 @@ mod workspace {
 }
 
 mod lsp {
-    use crate::lsp::{LspRequest, LspResponseTransmitter};
+    use crate::{
+        lsp::{LspConfig, LspRequest, LspRequestData, LspResponseTransmitter},
+        ts::Language,
+    };
+    use lsp_types::PositionEncodingKind;
     use std::{
         path::PathBuf,
-        sync::mpsc::{channel, Sender},
+        sync::{
+            mpsc::{channel, Sender},
+            Arc, Mutex,
+        },
     };
 
     #[derive(Debug, Clone)]
     pub(super) struct Lsp {
         sender: Sender<LspRequest>,
+        position_encoding: Arc<Mutex<PositionEncodingKind>>,
+        /// The `languageId` of the documents the server is for, see [LspConfig::language_id].
+        language_id: String,
     }
 
     impl Lsp {
         pub(super) fn new<T: LspResponseTransmitter>(
+            config: &LspConfig,
             workspace: PathBuf,
             file: PathBuf,
             sync: T,
         ) -> crate::Result<Self> {
             let (tx, rx) = channel();
+            let position_encoding = Arc::new(Mutex::new(PositionEncodingKind::UTF16));
 
-            crate::lsp::Lsp::run(rx, sync, workspace, file);
+            crate::lsp::Lsp::run(config, rx, sync, workspace, file, position_encoding.clone())?;
 
-            Ok(Self { sender: tx })
+            Ok(Self {
+                sender: tx,
+                position_encoding,
+                language_id: config.language_id.clone(),
+            })
+        }
+
+        #[cfg(test)]
+        pub(super) fn with_sender(sender: Sender<LspRequest>, language_id: &str) -> Self {
+            Self {
+                sender,
+                position_encoding: Arc::new(Mutex::new(PositionEncodingKind::UTF16)),
+                language_id: language_id.into(),
+            }
+        }
+
+        /// Whether the server is for documents in `language`.
+        pub(super) fn serves(&self, language: Language) -> bool {
+            language.lsp_id() == self.language_id
+        }
+
+        /// What the characters of positions count, UTF-16 until the server picked when initialized.
+        /// Shared with every buffer it serves.
+        pub(super) fn position_encoding(&self) -> Arc<Mutex<PositionEncodingKind>> {
+            self.position_encoding.clone()
         }
 
         pub fn send(&self, event: LspRequest) {
             self.sender.send(event).expect("Channel to be open");
         }
+
+        /// Tell the server `file` was closed. It may be gone already, then there is no one to tell.
+        pub(super) fn close(&self, file: PathBuf) {
+            let _ = self.sender.send(LspRequest {
+                file,
+                data: LspRequestData::DidClose,
+            });
+        }
+    }
+}
+
+/// Closing a buffer, like closing its tab, closes the document in the language server.
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if let (Some(lsp), Some(file)) = (&self.lsp, &self.buffer.path) {
+            if lsp.serves(self.language) {
+                lsp.close(file.clone());
+            }
+        }
     }
 }
 
 impl From<Cursor> for tree_sitter::Point {
     fn from(value: Cursor) -> Self {
         Self {
-            row: value.byte,
-            column: value.line,
+            row: value.line,
+            column: value.byte,
         }
     }
 }
@@ -439,8 +906,8 @@ impl From<Cursor> for tree_sitter::Point {
 impl From<CursorWithCharacter> for tree_sitter::Point {
     fn from(value: CursorWithCharacter) -> Self {
         Self {
-            row: value.byte,
-            column: value.line,
+            row: value.line,
+            column: value.byte,
         }
     }
 }
@@ -456,6 +923,744 @@ impl From<CursorWithCharacter> for Cursor {
 
 #[cfg(test)]
 mod tests {
+    use std::path::Path;
+
+    use super::{
+        action,
+        history::{Change, ChangeKind, History},
+        Action, BlockSelection, Buffer, Coalescing, Cursor, SimpleBuffer,
+    };
+    use crate::{
+        lsp::{LspConfig, LspRequestData, LspResponse, LspResponseTransmitter},
+        ts::{self, Language, Theme},
+    };
+
+    /// A buffer holding `text` as if opened from `name`, which only picks its language.
+    fn fixture(name: &str, text: &str) -> SimpleBuffer {
+        SimpleBuffer::from_str(text, Some(name.into()))
+    }
+
     #[test]
     fn test() {}
+
+    #[test]
+    fn set_language_switches_highlighting() {
+        let mut buffer = Buffer::new(
+            fixture("set_language", "fn main() {\n    let x = 1;\n}"),
+            None,
+        );
+        assert_eq!(buffer.language(), Language::PlainText);
+
+        let query = tree_sitter::Query::new(
+            &tree_sitter_rust::language(),
+            tree_sitter_rust::HIGHLIGHT_QUERY,
+        )
+        .unwrap();
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let theme = Theme::default();
+        assert!(buffer
+            .highlight(&mut cursor, &query, &theme, 0..3)
+            .is_none());
+
+        buffer.set_language(Language::Rust);
+        assert_eq!(buffer.language(), Language::Rust);
+
+        let mut highlights = buffer.highlight(&mut cursor, &query, &theme, 0..3).unwrap();
+        let first = highlights.next_line().unwrap().count();
+        assert!(first > 0);
+    }
+
+    #[derive(Clone)]
+    struct Ignore;
+
+    impl LspResponseTransmitter for Ignore {
+        type Error = std::io::Error;
+
+        fn send(&self, _: LspResponse) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn lsp_follows_config() {
+        let missing = LspConfig {
+            command: "paladin-missing-language-server".into(),
+            args: vec!["--stdio".into()],
+            language_id: "rust".into(),
+        };
+
+        let rust = || fixture("lsp_config.rs", "fn main() {}");
+        let text = fixture("lsp_config.txt", "fn main() {}");
+
+        // Other languages don't start the server.
+        let buffer = Buffer::create(text, ".".into(), Some(missing.clone()), Ignore).unwrap();
+        assert!(buffer.lsp.is_none());
+
+        let buffer = Buffer::create(rust(), ".".into(), None, Ignore);
+        assert!(buffer.unwrap().lsp.is_none());
+
+        // Rust does, and the configured command can't be found.
+        let buffer = Buffer::create(rust(), ".".into(), Some(missing), Ignore);
+        assert!(buffer.is_err());
+    }
+
+    #[test]
+    fn highlights_injected_rust() {
+        let buffer = Buffer::new(
+            fixture(
+                "injection.rs",
+                "fn main() {\n    println!(\"{}\", x.len());\n    vec![format!(\"{}\", y.max(\n        1))];\n}",
+            ),
+            None,
+        );
+        let query = tree_sitter::Query::new(
+            &tree_sitter_rust::language(),
+            tree_sitter_rust::HIGHLIGHT_QUERY,
+        )
+        .unwrap();
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let theme = Theme::default();
+        let mut highlights = buffer.highlight(&mut cursor, &query, &theme, 0..4).unwrap();
+
+        let mut lines = vec![];
+        while let Some(line) = highlights.next_line() {
+            lines.push(line.map(|(_, range)| range).collect::<Vec<_>>());
+        }
+
+        // `len` is only an identifier in the token tree of the host.
+        assert!(lines[1].contains(&(21..24)));
+        // `max` is inside `format!`, nested in `vec!`.
+        assert!(buffer.layers.iter().any(|layer| layer.depth == 2));
+        assert!(lines[2].contains(&(25..28)));
+    }
+
+    #[test]
+    fn highlights_doc_comment_markdown() {
+        let buffer = Buffer::new(
+            fixture("doc_comment.rs", "/// Returns **bold** text\nfn main() {}"),
+            None,
+        );
+        let query = tree_sitter::Query::new(
+            &tree_sitter_rust::language(),
+            tree_sitter_rust::HIGHLIGHT_QUERY,
+        )
+        .unwrap();
+        let mut cursor = tree_sitter::QueryCursor::new();
+        let theme = Theme::default();
+        let mut highlights = buffer.highlight(&mut cursor, &query, &theme, 0..2).unwrap();
+
+        let first: Vec<_> = highlights.next_line().unwrap().collect();
+
+        assert!(buffer
+            .layers
+            .iter()
+            .any(|layer| layer.language == Language::MarkdownInline));
+        // `**bold**` is drawn bold, over the comment color.
+        assert!(first
+            .iter()
+            .any(|(style, range)| style.bold && *range == (12..20)));
+    }
+
+    /// Every node with its kind, bytes and positions, in tree order.
+    fn nodes(
+        tree: &tree_sitter::Tree,
+    ) -> Vec<(&'static str, std::ops::Range<usize>, tree_sitter::Range)> {
+        let mut nodes = vec![];
+        let mut cursor = tree.walk();
+
+        'walk: loop {
+            let node = cursor.node();
+            nodes.push((node.kind(), node.byte_range(), node.range()));
+
+            if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                continue;
+            }
+
+            while cursor.goto_parent() {
+                if cursor.goto_next_sibling() {
+                    continue 'walk;
+                }
+            }
+
+            return nodes;
+        }
+    }
+
+    #[test]
+    fn incremental_reparse_matches_full() {
+        let items: String = (0..20)
+            .map(|idx| format!("fn item{idx}() {{\n    let x = {idx};\n}}\n"))
+            .collect();
+        let mut buffer = Buffer::new(
+            fixture(
+                "incremental_reparse.rs",
+                &format!("fn main() {{\n    let x = 1;\n}}\n\n{items}"),
+            ),
+            None,
+        );
+
+        buffer.goto_line(2);
+        buffer.insert("    let y = 2;");
+        buffer.insert("\n");
+        buffer.goto_line(5);
+        buffer.insert("struct S;");
+        buffer.insert("\n");
+        buffer.toggle_comment(1..3);
+        // Joins line 7 onto line 6.
+        buffer.goto_line(7);
+        buffer.back();
+
+        let full = ts::tree(&buffer.buffer.rope, Language::Rust, None).unwrap();
+
+        assert_eq!(nodes(buffer.tree.as_ref().unwrap()), nodes(&full));
+
+        // Before reparsing, the edit alone moves the nodes after it to their new lines.
+        let mut edited = ts::tree(&buffer.buffer.rope, Language::Rust, None).unwrap();
+        buffer.buffer.goto_line(4);
+        edited.edit(&buffer.buffer.insert("\n").to_ts());
+
+        let full = ts::tree(&buffer.buffer.rope, Language::Rust, None).unwrap();
+        let last = |tree: &tree_sitter::Tree| nodes(tree).last().unwrap().2;
+
+        assert_eq!(last(&edited), last(&full));
+    }
+
+    #[test]
+    fn queries_compile() {
+        let mut queries = ts::Queries::default();
+
+        let indents = queries
+            .load(Language::Rust, ts::QueryKind::Indents)
+            .unwrap();
+        assert!(indents.capture_names().contains(&"indent"));
+        assert!(indents.capture_names().contains(&"outdent"));
+
+        let locals = queries.load(Language::Rust, ts::QueryKind::Locals).unwrap();
+        assert!(locals.capture_names().contains(&"local.scope"));
+
+        // Languages without a grammar, or without the query
+        assert!(queries
+            .load(Language::Toml, ts::QueryKind::Highlights)
+            .is_none());
+        assert!(queries
+            .load(Language::PlainText, ts::QueryKind::Indents)
+            .is_none());
+        assert!(queries
+            .get(Language::Rust, ts::QueryKind::Highlights)
+            .is_none());
+    }
+
+    #[test]
+    fn buffers_share_compiled_query() {
+        let open = |name: &str| Buffer::scratch(fixture(name, "fn main() {}"));
+
+        let mut a = open("shared_query_a.rs");
+        let mut b = open("shared_query_b.rs");
+        assert_eq!(a.language(), Language::Rust);
+
+        let first: *const _ = a.query(ts::QueryKind::Highlights).unwrap();
+        let second: *const _ = b.query(ts::QueryKind::Highlights).unwrap();
+        assert!(std::ptr::eq(first, second));
+    }
+
+    #[test]
+    fn detect_language() {
+        let detect = |path: &str, first_line| Language::detect(Path::new(path), first_line);
+
+        assert_eq!(detect("main.rs", ""), Language::Rust);
+        assert_eq!(detect("Cargo.lock", ""), Language::Toml);
+        assert_eq!(detect("build", "#!/usr/bin/env bash"), Language::Shell);
+        assert_eq!(detect("run", "#!/bin/sh -e"), Language::Shell);
+        assert_eq!(detect("notes", "hello"), Language::PlainText);
+    }
+
+    #[test]
+    fn toggle_comment_two_lines() {
+        let mut buffer = fixture(
+            "toggle_comment.rs",
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}",
+        );
+
+        let edits = buffer.toggle_comment(1..3, "//");
+        assert_eq!(edits.len(), 2);
+        assert_eq!(
+            buffer.text(),
+            "fn main() {\n    // let x = 1;\n    // let y = 2;\n}"
+        );
+
+        buffer.toggle_comment(1..3, "//");
+        assert_eq!(
+            buffer.text(),
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}"
+        );
+
+        // Mixed lines are all commented.
+        buffer.toggle_comment(1..2, "//");
+        buffer.toggle_comment(1..3, "//");
+        assert_eq!(
+            buffer.text(),
+            "fn main() {\n    // let x = 1;\n    // let y = 2;\n}"
+        );
+    }
+
+    #[test]
+    fn crlf_is_one_step() {
+        let mut buffer = fixture("crlf.txt", "ab\r\ncd\r\n");
+
+        buffer.cursor_right();
+        buffer.cursor_right();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 2));
+
+        // Stops at the end of the line, not inside the \r\n
+        buffer.cursor_right();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 2));
+
+        // Over the \r\n, never between it
+        buffer.set_cursor_wrap(true);
+        buffer.cursor_right();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 0));
+        assert_eq!(buffer.current_char(), Some('c'));
+
+        buffer.cursor_left();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 2));
+        buffer.cursor_right();
+
+        buffer.set_cursor_wrap(false);
+        buffer.cursor_left();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 0));
+
+        buffer.back();
+        assert_eq!(buffer.text(), "abcd\r\n");
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 2));
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "ab\r\ncd\r\n");
+    }
+
+    #[test]
+    fn cursor_moves_by_grapheme() {
+        // A family of four joined by ZWJs, then an 'e' with a combining acute
+        let family = "👨\u{200d}👩\u{200d}👧\u{200d}👦";
+        let mut buffer = fixture("graphemes.txt", &format!("a{family}e\u{301}b\nab{family}"));
+        let right = |buffer: &mut SimpleBuffer| {
+            buffer.cursor_right();
+            buffer.cursor().byte
+        };
+
+        assert_eq!(right(&mut buffer), 1);
+        assert_eq!(right(&mut buffer), 1 + family.len());
+        assert_eq!(right(&mut buffer), 1 + family.len() + 3);
+
+        buffer.cursor_left();
+        assert_eq!(buffer.cursor().byte, 1 + family.len());
+        buffer.cursor_left();
+        assert_eq!(buffer.cursor().byte, 1);
+
+        // Moving down into the middle of the family lands before it
+        buffer.cursor_right();
+        buffer.cursor_down();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 2));
+
+        // Backspace takes the whole cluster
+        buffer.cursor_right();
+        buffer.back();
+        assert_eq!(buffer.line(1).to_string(), "ab");
+    }
+
+    #[test]
+    fn block_insert_three_lines() {
+        let mut buffer = fixture("block_insert.txt", "abcdef\nabcdef\nab\nabcdef");
+
+        let block = BlockSelection::between((0, 3), (2, 3));
+        buffer.insert_block(&block, "X");
+        assert_eq!(buffer.text(), "abcXdef\nabcXdef\nab X\nabcdef");
+
+        let block = BlockSelection::between((0, 1), (1, 3));
+        buffer.insert_block(&block, "");
+        assert_eq!(buffer.text(), "aXdef\naXdef\nab X\nabcdef");
+    }
+
+    #[test]
+    fn back_is_undone_like_new_line() {
+        let mut buffer = Buffer::new(fixture("back.txt", "abc\nd"), None);
+        buffer.buffer.cursor = Cursor::from_line_byte(0, 3);
+
+        action(&mut buffer, Action::Back);
+        action(&mut buffer, Action::Back);
+        assert_eq!(buffer.text(), "a\nd");
+
+        // Typed deletes join one step
+        action(&mut buffer, Action::Undo);
+        assert_eq!(buffer.text(), "abc\nd");
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 3));
+
+        action(&mut buffer, Action::Redo);
+        assert_eq!(buffer.text(), "a\nd");
+
+        // Joining two lines
+        buffer.buffer.cursor = Cursor::from_line_byte(1, 0);
+        action(&mut buffer, Action::Back);
+        action(&mut buffer, Action::NewLine);
+        assert_eq!(buffer.text(), "a\nd");
+
+        action(&mut buffer, Action::Undo);
+        assert_eq!(buffer.text(), "ad");
+        action(&mut buffer, Action::Undo);
+        assert_eq!(buffer.text(), "a\nd");
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 0));
+    }
+
+    #[test]
+    fn undo_redo_round_trip() {
+        let original = "fn main() {\n}";
+        let mut buffer = Buffer::new(fixture("undo_redo.rs", original), None);
+        buffer.buffer.cursor = Cursor::from_line_byte(0, 11);
+
+        assert_eq!(buffer.buffer.revision(), 0);
+        buffer.insert("\n    let x = 1;\n    let y = 2;\n");
+        let edited = "fn main() {\n    let x = 1;\n    let y = 2;\n\n}";
+        assert_eq!(buffer.text(), edited);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(3, 0));
+        let revision = buffer.buffer.revision();
+
+        // Joins the empty line with the one above.
+        buffer.back();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(2, 14));
+        assert_ne!(buffer.buffer.revision(), revision);
+
+        // Back to the same text, back to the same revision
+        buffer.undo();
+        assert_eq!(buffer.text(), edited);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(3, 0));
+        assert_eq!(buffer.buffer.revision(), revision);
+
+        buffer.undo();
+        assert_eq!(buffer.text(), original);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 11));
+        assert_eq!(buffer.buffer.revision(), 0);
+
+        // Nothing left to undo
+        buffer.undo();
+        assert_eq!(buffer.text(), original);
+
+        buffer.redo();
+        buffer.redo();
+        assert_eq!(
+            buffer.text(),
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}"
+        );
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(2, 14));
+
+        // The tree follows along.
+        assert_eq!(
+            buffer.tree.as_ref().unwrap().root_node().to_sexp(),
+            ts::tree(&buffer.buffer.rope, Language::Rust, None)
+                .unwrap()
+                .root_node()
+                .to_sexp()
+        );
+
+        // A comment toggle on two lines is one step.
+        buffer.toggle_comment(1..3);
+        buffer.undo();
+        assert_eq!(
+            buffer.text(),
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}"
+        );
+    }
+
+    #[test]
+    fn typing_is_one_undo_step() {
+        let mut buffer = fixture("coalescing.txt", "");
+
+        for c in ["h", "e", "l", "l", "o"] {
+            buffer.insert(c);
+        }
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "");
+        buffer.redo();
+
+        // Deleting doesn't join the typing
+        buffer.back();
+        buffer.back();
+        assert_eq!(buffer.text(), "hel");
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "hello");
+        buffer.undo();
+        assert_eq!(buffer.text(), "");
+
+        buffer.set_undo_coalescing(Coalescing {
+            enabled: false,
+            ..Default::default()
+        });
+        buffer.insert("a");
+        buffer.insert("b");
+        buffer.undo();
+        assert_eq!(buffer.text(), "a");
+    }
+
+    #[test]
+    fn new_edit_clears_redo() {
+        let mut buffer = fixture("redo_cleared.txt", "");
+
+        buffer.insert("one");
+        buffer.undo();
+        buffer.insert("two");
+
+        assert!(buffer.redo().is_empty());
+        assert_eq!(buffer.text(), "two");
+
+        // Undo still goes back from the new edit
+        buffer.undo();
+        assert_eq!(buffer.text(), "");
+    }
+
+    #[test]
+    fn pauses_break_undo_steps() {
+        let start = std::time::Instant::now();
+        let ms = |ms| start + std::time::Duration::from_millis(ms);
+        let typed = |byte: usize, text: &str| Change {
+            kind: ChangeKind::Insert,
+            byte,
+            text: text.into(),
+            cursor_before: Cursor::from_line_byte(0, byte),
+            cursor_after: Cursor::from_line_byte(0, byte + 1),
+        };
+
+        let mut history = History::default();
+        history.record_at(typed(0, "a"), ms(0));
+        history.record_at(typed(1, "b"), ms(500));
+        history.record_at(typed(2, "c"), ms(2000));
+        // Not next to the last one
+        history.record_at(typed(0, "d"), ms(2100));
+        history.seal();
+        history.record_at(typed(1, "e"), ms(2200));
+
+        let steps: Vec<_> = std::iter::from_fn(|| history.pop_undo())
+            .map(|step| step.changes.len())
+            .collect();
+        assert_eq!(steps, [1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn delete_selection_across_lines() {
+        let mut buffer = Buffer::new(
+            fixture("selection.rs", "fn main() {\n    let x = 1;\n}"),
+            None,
+        );
+        assert_eq!(buffer.buffer.selection_range(), None);
+
+        // Select backwards, from the `1` up to after `{`.
+        buffer.buffer.cursor = Cursor::from_line_byte(1, 12);
+        buffer.buffer.set_anchor();
+        buffer.cursor_up();
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 11));
+        assert_eq!(buffer.buffer.selection_range(), Some(11..24));
+
+        buffer.delete_selection();
+        assert_eq!(buffer.text(), "fn main() {1;\n}");
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(0, 11));
+        assert_eq!(buffer.buffer.selection_range(), None);
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "fn main() {\n    let x = 1;\n}");
+    }
+
+    #[test]
+    fn apply_two_text_edits() {
+        use lsp_types::{Position, Range, TextEdit};
+
+        let edit = |from: (u32, u32), to: (u32, u32), text: &str| TextEdit {
+            range: Range::new(Position::new(from.0, from.1), Position::new(to.0, to.1)),
+            new_text: text.into(),
+        };
+
+        let mut buffer = Buffer::new(
+            fixture("text_edits.rs", "fn main() {\n    let x = \"é\"; x\n}"),
+            None,
+        );
+        buffer.buffer.cursor = Cursor::from_line_byte(1, 19);
+
+        // A rename, the second occurrence given first. Positions count UTF-16 code units by default.
+        let rename = [edit((1, 17), (1, 18), "y"), edit((1, 8), (1, 9), "y")];
+        buffer.apply_text_edits(&rename).unwrap();
+        assert_eq!(buffer.text(), "fn main() {\n    let y = \"é\"; y\n}");
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 19));
+
+        // In UTF-32, and one that overlaps the other
+        buffer.set_position_encoding(lsp_types::PositionEncodingKind::UTF32);
+        let overlapping = [edit((1, 4), (1, 9), ""), edit((1, 8), (1, 12), "")];
+        assert!(buffer.apply_text_edits(&overlapping).is_err());
+        assert_eq!(buffer.text(), "fn main() {\n    let y = \"é\"; y\n}");
+
+        buffer.undo();
+        assert_eq!(buffer.text(), "fn main() {\n    let x = \"é\"; x\n}");
+    }
+
+    #[test]
+    fn scratch_buffer_from_a_string() {
+        let mut buffer = Buffer::scratch(SimpleBuffer::from_str("fn main() {\n}", None));
+        assert_eq!(buffer.buffer.path(), None);
+        assert_eq!(buffer.language(), Language::PlainText);
+
+        buffer.buffer.cursor = Cursor::from_line_byte(0, 11);
+        buffer.insert("\n    let x = 1;");
+        assert_eq!(buffer.text(), "fn main() {\n    let x = 1;\n}");
+
+        assert!(buffer.save().is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("scratch.rs");
+        buffer.save_as(path.clone()).unwrap();
+        assert_eq!(buffer.buffer.path(), Some(path.as_path()));
+        assert_eq!(buffer.language(), Language::Rust);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "fn main() {\n    let x = 1;\n}"
+        );
+    }
+
+    #[test]
+    fn closing_sends_did_close() {
+        let (sender, requests) = std::sync::mpsc::channel();
+        let lsp = super::lsp::Lsp::with_sender(sender, "rust");
+        let buffer = Buffer::new(fixture("did_close.rs", "fn main() {}"), Some(lsp));
+
+        assert!(requests.try_recv().is_err());
+
+        drop(buffer);
+
+        let request = requests.try_recv().unwrap();
+        assert_eq!(request.file, Path::new("did_close.rs"));
+        assert!(matches!(request.data, LspRequestData::DidClose));
+    }
+
+    #[test]
+    fn language_changes_follow_the_served_language() {
+        let (sender, requests) = std::sync::mpsc::channel();
+        let lsp = super::lsp::Lsp::with_sender(sender, "rust");
+        let mut buffer = Buffer::new(fixture("language.rs", "fn main() {}"), Some(lsp));
+
+        // The server doesn't serve shell, so it loses the document
+        buffer.set_language(Language::Shell);
+        let request = requests.try_recv().unwrap();
+        assert_eq!(request.file, Path::new("language.rs"));
+        assert!(matches!(request.data, LspRequestData::DidClose));
+        assert!(requests.try_recv().is_err());
+
+        // And hears nothing of it until it is rust again
+        buffer.insert("x");
+        action(&mut buffer, Action::Hover);
+        assert!(requests.try_recv().is_err());
+
+        buffer.set_language(Language::Rust);
+        let LspRequestData::DidOpen { language_id, text } = requests.try_recv().unwrap().data
+        else {
+            panic!("Expected an open");
+        };
+        assert_eq!(language_id, "rust");
+        assert_eq!(text, buffer.text());
+
+        drop(buffer);
+        assert!(matches!(
+            requests.try_recv().unwrap().data,
+            LspRequestData::DidClose
+        ));
+
+        // A buffer left in another language has nothing to close
+        let (sender, requests) = std::sync::mpsc::channel();
+        let lsp = super::lsp::Lsp::with_sender(sender, "rust");
+        let mut buffer = Buffer::new(fixture("language.rs", "fn main() {}"), Some(lsp));
+        buffer.set_language(Language::Shell);
+        requests.try_recv().unwrap();
+
+        drop(buffer);
+        assert!(requests.try_recv().is_err());
+    }
+
+    #[test]
+    fn sent_positions_use_the_picked_encoding() {
+        use lsp_types::{Position, PositionEncodingKind, Range};
+
+        let (sender, requests) = std::sync::mpsc::channel();
+        let lsp = super::lsp::Lsp::with_sender(sender, "rust");
+        // `é` is two bytes and one UTF-16 unit, `𝕏` four bytes and two units
+        let mut buffer = Buffer::new(fixture("encoding.rs", "let s = \"é𝕏\";"), Some(lsp));
+        buffer.set_position_encoding(PositionEncodingKind::UTF8);
+        buffer.goto_position(Position::new(0, 15));
+
+        for (encoding, character) in [
+            (PositionEncodingKind::UTF8, 15),
+            (PositionEncodingKind::UTF16, 12),
+            (PositionEncodingKind::UTF32, 11),
+        ] {
+            buffer.set_position_encoding(encoding);
+            let at = Position::new(0, character);
+
+            buffer.insert("x");
+            let LspRequestData::DidChange { edits } = requests.try_recv().unwrap().data else {
+                panic!("Expected a change");
+            };
+            assert_eq!(edits[0].range, Range::new(at, at));
+
+            buffer.back();
+            let LspRequestData::DidChange { edits } = requests.try_recv().unwrap().data else {
+                panic!("Expected a change");
+            };
+            assert_eq!(
+                edits[0].range,
+                Range::new(at, Position::new(0, character + 1))
+            );
+
+            action(&mut buffer, Action::Hover);
+            let LspRequestData::Hover { line, character } = requests.try_recv().unwrap().data
+            else {
+                panic!("Expected a hover");
+            };
+            assert_eq!(Position::new(line, character), at);
+        }
+    }
+
+    #[test]
+    fn navigate_between_diagnostics() {
+        use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+        let diagnostic = |line: u32, character: u32, severity| Diagnostic {
+            range: Range::new(
+                Position::new(line, character),
+                Position::new(line, character + 1),
+            ),
+            severity: Some(severity),
+            message: "unused".into(),
+            ..Default::default()
+        };
+
+        let mut buffer = Buffer::scratch(SimpleBuffer::from_str(
+            "fn main() {\n    let x = 1;\n    let y = 2;\n}",
+            None,
+        ));
+        buffer.set_diagnostics(vec![
+            diagnostic(2, 8, DiagnosticSeverity::WARNING),
+            diagnostic(1, 8, DiagnosticSeverity::WARNING),
+            diagnostic(2, 4, DiagnosticSeverity::ERROR),
+        ]);
+        assert_eq!(buffer.line_severity(1), Some(DiagnosticSeverity::WARNING));
+        assert_eq!(buffer.line_severity(2), Some(DiagnosticSeverity::ERROR));
+        assert_eq!(buffer.line_severity(0), None);
+
+        action(&mut buffer, Action::NextDiagnostic);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 8));
+
+        action(&mut buffer, Action::NextDiagnostic);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(2, 4));
+
+        action(&mut buffer, Action::PrevDiagnostic);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(1, 8));
+
+        // Around from the first to the last
+        action(&mut buffer, Action::PrevDiagnostic);
+        assert_eq!(buffer.cursor(), Cursor::from_line_byte(2, 8));
+    }
 }
@@ -0,0 +1,83 @@
+use std::ops::Range;
+
+use super::{Edit, SimpleBuffer};
+
+/// A rectangular selection, spanning the same character columns on each of its lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockSelection {
+    pub lines: Range<usize>,
+    pub columns: Range<usize>,
+}
+
+impl BlockSelection {
+    /// The block between two cursor positions, `(line, column)` in any order.
+    /// Both lines are included, the columns are those between the two cursors, none if they line up.
+    pub fn between(a: (usize, usize), b: (usize, usize)) -> Self {
+        Self {
+            lines: a.0.min(b.0)..a.0.max(b.0) + 1,
+            columns: a.1.min(b.1)..a.1.max(b.1),
+        }
+    }
+
+    /// Byte range the block covers on a line, clamped to its end.
+    pub fn bytes_in(&self, line: &str) -> Range<usize> {
+        let byte = |column| {
+            line.char_indices()
+                .nth(column)
+                .map_or(line.len(), |(idx, _)| idx)
+        };
+
+        byte(self.columns.start)..byte(self.columns.end)
+    }
+}
+
+impl SimpleBuffer {
+    /// Replace the block on every line with `text`.
+    /// Lines shorter than the block's left column are padded with spaces, so the text lines up.
+    pub(super) fn insert_block(
+        &mut self,
+        block: &BlockSelection,
+        text: &str,
+    ) -> Vec<(Edit, String)> {
+        self.history.start_group();
+
+        let mut edits = self.delete_block(block);
+
+        if text.is_empty() {
+            self.history.end_group();
+
+            return edits;
+        }
+
+        for line in block.lines.start..block.lines.end.min(self.rope.line_len()) {
+            let current = self.rope.line(line).to_string();
+            let byte = block.bytes_in(&current).start;
+            let padding = block.columns.start.saturating_sub(current.chars().count());
+
+            let text = format!("{}{text}", " ".repeat(padding));
+
+            edits.push((self.insert_in_line(line, byte, &text), text));
+        }
+
+        self.history.end_group();
+
+        edits
+    }
+
+    /// Delete the block on every line. Lines shorter than the block lose only what they have in it.
+    pub(super) fn delete_block(&mut self, block: &BlockSelection) -> Vec<(Edit, String)> {
+        self.history.start_group();
+
+        let edits = (block.lines.start..block.lines.end.min(self.rope.line_len()))
+            .filter_map(|line| {
+                let range = block.bytes_in(&self.rope.line(line).to_string());
+
+                (!range.is_empty()).then(|| (self.delete_in_line(line, range), String::new()))
+            })
+            .collect();
+
+        self.history.end_group();
+
+        edits
+    }
+}
@@ -1,40 +1,161 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use crop::{Rope, RopeSlice};
 use miette::IntoDiagnostic;
+use unicode_segmentation::UnicodeSegmentation;
 
-use super::{Cursor, CursorWithCharacter, Edit};
+use super::{
+    history::{Change, ChangeKind, Coalescing, History},
+    Cursor, CursorWithCharacter, Edit,
+};
 
 #[derive(Clone, Debug)]
 pub struct SimpleBuffer {
-    pub path: PathBuf,
+    /// Where the text is saved, `None` for a scratch buffer that was never saved.
+    pub path: Option<PathBuf>,
     pub(super) rope: Rope,
     pub(super) cursor: Cursor,
+    /// The other end of the selection, which runs from here to the cursor.
+    pub(super) selection_anchor: Option<Cursor>,
+    pub(super) history: History,
+    /// Whether left and right move over line breaks, off by default.
+    wrap_cursor: bool,
 }
 
 impl SimpleBuffer {
     pub fn open(path: PathBuf) -> crate::Result<Self> {
         let str = std::fs::read_to_string(&path).into_diagnostic()?;
-        let rope = Rope::from(str);
 
-        Ok(Self {
-            rope,
+        Ok(Self::from_str(&str, Some(path)))
+    }
+
+    /// A buffer holding `content`, without reading the file at `path`.
+    /// Without a path it is a scratch buffer, which needs [SimpleBuffer::save_as] to be saved.
+    pub fn from_str(content: &str, path: Option<PathBuf>) -> Self {
+        Self {
+            rope: Rope::from(content),
             cursor: Cursor::new(),
             path,
-        })
+            selection_anchor: None,
+            history: History::default(),
+            wrap_cursor: false,
+        }
     }
 
+    /// The whole text, copied into one string. Prefer [SimpleBuffer::lines_in_range] or
+    /// [SimpleBuffer::chunks] for large files.
     pub fn text(&self) -> String {
         self.rope.to_string()
     }
 
-    pub fn path(&self) -> &Path {
-        &self.path
+    /// Lines in `lines`, without copying them. Lines past the end are left out.
+    pub fn lines_in_range(&self, lines: Range<usize>) -> impl Iterator<Item = RopeSlice<'_>> {
+        let end = lines.end.min(self.rope.line_len());
+
+        (lines.start.min(end)..end).map(|line| self.rope.line(line))
+    }
+
+    /// The text in the pieces it is stored in, in order.
+    pub fn chunks(&self) -> impl Iterator<Item = &str> {
+        self.rope.chunks()
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Write the text to its path. A scratch buffer has none, ask for one and use [SimpleBuffer::save_as].
+    pub fn save(&self) -> crate::Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| miette::miette!("the scratch buffer has no path to save to"))?;
+        let mut file = BufWriter::new(File::create(path).into_diagnostic()?);
+
+        for chunk in self.chunks() {
+            file.write_all(chunk.as_bytes()).into_diagnostic()?;
+        }
+
+        file.flush().into_diagnostic()
+    }
+
+    /// Write the text to `path`, which it is saved to from now on.
+    pub fn save_as(&mut self, path: PathBuf) -> crate::Result<()> {
+        let previous = self.path.replace(path);
+
+        self.save().inspect_err(|_| self.path = previous)
+    }
+
+    /// Set when typing joins one undo step.
+    pub fn set_undo_coalescing(&mut self, coalescing: Coalescing) {
+        self.history.coalescing = coalescing;
+    }
+
+    /// Set when left at the start of a line moves to the end of the previous one,
+    /// and right at the end of a line to the start of the next. They stop at the line's ends otherwise.
+    pub fn set_cursor_wrap(&mut self, enabled: bool) {
+        self.wrap_cursor = enabled;
+    }
+
+    /// Which text undo and redo got to, the same whenever they get back to the same text,
+    /// like the one that was last saved.
+    pub fn revision(&self) -> u64 {
+        self.history.revision()
+    }
+
+    /// Start the next edit on an undo step of its own, even if typing would join it to the last one.
+    pub fn seal_undo_step(&mut self) {
+        self.history.seal();
+    }
+
+    /// Start selecting from the cursor. Moving the cursor afterwards extends the selection.
+    pub fn set_anchor(&mut self) {
+        self.selection_anchor = Some(self.cursor);
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_anchor = None;
+    }
+
+    /// The selected bytes of the whole text, in order no matter which way the selection was made.
+    pub fn selection_range(&self) -> Option<Range<usize>> {
+        let anchor = self.selection_anchor?;
+        let anchor = self.line_byte_to_global(anchor.line, anchor.byte);
+        let cursor = self.global_cursor_to_byte();
+
+        Some(anchor.min(cursor)..anchor.max(cursor))
+    }
+
+    /// Delete the selected text, leaving the cursor where it started. `None` when nothing is selected.
+    pub fn delete_selection(&mut self) -> Option<Edit> {
+        let range = self.selection_range()?;
+        self.selection_anchor = None;
+
+        if range.is_empty() {
+            return None;
+        }
+
+        let cursor_before = self.cursor;
+        let deleted = self.rope.byte_slice(range.clone()).to_string();
+        let edit = self.delete_bytes(range.clone());
+
+        let start = self.cursor_at_byte(range.start);
+        self.cursor = Cursor::from_line_byte(start.line, start.byte);
+
+        self.record(ChangeKind::Delete, range.start, deleted, cursor_before);
+
+        Some(edit)
     }
 
     pub(super) fn insert(&mut self, text: impl AsRef<str>) -> Edit {
-        let start = self.cursor.with_character(self.line_current_char_idx());
+        let start = self.with_character(self.cursor);
         let start_byte = self.global_cursor_to_byte();
+        let cursor_before = self.cursor;
 
         let text = text.as_ref();
 
@@ -47,16 +168,19 @@ impl SimpleBuffer {
 
             if new_lines > 0 {
                 self.cursor.line += new_lines;
-                self.cursor.byte = text.lines().last().map(|line| line.len()).unwrap_or(0);
+                // What follows the last newline, empty when the text ends with one.
+                self.cursor.byte = text.rsplit('\n').next().map_or(0, str::len);
             } else {
                 self.cursor.byte += len;
             }
+
+            self.record(ChangeKind::Insert, start_byte, text.into(), cursor_before);
         }
 
         Edit::Insert {
             start,
             start_byte,
-            new_end: self.cursor.with_character(self.line_current_char_idx()),
+            new_end: self.with_character(self.cursor),
             new_end_byte: self.global_cursor_to_byte(),
         }
     }
@@ -66,21 +190,27 @@ impl SimpleBuffer {
             return None;
         }
 
+        let cursor_before = self.cursor;
+
         if self.cursor.byte == 0 {
             self.cursor_line_up();
 
-            let from = self.cursor_with_character();
+            let from = self.with_character(self.cursor);
             let from_byte = self.global_cursor_to_byte();
 
-            let to_byte = self.global_cursor_to_byte() + 1;
+            // The whole line break, which is two bytes for \r\n.
+            let to_byte = self.line_byte_to_global(cursor_before.line, 0);
 
             let to = CursorWithCharacter {
                 byte: 0,
                 character: 0,
+                utf16: 0,
                 line: self.cursor.line + 1,
             };
 
+            let deleted = self.rope.byte_slice(from_byte..to_byte).to_string();
             self.rope.delete(from_byte..to_byte);
+            self.record(ChangeKind::Delete, from_byte, deleted, cursor_before);
 
             return Some(Edit::Delete {
                 from,
@@ -96,10 +226,11 @@ impl SimpleBuffer {
 
         let end = self.global_cursor_to_byte();
 
-        let to = self.cursor_with_character();
+        let to = self.with_character(self.cursor);
 
         let range = start..end;
 
+        let deleted = self.rope.byte_slice(range.clone()).to_string();
         self.rope.delete(range.clone());
 
         if self.cursor.byte == 0 {
@@ -108,7 +239,9 @@ impl SimpleBuffer {
             self.cursor.byte = self.cursor.byte.saturating_sub(range.len());
         }
 
-        let from = self.cursor.with_character(self.line_current_char_idx());
+        self.record(ChangeKind::Delete, start, deleted, cursor_before);
+
+        let from = self.with_character(self.cursor);
 
         Some(Edit::Delete {
             from,
@@ -118,57 +251,351 @@ impl SimpleBuffer {
         })
     }
 
-    fn cursor_with_character(&self) -> super::CursorWithCharacter {
-        self.cursor.with_character(self.line_current_char_idx())
+    /// Comment out `lines` by inserting `prefix` after their indentation, or uncomment them if every non-blank line already is.
+    /// Returns each edit with the text it inserted.
+    pub(super) fn toggle_comment(
+        &mut self,
+        lines: Range<usize>,
+        prefix: &str,
+    ) -> Vec<(Edit, String)> {
+        let lines = lines.start..lines.end.min(self.rope.line_len());
+
+        // (line, indentation in bytes, whether it is commented)
+        let targets: Vec<_> = lines
+            .filter_map(|line| {
+                let text = self.rope.line(line).to_string();
+                let code = text.trim_start();
+
+                (!code.is_empty())
+                    .then(|| (line, text.len() - code.len(), code.starts_with(prefix)))
+            })
+            .collect();
+
+        let uncomment = !targets.is_empty() && targets.iter().all(|(_, _, commented)| *commented);
+
+        self.history.start_group();
+
+        let edits = targets
+            .into_iter()
+            .filter(|(_, _, commented)| uncomment || !commented)
+            .map(|(line, indent, _)| {
+                if uncomment {
+                    let after = self.rope.line(line).byte_slice(indent + prefix.len()..);
+                    let space = after.chars().next() == Some(' ');
+                    let end = indent + prefix.len() + space as usize;
+
+                    (self.delete_in_line(line, indent..end), String::new())
+                } else {
+                    let text = format!("{prefix} ");
+
+                    (self.insert_in_line(line, indent, &text), text)
+                }
+            })
+            .collect();
+
+        self.history.end_group();
+
+        edits
     }
 
-    pub(super) fn cursor_line_up(&mut self) {
-        assert!(self.cursor.line > 0);
-        assert!(self.cursor.byte == 0);
+    /// Insert text without newlines at a byte of a line, keeping the cursor on the same character.
+    pub(super) fn insert_in_line(&mut self, line: usize, byte: usize, text: &str) -> Edit {
+        let start = Cursor::from_line_byte(line, byte);
+        let start_byte = self.line_byte_to_global(line, byte);
+        let cursor_before = self.cursor;
 
-        self.cursor.line = self.cursor.line.saturating_sub(1);
-        self.cursor.byte = self.current_line().byte_len();
+        self.rope.insert(start_byte, text);
+
+        if self.cursor.line == line && self.cursor.byte >= byte {
+            self.cursor.byte += text.len();
+        }
+
+        self.record(ChangeKind::Insert, start_byte, text.into(), cursor_before);
+
+        let new_end = Cursor::from_line_byte(line, byte + text.len());
+
+        Edit::Insert {
+            start: self.with_character(start),
+            start_byte,
+            new_end: self.with_character(new_end),
+            new_end_byte: start_byte + text.len(),
+        }
     }
 
-    pub(super) fn line_prev_char_index(&self) -> Option<usize> {
-        if self.cursor.byte == 0 {
-            return None;
+    /// Delete bytes within a line, keeping the cursor on the same character where possible.
+    pub(super) fn delete_in_line(&mut self, line: usize, range: Range<usize>) -> Edit {
+        let from = Cursor::from_line_byte(line, range.start);
+        let to = Cursor::from_line_byte(line, range.end);
+        let from = self.with_character(from);
+        let to = self.with_character(to);
+
+        let from_byte = self.line_byte_to_global(line, range.start);
+        let to_byte = self.line_byte_to_global(line, range.end);
+        let cursor_before = self.cursor;
+
+        let deleted = self.rope.byte_slice(from_byte..to_byte).to_string();
+        self.rope.delete(from_byte..to_byte);
+
+        if self.cursor.line == line && self.cursor.byte > range.start {
+            self.cursor.byte = range
+                .start
+                .max(self.cursor.byte.saturating_sub(range.len()));
         }
 
-        let line = self.current_line();
-        let mut row = self.cursor.byte;
+        self.record(ChangeKind::Delete, from_byte, deleted, cursor_before);
+
+        Edit::Delete {
+            from,
+            from_byte,
+            to,
+            to_byte,
+        }
+    }
+
+    /// Replace byte ranges of the whole text, as one undo step. Returns each edit with the text it inserted.
+    ///
+    /// `replacements` must be sorted from the last range to the first and not overlap,
+    /// so each range is still where it was in the text before any of them.
+    /// The cursor stays on the same text, or moves to the start of a range replaced around it.
+    pub(super) fn replace_ranges(
+        &mut self,
+        replacements: Vec<(Range<usize>, String)>,
+    ) -> Vec<(Edit, String)> {
+        let mut edits = vec![];
+
+        self.history.start_group();
+
+        for (range, text) in replacements {
+            let cursor_before = self.cursor;
+            let deleted = self.rope.byte_slice(range.clone()).to_string();
+
+            let mut cursor = self.global_cursor_to_byte();
+            if cursor >= range.end {
+                cursor = cursor - range.len() + text.len();
+            } else if cursor > range.start {
+                cursor = range.start;
+            }
+
+            if !range.is_empty() {
+                edits.push((self.delete_bytes(range.clone()), String::new()));
+            }
+            if !text.is_empty() {
+                edits.push((self.insert_bytes(range.start, &text), text.clone()));
+            }
+
+            self.cursor = self.cursor_at_byte(cursor).into();
 
-        loop {
-            debug_assert!(row >= 1);
-            row -= 1;
+            if !deleted.is_empty() {
+                self.record(ChangeKind::Delete, range.start, deleted, cursor_before);
+            }
+            if !text.is_empty() {
+                self.record(ChangeKind::Insert, range.start, text, cursor_before);
+            }
+        }
+
+        self.history.end_group();
+
+        edits
+    }
+
+    /// The byte of the whole text at an LSP `position`, its character counted in code units of `encoding`.
+    /// Positions past the end of a line are on its end, and past the last line on the end of the text.
+    pub(super) fn position_to_byte(
+        &self,
+        position: lsp_types::Position,
+        encoding: &lsp_types::PositionEncodingKind,
+    ) -> usize {
+        let line = position.line as usize;
+        if line >= self.rope.line_len() {
+            return self.rope.byte_len();
+        }
 
-            if line.is_char_boundary(row) {
-                return Some(row);
+        let unit_len = |c: char| match encoding.as_str() {
+            "utf-8" => c.len_utf8(),
+            "utf-16" => c.len_utf16(),
+            _ => 1,
+        };
+
+        let mut units = 0;
+        let mut byte = 0;
+        for c in self.rope.line(line).chars() {
+            units += unit_len(c);
+            if units > position.character as usize {
+                break;
             }
+            byte += c.len_utf8();
         }
+
+        self.line_byte_to_global(line, byte)
     }
 
-    pub(super) fn global_prev_char_index(&self) -> Option<usize> {
-        self.line_prev_char_index()
-            .map(|local| self.line_byte_to_global(self.cursor.line, local))
+    fn record(&mut self, kind: ChangeKind, byte: usize, text: String, cursor_before: Cursor) {
+        // The anchor may point anywhere after an edit.
+        self.selection_anchor = None;
+
+        self.history.record(Change {
+            kind,
+            byte,
+            text,
+            cursor_before,
+            cursor_after: self.cursor,
+        });
     }
 
-    pub(super) fn line_next_char_index(&self) -> Option<usize> {
-        let line = self.current_line();
+    /// Revert the last step, returning each edit with the text it inserted.
+    pub(super) fn undo(&mut self) -> Vec<(Edit, String)> {
+        let Some(step) = self.history.pop_undo() else {
+            return vec![];
+        };
+
+        self.selection_anchor = None;
+
+        let edits = step
+            .changes
+            .iter()
+            .rev()
+            .map(|change| match change.kind {
+                ChangeKind::Insert => {
+                    let end = change.byte + change.text.len();
+
+                    (self.delete_bytes(change.byte..end), String::new())
+                }
+                ChangeKind::Delete => (
+                    self.insert_bytes(change.byte, &change.text),
+                    change.text.clone(),
+                ),
+            })
+            .collect();
+
+        if let Some(first) = step.changes.first() {
+            self.cursor = first.cursor_before;
+        }
 
-        if self.cursor.byte == line.byte_len() {
-            return None;
+        self.history.push_redo(step);
+
+        edits
+    }
+
+    /// Apply the last undone step again, returning each edit with the text it inserted.
+    pub(super) fn redo(&mut self) -> Vec<(Edit, String)> {
+        let Some(step) = self.history.pop_redo() else {
+            return vec![];
+        };
+
+        self.selection_anchor = None;
+
+        let edits = step
+            .changes
+            .iter()
+            .map(|change| match change.kind {
+                ChangeKind::Insert => (
+                    self.insert_bytes(change.byte, &change.text),
+                    change.text.clone(),
+                ),
+                ChangeKind::Delete => {
+                    let end = change.byte + change.text.len();
+
+                    (self.delete_bytes(change.byte..end), String::new())
+                }
+            })
+            .collect();
+
+        if let Some(last) = step.changes.last() {
+            self.cursor = last.cursor_after;
         }
 
-        let mut row = self.cursor.byte;
+        self.history.push_undo(step);
 
-        loop {
-            debug_assert!(row <= line.byte_len());
-            row += 1;
+        edits
+    }
 
-            if line.is_char_boundary(row) {
-                return Some(row);
-            }
+    /// Insert at a byte of the whole text, without recording it or moving the cursor.
+    fn insert_bytes(&mut self, byte: usize, text: &str) -> Edit {
+        let start = self.cursor_at_byte(byte);
+
+        self.rope.insert(byte, text);
+
+        Edit::Insert {
+            start,
+            start_byte: byte,
+            new_end: self.cursor_at_byte(byte + text.len()),
+            new_end_byte: byte + text.len(),
+        }
+    }
+
+    /// Delete bytes of the whole text, without recording it or moving the cursor.
+    fn delete_bytes(&mut self, range: Range<usize>) -> Edit {
+        let from = self.cursor_at_byte(range.start);
+        let to = self.cursor_at_byte(range.end);
+
+        self.rope.delete(range.clone());
+
+        Edit::Delete {
+            from,
+            from_byte: range.start,
+            to,
+            to_byte: range.end,
+        }
+    }
+
+    pub(super) fn cursor_at_byte(&self, byte: usize) -> CursorWithCharacter {
+        let line = self.rope.line_of_byte(byte);
+        let cursor = Cursor::from_line_byte(line, byte - self.rope.byte_of_line(line));
+
+        self.with_character(cursor)
+    }
+
+    /// `cursor` with the characters before it on its line, counted in code points and in UTF-16 code units.
+    pub(super) fn with_character(&self, cursor: Cursor) -> CursorWithCharacter {
+        let character = self.line_char_idx(cursor);
+        let utf16 = self
+            .rope
+            .line(cursor.line)
+            .byte_slice(..cursor.byte)
+            .chars()
+            .map(char::len_utf16)
+            .sum();
+
+        CursorWithCharacter {
+            byte: cursor.byte,
+            character,
+            utf16,
+            line: cursor.line,
+        }
+    }
+
+    pub(super) fn cursor_line_up(&mut self) {
+        assert!(self.cursor.line > 0);
+        assert!(self.cursor.byte == 0);
+
+        self.cursor.line = self.cursor.line.saturating_sub(1);
+        self.cursor.byte = self.current_line().byte_len();
+    }
+
+    /// Where the grapheme cluster before the cursor starts, so emoji sequences and accented letters move as one.
+    pub(super) fn line_prev_char_index(&self) -> Option<usize> {
+        grapheme_boundaries(self.current_line())
+            .into_iter()
+            .take_while(|boundary| *boundary < self.cursor.byte)
+            .last()
+    }
+
+    pub(super) fn global_prev_char_index(&self) -> Option<usize> {
+        self.line_prev_char_index()
+            .map(|local| self.line_byte_to_global(self.cursor.line, local))
+    }
+
+    /// Where the grapheme cluster after the cursor ends.
+    pub(super) fn line_next_char_index(&self) -> Option<usize> {
+        grapheme_boundaries(self.current_line())
+            .into_iter()
+            .find(|boundary| *boundary > self.cursor.byte)
+    }
+
+    /// Move back to the start of the grapheme cluster the cursor is in, if it is in the middle of one.
+    fn snap_to_grapheme(&mut self) {
+        if !grapheme_boundaries(self.current_line()).contains(&self.cursor.byte) {
+            self.cursor.byte = self.line_prev_char_index().unwrap_or(0);
         }
     }
 
@@ -193,8 +620,14 @@ impl SimpleBuffer {
         self.cursor.byte = self.cursor.byte.clamp(0, max);
     }
 
+    /// Move a character left, or over the line break to the end of the previous line
+    /// if [SimpleBuffer::set_cursor_wrap] is on.
     pub(super) fn cursor_left(&mut self) {
         if self.cursor.byte == 0 {
+            if self.wrap_cursor && self.cursor.line > 0 {
+                self.cursor_line_up();
+            }
+
             return;
         }
 
@@ -212,25 +645,29 @@ impl SimpleBuffer {
             .min(self.rope.line_len().saturating_sub(1));
 
         self.clamp_cursor_max(self.current_line().byte_len());
-
-        if !self.current_line().is_char_boundary(self.cursor.byte) {
-            self.cursor.byte = self.line_prev_char_index().unwrap_or(0);
-        }
+        self.snap_to_grapheme();
     }
 
     pub(super) fn cursor_up(&mut self) {
         self.cursor.line = self.cursor.line.saturating_sub(1);
 
         self.clamp_cursor_max(self.current_line().byte_len());
+        self.snap_to_grapheme();
+    }
 
-        if !self.current_line().is_char_boundary(self.cursor.byte) {
-            self.cursor.byte = self.line_prev_char_index().unwrap_or(0);
-        }
+    /// Move to the start of `line`, clamped to the last line.
+    pub(super) fn goto_line(&mut self, line: usize) {
+        self.cursor.line = line.min(self.rope.line_len().saturating_sub(1));
+        self.cursor.byte = 0;
     }
 
+    /// Move a character right, or over the line break to the start of the next line
+    /// if [SimpleBuffer::set_cursor_wrap] is on.
     pub(super) fn cursor_right(&mut self) {
         if let Some(next) = self.global_next_char_index() {
             self.cursor.byte = next - self.current_line_start_byte();
+        } else if self.wrap_cursor && self.cursor.line + 1 < self.rope.line_len() {
+            self.cursor = Cursor::from_line_byte(self.cursor.line + 1, 0);
         }
     }
 
@@ -238,10 +675,6 @@ impl SimpleBuffer {
         line_char_idx(&self.rope, cursor)
     }
 
-    pub(super) fn line_current_char_idx(&self) -> usize {
-        self.line_char_idx(self.cursor)
-    }
-
     pub(super) fn current_line(&self) -> RopeSlice {
         self.rope.line(self.cursor.line)
     }
@@ -265,6 +698,16 @@ impl SimpleBuffer {
     }
 }
 
+/// Byte offsets in `line` where its extended grapheme clusters start, and its end.
+fn grapheme_boundaries(line: RopeSlice) -> Vec<usize> {
+    let line = line.to_string();
+
+    line.grapheme_indices(true)
+        .map(|(byte, _)| byte)
+        .chain([line.len()])
+        .collect()
+}
+
 pub(super) fn line_char_idx(rope: &Rope, cursor: Cursor) -> usize {
     let line = rope.line(cursor.line);
 
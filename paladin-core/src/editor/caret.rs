@@ -0,0 +1,137 @@
+use std::time::{Duration, Instant};
+
+use super::Mode;
+
+/// Shape of the caret as drawn over the text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretShape {
+    /// A thin vertical line before the character.
+    Bar,
+    /// Covers the whole character cell.
+    Block,
+    /// A line under the character.
+    Underline,
+}
+
+impl CaretShape {
+    /// Block in [Mode::Normal], bar in [Mode::Insert].
+    pub fn for_mode(mode: Mode) -> Self {
+        match mode {
+            Mode::Normal => CaretShape::Block,
+            Mode::Insert => CaretShape::Bar,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CaretStyle {
+    pub shape: CaretShape,
+    /// How long the caret stays on, and then off. `None` draws a solid caret.
+    pub blink_ms: Option<u32>,
+}
+
+impl CaretStyle {
+    /// The blink interval most platforms default to.
+    pub const DEFAULT_BLINK_MS: u32 = 530;
+
+    pub fn for_mode(mode: Mode) -> Self {
+        Self {
+            shape: CaretShape::for_mode(mode),
+            blink_ms: Some(Self::DEFAULT_BLINK_MS),
+        }
+    }
+}
+
+impl Default for CaretStyle {
+    fn default() -> Self {
+        Self::for_mode(Mode::Normal)
+    }
+}
+
+/// Blink state of a caret. The caller supplies the current time, so it can be driven by whatever ticks the editor.
+#[derive(Clone, Copy, Debug)]
+pub struct Caret {
+    pub style: CaretStyle,
+    phase_start: Instant,
+}
+
+impl Caret {
+    pub fn new(style: CaretStyle, now: Instant) -> Self {
+        Self {
+            style,
+            phase_start: now,
+        }
+    }
+
+    /// Restart the blink, so the caret is visible right after the cursor moves.
+    pub fn reset(&mut self, now: Instant) {
+        self.phase_start = now;
+    }
+
+    /// Switch to the shape of `mode`, keeping the blink rate.
+    pub fn set_mode(&mut self, mode: Mode, now: Instant) {
+        self.style.shape = CaretShape::for_mode(mode);
+        self.reset(now);
+    }
+
+    pub fn visible_at(&self, now: Instant) -> bool {
+        let Some(blink_ms) = self.style.blink_ms.filter(|ms| *ms > 0) else {
+            return true;
+        };
+
+        let elapsed = now.saturating_duration_since(self.phase_start).as_millis();
+
+        (elapsed / blink_ms as u128).is_multiple_of(2)
+    }
+
+    /// When the visibility next changes, if it ever does.
+    pub fn next_toggle(&self, now: Instant) -> Option<Instant> {
+        let blink_ms = self.style.blink_ms.filter(|ms| *ms > 0)? as u128;
+
+        let elapsed = now.saturating_duration_since(self.phase_start).as_millis();
+        let next = (elapsed / blink_ms + 1) * blink_ms;
+
+        Some(self.phase_start + Duration::from_millis(next as u64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::Mode;
+
+    use super::{Caret, CaretShape, CaretStyle};
+
+    #[test]
+    fn blinks_and_resets() {
+        let start = Instant::now();
+        let ms = |ms| start + Duration::from_millis(ms);
+
+        let mut caret = Caret::new(
+            CaretStyle {
+                shape: CaretShape::Block,
+                blink_ms: Some(500),
+            },
+            start,
+        );
+
+        assert!(caret.visible_at(ms(0)));
+        assert!(caret.visible_at(ms(499)));
+        assert!(!caret.visible_at(ms(500)));
+        assert!(caret.visible_at(ms(1000)));
+        assert_eq!(caret.next_toggle(ms(600)), Some(ms(1000)));
+
+        caret.reset(ms(700));
+        assert!(caret.visible_at(ms(700)));
+        assert!(!caret.visible_at(ms(1200)));
+
+        caret.set_mode(Mode::Insert, ms(1300));
+        assert_eq!(caret.style.shape, CaretShape::Bar);
+        assert!(caret.visible_at(ms(1300)));
+
+        caret.style.blink_ms = None;
+        assert!(caret.visible_at(ms(1800)));
+        assert_eq!(caret.next_toggle(ms(1800)), None);
+    }
+}
@@ -0,0 +1,176 @@
+use tree_sitter::{Node, Tree};
+
+/// Nodes whose lines can be folded away, like function bodies.
+const FOLDABLE_KINDS: &[&str] = &[
+    "block",
+    "declaration_list",
+    "field_declaration_list",
+    "enum_variant_list",
+    "match_block",
+    "use_list",
+    "block_comment",
+];
+
+/// Lines `start..=end`, collapsed into line `start`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Fold {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Fold {
+    fn contains(self, line: usize) -> bool {
+        (self.start..=self.end).contains(&line)
+    }
+
+    /// Whether folding hides `line`. The first line stays visible.
+    fn hides(self, line: usize) -> bool {
+        self.start < line && line <= self.end
+    }
+}
+
+/// Every range of `tree` that can be folded, ordered by start line.
+pub fn foldable(tree: &Tree) -> Vec<Fold> {
+    let mut folds = vec![];
+
+    collect(tree.root_node(), &mut folds);
+
+    folds.sort();
+    folds.dedup();
+
+    folds
+}
+
+fn collect(node: Node, folds: &mut Vec<Fold>) {
+    let fold = Fold {
+        start: node.start_position().row,
+        end: node.end_position().row,
+    };
+
+    if fold.end > fold.start && FOLDABLE_KINDS.contains(&node.kind()) {
+        folds.push(fold);
+    }
+
+    let mut cursor = node.walk();
+    let mut imports: Option<Fold> = None;
+
+    for child in node.children(&mut cursor) {
+        // Consecutive imports fold together.
+        if child.kind() == "use_declaration" {
+            let end = child.end_position().row;
+
+            imports = Some(match imports {
+                Some(imports) => Fold { end, ..imports },
+                None => Fold {
+                    start: child.start_position().row,
+                    end,
+                },
+            });
+        } else if let Some(imports) = imports.take() {
+            if imports.end > imports.start {
+                folds.push(imports);
+            }
+        }
+
+        collect(child, folds);
+    }
+
+    if let Some(imports) = imports.filter(|it| it.end > it.start) {
+        folds.push(imports);
+    }
+}
+
+/// The folds that are currently collapsed.
+///
+/// Folds inside a collapsed fold keep their state, and show up again as it was once the outer one is expanded.
+#[derive(Debug, Default)]
+pub struct Folds {
+    folded: Vec<Fold>,
+}
+
+impl Folds {
+    pub fn folded(&self) -> &[Fold] {
+        &self.folded
+    }
+
+    /// Expand the fold starting at `line`, or collapse the innermost of `foldable` around it.
+    pub fn toggle(&mut self, line: usize, foldable: &[Fold]) {
+        if let Some(idx) = self.folded.iter().position(|fold| fold.start == line) {
+            self.folded.remove(idx);
+
+            return;
+        }
+
+        let innermost = foldable
+            .iter()
+            .filter(|fold| fold.contains(line) && !self.folded.contains(fold))
+            .min_by_key(|fold| fold.end - fold.start);
+
+        if let Some(fold) = innermost {
+            self.folded.push(*fold);
+        }
+    }
+
+    /// Drop collapsed folds that are no longer in `foldable`, after an edit.
+    pub fn retain(&mut self, foldable: &[Fold]) {
+        self.folded.retain(|fold| foldable.contains(fold));
+    }
+
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.folded.iter().any(|fold| fold.hides(line))
+    }
+
+    /// Whether `line` is the visible first line of a collapsed fold.
+    pub fn is_folded(&self, line: usize) -> bool {
+        !self.is_hidden(line) && self.folded.iter().any(|fold| fold.start == line)
+    }
+
+    /// Row `line` is drawn at, counting only visible lines, or `None` when it is hidden.
+    pub fn display_line(&self, line: usize) -> Option<usize> {
+        if self.is_hidden(line) {
+            return None;
+        }
+
+        Some((0..line).filter(|line| !self.is_hidden(*line)).count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crop::Rope;
+
+    use super::{foldable, Fold, Folds};
+    use crate::ts::{self, Language};
+
+    #[test]
+    fn fold_function_body() {
+        let source = Rope::from(
+            "use std::fmt;\nuse std::io;\n\nfn main() {\n    if true {\n        let x = 1;\n    }\n}\n",
+        );
+        let tree = ts::tree(&source, Language::Rust, None).unwrap();
+
+        let ranges = foldable(&tree);
+        assert_eq!(
+            ranges,
+            vec![
+                Fold { start: 0, end: 1 },
+                Fold { start: 3, end: 7 },
+                Fold { start: 4, end: 6 },
+            ]
+        );
+
+        let mut folds = Folds::default();
+
+        // Innermost first, then the function around it.
+        folds.toggle(5, &ranges);
+        assert!(folds.is_hidden(5) && !folds.is_hidden(4));
+        folds.toggle(3, &ranges);
+        assert!(folds.is_folded(3) && !folds.is_folded(4));
+        assert_eq!(folds.display_line(8), Some(4));
+
+        // The inner fold is still collapsed once the function is expanded.
+        folds.toggle(3, &ranges);
+        assert!(folds.is_folded(4));
+        assert_eq!(folds.display_line(7), Some(5));
+    }
+}
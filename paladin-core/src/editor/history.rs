@@ -0,0 +1,183 @@
+use std::time::{Duration, Instant};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::Cursor;
+
+/// A change to the text, with what it takes to revert it.
+#[derive(Clone, Debug)]
+pub(super) struct Change {
+    pub(super) kind: ChangeKind,
+    /// Where the text was inserted or deleted.
+    pub(super) byte: usize,
+    pub(super) text: String,
+    pub(super) cursor_before: Cursor,
+    pub(super) cursor_after: Cursor,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum ChangeKind {
+    Insert,
+    Delete,
+}
+
+/// When typing joins the undo step before it, so one undo takes back a word rather than a letter.
+///
+/// A single character insert joins the step of the one before it, and so does a single character delete.
+/// They only join if they are next to each other, the cursor didn't move in between,
+/// and the pause between them is at most `window`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Coalescing {
+    pub enabled: bool,
+    pub window: Duration,
+}
+
+impl Default for Coalescing {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            window: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The changes one action made, in order.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Step {
+    pub(super) changes: Vec<Change>,
+    /// Tells the text this step leaves apart from that of any other step, see [History::revision].
+    revision: u64,
+}
+
+/// Undo and redo stacks of [Step]s.
+#[derive(Clone, Debug, Default)]
+pub(super) struct History {
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+    /// The revision of the last step, new steps count up from it.
+    latest: u64,
+    /// Open groups, changes recorded while above zero join the same step.
+    depth: usize,
+    pub(super) coalescing: Coalescing,
+    /// When the last step was typed, while the next change may still join it.
+    typed_at: Option<Instant>,
+}
+
+impl History {
+    pub(super) fn record(&mut self, change: Change) {
+        self.record_at(change, Instant::now());
+    }
+
+    /// Like [History::record], with the change made at `now`.
+    pub(super) fn record_at(&mut self, change: Change, now: Instant) {
+        // Redoing into a different history makes no sense.
+        self.redo.clear();
+
+        let joins = self.joins(&change, now);
+
+        if self.depth == 0 {
+            self.typed_at = typed(&change).then_some(now);
+        }
+
+        match self.undo.last_mut() {
+            Some(step) if self.depth > 0 => step.changes.push(change),
+            Some(step) if joins => {
+                // The joined step leaves a different text than before.
+                self.latest += 1;
+                step.revision = self.latest;
+                step.changes.push(change);
+            }
+            _ => {
+                let step = self.step(vec![change]);
+                self.undo.push(step);
+            }
+        }
+    }
+
+    /// A step of `changes` with a revision of its own.
+    fn step(&mut self, changes: Vec<Change>) -> Step {
+        self.latest += 1;
+
+        Step {
+            changes,
+            revision: self.latest,
+        }
+    }
+
+    /// Which text undoing and redoing got to, the same whenever it gets back to the same text, `0` before any change.
+    pub(super) fn revision(&self) -> u64 {
+        self.undo.last().map_or(0, |step| step.revision)
+    }
+
+    /// Whether `change` joins the last step, see [Coalescing].
+    fn joins(&self, change: &Change, now: Instant) -> bool {
+        let (Some(typed_at), Some(previous)) = (
+            self.typed_at,
+            self.undo.last().and_then(|step| step.changes.last()),
+        ) else {
+            return false;
+        };
+
+        let adjacent = match change.kind {
+            ChangeKind::Insert => previous.byte + previous.text.len() == change.byte,
+            // Backspace
+            ChangeKind::Delete => change.byte + change.text.len() == previous.byte,
+        };
+
+        self.coalescing.enabled
+            && self.depth == 0
+            && now.saturating_duration_since(typed_at) <= self.coalescing.window
+            && typed(change)
+            && previous.kind == change.kind
+            && previous.cursor_after == change.cursor_before
+            && adjacent
+    }
+
+    /// Start the next change on a step of its own, like after switching modes.
+    pub(super) fn seal(&mut self) {
+        self.typed_at = None;
+    }
+
+    /// Start recording changes as a single step, until the matching [History::end_group].
+    pub(super) fn start_group(&mut self) {
+        self.seal();
+
+        if self.depth == 0 {
+            let step = self.step(vec![]);
+            self.undo.push(step);
+        }
+
+        self.depth += 1;
+    }
+
+    pub(super) fn end_group(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+
+        if self.depth == 0 && self.undo.last().is_some_and(|step| step.changes.is_empty()) {
+            self.undo.pop();
+        }
+    }
+
+    pub(super) fn pop_undo(&mut self) -> Option<Step> {
+        self.seal();
+        self.undo.pop()
+    }
+
+    pub(super) fn pop_redo(&mut self) -> Option<Step> {
+        self.redo.pop()
+    }
+
+    pub(super) fn push_undo(&mut self, step: Step) {
+        self.seal();
+        self.undo.push(step);
+    }
+
+    pub(super) fn push_redo(&mut self, step: Step) {
+        self.redo.push(step);
+    }
+}
+
+/// Whether `change` is one typed character, which may join others into one step.
+fn typed(change: &Change) -> bool {
+    change.text.graphemes(true).count() == 1 && !change.text.contains('\n')
+}
@@ -0,0 +1,170 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use super::{Buffer, Cursor};
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Byte range of the word containing `byte`, or `None` when `byte` is not on a word character.
+pub fn word_at(line: &str, byte: usize) -> Option<Range<usize>> {
+    let c = line.get(byte..)?.chars().next()?;
+
+    if !is_word_char(c) {
+        return None;
+    }
+
+    let start = line[..byte]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_word_char(*c))
+        .last()
+        .map_or(byte, |(idx, _)| idx);
+
+    let end = line[byte..]
+        .char_indices()
+        .find(|(_, c)| !is_word_char(*c))
+        .map_or(line.len(), |(idx, _)| byte + idx);
+
+    Some(start..end)
+}
+
+/// Byte ranges of every whole-word occurrence of `word` in `line`.
+pub fn occurrences(line: &str, word: &str) -> Vec<Range<usize>> {
+    if word.is_empty() {
+        return vec![];
+    }
+
+    line.match_indices(word)
+        .map(|(start, _)| start..start + word.len())
+        .filter(|range| {
+            let before = line[..range.start].chars().next_back();
+            let after = line[range.end..].chars().next();
+
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        })
+        .collect()
+}
+
+/// Occurrences of the word under `cursor` in `lines`, as (line, byte range) pairs.
+/// Empty when the cursor is not on a word.
+pub fn matching_words<'a>(
+    lines: impl IntoIterator<Item = (usize, &'a str)>,
+    cursor_line: &str,
+    cursor: Cursor,
+) -> Vec<(usize, Range<usize>)> {
+    let Some(word) = word_at(cursor_line, cursor.byte) else {
+        return vec![];
+    };
+
+    let word = &cursor_line[word];
+
+    lines
+        .into_iter()
+        .flat_map(|(idx, line)| {
+            occurrences(line, word)
+                .into_iter()
+                .map(move |range| (idx, range))
+        })
+        .collect()
+}
+
+/// The occurrences of the word under the cursor, recomputed once the cursor has rested for `delay`.
+#[derive(Debug)]
+pub struct WordHighlights {
+    pub delay: Duration,
+    moved: Option<(Cursor, Instant)>,
+    computed_for: Option<Cursor>,
+    matches: Vec<(usize, Range<usize>)>,
+}
+
+impl WordHighlights {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            moved: None,
+            computed_for: None,
+            matches: vec![],
+        }
+    }
+
+    pub fn matches(&self) -> &[(usize, Range<usize>)] {
+        &self.matches
+    }
+
+    /// Track the cursor of `buffer`, searching `lines` once it has settled.
+    /// Returns when to call again, if the search is still pending.
+    pub fn update(
+        &mut self,
+        buffer: &Buffer,
+        lines: Range<usize>,
+        now: Instant,
+    ) -> Option<Instant> {
+        let cursor = buffer.cursor();
+
+        if self.computed_for == Some(cursor) {
+            return None;
+        }
+
+        let moved_at = match self.moved {
+            Some((pending, at)) if pending == cursor => at,
+            _ => {
+                // Stale highlights would point at the wrong word while waiting.
+                self.matches.clear();
+                self.moved = Some((cursor, now));
+                now
+            }
+        };
+
+        if now < moved_at + self.delay {
+            return Some(moved_at + self.delay);
+        }
+
+        let lines = lines.start..lines.end.min(buffer.line_len());
+        let text: Vec<_> = lines
+            .clone()
+            .map(|idx| buffer.line(idx).to_string())
+            .collect();
+        let cursor_line = buffer.line(cursor.line).to_string();
+
+        self.matches = matching_words(
+            lines.zip(text.iter().map(String::as_str)),
+            &cursor_line,
+            cursor,
+        );
+        self.computed_for = Some(cursor);
+        self.moved = None;
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Cursor;
+
+    use super::{matching_words, word_at};
+
+    #[test]
+    fn three_occurrences() {
+        let lines = ["let count = 1;", "count += count_max;", "print(count)"];
+
+        let matches = matching_words(
+            lines.iter().copied().enumerate(),
+            lines[0],
+            Cursor::from_line_byte(0, 6),
+        );
+
+        assert_eq!(matches, vec![(0, 4..9), (1, 0..5), (2, 6..11)]);
+    }
+
+    #[test]
+    fn not_on_whitespace() {
+        assert_eq!(word_at("let count", 3), None);
+        assert_eq!(word_at("let count", 8), Some(4..9));
+        assert!(matching_words([(0, "a a")], "a a", Cursor::from_line_byte(0, 1)).is_empty());
+    }
+}
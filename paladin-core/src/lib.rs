@@ -3,6 +3,7 @@ mod editor;
 pub use editor::*;
 
 pub mod lsp;
+pub mod problems;
 pub mod ts;
 
 pub type Result<T> = miette::Result<T>;
@@ -8,12 +8,20 @@ use std::{
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
+use bevy_reflect::Reflect;
+use miette::{IntoDiagnostic, WrapErr};
+
 use lsp_types::{
-    notification::{DidChangeTextDocument, DidOpenTextDocument, Initialized},
-    request::{Completion, HoverRequest, Initialize, Request},
+    notification::{DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, Initialized},
+    request::{
+        Completion, DocumentDiagnosticRequest, GotoDefinition, HoverRequest, Initialize, Request,
+    },
     CodeActionCapabilityResolveSupport, CompletionParams, DidChangeTextDocumentParams,
-    DidOpenTextDocumentParams, HoverParams, InitializedParams, PartialResultParams, Position,
-    PositionEncodingKind, TextDocumentContentChangeEvent, WorkspaceFolder,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DocumentDiagnosticParams,
+    DocumentDiagnosticReport, DocumentDiagnosticReportKind, DocumentDiagnosticReportResult,
+    GotoDefinitionParams, HoverParams, InitializedParams, PartialResultParams, Position,
+    PositionEncodingKind, PublishDiagnosticsParams, TextDocumentContentChangeEvent, Url,
+    WorkspaceFolder,
 };
 
 #[derive(Debug, Clone)]
@@ -25,7 +33,10 @@ pub struct LspResult {
 pub enum LspResultData {
     Hover(<HoverRequest as Request>::Result),
     Completion(<Completion as Request>::Result),
-    Initialized,
+    Definition(<GotoDefinition as Request>::Result),
+    // Published as diagnostics notifications, see [Diagnostics::pulled]
+    PulledDiagnostics(<DocumentDiagnosticRequest as Request>::Result),
+    Initialized(Box<<Initialize as Request>::Result>),
 }
 
 // LSP sends message
@@ -38,6 +49,7 @@ pub enum LspResponse {
 #[derive(Debug, Clone)]
 pub enum LspNotification {
     WorkDoneProgress(lsp_types::ProgressParams),
+    Diagnostics(lsp_types::PublishDiagnosticsParams),
 }
 
 // Requests to the LSP server
@@ -53,13 +65,23 @@ pub enum LspRequestData {
     // Request a hover
     Hover { line: u32, character: u32 },
     Completion { line: u32, character: u32 },
-    DidChange { edit: LspEdit },
+    GotoDefinition { line: u32, character: u32 },
+    // Changes in order, each in the text left by those before it
+    DidChange { edits: Vec<LspEdit> },
+    // Open the document, as it was left in a language the server serves
+    DidOpen { language_id: String, text: String },
+    // The buffer was closed
+    DidClose,
+    // Pull the document's diagnostics, for servers that don't push them
+    Diagnostics,
 }
 
 #[derive(Debug, Clone, Copy)]
 enum LspSendRequestKind {
     Hover,
     Completion,
+    GotoDefinition,
+    Diagnostics,
     Initialize,
 }
 
@@ -79,6 +101,25 @@ enum CalculatedReadResult {
     Unknown(serde_json::Value),
 }
 
+/// The language server to launch, and the language it serves.
+#[derive(Debug, Clone, PartialEq, Eq, Reflect)]
+pub struct LspConfig {
+    pub command: String,
+    pub args: Vec<String>,
+    /// Only buffers of this language, see [Language::lsp_id](crate::ts::Language::lsp_id), start the server.
+    pub language_id: String,
+}
+
+impl LspConfig {
+    pub fn rust_analyzer() -> Self {
+        Self {
+            command: "rust-analyzer".into(),
+            args: vec![],
+            language_id: "rust".into(),
+        }
+    }
+}
+
 pub trait LspResponseTransmitter: Clone + Send + 'static {
     type Error: std::error::Error;
 
@@ -87,26 +128,194 @@ pub trait LspResponseTransmitter: Clone + Send + 'static {
 
 pub struct Lsp {
     next_id: u32,
+    versions: Versions,
     sent_requests: Arc<Mutex<ahash::HashMap<u32, SentRequestData>>>,
+    diagnostics: Arc<Mutex<Diagnostics>>,
+    /// What the characters of positions count, as the server picked when initialized.
+    position_encoding: Arc<Mutex<PositionEncodingKind>>,
     writer: BufWriter<ChildStdin>,
     child: Child,
 }
 
+/// The diagnostics last published for each document, pushed by the server or pulled from it.
+/// Both can report the same diagnostics, they are only published when they change.
+#[derive(Debug, Default)]
+struct Diagnostics {
+    published: ahash::HashMap<Url, Vec<lsp_types::Diagnostic>>,
+    /// The id of each document's last report, so the server can answer a pull with "unchanged".
+    result_ids: ahash::HashMap<Url, String>,
+    /// The document each pull waiting for its report is for, by request id.
+    pulls: ahash::HashMap<u32, Url>,
+}
+
+impl Diagnostics {
+    /// The pushed `params` to publish, `None` if the document already has those diagnostics.
+    fn pushed(&mut self, params: PublishDiagnosticsParams) -> Option<PublishDiagnosticsParams> {
+        if self.published.get(&params.uri) == Some(&params.diagnostics) {
+            return None;
+        }
+
+        self.published
+            .insert(params.uri.clone(), params.diagnostics.clone());
+
+        Some(params)
+    }
+
+    /// The diagnostics to publish from a pulled report on `uri`, and on the documents related to it.
+    fn pulled(
+        &mut self,
+        uri: Url,
+        report: DocumentDiagnosticReportResult,
+    ) -> Vec<PublishDiagnosticsParams> {
+        let (report, related) = match report {
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Full(full)) => (
+                Some(DocumentDiagnosticReportKind::Full(
+                    full.full_document_diagnostic_report,
+                )),
+                full.related_documents,
+            ),
+            DocumentDiagnosticReportResult::Report(DocumentDiagnosticReport::Unchanged(
+                unchanged,
+            )) => (
+                Some(DocumentDiagnosticReportKind::Unchanged(
+                    unchanged.unchanged_document_diagnostic_report,
+                )),
+                unchanged.related_documents,
+            ),
+            DocumentDiagnosticReportResult::Partial(partial) => (None, partial.related_documents),
+        };
+
+        report
+            .map(|report| (uri, report))
+            .into_iter()
+            .chain(related.into_iter().flatten())
+            .filter_map(|(uri, report)| match report {
+                DocumentDiagnosticReportKind::Full(full) => {
+                    match full.result_id {
+                        Some(id) => self.result_ids.insert(uri.clone(), id),
+                        None => self.result_ids.remove(&uri),
+                    };
+
+                    self.pushed(PublishDiagnosticsParams {
+                        uri,
+                        diagnostics: full.items,
+                        version: None,
+                    })
+                }
+                DocumentDiagnosticReportKind::Unchanged(unchanged) => {
+                    self.result_ids.insert(uri, unchanged.result_id);
+
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// The version of each open document. Servers order changes by it, so it must keep increasing.
+#[derive(Debug, Default)]
+struct Versions {
+    /// Keyed by canonical path.
+    versions: ahash::HashMap<PathBuf, i32>,
+    /// The canonical path of each document, by the path requests name it with.
+    canonical: ahash::HashMap<PathBuf, PathBuf>,
+}
+
+impl Versions {
+    /// The version to open `file` with. Reopening continues from the last version.
+    fn open(&mut self, file: &Path) -> i32 {
+        let canonical = file
+            .canonicalize()
+            .or_else(|_| std::path::absolute(file))
+            .unwrap_or_else(|_| file.to_owned());
+        self.canonical.insert(file.to_owned(), canonical);
+
+        self.next(file)
+    }
+
+    /// The version after an edit to `file`.
+    fn change(&mut self, file: &Path) -> i32 {
+        self.next(file)
+    }
+
+    /// The path `file` was opened by. The document is opened by its canonical path,
+    /// but requests name it however the buffer was opened.
+    fn path<'a>(&'a self, file: &'a Path) -> &'a Path {
+        self.canonical.get(file).map_or(file, PathBuf::as_path)
+    }
+
+    /// The URI of the document `file` was opened as, see [Versions::path].
+    fn uri(&self, file: &Path) -> crate::Result<Url> {
+        let path = self.path(file);
+
+        Url::from_file_path(path)
+            .map_err(|()| miette::miette!("{} is not an absolute path", path.display()))
+    }
+
+    /// Open `file` as a document of `language_id` holding `text`.
+    fn did_open(
+        &mut self,
+        file: &Path,
+        language_id: String,
+        text: String,
+    ) -> crate::Result<DidOpenTextDocumentParams> {
+        let version = self.open(file);
+
+        Ok(DidOpenTextDocumentParams {
+            text_document: lsp_types::TextDocumentItem {
+                uri: self.uri(file)?,
+                language_id,
+                version,
+                text,
+            },
+        })
+    }
+
+    /// Close the document `file` was opened as, forgetting its version.
+    fn did_close(&mut self, file: &Path) -> crate::Result<DidCloseTextDocumentParams> {
+        let uri = self.uri(file)?;
+
+        if let Some(canonical) = self.canonical.remove(file) {
+            self.versions.remove(&canonical);
+        }
+
+        Ok(DidCloseTextDocumentParams {
+            text_document: lsp_types::TextDocumentIdentifier { uri },
+        })
+    }
+
+    fn next(&mut self, file: &Path) -> i32 {
+        let version = self.versions.entry(self.path(file).to_owned()).or_insert(0);
+        *version += 1;
+
+        *version
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct SentRequestData {
     kind: LspSendRequestKind,
 }
 
 impl Lsp {
-    fn new() -> (Self, BufReader<ChildStdout>) {
-        let mut command = std::process::Command::new("rust-analyzer");
+    fn new(
+        config: &LspConfig,
+        position_encoding: Arc<Mutex<PositionEncodingKind>>,
+    ) -> crate::Result<(Self, BufReader<ChildStdout>)> {
+        let mut command = std::process::Command::new(&config.command);
 
-        command.stdin(Stdio::piped()).stdout(Stdio::piped());
+        command
+            .args(&config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped());
 
         #[cfg(target_os = "windows")]
         command.creation_flags(0x08000000);
 
-        let mut child = command.spawn().expect("Failed to start child");
+        let mut child = command
+            .spawn()
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to start {}", config.command))?;
 
         let stdin = child.stdin.take().unwrap();
         let stdout = child.stdout.take().unwrap();
@@ -116,15 +325,24 @@ impl Lsp {
 
         let this = Self {
             next_id: 0,
+            versions: Versions::default(),
             sent_requests: Arc::new(Mutex::new(Default::default())),
+            diagnostics: Arc::new(Mutex::new(Default::default())),
+            position_encoding,
             child,
             writer,
         };
 
-        (this, reader)
+        Ok((this, reader))
     }
 
-    fn init(&mut self, reader: &mut BufReader<ChildStdout>, workspace: &Path, file: &Path) {
+    fn init(
+        &mut self,
+        reader: &mut BufReader<ChildStdout>,
+        workspace: &Path,
+        file: &Path,
+        language_id: String,
+    ) -> crate::Result<()> {
         let params = init_params(workspace);
 
         let initialize_request = jsonrpc::request::<Initialize>(
@@ -140,44 +358,56 @@ impl Lsp {
             jsonrpc::read(reader, &self.sent_requests, &mut vec![], &mut String::new());
 
         match initialize_result {
-            CalculatedReadResult::Response { .. } => {}
+            CalculatedReadResult::Response {
+                result: LspResultData::Initialized(result),
+                ..
+            } => {
+                *self.position_encoding.lock().unwrap() = negotiated_encoding(&result);
+            }
             _ => panic!("Expected initialize result after Initialize notification"),
         }
 
         let initialized_notification = jsonrpc::notification::<Initialized>(InitializedParams {});
         self.write_immediate(&initialized_notification);
 
-        let path = file.canonicalize().expect("Path to exist");
-
-        let file = std::fs::read_to_string(&path).unwrap();
-        let message = jsonrpc::notification::<DidOpenTextDocument>(DidOpenTextDocumentParams {
-            text_document: lsp_types::TextDocumentItem {
-                uri: url::Url::from_file_path(&path).unwrap(),
-                language_id: "rust".into(),
-                version: 1,
-                text: file,
-            },
-        });
+        let text = std::fs::read_to_string(file)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("Failed to open {}", file.display()))?;
+        let message = jsonrpc::notification::<DidOpenTextDocument>(self.versions.did_open(
+            file,
+            language_id,
+            text,
+        )?);
 
         self.write_immediate(&message);
+
+        Ok(())
     }
 
     fn send(sender: &impl LspResponseTransmitter, event: LspResponse) {
         sender.send(event).expect("Sending LSP event to work");
     }
 
+    /// Start the server described by `config`, answering `receiver`'s requests on a background thread.
+    /// Once initialized, `position_encoding` is set to the encoding the server picked.
     pub fn run(
+        config: &LspConfig,
         receiver: Receiver<LspRequest>,
         sender: impl LspResponseTransmitter,
         workspace: PathBuf,
         file: PathBuf,
-    ) {
-        let (mut lsp, mut reader) = Self::new();
+        position_encoding: Arc<Mutex<PositionEncodingKind>>,
+    ) -> crate::Result<()> {
+        let (mut lsp, mut reader) = Self::new(config, position_encoding)?;
+        let language_id = config.language_id.clone();
 
         std::thread::spawn(move || {
-            lsp.init(&mut reader, &workspace, &file);
+            if let Err(err) = lsp.init(&mut reader, &workspace, &file, language_id) {
+                dbg!("WARN: ", err);
+            }
 
             let sent_requests = lsp.sent_requests.clone();
+            let diagnostics = lsp.diagnostics.clone();
 
             // Spawn the receiver
             std::thread::spawn(move || {
@@ -198,7 +428,23 @@ impl Lsp {
                                 .remove(&id)
                                 .expect("Receiver to remove request ids");
 
-                            Self::send(&sender, LspResponse::Result(LspResult { data: result }))
+                            if let LspResultData::PulledDiagnostics(report) = result {
+                                let mut diagnostics = diagnostics.lock().unwrap();
+                                let Some(uri) = diagnostics.pulls.remove(&id) else {
+                                    continue;
+                                };
+
+                                for params in diagnostics.pulled(uri, report) {
+                                    Self::send(
+                                        &sender,
+                                        LspResponse::Notification(LspNotification::Diagnostics(
+                                            params,
+                                        )),
+                                    )
+                                }
+                            } else {
+                                Self::send(&sender, LspResponse::Result(LspResult { data: result }))
+                            }
                         }
                         CalculatedReadResult::Request { params, .. } => {
                             dbg!("{params:?}");
@@ -210,6 +456,16 @@ impl Lsp {
                                     progress,
                                 )),
                             ),
+                            jsonrpc::NotificationParam::PublishDiagnostics(params) => {
+                                if let Some(params) = diagnostics.lock().unwrap().pushed(params) {
+                                    Self::send(
+                                        &sender,
+                                        LspResponse::Notification(LspNotification::Diagnostics(
+                                            params,
+                                        )),
+                                    )
+                                }
+                            }
                         },
                         CalculatedReadResult::Unknown(value) => {
                             dbg!("Unprocessed jsonrpc message");
@@ -224,77 +480,157 @@ impl Lsp {
 
             lsp.child.kill().unwrap();
         });
+
+        Ok(())
     }
 
     fn run_sender(&mut self, request_receiver: Receiver<LspRequest>) {
-        while let Ok(event) = request_receiver.recv() {
-            let LspRequest { file, data } = event;
-
-            match data {
-                LspRequestData::Hover { line, character } => {
-                    let message = jsonrpc::request::<HoverRequest>(
-                        self.next_id(SentRequestData {
-                            kind: LspSendRequestKind::Hover,
-                        }),
-                        HoverParams {
-                            text_document_position_params: lsp_types::TextDocumentPositionParams {
-                                text_document: lsp_types::TextDocumentIdentifier {
-                                    uri: url::Url::from_file_path(&file).unwrap(),
-                                },
-                                position: Position { line, character },
-                            },
-                            work_done_progress_params: lsp_types::WorkDoneProgressParams {
-                                work_done_token: None,
-                            },
+        while let Ok(LspRequest { file, data }) = request_receiver.recv() {
+            if let Err(err) = self.send_request(&file, data) {
+                dbg!("WARN: ", err);
+            }
+        }
+    }
+
+    /// Send a request about `file` to the server, naming the document as it was opened.
+    fn send_request(&mut self, file: &Path, data: LspRequestData) -> crate::Result<()> {
+        match data {
+            LspRequestData::Hover { line, character } => {
+                let uri = self.versions.uri(file)?;
+
+                let message = jsonrpc::request::<HoverRequest>(
+                    self.next_id(SentRequestData {
+                        kind: LspSendRequestKind::Hover,
+                    }),
+                    HoverParams {
+                        text_document_position_params: lsp_types::TextDocumentPositionParams {
+                            text_document: lsp_types::TextDocumentIdentifier { uri },
+                            position: Position { line, character },
+                        },
+                        work_done_progress_params: lsp_types::WorkDoneProgressParams {
+                            work_done_token: None,
                         },
-                    );
+                    },
+                );
 
-                    self.write_immediate(&message);
-                }
-                LspRequestData::Completion { line, character } => {
-                    let message = jsonrpc::request::<Completion>(
-                        self.next_id(SentRequestData {
-                            kind: LspSendRequestKind::Completion,
-                        }),
-                        CompletionParams {
-                            text_document_position: lsp_types::TextDocumentPositionParams {
-                                text_document: lsp_types::TextDocumentIdentifier {
-                                    uri: url::Url::from_file_path(&file).unwrap(),
-                                },
-                                position: Position { line, character },
-                            },
-                            work_done_progress_params: lsp_types::WorkDoneProgressParams {
-                                work_done_token: None,
-                            },
-                            partial_result_params: PartialResultParams {
-                                partial_result_token: None,
-                            },
-                            context: None,
+                self.write_immediate(&message);
+            }
+            LspRequestData::Completion { line, character } => {
+                let uri = self.versions.uri(file)?;
+
+                let message = jsonrpc::request::<Completion>(
+                    self.next_id(SentRequestData {
+                        kind: LspSendRequestKind::Completion,
+                    }),
+                    CompletionParams {
+                        text_document_position: lsp_types::TextDocumentPositionParams {
+                            text_document: lsp_types::TextDocumentIdentifier { uri },
+                            position: Position { line, character },
+                        },
+                        work_done_progress_params: lsp_types::WorkDoneProgressParams {
+                            work_done_token: None,
                         },
-                    );
+                        partial_result_params: PartialResultParams {
+                            partial_result_token: None,
+                        },
+                        context: None,
+                    },
+                );
 
-                    self.write_immediate(&message);
-                }
-                LspRequestData::DidChange { edit } => {
-                    let message = jsonrpc::notification::<DidChangeTextDocument>(
-                        DidChangeTextDocumentParams {
-                            text_document: lsp_types::VersionedTextDocumentIdentifier {
-                                // TODO
-                                version: 0,
-                                uri: url::Url::from_file_path(&file).unwrap(),
-                            },
-                            content_changes: vec![TextDocumentContentChangeEvent {
+                self.write_immediate(&message);
+            }
+            LspRequestData::GotoDefinition { line, character } => {
+                let uri = self.versions.uri(file)?;
+
+                let message = jsonrpc::request::<GotoDefinition>(
+                    self.next_id(SentRequestData {
+                        kind: LspSendRequestKind::GotoDefinition,
+                    }),
+                    GotoDefinitionParams {
+                        text_document_position_params: lsp_types::TextDocumentPositionParams {
+                            text_document: lsp_types::TextDocumentIdentifier { uri },
+                            position: Position { line, character },
+                        },
+                        work_done_progress_params: lsp_types::WorkDoneProgressParams {
+                            work_done_token: None,
+                        },
+                        partial_result_params: PartialResultParams {
+                            partial_result_token: None,
+                        },
+                    },
+                );
+
+                self.write_immediate(&message);
+            }
+            LspRequestData::DidChange { edits } => {
+                let uri = self.versions.uri(file)?;
+
+                let message =
+                    jsonrpc::notification::<DidChangeTextDocument>(DidChangeTextDocumentParams {
+                        text_document: lsp_types::VersionedTextDocumentIdentifier {
+                            version: self.versions.change(file),
+                            uri,
+                        },
+                        content_changes: edits
+                            .into_iter()
+                            .map(|edit| TextDocumentContentChangeEvent {
                                 range: Some(edit.range),
                                 text: edit.text,
                                 range_length: None,
-                            }],
+                            })
+                            .collect(),
+                    });
+
+                self.write_immediate(&message)
+            }
+            LspRequestData::Diagnostics => {
+                let uri = self.versions.uri(file)?;
+
+                let id = self.next_id(SentRequestData {
+                    kind: LspSendRequestKind::Diagnostics,
+                });
+                let previous_result_id = {
+                    let mut diagnostics = self.diagnostics.lock().unwrap();
+                    diagnostics.pulls.insert(id, uri.clone());
+
+                    diagnostics.result_ids.get(&uri).cloned()
+                };
+
+                let message = jsonrpc::request::<DocumentDiagnosticRequest>(
+                    id,
+                    DocumentDiagnosticParams {
+                        text_document: lsp_types::TextDocumentIdentifier { uri },
+                        identifier: None,
+                        previous_result_id,
+                        work_done_progress_params: lsp_types::WorkDoneProgressParams {
+                            work_done_token: None,
+                        },
+                        partial_result_params: PartialResultParams {
+                            partial_result_token: None,
                         },
-                    );
+                    },
+                );
 
-                    self.write_immediate(&message)
-                }
+                self.write_immediate(&message);
+            }
+            LspRequestData::DidClose => {
+                let message =
+                    jsonrpc::notification::<DidCloseTextDocument>(self.versions.did_close(file)?);
+
+                self.write_immediate(&message);
+            }
+            LspRequestData::DidOpen { language_id, text } => {
+                let open = jsonrpc::notification::<DidOpenTextDocument>(self.versions.did_open(
+                    file,
+                    language_id,
+                    text,
+                )?);
+
+                self.write_immediate(&open);
             }
         }
+
+        Ok(())
     }
 
     fn write_immediate(&mut self, message: &str) {
@@ -329,7 +665,10 @@ mod jsonrpc {
 
     use lsp_types::{
         notification::Notification,
-        request::{Completion, HoverRequest, Request},
+        request::{
+            Completion, DocumentDiagnosticRequest, GotoDefinition, HoverRequest, Initialize,
+            Request,
+        },
     };
     use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
@@ -377,6 +716,8 @@ mod jsonrpc {
     pub enum NotificationParam {
         #[serde(rename = "$/progress")]
         Progress(lsp_types::ProgressParams),
+        #[serde(rename = "textDocument/publishDiagnostics")]
+        PublishDiagnostics(lsp_types::PublishDiagnosticsParams),
     }
 
     pub fn request<T: Request>(id: u32, params: T::Params) -> String {
@@ -496,7 +837,19 @@ mod jsonrpc {
                         LspSendRequestKind::Completion => {
                             LspResultData::Completion(deser_request::<Completion>(buffer_vec))
                         }
-                        LspSendRequestKind::Initialize => LspResultData::Initialized,
+                        LspSendRequestKind::GotoDefinition => {
+                            LspResultData::Definition(deser_request::<GotoDefinition>(buffer_vec))
+                        }
+                        LspSendRequestKind::Diagnostics => {
+                            LspResultData::PulledDiagnostics(deser_request::<
+                                DocumentDiagnosticRequest,
+                            >(
+                                buffer_vec
+                            ))
+                        }
+                        LspSendRequestKind::Initialize => LspResultData::Initialized(Box::new(
+                            deser_request::<Initialize>(buffer_vec),
+                        )),
                     },
                 }
             }
@@ -519,6 +872,15 @@ mod jsonrpc {
     }
 }
 
+/// The encoding the server picked from the ones offered in [init_params], UTF-16 if it didn't say.
+fn negotiated_encoding(result: &lsp_types::InitializeResult) -> PositionEncodingKind {
+    result
+        .capabilities
+        .position_encoding
+        .clone()
+        .unwrap_or(PositionEncodingKind::UTF16)
+}
+
 fn init_params(workspace: &Path) -> lsp_types::InitializeParams {
     lsp_types::InitializeParams {
         process_id: Some(std::process::id()),
@@ -599,6 +961,10 @@ fn init_params(workspace: &Path) -> lsp_types::InitializeParams {
                     context_support: None, // additional context information Some(true)
                     ..Default::default()
                 }),
+                diagnostic: Some(lsp_types::DiagnosticClientCapabilities {
+                    dynamic_registration: Some(false),
+                    related_document_support: Some(true),
+                }),
                 hover: Some(lsp_types::HoverClientCapabilities {
                     // if not specified, rust-analyzer returns plaintext marked as markdown but
                     // badly formatted.
@@ -682,4 +1048,189 @@ fn init_params(workspace: &Path) -> lsp_types::InitializeParams {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use lsp_types::{InitializeResult, PositionEncodingKind, ServerCapabilities};
+
+    use super::{jsonrpc::NotificationParam, negotiated_encoding, Diagnostics, Versions};
+
+    #[test]
+    fn versions_increase() {
+        let mut versions = Versions::default();
+        let main = Path::new("/src/main.rs");
+        let lib = Path::new("/src/lib.rs");
+
+        assert_eq!(versions.open(main), 1);
+        assert_eq!(versions.change(main), 2);
+        assert_eq!(versions.change(main), 3);
+
+        // Counted per document
+        assert_eq!(versions.open(lib), 1);
+
+        // Reopening doesn't go back
+        assert_eq!(versions.open(main), 4);
+        assert_eq!(versions.change(main), 5);
+    }
+
+    #[test]
+    fn versions_follow_the_opened_path() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("main.rs"), "").unwrap();
+        let file = dir.path().join("src/../main.rs");
+
+        let mut versions = Versions::default();
+        assert_eq!(versions.open(&file), 1);
+        assert_eq!(
+            versions.path(&file),
+            dir.path().canonicalize().unwrap().join("main.rs")
+        );
+
+        // Edits don't look at the file again, it was canonicalized when opened
+        std::fs::remove_file(dir.path().join("main.rs")).unwrap();
+        assert_eq!(versions.change(&file), 2);
+        let canonical = versions.path(&file).to_owned();
+        assert_eq!(versions.change(&canonical), 3);
+    }
+
+    /// A `main.rs` in a new directory under the working one, and the relative path to it.
+    fn relative_main() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir_in(".").unwrap();
+        std::fs::write(dir.path().join("main.rs"), "").unwrap();
+        let file = dir
+            .path()
+            .strip_prefix(std::env::current_dir().unwrap())
+            .unwrap_or(dir.path())
+            .join("main.rs");
+        assert!(file.is_relative());
+
+        (dir, file)
+    }
+
+    #[test]
+    fn relative_paths_name_the_opened_document() {
+        let (dir, file) = relative_main();
+
+        let mut versions = Versions::default();
+        versions.open(&file);
+
+        let uri = versions.uri(&file).unwrap();
+        assert_eq!(
+            uri.to_file_path().unwrap(),
+            dir.path().canonicalize().unwrap().join("main.rs")
+        );
+        assert_eq!(versions.uri(versions.path(&file)).unwrap(), uri);
+
+        // Never opened, there is no document to name
+        assert!(versions.uri(Path::new("src/missing.rs")).is_err());
+    }
+
+    #[test]
+    fn closes_the_opened_document() {
+        let (_dir, file) = relative_main();
+        let mut versions = Versions::default();
+
+        let open = versions
+            .did_open(&file, "rust".into(), String::new())
+            .unwrap();
+        let close = versions.did_close(&file).unwrap();
+        assert_eq!(close.text_document.uri, open.text_document.uri);
+
+        // Forgotten once closed
+        assert!(versions.uri(&file).is_err());
+        assert_eq!(versions.open(&file), 1);
+    }
+
+    #[test]
+    fn uses_the_picked_encoding() {
+        let result = |position_encoding| InitializeResult {
+            capabilities: ServerCapabilities {
+                position_encoding,
+                ..Default::default()
+            },
+            server_info: None,
+        };
+
+        assert_eq!(
+            negotiated_encoding(&result(Some(PositionEncodingKind::UTF8))),
+            PositionEncodingKind::UTF8
+        );
+
+        // Servers that don't say use UTF-16
+        assert_eq!(
+            negotiated_encoding(&result(None)),
+            PositionEncodingKind::UTF16
+        );
+    }
+
+    #[test]
+    fn parses_diagnostics() {
+        let message = r#"{
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": {
+                "uri": "file:///src/main.rs",
+                "version": 3,
+                "diagnostics": [{
+                    "range": {
+                        "start": { "line": 1, "character": 4 },
+                        "end": { "line": 1, "character": 9 }
+                    },
+                    "severity": 1,
+                    "message": "cannot find value `x` in this scope"
+                }]
+            }
+        }"#;
+
+        let NotificationParam::PublishDiagnostics(params) = serde_json::from_str(message).unwrap()
+        else {
+            panic!("Expected diagnostics");
+        };
+
+        assert_eq!(params.version, Some(3));
+        assert_eq!(params.diagnostics.len(), 1);
+        assert_eq!(params.diagnostics[0].range.start.character, 4);
+        assert_eq!(
+            params.diagnostics[0].severity,
+            Some(lsp_types::DiagnosticSeverity::ERROR)
+        );
+    }
+
+    #[test]
+    fn pulled_report_is_published_once() {
+        let report = r#"{
+            "kind": "full",
+            "resultId": "1",
+            "items": [{
+                "range": {
+                    "start": { "line": 2, "character": 8 },
+                    "end": { "line": 2, "character": 9 }
+                },
+                "severity": 2,
+                "message": "unused variable: `x`"
+            }]
+        }"#;
+        let uri = lsp_types::Url::parse("file:///src/main.rs").unwrap();
+        let mut diagnostics = Diagnostics::default();
+
+        let published = diagnostics.pulled(uri.clone(), serde_json::from_str(report).unwrap());
+        assert_eq!(published.len(), 1);
+        assert_eq!(published[0].uri, uri);
+        assert_eq!(published[0].diagnostics[0].message, "unused variable: `x`");
+        assert_eq!(
+            published[0].diagnostics[0].severity,
+            Some(lsp_types::DiagnosticSeverity::WARNING)
+        );
+        assert_eq!(diagnostics.result_ids[&uri], "1");
+
+        // Pushing the same diagnostics publishes nothing new
+        let pushed = published[0].clone();
+        assert!(diagnostics.pushed(pushed).is_none());
+
+        let unchanged = r#"{ "kind": "unchanged", "resultId": "2" }"#;
+        let published = diagnostics.pulled(uri.clone(), serde_json::from_str(unchanged).unwrap());
+        assert!(published.is_empty());
+        assert_eq!(diagnostics.result_ids[&uri], "2");
+    }
+}
@@ -0,0 +1,208 @@
+//! Diagnostics of every open file, gathered to list the problems in the workspace.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use lsp_types::{Diagnostic, DiagnosticSeverity, PublishDiagnosticsParams};
+
+use crate::Buffer;
+
+/// The diagnostics last published for each file.
+#[derive(Debug, Default, Clone)]
+pub struct Problems {
+    files: BTreeMap<PathBuf, Vec<Diagnostic>>,
+}
+
+/// The diagnostics of one severity in a file, ordered by where they start.
+#[derive(Debug)]
+pub struct Group<'a> {
+    pub path: &'a Path,
+    pub severity: DiagnosticSeverity,
+    pub diagnostics: Vec<&'a Diagnostic>,
+}
+
+impl Problems {
+    /// The diagnostics of every buffer that is a file.
+    pub fn from_buffers<'a>(buffers: impl IntoIterator<Item = &'a Buffer>) -> Self {
+        let mut problems = Self::default();
+
+        for buffer in buffers {
+            if let Some(path) = buffer.buffer.path() {
+                problems.set(path.to_owned(), buffer.diagnostics().to_vec());
+            }
+        }
+
+        problems
+    }
+
+    /// Replace the diagnostics of the file the language server published for.
+    /// Documents that aren't files are ignored.
+    pub fn publish(&mut self, params: PublishDiagnosticsParams) {
+        if let Ok(path) = params.uri.to_file_path() {
+            self.set(path, params.diagnostics);
+        }
+    }
+
+    /// Replace the diagnostics of `path`, forgetting the file once it has none.
+    pub fn set(&mut self, path: PathBuf, mut diagnostics: Vec<Diagnostic>) {
+        if diagnostics.is_empty() {
+            self.files.remove(&path);
+            return;
+        }
+
+        diagnostics.sort_by_key(|it| it.range.start);
+        self.files.insert(path, diagnostics);
+    }
+
+    /// Diagnostics by file, and within a file by severity, the most severe first.
+    pub fn groups(&self) -> Vec<Group<'_>> {
+        let mut groups = vec![];
+
+        for (path, diagnostics) in &self.files {
+            let mut by_severity = BTreeMap::<_, Vec<_>>::new();
+
+            for diagnostic in diagnostics {
+                by_severity
+                    .entry(severity(diagnostic))
+                    .or_default()
+                    .push(diagnostic);
+            }
+
+            groups.extend(
+                by_severity
+                    .into_iter()
+                    .map(|(severity, diagnostics)| Group {
+                        path,
+                        severity,
+                        diagnostics,
+                    }),
+            );
+        }
+
+        groups
+    }
+
+    /// How many diagnostics of `severity` there are, in every file.
+    pub fn count(&self, severity: DiagnosticSeverity) -> usize {
+        self.files
+            .values()
+            .flatten()
+            .filter(|it| self::severity(it) == severity)
+            .count()
+    }
+
+    /// The diagnostics of `path`, in order.
+    pub fn diagnostics(&self, path: &Path) -> &[Diagnostic] {
+        self.files.get(path).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+}
+
+/// The severity of `diagnostic`, those without one count as errors.
+pub fn severity(diagnostic: &Diagnostic) -> DiagnosticSeverity {
+    diagnostic.severity.unwrap_or(DiagnosticSeverity::ERROR)
+}
+
+/// `file:line: message` with a one-based line, as listed.
+pub fn label(path: &Path, diagnostic: &Diagnostic) -> String {
+    format!(
+        "{}:{}: {}",
+        path.display(),
+        diagnostic.range.start.line + 1,
+        diagnostic.message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+    use super::{label, Problems};
+    use crate::{Buffer, SimpleBuffer};
+
+    #[test]
+    fn group_two_buffers() {
+        let diagnostic = |line, severity| Diagnostic {
+            range: Range::new(Position::new(line, 0), Position::new(line, 1)),
+            severity: Some(severity),
+            message: format!("on {line}"),
+            ..Default::default()
+        };
+
+        let open = |name: &str, diagnostics| {
+            let text = SimpleBuffer::from_str("fn main() {\n    let x = 1;\n}", Some(name.into()));
+
+            let mut buffer = Buffer::scratch(text);
+            buffer.set_diagnostics(diagnostics);
+            buffer
+        };
+
+        let a = open(
+            "paladin_problems_a.rs",
+            vec![
+                diagnostic(2, DiagnosticSeverity::WARNING),
+                diagnostic(1, DiagnosticSeverity::ERROR),
+                diagnostic(0, DiagnosticSeverity::WARNING),
+            ],
+        );
+        let b = open(
+            "paladin_problems_b.rs",
+            vec![diagnostic(1, DiagnosticSeverity::HINT)],
+        );
+        let scratch = {
+            let mut buffer = Buffer::scratch(SimpleBuffer::from_str("", None));
+            buffer.set_diagnostics(vec![diagnostic(0, DiagnosticSeverity::ERROR)]);
+            buffer
+        };
+
+        let problems = Problems::from_buffers([&a, &b, &scratch]);
+
+        assert_eq!(problems.count(DiagnosticSeverity::ERROR), 1);
+        assert_eq!(problems.count(DiagnosticSeverity::WARNING), 2);
+        assert_eq!(problems.count(DiagnosticSeverity::INFORMATION), 0);
+        assert_eq!(problems.count(DiagnosticSeverity::HINT), 1);
+
+        let groups = problems.groups();
+        let summary: Vec<_> = groups
+            .iter()
+            .map(|it| {
+                (
+                    it.path.file_name().unwrap(),
+                    it.severity,
+                    it.diagnostics.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "paladin_problems_a.rs".as_ref(),
+                    DiagnosticSeverity::ERROR,
+                    1
+                ),
+                (
+                    "paladin_problems_a.rs".as_ref(),
+                    DiagnosticSeverity::WARNING,
+                    2
+                ),
+                (
+                    "paladin_problems_b.rs".as_ref(),
+                    DiagnosticSeverity::HINT,
+                    1
+                ),
+            ]
+        );
+
+        let warnings = &groups[1];
+        assert_eq!(
+            label(warnings.path, warnings.diagnostics[1]),
+            format!("{}:3: on 2", warnings.path.display())
+        );
+    }
+}
@@ -1,5 +1,11 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex, OnceLock},
+};
+
 use crop::Rope;
-use tree_sitter::{Parser, Tree};
+use tree_sitter::{Parser, Query, Tree};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
@@ -19,27 +25,527 @@ impl Color {
     }
 }
 
-pub fn tree(source: &Rope, old_tree: Option<&Tree>) -> Tree {
+/// How text of a capture is drawn.
+#[derive(Debug, Clone, Copy)]
+pub struct HighlightStyle {
+    pub color: Color,
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl HighlightStyle {
+    pub fn plain(color: Color) -> Self {
+        Self {
+            color,
+            bold: false,
+            italic: false,
+        }
+    }
+
+    pub fn bold(self) -> Self {
+        Self { bold: true, ..self }
+    }
+
+    pub fn italic(self) -> Self {
+        Self {
+            italic: true,
+            ..self
+        }
+    }
+}
+
+impl From<Color> for HighlightStyle {
+    fn from(color: Color) -> Self {
+        Self::plain(color)
+    }
+}
+
+/// Styles of the capture names of highlight queries, like `keyword` or `function.method`.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    styles: HashMap<String, HighlightStyle, ahash::RandomState>,
+    /// Used for captures the theme has no style for.
+    pub fallback: HighlightStyle,
+}
+
+impl Theme {
+    /// A theme coloring each capture name, e.g. as read from a user's config.
+    pub fn from_pairs<S: Into<String>>(
+        pairs: impl IntoIterator<Item = (S, Color)>,
+        fallback: Color,
+    ) -> Self {
+        Self {
+            styles: pairs
+                .into_iter()
+                .map(|(capture, color)| (capture.into(), color.into()))
+                .collect(),
+            fallback: fallback.into(),
+        }
+    }
+
+    /// Style a capture, e.g. to make it bold.
+    pub fn set(&mut self, capture: impl Into<String>, style: HighlightStyle) {
+        self.styles.insert(capture.into(), style);
+    }
+
+    pub fn get(&self, capture: &str) -> Option<HighlightStyle> {
+        self.styles.get(capture).copied()
+    }
+
+    /// The style of `capture`, or the fallback.
+    pub fn style(&self, capture: &str) -> HighlightStyle {
+        self.get(capture).unwrap_or(self.fallback)
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let mut theme = Self::from_pairs(
+            [
+                ("constructor", Color::rgb(60, 69, 112)),
+                ("function", Color::rgb(234, 184, 120)),
+                ("function.method", Color::rgb(234, 184, 120)),
+                ("function.macro", Color::rgb(234, 184, 120)),
+                ("keyword", Color::rgb(204, 139, 96)),
+                ("punctuation.delimiter", Color::rgb(204, 139, 96)),
+                ("punctuation.bracket", Color::rgb(255, 255, 255)),
+                ("type", Color::rgb(60, 69, 112)),
+                ("type.builtin", Color::rgb(60, 69, 112)),
+                ("property", Color::rgb(130, 130, 200)),
+                ("string", Color::rgb(149, 175, 97)),
+                ("operator", Color::rgb(204, 139, 96)),
+                ("variable.builtin", Color::rgb(60, 69, 112)),
+                ("variable.parameter", Color::rgb(60, 69, 112)),
+                ("comment", Color::rgb(128, 128, 128)),
+                ("constant.builtin", Color::rgb(212, 252, 182)),
+                ("escape", Color::rgb(113, 10, 250)),
+                ("attribute", Color::rgb(219, 211, 186)),
+                ("label", Color::rgb(134, 173, 199)),
+            ],
+            Color::rgb(255, 0, 0),
+        );
+
+        // Markdown, like in doc comments
+        theme.set(
+            "text.strong",
+            HighlightStyle::plain(Color::rgb(219, 211, 186)).bold(),
+        );
+        theme.set(
+            "text.emphasis",
+            HighlightStyle::plain(Color::rgb(219, 211, 186)).italic(),
+        );
+        theme.set("text.literal", Color::rgb(149, 175, 97).into());
+
+        theme
+    }
+}
+
+/// The queries a grammar can come with, each powering a feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QueryKind {
+    /// Captures named after the [Theme] styles.
+    Highlights,
+    /// Scopes, and the definitions and references in them.
+    Locals,
+    /// Nodes whose contents are indented, and delimiters that close them.
+    Indents,
+    /// Ranges holding another language, see [injection].
+    Injections,
+}
+
+/// The language a buffer is highlighted, commented and served by the LSP as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Language {
+    Rust,
+    Toml,
+    Shell,
+    /// Markdown inside paragraphs, like the emphasis of a doc comment.
+    MarkdownInline,
+    PlainText,
+}
+
+impl Language {
+    /// Guess the language from the extension, then well-known file names, then a shebang on the first line.
+    pub fn detect(path: &Path, first_line: &str) -> Self {
+        let by_extension = path
+            .extension()
+            .and_then(|it| it.to_str())
+            .and_then(|extension| match extension {
+                "rs" => Some(Self::Rust),
+                "toml" => Some(Self::Toml),
+                "sh" | "bash" | "zsh" => Some(Self::Shell),
+                _ => None,
+            });
+
+        let by_name = || {
+            path.file_name()
+                .and_then(|it| it.to_str())
+                .and_then(|name| match name {
+                    "Cargo.lock" => Some(Self::Toml),
+                    ".bashrc" | ".bash_profile" | ".profile" | ".zshrc" => Some(Self::Shell),
+                    _ => None,
+                })
+        };
+
+        let by_shebang = || {
+            let interpreter = first_line.strip_prefix("#!")?.split_whitespace();
+            // `#!/usr/bin/env sh` names the interpreter as an argument.
+            let program = interpreter
+                .map(|it| it.rsplit('/').next().unwrap_or(it))
+                .find(|it| *it != "env")?;
+
+            match program {
+                "sh" | "bash" | "zsh" | "dash" => Some(Self::Shell),
+                "rust-script" | "cargo" => Some(Self::Rust),
+                _ => None,
+            }
+        };
+
+        by_extension
+            .or_else(by_name)
+            .or_else(by_shebang)
+            .unwrap_or(Self::PlainText)
+    }
+
+    /// The tree-sitter grammar, for the languages paladin can highlight.
+    pub fn grammar(self) -> Option<tree_sitter::Language> {
+        match self {
+            Self::Rust => Some(tree_sitter_rust::language()),
+            Self::MarkdownInline => Some(tree_sitter_md::inline_language()),
+            _ => None,
+        }
+    }
+
+    /// The language an injection query names, as in `(#set! injection.language "rust")`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rust" => Some(Self::Rust),
+            "toml" => Some(Self::Toml),
+            "bash" | "sh" | "shell" => Some(Self::Shell),
+            "markdown_inline" => Some(Self::MarkdownInline),
+            _ => None,
+        }
+    }
+
+    /// The source of the `kind` query, for the languages that have one.
+    /// Queries the grammar crate doesn't ship are in `queries/<language>`.
+    pub fn query(self, kind: QueryKind) -> Option<&'static str> {
+        match (self, kind) {
+            (Self::Rust, QueryKind::Highlights) => Some(tree_sitter_rust::HIGHLIGHT_QUERY),
+            (Self::Rust, QueryKind::Locals) => Some(include_str!("../queries/rust/locals.scm")),
+            (Self::Rust, QueryKind::Indents) => Some(include_str!("../queries/rust/indents.scm")),
+            (Self::Rust, QueryKind::Injections) => {
+                Some(include_str!("../queries/rust/injections.scm"))
+            }
+            (Self::MarkdownInline, QueryKind::Highlights) => {
+                Some(tree_sitter_md::HIGHLIGHT_QUERY_INLINE)
+            }
+            _ => None,
+        }
+    }
+
+    /// The token starting a line comment.
+    pub fn line_comment(self) -> Option<&'static str> {
+        match self {
+            Self::Rust => Some("//"),
+            Self::Toml | Self::Shell => Some("#"),
+            Self::MarkdownInline | Self::PlainText => None,
+        }
+    }
+
+    /// The `languageId` of a text document in this language.
+    pub fn lsp_id(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Toml => "toml",
+            Self::Shell => "shellscript",
+            Self::MarkdownInline => "markdown",
+            Self::PlainText => "plaintext",
+        }
+    }
+}
+
+/// The compiled queries of every language met so far, shared with the other buffers.
+#[derive(Debug, Default)]
+pub struct Queries {
+    /// `None` for languages without the query, or whose query doesn't compile.
+    compiled: Compiled,
+}
+
+type Compiled = HashMap<(Language, QueryKind), Option<Arc<Query>>>;
+
+impl Queries {
+    /// The `kind` query of `language`, compiled the first time any buffer asks for it.
+    pub fn load(&mut self, language: Language, kind: QueryKind) -> Option<&Query> {
+        self.compiled
+            .entry((language, kind))
+            .or_insert_with(|| shared(language, kind))
+            .as_deref()
+    }
+
+    /// The `kind` query of `language`, if it was [loaded](Queries::load) before.
+    pub fn get(&self, language: Language, kind: QueryKind) -> Option<&Query> {
+        self.compiled.get(&(language, kind))?.as_deref()
+    }
+}
+
+/// The `kind` query of `language`, compiled once for every buffer in the process.
+pub fn shared(language: Language, kind: QueryKind) -> Option<Arc<Query>> {
+    static COMPILED: OnceLock<Mutex<Compiled>> = OnceLock::new();
+
+    COMPILED
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap()
+        .entry((language, kind))
+        .or_insert_with(|| compile(language, language.query(kind)?).map(Arc::new))
+        .clone()
+}
+
+fn compile(language: Language, source: &str) -> Option<Query> {
+    match Query::new(&language.grammar()?, source) {
+        Ok(query) => Some(query),
+        Err(err) => {
+            dbg!("WARN: ", language, err);
+            None
+        }
+    }
+}
+
+/// Parse `source`, or `None` when the language has no grammar.
+pub fn tree(source: &Rope, language: Language, old_tree: Option<&Tree>) -> Option<Tree> {
     let mut parser = Parser::new();
 
-    parser.set_language(&tree_sitter_rust::language()).unwrap();
+    parser.set_language(&language.grammar()?).unwrap();
+
+    parse(&mut parser, source, old_tree)
+}
+
+fn parse(parser: &mut Parser, source: &Rope, old_tree: Option<&Tree>) -> Option<Tree> {
+    parser.parse_with(
+        &mut |a, _| {
+            let mut str = source.byte_slice(a..).chunks();
+
+            str.next().unwrap_or("")
+        },
+        old_tree,
+    )
+}
+
+/// Languages embedded in another, like Rust in macro bodies.
+///
+/// Each injected range is parsed with its own grammar into a [Layer], over the byte offsets of the whole document.
+pub mod injection {
+    use std::ops::Range;
+
+    use crop::Rope;
+    use tree_sitter::{Node, Parser, Point, QueryCursor, Tree};
+
+    use super::{highlight::RopeTextProvider, HighlightStyle, Language, Queries, QueryKind, Theme};
+
+    /// Injections nested deeper than this are left to their host.
+    const MAX_DEPTH: usize = 4;
+
+    #[derive(Debug)]
+    pub struct Layer {
+        pub language: Language,
+        pub tree: Tree,
+        /// 1 for injections into the document, 2 for injections into those, and so on.
+        pub depth: usize,
+    }
+
+    /// Parse the injections of `tree`, then the injections of those, shallowest first.
+    pub fn layers(
+        tree: &Tree,
+        language: Language,
+        source: &Rope,
+        queries: &mut Queries,
+    ) -> Vec<Layer> {
+        let mut layers: Vec<Layer> = injections(tree, language, source, queries)
+            .into_iter()
+            .map(|(language, tree)| Layer {
+                language,
+                tree,
+                depth: 1,
+            })
+            .collect();
+
+        let mut next = 0;
+
+        while let Some(layer) = layers.get(next) {
+            let depth = layer.depth + 1;
+
+            if depth <= MAX_DEPTH {
+                let nested = injections(&layer.tree, layer.language, source, queries);
+
+                layers.extend(nested.into_iter().map(|(language, tree)| Layer {
+                    language,
+                    tree,
+                    depth,
+                }));
+            }
+
+            next += 1;
+        }
+
+        for layer in &layers {
+            queries.load(layer.language, QueryKind::Highlights);
+        }
+
+        layers
+    }
+
+    fn injections(
+        tree: &Tree,
+        language: Language,
+        source: &Rope,
+        queries: &mut Queries,
+    ) -> Vec<(Language, Tree)> {
+        let Some(query) = queries.load(language, QueryKind::Injections) else {
+            return vec![];
+        };
+
+        let Some(content) = query.capture_index_for_name("injection.content") else {
+            return vec![];
+        };
 
-    parser
-        .parse_with(
-            &mut |a, _| {
-                let mut str = source.byte_slice(a..).chunks();
+        let mut cursor = QueryCursor::new();
+        let provider = RopeTextProvider {
+            inner: source.byte_slice(..),
+        };
+
+        let sites: Vec<_> = cursor
+            .matches(query, tree.root_node(), provider)
+            .filter_map(|found| {
+                let settings = query.property_settings(found.pattern_index);
+                let setting = |key: &str| settings.iter().find(|it| &*it.key == key);
+
+                let language =
+                    Language::from_name(setting("injection.language")?.value.as_deref()?)?;
+                let node = found.captures.iter().find(|it| it.index == content)?.node;
+
+                Some((
+                    language,
+                    ranges(node, setting("injection.include-children").is_some()),
+                ))
+            })
+            .collect();
+
+        sites
+            .into_iter()
+            // No included ranges would parse the whole document.
+            .filter(|(_, ranges)| !ranges.is_empty())
+            .filter_map(|(language, ranges)| {
+                let mut parser = Parser::new();
+                parser.set_language(&language.grammar()?).ok()?;
+                parser.set_included_ranges(&ranges).ok()?;
+
+                Some((language, super::parse(&mut parser, source, None)?))
+            })
+            .collect()
+    }
+
+    /// The ranges of `node`, leaving out its children unless they are included.
+    fn ranges(node: Node, include_children: bool) -> Vec<tree_sitter::Range> {
+        if include_children {
+            return vec![node.range()];
+        }
+
+        let span = |start: (usize, Point), end: (usize, Point)| tree_sitter::Range {
+            start_byte: start.0,
+            start_point: start.1,
+            end_byte: end.0,
+            end_point: end.1,
+        };
+
+        let mut ranges = vec![];
+        let mut start = (node.start_byte(), node.start_position());
+
+        let mut cursor = node.walk();
+        for child in node.children(&mut cursor) {
+            if child.start_byte() > start.0 {
+                ranges.push(span(start, (child.start_byte(), child.start_position())));
+            }
+
+            start = (child.end_byte(), child.end_position());
+        }
+
+        if node.end_byte() > start.0 {
+            ranges.push(span(start, (node.end_byte(), node.end_position())));
+        }
 
-                str.next().unwrap_or("")
+        ranges
+    }
+
+    /// Highlights of the layers on `lines`, as (line, style, byte range within the line), ordered by line.
+    /// Deeper layers come later on each line, so they are painted over their hosts.
+    pub fn line_spans(
+        layers: &[Layer],
+        queries: &Queries,
+        theme: &Theme,
+        source: &Rope,
+        lines: Range<usize>,
+    ) -> Vec<(usize, HighlightStyle, Range<usize>)> {
+        let source = source.byte_slice(..);
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_point_range(
+            Point {
+                row: lines.start,
+                column: 0,
+            }..Point {
+                row: lines.end,
+                column: usize::MAX,
             },
-            old_tree,
-        )
-        .unwrap()
+        );
+
+        let mut spans = vec![];
+
+        for layer in layers {
+            let Some(query) = queries.get(layer.language, QueryKind::Highlights) else {
+                continue;
+            };
+
+            let provider = RopeTextProvider { inner: source };
+
+            for (found, idx) in cursor.captures(query, layer.tree.root_node(), provider) {
+                let capture = found.captures[idx];
+                let name = query.capture_names()[capture.index as usize];
+
+                let Some(style) = theme.get(name) else {
+                    continue;
+                };
+
+                let (start, end) = (capture.node.start_byte(), capture.node.end_byte());
+
+                // A capture over several lines, like a block comment, gets a span on each.
+                for line in source.line_of_byte(start)..=source.line_of_byte(end) {
+                    if !lines.contains(&line) {
+                        continue;
+                    }
+
+                    let line_start = source.byte_of_line(line);
+                    let line_end = line_start + source.line(line).byte_len();
+                    let range = start.max(line_start) - line_start..end.min(line_end) - line_start;
+
+                    if !range.is_empty() {
+                        spans.push((line, style, range));
+                    }
+                }
+            }
+        }
+
+        // Stable, keeping deeper layers after shallower ones.
+        spans.sort_by_key(|(line, ..)| *line);
+
+        spans
+    }
 }
 
 pub mod highlight {
-    use std::{collections::HashMap, iter::Peekable, ops::Range};
+    use std::{collections::VecDeque, iter::Peekable, ops::Range};
 
-    use super::Color;
+    use super::{HighlightStyle, Theme};
     use crop::{Rope, RopeSlice};
     use tree_sitter::{Query, QueryCaptures, QueryCursor, TextProvider, Tree};
 
@@ -47,8 +553,10 @@ pub mod highlight {
         tree: &'tree Tree,
         cursor: &'query mut QueryCursor,
         query: &'query Query,
+        theme: &'query Theme,
         source: &'rope Rope,
         range: std::ops::Range<usize>,
+        injected: Vec<(usize, HighlightStyle, Range<usize>)>,
     ) -> LineHighlights<'query, 'tree, 'rope> {
         let source = source.byte_slice(..);
 
@@ -67,28 +575,6 @@ pub mod highlight {
         let root_node = tree.root_node();
         let captures = cursor.captures(query, root_node, provider);
 
-        let mut map = HashMap::with_hasher(ahash::RandomState::new());
-
-        map.insert("constructor", Color::rgb(60, 69, 112));
-        map.insert("function", Color::rgb(234, 184, 120));
-        map.insert("function.method", Color::rgb(234, 184, 120));
-        map.insert("function.macro", Color::rgb(234, 184, 120));
-        map.insert("keyword", Color::rgb(204, 139, 96));
-        map.insert("punctuation.delimiter", Color::rgb(204, 139, 96));
-        map.insert("punctuation.bracket", Color::rgb(255, 255, 255));
-        map.insert("type", Color::rgb(60, 69, 112));
-        map.insert("type.builtin", Color::rgb(60, 69, 112));
-        map.insert("property", Color::rgb(130, 130, 200));
-        map.insert("string", Color::rgb(149, 175, 97));
-        map.insert("operator", Color::rgb(204, 139, 96));
-        map.insert("variable.builtin", Color::rgb(60, 69, 112));
-        map.insert("variable.parameter", Color::rgb(60, 69, 112));
-        map.insert("comment", Color::rgb(128, 128, 128));
-        map.insert("constant.builtin", Color::rgb(212, 252, 182));
-        map.insert("escape", Color::rgb(113, 10, 250));
-        map.insert("attribute", Color::rgb(219, 211, 186));
-        map.insert("label", Color::rgb(134, 173, 199));
-
         let mut inner = captures.peekable();
 
         let byte = inner
@@ -107,7 +593,8 @@ pub mod highlight {
             inner,
             names: query.capture_names(),
             current: line,
-            map,
+            theme,
+            injected: injected.into(),
         }
     }
 
@@ -116,19 +603,39 @@ pub mod highlight {
         pub inner: Peekable<QueryCaptures<'query, 'tree, RopeTextProvider<'rope>, &'rope [u8]>>,
         pub names: &'query [&'query str],
         pub current: usize,
-        pub map: HashMap<&'static str, Color, ahash::RandomState>,
+        pub theme: &'query Theme,
+        /// Spans of injected languages, as (line, style, range), ordered by line.
+        pub injected: VecDeque<(usize, HighlightStyle, Range<usize>)>,
     }
 
     impl<'query, 'tree: 'query, 'rope> LineHighlights<'query, 'tree, 'rope> {
         pub fn next_line(&'_ mut self) -> Option<LineHighlight<'_, 'query, 'tree, 'rope>> {
-            let _ = self.inner.peek()?;
+            while self
+                .injected
+                .front()
+                .is_some_and(|(line, ..)| *line < self.current)
+            {
+                self.injected.pop_front();
+            }
+
+            if self.inner.peek().is_none() && self.injected.is_empty() {
+                return None;
+            }
+
+            let line = self.current;
 
-            Some(LineHighlight { iter: self })
+            Some(LineHighlight {
+                iter: self,
+                line,
+                host_done: false,
+            })
         }
     }
 
     pub struct LineHighlight<'parent, 'query, 'tree, 'rope> {
         pub iter: &'parent mut LineHighlights<'query, 'tree, 'rope>,
+        line: usize,
+        host_done: bool,
     }
 
     impl<'query, 'tree, 'rope> LineHighlight<'_, 'query, 'tree, 'rope> {
@@ -138,9 +645,33 @@ pub mod highlight {
     }
 
     impl<'query, 'tree, 'rope> Iterator for LineHighlight<'_, 'query, 'tree, 'rope> {
-        type Item = (Color, Range<usize>);
+        type Item = (HighlightStyle, Range<usize>);
 
         fn next(&mut self) -> Option<Self::Item> {
+            if !self.host_done {
+                if let Some(span) = self.host() {
+                    return Some(span);
+                }
+
+                self.host_done = true;
+                // The host may run out of captures before moving past the line.
+                self.iter.current = self.iter.current.max(self.line + 1);
+            }
+
+            // Injected spans come last, so they are painted over the host.
+            if self.iter.injected.front()?.0 != self.line {
+                return None;
+            }
+
+            self.iter
+                .injected
+                .pop_front()
+                .map(|(_, style, range)| (style, range))
+        }
+    }
+
+    impl<'query, 'tree, 'rope> LineHighlight<'_, 'query, 'tree, 'rope> {
+        fn host(&mut self) -> Option<(HighlightStyle, Range<usize>)> {
             let (capture, idx) = self.iter.inner.peek()?;
 
             let node = capture.captures[*idx].node;
@@ -180,11 +711,11 @@ pub mod highlight {
 
                 let kind = self.iter.names.get(capture.index as usize).unwrap();
 
-                let color = *self.iter.map.get(kind).unwrap();
+                let style = self.iter.theme.style(kind);
 
                 self.iter.current += 1;
 
-                return Some((color, range));
+                return Some((style, range));
             }
 
             debug_assert_eq!(line1, line2);
@@ -203,11 +734,11 @@ pub mod highlight {
 
             let kind = self.iter.names.get(capture.index as usize).unwrap();
 
-            let color = *self.iter.map.get(kind).unwrap_or(&Color::rgb(255, 0, 0));
+            let style = self.iter.theme.style(kind);
 
             let range = (node.start_byte() - start)..node.end_byte() - start;
 
-            Some((color, range))
+            Some((style, range))
         }
     }
 
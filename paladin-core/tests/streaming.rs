@@ -0,0 +1,55 @@
+//! Its own binary, so the counting allocator only sees this test.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+
+use paladin_core::SimpleBuffer;
+
+thread_local! {
+    static LARGEST_ALLOCATION: Cell<usize> = const { Cell::new(0) };
+}
+
+/// The system allocator, noting the largest allocation made on each thread.
+struct Counting;
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ =
+            LARGEST_ALLOCATION.try_with(|largest| largest.set(largest.get().max(layout.size())));
+
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+#[test]
+fn lines_stream_without_copying() {
+    let text: String = (0..10_000).map(|line| format!("line {line}\n")).collect();
+
+    let buffer = SimpleBuffer::from_str(&text, None);
+    LARGEST_ALLOCATION.with(|largest| largest.set(0));
+
+    let bytes: usize = buffer
+        .lines_in_range(100..200)
+        .map(|line| line.byte_len())
+        .sum();
+    let chunked: usize = buffer.chunks().map(str::len).sum();
+    let past_end = buffer.lines_in_range(9_990..20_000).count();
+
+    assert!(LARGEST_ALLOCATION.with(Cell::get) < text.len() / 2);
+    assert_eq!(bytes, 100 * "line 100".len());
+    assert_eq!(chunked, text.len());
+    assert_eq!(past_end, 10);
+    assert_eq!(
+        buffer.lines_in_range(100..101).next().unwrap().to_string(),
+        "line 100"
+    );
+}
@@ -1,20 +1,39 @@
 use std::{
-    collections::{HashMap, VecDeque},
+    cell::OnceCell,
+    collections::{HashMap, HashSet, VecDeque},
+    time::Instant,
     usize,
 };
 
-use bevy_reflect::{Reflect, TypeRegistry};
+use bevy_reflect::{
+    serde::{ReflectDeserializer, ReflectSerializer},
+    Reflect, TypeRegistry,
+};
+use bincode::Options;
+use cosmic_text::FontSystem;
+use femtovg::renderer::Renderer;
+use serde::{de::DeserializeSeed, Deserialize, Serialize};
 use taffy::{prelude::length, NodeId, Size, TaffyTree, TraversePartialTree};
-use winit::dpi::PhysicalSize;
+use winit::{
+    dpi::PhysicalSize,
+    event::ElementState,
+    keyboard::{Key, NamedKey},
+};
 
 use crate::{
-    BuildResult, Canvas, Element, InsertChildren, InsertContext, KeyEvent, Layout, MountedWidget,
-    Point, RebuildChildren, RebuildContext, ReflectStateTrait, View, Widget,
+    inspector::Inspector, BuildResult, Canvas, DisplayList, Element, EventContext, InsertChildren,
+    InsertContext, KeyEvent, Layout, MountedWidget, Point, RebuildChildren, RebuildContext,
+    ReflectStateTrait, RenderCache, View, ViewWidget, Widget, WidgetEvent,
 };
 
 pub(crate) struct App {
     tree: WidgetTree,
     registry: TypeRegistry,
+    inspector: Inspector,
+    /// Nodes under the pointer, topmost first.
+    hovered: Vec<NodeId>,
+    /// The only widget getting keys, see [Widget::accepts_focus].
+    focused: Option<NodeId>,
 }
 
 // Global events passed through from the event loop abstraction.
@@ -24,6 +43,14 @@ pub(crate) enum AppEvent {
     Resize(PhysicalSize<u32>),
     Clicked(u32, u32),
     Key(KeyEvent),
+    /// The wheel moved with the pointer at `x`, `y`, see [WidgetEvent::Scroll](crate::WidgetEvent::Scroll).
+    Scroll {
+        x: u32,
+        y: u32,
+        delta_x: f32,
+        delta_y: f32,
+    },
+    MouseMoved(u32, u32),
     Paint(PhysicalSize<u32>),
 }
 
@@ -37,30 +64,24 @@ impl App {
 
         Self {
             registry: type_registry,
+            focused: tree.initially_focused(),
             tree,
+            inspector: Inspector::default(),
+            hovered: vec![],
         }
     }
 }
 
 impl App {
-    pub(crate) fn event(&mut self, event: AppEvent, canvas: &mut Canvas) {
+    pub(crate) fn event<R: Renderer>(&mut self, event: AppEvent, canvas: &mut Canvas<R>) {
         match event {
             AppEvent::Clicked(x, y) => {
-                for (_, node) in iter_elements_from(&self.tree.taffy, self.tree.root) {
-                    let el = self.tree.widgets.get_mut(&node).unwrap();
-                    let layout: Layout = self.tree.taffy.layout(node).unwrap().clone().into();
-                    let MountedWidget::Button(_) = el else {
-                        continue;
-                    };
+                // Clicking anything that doesn't take keys takes the focus away.
+                let focus = self.tree.focusable_at(x, y);
+                self.focused = self.tree.move_focus(self.focused, focus);
 
-                    if layout.location.x < x
-                        && layout.location.y < y
-                        && x < layout.location.x + layout.size.width
-                        && y < layout.location.y + layout.size.height
-                    {
-                        el.event(crate::WidgetEvent::Click(x, y));
-                    }
-                }
+                self.tree
+                    .deliver(x, y, crate::WidgetEvent::Click(x, y), canvas.font_system());
             }
             AppEvent::Resize(new_size) => {
                 self.tree
@@ -78,16 +99,50 @@ impl App {
                     )
                     .expect("Root doesn't exist")
             }
+            AppEvent::Scroll {
+                x,
+                y,
+                delta_x,
+                delta_y,
+            } => {
+                let event = crate::WidgetEvent::Scroll {
+                    x,
+                    y,
+                    delta_x,
+                    delta_y,
+                };
+
+                self.tree.deliver(x, y, event, canvas.font_system());
+            }
+            AppEvent::MouseMoved(x, y) => {
+                let event = crate::WidgetEvent::PointerMove(x, y);
+                let hit = self.tree.deliver(x, y, event, canvas.font_system());
+
+                let hovered = self.tree.pointer_moved(&self.hovered, &hit);
+
+                // Most moves stay over the same widgets, nothing to rebuild for those.
+                if hovered == self.hovered {
+                    return;
+                }
+
+                self.hovered = hovered;
+            }
             AppEvent::Paint(size) => self.paint(size, canvas),
             AppEvent::Key(key_event) => {
-                for (_, node) in iter_elements_from(&self.tree.taffy, self.tree.root) {
-                    let el = self.tree.widgets.get_mut(&node).unwrap();
-                    let layout: Layout = self.tree.taffy.layout(node).unwrap().clone().into();
-                    let MountedWidget::Button(_) = el else {
-                        continue;
-                    };
+                if self.inspector.key(&key_event) {
+                    return;
+                }
 
-                    el.event(crate::WidgetEvent::Key(key_event.clone()));
+                // Tab moves the focus, rather than going to the focused widget.
+                if key_event.logical_key == Key::Named(NamedKey::Tab) {
+                    if key_event.state == ElementState::Pressed {
+                        let next = self.tree.next_focus(self.focused);
+                        self.focused = self.tree.move_focus(self.focused, next);
+                    }
+                } else if let Some(widget) =
+                    (self.focused).and_then(|node| self.tree.widgets.get_mut(&node))
+                {
+                    widget.event(crate::WidgetEvent::Key(key_event));
                 }
             }
         }
@@ -95,83 +150,187 @@ impl App {
         self.dirty()
     }
 
+    /// Whether any widget asked to be painted again, see [Widget::take_dirty].
+    pub(crate) fn take_dirty(&mut self) -> bool {
+        self.tree.take_dirty()
+    }
+
+    /// Returns whether a redraw is needed.
+    pub(crate) fn cursor_moved(&mut self, at: Point) -> bool {
+        self.inspector.cursor_moved(at)
+    }
+
     pub(crate) fn hint_dirty(&mut self, hint: NodeId) {
-        let mut dirty_views = vec![];
+        self.tree.hint_dirty(&mut self.registry, hint);
+    }
 
-        // iter_elements doesnt include the node itself
-        let from = self.tree.taffy.parent(hint).unwrap_or(hint);
+    pub(crate) fn dirty(&mut self) {
+        self.hint_dirty(self.tree.root);
+    }
 
-        for (_, node) in iter_elements_from(&self.tree.taffy, from) {
-            let Some(MountedView(view)) = self.tree.views.get_mut(&node) else {
-                continue;
-            };
+    fn paint<R: Renderer>(&mut self, size: winit::dpi::PhysicalSize<u32>, canvas: &mut Canvas<R>) {
+        self.tree.frame(&mut self.registry, Instant::now());
 
-            let mut is_dirty = false;
+        {
+            trace_span!("compute_layout", width = size.width, height = size.height);
 
-            iter_fields(view.as_reflect_mut(), |_, field| {
-                if let Some(reflect_state) = self
-                    .registry
-                    .get_type_data::<ReflectStateTrait>(field.type_id())
-                {
-                    let Some(state) = reflect_state.get_mut(field) else {
-                        return;
-                    };
+            self.tree.compute_layout(
+                Size {
+                    width: length(size.width as f32),
+                    height: length(size.height as f32),
+                },
+                &mut canvas.text_cache,
+            );
+        }
 
-                    if state.is_dirty() {
-                        state.process();
-                        is_dirty = true;
-                    }
+        self.tree.align_baselines(canvas.font_system());
+
+        canvas.text_cache.start_frame();
+
+        let mut list = DisplayList::default();
+        // Nodes drawn as part of a cached image
+        let mut cached = HashSet::new();
+
+        for (parent, node, layout) in self.tree.window_layouts().to_vec() {
+            if cached.contains(&parent) {
+                cached.insert(node);
+                continue;
+            }
+
+            if let Some(MountedWidget::Cached(widget)) = self.tree.widgets.get(&node) {
+                if widget.is_stale(layout.size) {
+                    render_cached(
+                        &self.tree.taffy,
+                        &mut self.tree.widgets,
+                        node,
+                        layout.size,
+                        canvas,
+                    );
                 }
-            });
 
-            if is_dirty {
-                dirty_views.push(node);
+                cached.insert(node);
+            }
+
+            let v = self.tree.widgets.get_mut(&node).unwrap();
+
+            trace!(node = ?node, widget = v.kind(), "paint");
+
+            if let MountedWidget::Image(image) = v {
+                image.prepare(canvas);
             }
+
+            layout_widget(v, layout, canvas);
+            render_widget(v, layout, &mut list);
+            list.replay(canvas);
         }
 
-        for dirty in dirty_views {
-            self.tree.modify_if_necessary(&mut self.registry, dirty);
+        self.inspector.paint(
+            &self.tree,
+            crate::Size {
+                width: size.width,
+                height: size.height,
+            },
+            canvas,
+        );
+    }
+}
+
+/// Lay out the widget, sharing shaped text between identical [Text](crate::Text)s.
+fn layout_widget<R: Renderer>(widget: &mut MountedWidget, layout: Layout, canvas: &mut Canvas<R>) {
+    match widget {
+        MountedWidget::Text(text) => text.layout_shared(layout, &mut canvas.text_cache),
+        MountedWidget::TextInput(input) => {
+            let cache = &mut canvas.text_cache;
+            input.layout_in(layout, &mut cache.font_system, &cache.fallbacks);
         }
+        widget => widget.layout(layout, canvas.font_system()),
     }
+}
 
-    fn dirty(&mut self) {
-        self.hint_dirty(self.tree.root);
+/// Paint the widget's background and border from its [Style](crate::Style), then let it render itself.
+fn render_widget(widget: &MountedWidget, layout: Layout, list: &mut DisplayList) {
+    let style = widget.style();
+    let radius = style.border_radius();
+    let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+    let (width, height) = (layout.size.width as f32, layout.size.height as f32);
+
+    match style.background() {
+        Some(color) if radius > 0. => list.rounded_rect(x, y, width, height, radius, color),
+        Some(color) => list.fill_rect(x, y, width, height, color),
+        None => {}
     }
 
-    fn paint(&mut self, size: winit::dpi::PhysicalSize<u32>, canvas: &mut Canvas) {
-        self.tree
-            .taffy
-            .compute_layout(
-                self.tree.root,
-                Size {
-                    width: length(size.width as f32),
-                    height: length(size.height as f32),
-                },
-            )
-            .unwrap();
+    if let Some(color) = style.border_color() {
+        list.border(layout, radius, color);
+    }
 
-        let mut acc_point = Point { x: 0, y: 0 };
-        let mut prev_parent = self.tree.root;
+    widget.render(layout, list);
+}
 
-        for (parent, node) in iter_elements_from(&self.tree.taffy, self.tree.root) {
-            let parent_layout = self.tree.taffy.layout(parent).unwrap();
+/// Render the descendants of a [Cached](crate::Cached) node into its image.
+fn render_cached<R: Renderer>(
+    taffy: &TaffyTree,
+    widgets: &mut HashMap<NodeId, MountedWidget>,
+    node: NodeId,
+    size: crate::Size,
+    canvas: &mut Canvas<R>,
+) {
+    let Some(MountedWidget::Cached(cached)) = widgets.get_mut(&node) else {
+        unreachable!()
+    };
+
+    let Some(image) = cached.render_target(canvas, size) else {
+        return;
+    };
+
+    canvas
+        .draw()
+        .set_render_target(femtovg::RenderTarget::Image(image));
+    canvas.clear_rect(
+        0,
+        0,
+        size.width,
+        size.height,
+        crate::Color::rgba(0, 0, 0, 0),
+    );
+
+    let mut list = DisplayList::default();
+
+    for (_, child) in iter_elements_from(taffy, node) {
+        let mut layout: Layout = (*taffy.layout(child).unwrap()).into();
+        layout.location = location_within(taffy, child, node);
+
+        let widget = widgets.get_mut(&child).unwrap();
+
+        if let MountedWidget::Image(image) = widget {
+            image.prepare(canvas);
+        }
 
-            if parent != prev_parent {
-                prev_parent = parent;
-                acc_point = Point {
-                    x: acc_point.x + parent_layout.location.x as u32,
-                    y: acc_point.y + parent_layout.location.y as u32,
-                }
-            }
+        layout_widget(widget, layout, canvas);
+        render_widget(widget, layout, &mut list);
+        list.replay(canvas);
+    }
 
-            let layout: Layout = self.tree.taffy.layout(node).unwrap().clone().into();
+    canvas
+        .draw()
+        .set_render_target(femtovg::RenderTarget::Screen);
+}
 
-            let v = self.tree.widgets.get_mut(&node).unwrap();
+/// The location of `node`, relative to its ancestor.
+fn location_within(taffy: &TaffyTree, node: NodeId, ancestor: NodeId) -> Point {
+    let mut location = Point { x: 0, y: 0 };
+    let mut current = node;
 
-            v.layout(layout.plus_location(acc_point), canvas.font_system());
-            v.render(layout.plus_location(acc_point), canvas);
-        }
+    while current != ancestor {
+        let layout = taffy.layout(current).unwrap();
+
+        location.x += layout.location.x as u32;
+        location.y += layout.location.y as u32;
+
+        current = taffy.parent(current).unwrap();
     }
+
+    location
 }
 
 fn iter_elements_from<'a>(
@@ -189,19 +348,17 @@ fn iter_elements_from<'a>(
         type Item = (NodeId, NodeId);
 
         fn next(&mut self) -> Option<Self::Item> {
-            if let Ok(next_child) = self.taffy.child_at_index(self.parent, self.index) {
-                self.to_process.push_back(next_child);
-                self.index += 1;
+            // Skips past any number of leaves without recursing.
+            loop {
+                if let Ok(next_child) = self.taffy.child_at_index(self.parent, self.index) {
+                    self.to_process.push_back(next_child);
+                    self.index += 1;
 
-                Some((self.parent, next_child))
-            } else {
-                let Some(new_current) = self.to_process.remove(0) else {
-                    return None;
-                };
+                    return Some((self.parent, next_child));
+                }
 
-                self.parent = new_current;
+                self.parent = self.to_process.pop_front()?;
                 self.index = 0;
-                self.next()
             }
         }
     }
@@ -255,7 +412,84 @@ pub(crate) fn iter_fields(of: &mut dyn Reflect, mut f: impl FnMut(usize, &mut dy
     }
 }
 
-struct MountedView(Box<dyn View>);
+fn fields(of: &dyn Reflect) -> Vec<&dyn Reflect> {
+    match of.reflect_ref() {
+        bevy_reflect::ReflectRef::Struct(s) => s.iter_fields().collect(),
+        bevy_reflect::ReflectRef::TupleStruct(ts) => ts.iter_fields().collect(),
+        bevy_reflect::ReflectRef::Enum(e) => e.iter_fields().map(|field| field.value()).collect(),
+        _ => vec![],
+    }
+}
+
+pub(crate) fn field_at_mut(of: &mut dyn Reflect, index: usize) -> Option<&mut dyn Reflect> {
+    match of.reflect_mut() {
+        bevy_reflect::ReflectMut::Struct(s) => s.field_at_mut(index),
+        bevy_reflect::ReflectMut::Enum(e) => e.field_at_mut(index),
+        bevy_reflect::ReflectMut::TupleStruct(ts) => ts.field_mut(index),
+        _ => None,
+    }
+}
+
+/// Initialize every [State](crate::State) on a freshly created view.
+pub(crate) fn init_states(view: &mut dyn Reflect, registry: &TypeRegistry) {
+    iter_fields(view, |_, field| {
+        if let Some(reflect_state) = registry.get_type_data::<ReflectStateTrait>(field.type_id()) {
+            let Some(state) = reflect_state.get_mut(field) else {
+                return;
+            };
+
+            state.init()
+        }
+    });
+}
+
+/// Move every [State](crate::State) from the old, mounted view into its replacement.
+/// Both must be of the same type.
+pub(crate) fn reuse_states(view: &mut dyn Reflect, old: &mut dyn Reflect, registry: &TypeRegistry) {
+    iter_fields(view, |index, field| {
+        if let Some(reflect_state) = registry.get_type_data::<ReflectStateTrait>(field.type_id()) {
+            let (Some(state), Some(old_field)) =
+                (reflect_state.get_mut(field), field_at_mut(old, index))
+            else {
+                return;
+            };
+
+            state.reuse(old_field)
+        }
+    });
+}
+
+/// Whether a view has the same props as the mounted view it would replace, so its subtree can be kept.
+/// Props are every reflected field but [State](crate::State)s, compared with [Reflect::reflect_partial_eq].
+/// A field that cannot be compared, or an old state with unprocessed changes, counts as different.
+pub(crate) fn props_eq(view: &dyn Reflect, old: &dyn Reflect, registry: &TypeRegistry) -> bool {
+    let (view, old) = (fields(view), fields(old));
+
+    view.len() == old.len()
+        && view.iter().zip(old).all(|(field, old_field)| {
+            match registry.get_type_data::<ReflectStateTrait>(field.type_id()) {
+                Some(reflect_state) => !reflect_state
+                    .get(old_field)
+                    .is_some_and(|state| state.is_dirty()),
+                None => field.reflect_partial_eq(old_field) == Some(true),
+            }
+        })
+}
+
+/// The state of every view in a tree, so it survives rebuilding the tree from scratch, like when hot-reloading.
+/// Views are matched by their position in the tree and their type.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StateSnapshot {
+    entries: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SnapshotEntry {
+    path: Vec<usize>,
+    view: String,
+    field: usize,
+    state: Vec<u8>,
+}
 
 // Should only be used by DynView
 #[doc(hidden)]
@@ -263,12 +497,103 @@ pub struct WidgetTree {
     // Also holds parent, child information
     taffy: TaffyTree,
     widgets: HashMap<NodeId, MountedWidget>,
-    views: HashMap<NodeId, MountedView>,
     root: NodeId,
+    /// How far down a node was moved to line up baselines, see [WidgetTree::align_baselines].
+    baseline_shifts: HashMap<NodeId, u32>,
+    /// Worked out once per layout and reused for hit testing, see [WidgetTree::window_layouts].
+    window_layouts: OnceCell<Vec<(NodeId, NodeId, Layout)>>,
 }
 
 impl WidgetTree {
-    pub(crate) fn create<V: View>(
+    /// Reset the dirty flag of every widget, returning whether any was set.
+    fn take_dirty(&mut self) -> bool {
+        let mut dirty = false;
+
+        // Every flag is taken, not just up to the first dirty widget.
+        for widget in self.widgets.values_mut() {
+            dirty |= widget.take_dirty();
+        }
+
+        dirty
+    }
+
+    /// Handle the messages sent to the states of every view, rebuilding the views whose state changed.
+    pub fn update(&mut self, registry: &mut TypeRegistry) {
+        self.hint_dirty(registry, self.root);
+    }
+
+    pub(crate) fn hint_dirty(&mut self, registry: &mut TypeRegistry, hint: NodeId) {
+        let mut dirty_views = vec![];
+
+        // iter_elements doesnt include the node itself
+        let from = self.taffy.parent(hint).unwrap_or(hint);
+
+        for (_, node) in iter_elements_from(&self.taffy, from) {
+            let Some(MountedWidget::View(ViewWidget(view))) = self.widgets.get_mut(&node) else {
+                continue;
+            };
+
+            let mut is_dirty = false;
+
+            iter_fields(view.as_reflect_mut(), |_, field| {
+                if let Some(reflect_state) =
+                    registry.get_type_data::<ReflectStateTrait>(field.type_id())
+                {
+                    let Some(state) = reflect_state.get_mut(field) else {
+                        return;
+                    };
+
+                    if state.is_dirty() {
+                        state.process();
+                        is_dirty = true;
+                    }
+                }
+            });
+
+            if is_dirty {
+                trace!(node = ?node, view = view.reflect_type_path(), "dirty view");
+                dirty_views.push(node);
+            }
+        }
+
+        for dirty in dirty_views {
+            self.modify_if_necessary(registry, dirty);
+        }
+    }
+
+    /// Give every state the frame painted at `now`, see [Reducer::frame](crate::state::Reducer::frame).
+    /// Views whose state changed are rebuilt.
+    pub fn frame(&mut self, registry: &mut TypeRegistry, now: Instant) {
+        let mut changed = vec![];
+
+        for (_, node) in iter_elements_from(&self.taffy, self.root) {
+            let Some(MountedWidget::View(ViewWidget(view))) = self.widgets.get_mut(&node) else {
+                continue;
+            };
+
+            let mut is_changed = false;
+
+            iter_fields(view.as_reflect_mut(), |_, field| {
+                if let Some(reflect_state) =
+                    registry.get_type_data::<ReflectStateTrait>(field.type_id())
+                {
+                    if let Some(state) = reflect_state.get_mut(field) {
+                        is_changed |= state.frame(now);
+                    }
+                }
+            });
+
+            if is_changed {
+                changed.push(node);
+            }
+        }
+
+        for node in changed {
+            self.modify_if_necessary(registry, node);
+        }
+    }
+
+    pub fn create<V: View>(
         registry: &mut TypeRegistry,
         root_item: V,
         size: PhysicalSize<u32>,
@@ -296,8 +621,9 @@ impl WidgetTree {
         let mut this = Self {
             taffy,
             widgets: HashMap::default(),
-            views: HashMap::default(),
             root,
+            baseline_shifts: HashMap::default(),
+            window_layouts: OnceCell::new(),
         };
 
         mount_children(registry, &mut this, root, element, None);
@@ -305,7 +631,35 @@ impl WidgetTree {
         this
     }
 
+    /// Lay out the tree in `available` space, measuring widgets whose size is left to their content.
+    /// Text is measured with `cache`'s fallbacks, see [RenderCache::set_fallback_family](crate::RenderCache::set_fallback_family)
+    /// and [RenderCache::set_fallback_fonts](crate::RenderCache::set_fallback_fonts),
+    /// and the same text at the same width only once.
+    pub(crate) fn compute_layout(
+        &mut self,
+        available: Size<taffy::AvailableSpace>,
+        cache: &mut RenderCache,
+    ) {
+        self.window_layouts.take();
+
+        let widgets = &mut self.widgets;
+
+        self.taffy
+            .compute_layout_with_measure(self.root, available, |known, available, node, _, _| {
+                widgets
+                    .get_mut(&node)
+                    .and_then(|widget| match widget {
+                        MountedWidget::Text(text) => text.measure_shared(known, available, cache),
+                        widget => widget.measure(known, available, &mut cache.font_system),
+                    })
+                    .unwrap_or(Size::ZERO)
+            })
+            .unwrap();
+    }
+
     pub(crate) fn insert(&mut self, widget: MountedWidget, parent: NodeId) -> NodeId {
+        self.window_layouts.take();
+
         let id = self.taffy.new_leaf(widget.style().0).unwrap();
         self.taffy.add_child(parent, id).unwrap();
 
@@ -320,6 +674,8 @@ impl WidgetTree {
         parent: NodeId,
         idx: usize,
     ) -> NodeId {
+        self.window_layouts.take();
+
         let id = self.taffy.new_leaf(element.style().0).unwrap();
 
         self.taffy.insert_child_at_index(parent, idx, id).unwrap();
@@ -328,102 +684,538 @@ impl WidgetTree {
         id
     }
 
-    pub(crate) fn modify_if_necessary(&mut self, registry: &mut TypeRegistry, changed: NodeId) {
-        self.comp_exchange(changed, registry);
+    /// Every mounted node breadth-first, with its parent, widget and last computed layout.
+    /// Layout is relative to the parent.
+    pub fn nodes(&self) -> impl Iterator<Item = (NodeId, NodeId, &MountedWidget, Layout)> {
+        iter_elements_from(&self.taffy, self.root).map(|(parent, node)| {
+            (
+                node,
+                parent,
+                &self.widgets[&node],
+                (*self.taffy.layout(node).unwrap()).into(),
+            )
+        })
     }
 
-    fn comp_exchange(&mut self, view_id: NodeId, registry: &mut TypeRegistry) {
-        debug_assert!(self.taffy.child_count(view_id) == 1);
-        let only_child = self.taffy.child_at_index(view_id, 0).unwrap();
+    /// Save the [State](crate::State) of every mounted view, see [StateSnapshot].
+    pub fn snapshot(&self, registry: &TypeRegistry) -> StateSnapshot {
+        let mut entries = vec![];
 
-        let Some(view) = self.views.remove(&view_id) else {
-            unreachable!()
-        };
+        for (_, node) in iter_elements_from(&self.taffy, self.root) {
+            let Some(MountedWidget::View(ViewWidget(view))) = self.widgets.get(&node) else {
+                continue;
+            };
 
-        view.0.dyn_cmp(only_child, self, registry);
+            for (field, value) in fields(view.as_reflect()).into_iter().enumerate() {
+                if registry
+                    .get_type_data::<ReflectStateTrait>(value.type_id())
+                    .is_none()
+                {
+                    continue;
+                }
 
-        // todo avoid this by passing in tree?
-        self.views.insert(view_id, view);
-    }
-}
+                let serialized = bincode::DefaultOptions::new()
+                    .serialize(&ReflectSerializer::new(value, registry));
+
+                match serialized {
+                    Ok(state) => entries.push(SnapshotEntry {
+                        path: self.path_of(node),
+                        view: view.reflect_type_path().to_string(),
+                        field,
+                        state,
+                    }),
+                    Err(err) => {
+                        dbg!("WARN: ", err);
+                    }
+                }
+            }
+        }
 
-#[doc(hidden)]
-pub fn iter_elements_cmp<E: Element>(
-    tree: &mut WidgetTree,
-    processing: NodeId,
-    new_element_at_position: E,
-    registry: &mut TypeRegistry,
-) {
-    struct CompareInsertContext<'a> {
-        tree: &'a mut WidgetTree,
-        processing: NodeId,
-        registry: &'a mut TypeRegistry,
-        child_idx: usize,
+        StateSnapshot { entries }
     }
 
-    impl<'a> RebuildContext for CompareInsertContext<'a> {
-        fn rebuild_child<E: Element>(&mut self, e: E) {
-            iter_elements_cmp(
-                self.tree,
-                self.tree
-                    .taffy
-                    .child_at_index(self.processing, self.child_idx)
-                    .unwrap(),
-                e,
-                self.registry,
+    /// Restore a [StateSnapshot] into this tree, rebuilding the views that got their state back.
+    /// Entries that no longer match a view of the same type at the same position are skipped.
+    pub fn restore(&mut self, snapshot: &StateSnapshot, registry: &mut TypeRegistry) {
+        let mut restored = vec![];
+
+        for entry in &snapshot.entries {
+            let Some(node) = self.node_at(&entry.path) else {
+                continue;
+            };
+
+            let Some(MountedWidget::View(ViewWidget(view))) = self.widgets.get_mut(&node) else {
+                continue;
+            };
+
+            if view.reflect_type_path() != entry.view {
+                continue;
+            }
+
+            let Some(field) = field_at_mut(view.as_reflect_mut(), entry.field) else {
+                continue;
+            };
+
+            let state = ReflectDeserializer::new(registry).deserialize(
+                &mut bincode::Deserializer::from_slice(
+                    &entry.state,
+                    bincode::DefaultOptions::new(),
+                ),
             );
 
-            self.child_idx += 1;
+            match state.map(|state| field.try_apply(&*state)) {
+                Ok(Ok(())) => restored.push(node),
+                Ok(Err(err)) => {
+                    dbg!("WARN: ", err);
+                }
+                Err(err) => {
+                    dbg!("WARN: ", err);
+                }
+            }
         }
-    }
 
-    let element_at_current_position = tree.widgets.remove(&processing).unwrap();
-
-    let BuildResult { widget, children } =
-        new_element_at_position.compare_rebuild(element_at_current_position);
+        for node in restored {
+            self.modify_if_necessary(registry, node);
+        }
+    }
 
-    tree.widgets.insert(processing, widget);
+    /// The child indices leading from the root to node.
+    fn path_of(&self, mut node: NodeId) -> Vec<usize> {
+        let mut path = vec![];
+
+        while let Some(parent) = self.taffy.parent(node) {
+            let index = self
+                .taffy
+                .children(parent)
+                .unwrap()
+                .iter()
+                .position(|child| *child == node)
+                .unwrap();
+
+            path.push(index);
+            node = parent;
+        }
 
-    if let Some(children) = children {
-        let rebuilder = &mut CompareInsertContext {
-            tree,
-            processing,
-            registry,
-            child_idx: 0,
-        };
+        path.reverse();
+        path
+    }
 
-        children.rebuild_children(rebuilder)
+    fn node_at(&self, path: &[usize]) -> Option<NodeId> {
+        path.iter().try_fold(self.root, |node, index| {
+            self.taffy.child_at_index(node, *index).ok()
+        })
     }
 
-    // self.processing
+    /// Every node below the root with its parent, breadth-first, and where it is in the window
+    /// as it is painted. Offsets are accumulated from the parent's, which always comes first.
+    /// Worked out once until the tree is laid out again or changes.
+    pub(crate) fn window_layouts(&self) -> &[(NodeId, NodeId, Layout)] {
+        self.window_layouts.get_or_init(|| {
+            let mut origins = HashMap::from([(self.root, Point { x: 0, y: 0 })]);
+
+            iter_elements_from(&self.taffy, self.root)
+                .map(|(parent, node)| {
+                    let mut layout: Layout = (*self.taffy.layout(node).unwrap()).into();
+                    let origin = origins[&parent];
+
+                    layout.location.x += origin.x;
+                    layout.location.y += origin.y + self.baseline_shifts.get(&node).unwrap_or(&0);
+                    origins.insert(node, layout.location);
+
+                    (parent, node, layout)
+                })
+                .collect()
+        })
+    }
 
-    // let ElementTree {
-    //     taffy, elements, ..
-    // } = tree;
+    /// The nodes under a point in the window, topmost first: the later a node is painted, the higher it is.
+    pub(crate) fn hit(&self, x: u32, y: u32) -> Vec<(NodeId, Layout)> {
+        let mut hit: Vec<_> = self
+            .window_layouts()
+            .iter()
+            .filter(|(_, _, layout)| layout.contains(x, y))
+            .map(|(_, node, layout)| (*node, *layout))
+            .collect();
 
-    // let parent = taffy.parent(processing).unwrap();
-    // let to_delete = iter_elements_from(&taffy, processing)
-    //     .map(|it| it.1)
-    //     .collect::<Vec<_>>();
+        hit.reverse();
 
-    // for to_delete in to_delete {
-    //     elements.remove(&to_delete).unwrap();
-    //     taffy.remove(to_delete).unwrap();
-    // }
+        hit
+    }
 
-    // let mut idx = 0;
+    /// Move the children of rows styled with [Styleable::align_baselines](crate::Styleable::align_baselines)
+    /// down until their first baselines meet. Taffy can't tell where text sits in a leaf, so it lines up
+    /// their bottoms instead. Text that hasn't been laid out yet is shaped here.
+    pub(crate) fn align_baselines(&mut self, font_system: &mut FontSystem) {
+        self.window_layouts.take();
+        self.baseline_shifts.clear();
+
+        let rows: Vec<_> = self
+            .widgets
+            .keys()
+            .copied()
+            .filter(|node| {
+                let style = self.taffy.style(*node).unwrap();
+
+                style.align_items == Some(taffy::AlignItems::Baseline)
+                    && matches!(
+                        style.flex_direction,
+                        taffy::FlexDirection::Row | taffy::FlexDirection::RowReverse
+                    )
+            })
+            .collect();
 
-    // while let false = taffy.child_at_index(parent, idx).unwrap() == processing {
-    //     idx += 1;
-    // }
+        for row in rows {
+            // Where each child's baseline is in the row
+            let mut baselines = vec![];
 
-    // taffy.remove(processing).unwrap();
+            for child in self.taffy.children(row).unwrap() {
+                let layout: Layout = (*self.taffy.layout(child).unwrap()).into();
 
-    // mount_children(registry, tree, parent, with, Some(idx));
+                let baseline = match self.widgets.get_mut(&child) {
+                    Some(MountedWidget::Text(text)) => {
+                        if text.baseline().is_none() {
+                            text.layout(layout, font_system);
+                        }
+
+                        text.baseline()
+                    }
+                    _ => None,
+                };
+
+                let baseline = baseline.unwrap_or(layout.size.height as f32);
+                baselines.push((child, layout.location.y as f32 + baseline));
+            }
+
+            let line = baselines.iter().map(|(_, at)| *at).fold(0., f32::max);
+
+            for (child, at) in baselines {
+                let shift = (line - at).round() as u32;
+
+                if shift > 0 {
+                    self.baseline_shifts.insert(child, shift);
+                }
+            }
+        }
+    }
+
+    /// Tell the nodes the pointer moved off of, and then the ones it moved onto, given those it was over
+    /// before and what it [hit](WidgetTree::hit) now. Leaving goes from the innermost out and entering from the outermost in, so nested widgets
+    /// get their events paired even when the pointer jumps across several at once.
+    /// Returns the nodes now under the pointer, topmost first.
+    pub(crate) fn pointer_moved(
+        &mut self,
+        hovered: &[NodeId],
+        hit: &[(NodeId, Layout)],
+    ) -> Vec<NodeId> {
+        let now: Vec<_> = hit.iter().map(|(node, _)| *node).collect();
+
+        for node in hovered.iter().filter(|node| !now.contains(node)) {
+            // Unmounted since
+            if let Some(widget) = self.widgets.get_mut(node) {
+                widget.event(WidgetEvent::PointerLeave);
+            }
+        }
+
+        for node in now.iter().rev().filter(|node| !hovered.contains(node)) {
+            self.widgets
+                .get_mut(node)
+                .unwrap()
+                .event(WidgetEvent::PointerEnter);
+        }
+
+        now
+    }
+
+    /// Nodes that accept focus, in the order Tab moves through them: depth first, like reading the tree.
+    pub(crate) fn focus_order(&self) -> Vec<NodeId> {
+        let mut order = vec![];
+        let mut stack = vec![self.root];
+
+        while let Some(node) = stack.pop() {
+            if self.widgets.get(&node).is_some_and(Widget::accepts_focus) {
+                order.push(node);
+            }
+
+            stack.extend(self.taffy.children(node).unwrap().into_iter().rev());
+        }
+
+        order
+    }
+
+    /// The node Tab moves the focus to from `from`, wrapping around to the first.
+    pub(crate) fn next_focus(&self, from: Option<NodeId>) -> Option<NodeId> {
+        let order = self.focus_order();
+        let next = from
+            .and_then(|from| order.iter().position(|node| *node == from))
+            .map_or(0, |index| index + 1);
+
+        order.get(next).or(order.first()).copied()
+    }
+
+    /// The topmost node under a point in the window that accepts focus.
+    pub(crate) fn focusable_at(&self, x: u32, y: u32) -> Option<NodeId> {
+        self.hit(x, y)
+            .into_iter()
+            .map(|(node, _)| node)
+            .find(|node| self.widgets[node].accepts_focus())
+    }
+
+    /// Move the focus, sending [WidgetEvent::Blur] and [WidgetEvent::Focus] to the nodes still mounted.
+    /// Returns the node focused now.
+    pub(crate) fn move_focus(
+        &mut self,
+        from: Option<NodeId>,
+        to: Option<NodeId>,
+    ) -> Option<NodeId> {
+        if from == to {
+            return to;
+        }
+
+        if let Some(widget) = from.and_then(|node| self.widgets.get_mut(&node)) {
+            widget.event(WidgetEvent::Blur);
+        }
+
+        if let Some(widget) = to.and_then(|node| self.widgets.get_mut(&node)) {
+            widget.event(WidgetEvent::Focus);
+        }
+
+        to
+    }
+
+    /// A [TextInput](crate::TextInput) built [focused](crate::TextInput::focused), to get the keys from the start.
+    fn initially_focused(&self) -> Option<NodeId> {
+        self.focus_order().into_iter().find(
+            |node| matches!(&self.widgets[node], MountedWidget::TextInput(input) if input.is_focused()),
+        )
+    }
+
+    /// Send `event` to every node under a point in the window, topmost first. Returns the nodes hit.
+    pub(crate) fn deliver(
+        &mut self,
+        x: u32,
+        y: u32,
+        event: WidgetEvent,
+        font_system: &mut FontSystem,
+    ) -> Vec<(NodeId, Layout)> {
+        let hit = self.hit(x, y);
+
+        for (node, layout) in &hit {
+            let mut context = EventContext::new(*layout, font_system);
+
+            self.widgets
+                .get_mut(node)
+                .unwrap()
+                .event_with(event.clone(), &mut context);
+        }
+
+        hit
+    }
+
+    /// Remove a node and all of its descendants.
+    pub(crate) fn remove_subtree(&mut self, node: NodeId) {
+        self.window_layouts.take();
+
+        let descendants = iter_elements_from(&self.taffy, node)
+            .map(|(_, child)| child)
+            .collect::<Vec<_>>();
+
+        // Deepest first, so children are unmounted before their parents.
+        for removed in [node].into_iter().chain(descendants).rev() {
+            if let Some(widget) = self.widgets.get_mut(&removed) {
+                widget.unmounted();
+            }
+
+            self.widgets.remove(&removed);
+            self.taffy.remove(removed).unwrap();
+        }
+    }
+
+    pub(crate) fn modify_if_necessary(&mut self, registry: &mut TypeRegistry, changed: NodeId) {
+        self.comp_exchange(changed, registry);
+    }
+
+    fn comp_exchange(&mut self, view_id: NodeId, registry: &mut TypeRegistry) {
+        let mut ancestor = self.taffy.parent(view_id);
+
+        while let Some(node) = ancestor {
+            if let Some(MountedWidget::Cached(cached)) = self.widgets.get_mut(&node) {
+                cached.invalidate();
+            }
+
+            ancestor = self.taffy.parent(node);
+        }
+
+        let Some(MountedWidget::View(view)) = self.widgets.remove(&view_id) else {
+            unreachable!()
+        };
+
+        // The stored view is the latest one: a rebuilding parent replaces it in `Element::compare_rebuild`,
+        // so only its own state has changed since and it is rebuilt as is.
+        view.0.dyn_cmp(view_id, self, registry);
+
+        // todo avoid this by passing in tree?
+        self.widgets.insert(view_id, MountedWidget::View(view));
+    }
+}
+
+struct CompareInsertContext<'a> {
+    tree: &'a mut WidgetTree,
+    processing: NodeId,
+    registry: &'a mut TypeRegistry,
+    child_idx: usize,
+}
+
+impl<'a> CompareInsertContext<'a> {
+    /// Remove the old children that were not rebuilt, as the new element has fewer.
+    fn remove_surplus(self) {
+        while self.tree.taffy.child_count(self.processing) > self.child_idx {
+            let surplus = self
+                .tree
+                .taffy
+                .child_at_index(self.processing, self.child_idx)
+                .unwrap();
+
+            self.tree.remove_subtree(surplus);
+        }
+    }
+}
+
+impl<'a> RebuildContext for CompareInsertContext<'a> {
+    fn rebuild_child<E: Element>(&mut self, e: E) {
+        // Nothing to compare against, the element is new
+        if self.child_idx >= self.tree.taffy.child_count(self.processing) {
+            mount_children(
+                self.registry,
+                self.tree,
+                self.processing,
+                e,
+                Some(self.child_idx),
+            );
+
+            self.child_idx += 1;
+
+            return;
+        }
+
+        iter_elements_cmp(
+            self.tree,
+            self.tree
+                .taffy
+                .child_at_index(self.processing, self.child_idx)
+                .unwrap(),
+            e,
+            self.registry,
+        );
+
+        self.child_idx += 1;
+    }
+
+    fn keep_children(&mut self) {
+        self.child_idx = self.tree.taffy.child_count(self.processing);
+    }
+}
+
+/// Diff `element` against the children of `parent`, positionally.
+/// This is how a dirty view rebuilds whatever it is holding.
+#[doc(hidden)]
+pub fn rebuild_children<E: Element>(
+    tree: &mut WidgetTree,
+    parent: NodeId,
+    element: E,
+    registry: &mut TypeRegistry,
+) {
+    let mut rebuilder = CompareInsertContext {
+        tree,
+        processing: parent,
+        registry,
+        child_idx: 0,
+    };
+
+    element.rebuild(&mut rebuilder);
+    rebuilder.remove_surplus();
+}
+
+#[doc(hidden)]
+pub fn iter_elements_cmp<E: Element>(
+    tree: &mut WidgetTree,
+    processing: NodeId,
+    new_element_at_position: E,
+    registry: &mut TypeRegistry,
+) {
+    trace_span!("rebuild", node = ?processing);
+
+    let mut element_at_current_position = tree.widgets.remove(&processing).unwrap();
+    let replaced = new_element_at_position.kind() != element_at_current_position.kind();
+
+    trace!(old = element_at_current_position.kind(), replaced);
+
+    // Nothing of a widget of another kind is reused, so it goes the way a removed one does.
+    if replaced {
+        element_at_current_position.unmounted();
+
+        for child in tree.taffy.children(processing).unwrap() {
+            tree.remove_subtree(child);
+        }
+    }
+
+    let BuildResult { widget, children } =
+        new_element_at_position.compare_rebuild(element_at_current_position, registry);
+
+    trace!(new = widget.kind(), rebuild_children = children.is_some());
+
+    tree.widgets.insert(processing, widget);
+    // Its content may measure differently now
+    tree.taffy.mark_dirty(processing).unwrap();
+
+    let mut rebuilder = CompareInsertContext {
+        tree,
+        processing,
+        registry,
+        child_idx: 0,
+    };
+
+    // A widget without children, like a container rebuilt as a leaf, holds none of the old ones.
+    if let Some(children) = children {
+        children.rebuild_children(&mut rebuilder);
+    }
+
+    rebuilder.remove_surplus();
+
+    if replaced {
+        tree.widgets.get_mut(&processing).unwrap().mounted();
+    }
+
+    // self.processing
+
+    // let ElementTree {
+    //     taffy, elements, ..
+    // } = tree;
+
+    // let parent = taffy.parent(processing).unwrap();
+    // let to_delete = iter_elements_from(&taffy, processing)
+    //     .map(|it| it.1)
+    //     .collect::<Vec<_>>();
+
+    // for to_delete in to_delete {
+    //     elements.remove(&to_delete).unwrap();
+    //     taffy.remove(to_delete).unwrap();
+    // }
+
+    // let mut idx = 0;
+
+    // while let false = taffy.child_at_index(parent, idx).unwrap() == processing {
+    //     idx += 1;
+    // }
+
+    // taffy.remove(processing).unwrap();
+
+    // mount_children(registry, tree, parent, with, Some(idx));
 
     // todo update style??
 }
 
+/// Mount `element` under `parent`, at `idx` if given.
+/// Most elements mount as a single node, but some (like [Fragment](crate::Fragment)) mount any number of siblings.
 pub(crate) fn mount_children<T: Element>(
     registry: &mut TypeRegistry,
     tree: &mut WidgetTree,
@@ -431,31 +1223,1417 @@ pub(crate) fn mount_children<T: Element>(
     element: T,
     idx: Option<usize>,
 ) {
-    struct Mounter<'a> {
-        tree: &'a mut WidgetTree,
-        parent: NodeId,
-        registry: &'a mut TypeRegistry,
+    element.insert(&mut Mounter {
+        tree,
+        parent,
+        registry,
+        idx,
+    });
+}
+
+struct Mounter<'a> {
+    tree: &'a mut WidgetTree,
+    parent: NodeId,
+    registry: &'a mut TypeRegistry,
+    idx: Option<usize>,
+}
+
+impl<'a> InsertContext for Mounter<'a> {
+    fn insert_child<E: Element>(&mut self, e: E) {
+        trace_span!("mount", parent = ?self.parent);
+
+        let BuildResult { widget, children } = e.create(self.registry);
+
+        trace!(widget = widget.kind());
+
+        let id = if let Some(idx) = &mut self.idx {
+            let id = self.tree.insert_at(widget, self.parent, *idx);
+            *idx += 1;
+
+            id
+        } else {
+            self.tree.insert(widget, self.parent)
+        };
+
+        trace!(node = ?id, "mounted");
+
+        if let Some(children) = children {
+            children.insert_children(&mut Mounter {
+                tree: self.tree,
+                parent: id,
+                registry: self.registry,
+                idx: None,
+            });
+        }
+
+        self.tree.widgets.get_mut(&id).unwrap().mounted();
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::{Cell, RefCell},
+        rc::Rc,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+        time::{Duration, Instant},
+    };
+
+    use bevy_reflect::TypeRegistry;
+    use cosmic_text::{fontdb, FontSystem};
+    use winit::dpi::PhysicalSize;
+
+    use crate::{prelude::*, DrawCommand, Issued, MountedWidget, RenderCache, Style};
 
-    impl<'a> InsertContext for Mounter<'a> {
-        fn insert_child<E: Element>(&mut self, e: E) {
-            mount_children(&mut self.registry, self.tree, self.parent, e, None)
+    use super::{
+        iter_elements_from, length, location_within, rebuild_children, render_widget, App,
+        AppEvent, WidgetTree,
+    };
+
+    #[view]
+    struct Siblings;
+
+    impl View for Siblings {
+        fn build(&self) -> impl Element + use<> {
+            fragment(("Hello", "World"))
         }
     }
 
-    let BuildResult { widget, children } = element.create(registry);
+    #[test]
+    fn view_with_siblings() {
+        let tree = WidgetTree::create(
+            &mut TypeRegistry::new(),
+            Siblings,
+            PhysicalSize::new(100, 100),
+        );
+
+        let view = tree.taffy.child_at_index(tree.root, 0).unwrap();
+        assert!(matches!(tree.widgets[&view], MountedWidget::View(_)));
+
+        let children = tree.taffy.children(view).unwrap();
+        assert_eq!(children.len(), 2);
+        assert!(children
+            .iter()
+            .all(|child| matches!(tree.widgets[child], MountedWidget::Text(_))));
+    }
 
-    if let Some(idx) = idx {
-        tree.insert_at(widget, parent, idx);
-    } else {
-        tree.insert(widget, parent);
+    #[test]
+    fn twelve_children_in_order() {
+        let twelve = || {
+            hstack((
+                "0",
+                "1",
+                "2",
+                "3",
+                Button::on_click(|| {}),
+                "5",
+                "6",
+                "7",
+                "8",
+                "9",
+                "10",
+                Button::on_click(|| {}),
+            ))
+        };
+
+        let mut registry = TypeRegistry::new();
+        let mut tree =
+            WidgetTree::create_internal(&mut registry, twelve(), PhysicalSize::new(100, 100));
+
+        let buttons = |tree: &WidgetTree| {
+            let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+            let children = tree.taffy.children(stack).unwrap();
+            assert_eq!(children.len(), 12);
+
+            children
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| matches!(tree.widgets[child], MountedWidget::Button(_)))
+                .map(|(index, _)| index)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(buttons(&tree), vec![4, 11]);
+
+        let root = tree.root;
+        rebuild_children(&mut tree, root, twelve(), &mut registry);
+        assert_eq!(buttons(&tree), vec![4, 11]);
     }
 
-    if let Some(children) = children {
-        children.insert_children(&mut Mounter {
-            tree,
-            parent,
-            registry,
-        });
+    #[derive(Reflect, Default)]
+    struct Flipped(bool);
+
+    impl Reducer<ButtonMessage> for Flipped {
+        fn reduce(&mut self, _: ButtonMessage) {
+            self.0 = !self.0;
+        }
+    }
+
+    #[view]
+    #[derive(Default)]
+    struct Flip {
+        flipped: State<ButtonMessage, Flipped>,
+    }
+
+    impl View for Flip {
+        fn build(&self) -> impl Element + use<> {
+            let label = |flipped| {
+                if flipped {
+                    Button::on_click(|| {}).right()
+                } else {
+                    "Not flipped".left()
+                }
+            };
+
+            fragment((
+                Button::sends(&self.flipped, ButtonMessage::Clicked(0, 0)),
+                label(self.flipped.0),
+                label(self.flipped.0),
+            ))
+        }
+    }
+
+    #[test]
+    fn rebuild_view_with_siblings() {
+        let mut app = App::new(Flip::default(), PhysicalSize::new(100, 100));
+
+        let view = app.tree.taffy.child_at_index(app.tree.root, 0).unwrap();
+        let children = app.tree.taffy.children(view).unwrap();
+        assert_eq!(children.len(), 3);
+
+        app.tree
+            .widgets
+            .get_mut(&children[0])
+            .unwrap()
+            .event(WidgetEvent::Click(0, 0));
+        app.dirty();
+
+        let children = app.tree.taffy.children(view).unwrap();
+        assert_eq!(children.len(), 3);
+        assert!(children
+            .iter()
+            .all(|child| matches!(app.tree.widgets[child], MountedWidget::Button(_))));
+    }
+
+    #[view]
+    #[derive(Default)]
+    struct Tabs {
+        extra: State<ButtonMessage, Flipped>,
+    }
+
+    impl View for Tabs {
+        fn build(&self) -> impl Element + use<> {
+            let mut tabs = vec!["main.rs", "lib.rs"];
+
+            if self.extra.0 {
+                tabs.push("app.rs");
+            }
+
+            fragment((
+                Button::sends(&self.extra, ButtonMessage::Clicked(0, 0)),
+                hstack(tabs),
+            ))
+        }
+    }
+
+    #[test]
+    fn state_changes_child_count() {
+        let mut app = App::new(Tabs::default(), PhysicalSize::new(100, 100));
+
+        let view = app.tree.taffy.child_at_index(app.tree.root, 0).unwrap();
+        let button = app.tree.taffy.child_at_index(view, 0).unwrap();
+        let stack = app.tree.taffy.child_at_index(view, 1).unwrap();
+
+        for expected in [3, 2, 3] {
+            app.tree
+                .widgets
+                .get_mut(&button)
+                .unwrap()
+                .event(WidgetEvent::Click(0, 0));
+            app.dirty();
+
+            assert_eq!(app.tree.taffy.children(stack).unwrap().len(), expected);
+            // Every node but the root has a widget.
+            assert_eq!(
+                app.tree.widgets.len() + 1,
+                app.tree.taffy.total_node_count()
+            );
+        }
+    }
+
+    #[test]
+    fn container_rebuilt_as_leaf() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(("main.rs", "lib.rs", "app.rs")),
+            PhysicalSize::new(100, 100),
+        );
+
+        let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+        let children = tree.taffy.children(stack).unwrap();
+        assert_eq!(children.len(), 3);
+
+        let root = tree.root;
+        rebuild_children(&mut tree, root, "No files", &mut registry);
+
+        assert!(matches!(tree.widgets[&stack], MountedWidget::Text(_)));
+        assert_eq!(tree.taffy.child_count(stack), 0);
+        assert!(children
+            .iter()
+            .all(|child| !tree.widgets.contains_key(child)));
+        assert_eq!(tree.widgets.len() + 1, tree.taffy.total_node_count());
+    }
+
+    fn mixed(first: AnyElement) -> impl Element {
+        hstack(vec![
+            first,
+            AnyElement::new(Button::on_click(|| {})),
+            AnyElement::new(hstack(("Hello", "World"))),
+        ])
+    }
+
+    #[test]
+    fn any_elements() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            mixed(AnyElement::new("Hello")),
+            PhysicalSize::new(100, 100),
+        );
+
+        let assert_mounted = |tree: &WidgetTree, first: fn(&MountedWidget) -> bool| {
+            let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+            let children = tree.taffy.children(stack).unwrap();
+
+            assert_eq!(children.len(), 3);
+            assert!(first(&tree.widgets[&children[0]]));
+            assert!(matches!(
+                tree.widgets[&children[1]],
+                MountedWidget::Button(_)
+            ));
+            assert!(matches!(
+                tree.widgets[&children[2]],
+                MountedWidget::HStack(_)
+            ));
+            assert_eq!(tree.taffy.children(children[2]).unwrap().len(), 2);
+        };
+
+        assert_mounted(&tree, |first| matches!(first, MountedWidget::Text(_)));
+
+        let root = tree.root;
+        rebuild_children(
+            &mut tree,
+            root,
+            mixed(AnyElement::new(Button::on_click(|| {}))),
+            &mut registry,
+        );
+
+        assert_mounted(&tree, |first| matches!(first, MountedWidget::Button(_)));
+    }
+
+    static BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    #[view]
+    struct Expensive(u32);
+
+    impl View for Expensive {
+        fn build(&self) -> impl Element + use<> {
+            BUILDS.fetch_add(1, Ordering::SeqCst);
+
+            "Expensive"
+        }
+    }
+
+    #[test]
+    fn memoized_skips_build() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            memoized(1, |props| Expensive(*props)),
+            PhysicalSize::new(100, 100),
+        );
+        let root = tree.root;
+
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 1);
+
+        rebuild_children(
+            &mut tree,
+            root,
+            memoized(1, |props| Expensive(*props)),
+            &mut registry,
+        );
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 1);
+
+        rebuild_children(
+            &mut tree,
+            root,
+            memoized(2, |props| Expensive(*props)),
+            &mut registry,
+        );
+        assert_eq!(BUILDS.load(Ordering::SeqCst), 2);
+    }
+
+    static UNCHANGED_BUILDS: AtomicUsize = AtomicUsize::new(0);
+
+    #[view]
+    struct Unchanged(u32);
+
+    impl View for Unchanged {
+        fn build(&self) -> impl Element + use<> {
+            UNCHANGED_BUILDS.fetch_add(1, Ordering::SeqCst);
+
+            "Unchanged"
+        }
+    }
+
+    #[test]
+    fn unchanged_props_skip_build() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((Unchanged(1), "Sibling")),
+            PhysicalSize::new(100, 100),
+        );
+        let root = tree.root;
+
+        assert_eq!(UNCHANGED_BUILDS.load(Ordering::SeqCst), 1);
+
+        rebuild_children(
+            &mut tree,
+            root,
+            hstack((Unchanged(1), "Sibling")),
+            &mut registry,
+        );
+        assert_eq!(UNCHANGED_BUILDS.load(Ordering::SeqCst), 1);
+
+        rebuild_children(
+            &mut tree,
+            root,
+            hstack((Unchanged(2), "Sibling")),
+            &mut registry,
+        );
+        assert_eq!(UNCHANGED_BUILDS.load(Ordering::SeqCst), 2);
+    }
+
+    #[view]
+    struct Props(u32);
+
+    impl View for Props {
+        fn build(&self) -> impl Element + use<> {
+            Text::builder().text(self.0.to_string()).build()
+        }
+    }
+
+    #[test]
+    fn stores_latest_props() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((Props(1),)),
+            PhysicalSize::new(100, 100),
+        );
+        let root = tree.root;
+
+        let stored = |tree: &WidgetTree| {
+            tree.nodes()
+                .find_map(|(_, _, widget, _)| match widget {
+                    MountedWidget::View(view) => {
+                        view.0.as_any().downcast_ref::<Props>().map(|p| p.0)
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        rebuild_children(&mut tree, root, hstack((Props(2),)), &mut registry);
+        assert_eq!(stored(&tree), 2);
+
+        // Compared against the stored props, not the ones first mounted.
+        rebuild_children(&mut tree, root, hstack((Props(1),)), &mut registry);
+        assert_eq!(stored(&tree), 1);
+    }
+
+    /// The kind of every node, in the order they are iterated.
+    fn shape(tree: &WidgetTree) -> Vec<std::mem::Discriminant<MountedWidget>> {
+        iter_elements_from(&tree.taffy, tree.root)
+            .map(|(_, node)| std::mem::discriminant(&tree.widgets[&node]))
+            .collect()
+    }
+
+    #[test]
+    fn trace_keeps_tree() {
+        let build = || {
+            let mut registry = TypeRegistry::new();
+            let mut tree =
+                WidgetTree::create(&mut registry, Flip::default(), PhysicalSize::new(100, 100));
+            let view = tree.taffy.child_at_index(tree.root, 0).unwrap();
+
+            rebuild_children(
+                &mut tree,
+                view,
+                fragment((Button::on_click(|| {}), "Hello", Button::on_click(|| {}))),
+                &mut registry,
+            );
+
+            shape(&tree)
+        };
+
+        let untraced = build();
+
+        #[cfg(feature = "trace")]
+        let traced = tracing::subscriber::with_default(
+            tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::TRACE)
+                .with_test_writer()
+                .finish(),
+            build,
+        );
+        #[cfg(not(feature = "trace"))]
+        let traced = build();
+
+        assert_eq!(untraced, traced);
+    }
+
+    #[test]
+    fn rebuild_grows_children() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(vec!["Hello"]),
+            PhysicalSize::new(100, 100),
+        );
+        let root = tree.root;
+        let stack = tree.taffy.child_at_index(root, 0).unwrap();
+
+        rebuild_children(
+            &mut tree,
+            root,
+            hstack(vec!["Hello", "World"]),
+            &mut registry,
+        );
+
+        let children = tree.taffy.children(stack).unwrap();
+        assert_eq!(children.len(), 2);
+        assert!(matches!(tree.widgets[&children[1]], MountedWidget::Text(_)));
+    }
+
+    #[test]
+    fn rebuild_removes_surplus_children() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(vec!["Hello", "World", "!"]),
+            PhysicalSize::new(100, 100),
+        );
+        let root = tree.root;
+        let stack = tree.taffy.child_at_index(root, 0).unwrap();
+        let nodes = tree.taffy.total_node_count();
+
+        rebuild_children(&mut tree, root, hstack(vec!["Hello"]), &mut registry);
+
+        assert_eq!(tree.taffy.children(stack).unwrap().len(), 1);
+        assert_eq!(tree.taffy.total_node_count(), nodes - 2);
+        assert_eq!(tree.widgets.len(), nodes - 3);
+    }
+
+    #[test]
+    fn center_fixed_size_child() {
+        let fixed = || {
+            let mut button = Button::on_click(|| {});
+            button.style_mut().0.size = taffy::Size {
+                width: length(20_f32),
+                height: length(10_f32),
+            };
+            button
+        };
+
+        let mut registry = TypeRegistry::new();
+
+        let mut position = |element| {
+            let mut tree =
+                WidgetTree::create_internal(&mut registry, element, PhysicalSize::new(100, 60));
+            tree.taffy
+                .compute_layout(
+                    tree.root,
+                    taffy::Size {
+                        width: length(100_f32),
+                        height: length(60_f32),
+                    },
+                )
+                .unwrap();
+
+            let container = tree.taffy.child_at_index(tree.root, 0).unwrap();
+            let child = tree.taffy.child_at_index(container, 0).unwrap();
+            tree.taffy.layout(child).unwrap().location
+        };
+
+        assert_eq!(position(center(fixed())), taffy::Point { x: 40., y: 25. });
+        assert_eq!(position(center_x(fixed())), taffy::Point { x: 40., y: 0. });
+        assert_eq!(position(center_y(fixed())), taffy::Point { x: 0., y: 25. });
+    }
+
+    #[test]
+    fn spacer_pushes_siblings_apart() {
+        let fixed = || {
+            Button::on_click(|| {})
+                .size(taffy::Dimension::Length(20.), taffy::Dimension::Length(10.))
+        };
+
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((fixed(), spacer(), fixed())),
+            PhysicalSize::new(200, 60),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(200_f32),
+                    height: length(60_f32),
+                },
+            )
+            .unwrap();
+
+        let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+        let layouts: Vec<_> = tree
+            .taffy
+            .children(stack)
+            .unwrap()
+            .into_iter()
+            .map(|child| *tree.taffy.layout(child).unwrap())
+            .collect();
+
+        assert_eq!(layouts[0].location.x, 0.);
+        assert_eq!(layouts[1].size.width, 160.);
+        assert_eq!(layouts[2].location.x, 180.);
+    }
+
+    struct Blink {
+        dirty: bool,
+    }
+
+    impl Widget for Blink {
+        fn take_dirty(&mut self) -> bool {
+            std::mem::take(&mut self.dirty)
+        }
+    }
+
+    #[test]
+    fn custom_widget_requests_repaint() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((custom(Blink { dirty: true }), "Still")),
+            PhysicalSize::new(100, 100),
+        );
+
+        assert!(tree.take_dirty());
+
+        // Taken, so the next frame is clean.
+        assert!(!tree.take_dirty());
+    }
+
+    #[test]
+    fn empty_vec_of_children() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(Vec::<&str>::new()),
+            PhysicalSize::new(100, 100),
+        );
+        let root = tree.root;
+        let stack = tree.taffy.child_at_index(root, 0).unwrap();
+        assert!(tree.taffy.children(stack).unwrap().is_empty());
+
+        rebuild_children(&mut tree, root, hstack(vec!["Tab"]), &mut registry);
+        assert_eq!(tree.taffy.children(stack).unwrap().len(), 1);
+
+        rebuild_children(&mut tree, root, hstack(Vec::<&str>::new()), &mut registry);
+        assert!(tree.taffy.children(stack).unwrap().is_empty());
+    }
+
+    #[test]
+    fn children_from_an_iterator() {
+        let numbers = |count| hstack(children((0..count).map(|i| format!("{i}"))));
+
+        let mut registry = TypeRegistry::new();
+        let mut tree =
+            WidgetTree::create_internal(&mut registry, numbers(5), PhysicalSize::new(100, 100));
+        let root = tree.root;
+        let stack = tree.taffy.child_at_index(root, 0).unwrap();
+
+        let texts = |tree: &WidgetTree| {
+            let children = tree.taffy.children(stack).unwrap();
+            assert!(children
+                .iter()
+                .all(|child| matches!(tree.widgets[child], MountedWidget::Text(_))));
+
+            children.len()
+        };
+
+        assert_eq!(texts(&tree), 5);
+
+        rebuild_children(&mut tree, root, numbers(3), &mut registry);
+        assert_eq!(texts(&tree), 3);
+    }
+
+    #[test]
+    fn iterates_breadth_first() {
+        let mut registry = TypeRegistry::new();
+        let tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((hstack(("a", "b")), "c", hstack((hstack(("d",)),)))),
+            PhysicalSize::new(100, 100),
+        );
+
+        let order: Vec<_> = iter_elements_from(&tree.taffy, tree.root)
+            .map(|(_, node)| tree.widgets[&node].kind())
+            .collect();
+
+        // Each level in full before the next
+        assert_eq!(
+            order,
+            ["HStack", "HStack", "Text", "HStack", "Text", "Text", "HStack", "Text"]
+        );
+
+        // Many leaves in a row, each skipped without growing the stack
+        let wide = WidgetTree::create_internal(
+            &mut registry,
+            hstack((0..50_000).map(|_| on_mount(|| {})).collect::<Vec<_>>()),
+            PhysicalSize::new(100, 100),
+        );
+        assert_eq!(iter_elements_from(&wide.taffy, wide.root).count(), 50_001);
+    }
+
+    #[derive(Reflect, Default)]
+    struct Clicks(u32);
+
+    impl Reducer<ButtonMessage> for Clicks {
+        fn reduce(&mut self, _: ButtonMessage) {
+            self.0 += 1;
+        }
+    }
+
+    #[view]
+    #[derive(Default)]
+    struct Clicker {
+        clicks: State<ButtonMessage, Clicks>,
+    }
+
+    impl View for Clicker {
+        fn build(&self) -> impl Element + use<> {
+            fragment((
+                Button::sends(&self.clicks, ButtonMessage::Clicked(0, 0)),
+                Text::builder().text(self.clicks.0.to_string()).build(),
+            ))
+        }
+    }
+
+    fn clicks(app: &App) -> u32 {
+        let view = app.tree.taffy.child_at_index(app.tree.root, 0).unwrap();
+        let MountedWidget::View(view) = &app.tree.widgets[&view] else {
+            unreachable!()
+        };
+
+        view.0.as_any().downcast_ref::<Clicker>().unwrap().clicks.0
+    }
+
+    #[test]
+    fn snapshot_restores_state() {
+        let mut app = App::new(Clicker::default(), PhysicalSize::new(100, 100));
+
+        let view = app.tree.taffy.child_at_index(app.tree.root, 0).unwrap();
+        let button = app.tree.taffy.child_at_index(view, 0).unwrap();
+
+        for _ in 0..2 {
+            app.tree
+                .widgets
+                .get_mut(&button)
+                .unwrap()
+                .event(WidgetEvent::Click(0, 0));
+            app.dirty();
+        }
+
+        assert_eq!(clicks(&app), 2);
+
+        let snapshot = bincode::serialize(&app.tree.snapshot(&app.registry)).unwrap();
+
+        let mut reloaded = App::new(Clicker::default(), PhysicalSize::new(100, 100));
+        assert_eq!(clicks(&reloaded), 0);
+
+        reloaded.tree.restore(
+            &bincode::deserialize(&snapshot).unwrap(),
+            &mut reloaded.registry,
+        );
+
+        assert_eq!(clicks(&reloaded), 2);
+    }
+
+    static MAILBOX: std::sync::Mutex<Option<Mailbox<ButtonMessage>>> = std::sync::Mutex::new(None);
+
+    #[test]
+    fn mailbox_sends_from_other_threads() {
+        let clicker = Clicker {
+            clicks: State::create_state_with(|mailbox| {
+                *MAILBOX.lock().unwrap() = Some(mailbox);
+                Clicks::default()
+            }),
+        };
+        let mut app = App::new(clicker, PhysicalSize::new(100, 100));
+
+        let mailbox = MAILBOX.lock().unwrap().take().unwrap();
+        std::thread::spawn(move || mailbox.send(ButtonMessage::Clicked(0, 0)))
+            .join()
+            .unwrap();
+        assert_eq!(clicks(&app), 0);
+
+        app.dirty();
+        assert_eq!(clicks(&app), 1);
+    }
+
+    static MOUNTS: AtomicUsize = AtomicUsize::new(0);
+    static UNMOUNTS: AtomicUsize = AtomicUsize::new(0);
+
+    #[view]
+    struct Opened;
+
+    impl View for Opened {
+        fn build(&self) -> impl Element + use<> {
+            fragment((
+                on_mount(|| {
+                    MOUNTS.fetch_add(1, Ordering::SeqCst);
+                })
+                .on_unmount(|| {
+                    UNMOUNTS.fetch_add(1, Ordering::SeqCst);
+                }),
+                "Opened",
+            ))
+        }
+    }
+
+    #[test]
+    fn effect_runs_on_mount_and_unmount() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(("Before", Opened)),
+            PhysicalSize::new(100, 100),
+        );
+        let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+
+        assert_eq!(MOUNTS.load(Ordering::SeqCst), 1);
+
+        // Rebuilt in place, still the same mount
+        rebuild_children(
+            &mut tree,
+            stack,
+            fragment(("Before", Opened)),
+            &mut registry,
+        );
+        assert_eq!(MOUNTS.load(Ordering::SeqCst), 1);
+        assert_eq!(UNMOUNTS.load(Ordering::SeqCst), 0);
+
+        rebuild_children(&mut tree, stack, "Before", &mut registry);
+        assert_eq!(MOUNTS.load(Ordering::SeqCst), 1);
+        assert_eq!(UNMOUNTS.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn replaced_effect_unmounts() {
+        let unmounted = Rc::new(Cell::new(false));
+        let effect = |swapped: bool| {
+            let unmounted = unmounted.clone();
+
+            if swapped {
+                "Closed".right()
+            } else {
+                on_unmount(move || unmounted.set(true)).left()
+            }
+        };
+
+        let mut registry = TypeRegistry::new();
+        let mut tree =
+            WidgetTree::create_internal(&mut registry, effect(false), PhysicalSize::new(100, 100));
+        assert!(!unmounted.get());
+
+        let root = tree.root;
+        rebuild_children(&mut tree, root, effect(true), &mut registry);
+        assert!(unmounted.get());
+    }
+
+    static STOPPED: AtomicBool = AtomicBool::new(false);
+
+    #[view]
+    struct Worker;
+
+    impl View for Worker {
+        fn build(&self) -> impl Element + use<> {
+            fragment((
+                task(|token| {
+                    while !token.is_cancelled() {
+                        std::thread::sleep(Duration::from_millis(1));
+                    }
+
+                    STOPPED.store(true, Ordering::SeqCst);
+                }),
+                "Working",
+            ))
+        }
+    }
+
+    #[test]
+    fn unmount_cancels_task() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(("Before", Worker)),
+            PhysicalSize::new(100, 100),
+        );
+        let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+
+        rebuild_children(
+            &mut tree,
+            stack,
+            fragment(("Before", Worker)),
+            &mut registry,
+        );
+        std::thread::sleep(Duration::from_millis(20));
+        assert!(!STOPPED.load(Ordering::SeqCst));
+
+        rebuild_children(&mut tree, stack, "Before", &mut registry);
+
+        let start = Instant::now();
+        while !STOPPED.load(Ordering::SeqCst) && start.elapsed() < Duration::from_secs(5) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert!(STOPPED.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn background_paints_under_widget() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(("Panel",)).background(Color::rgb(30, 30, 40)),
+            PhysicalSize::new(100, 60),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(100_f32),
+                    height: length(60_f32),
+                },
+            )
+            .unwrap();
+
+        let stack = tree.taffy.child_at_index(tree.root, 0).unwrap();
+        let layout = (*tree.taffy.layout(stack).unwrap()).into();
+        let mut list = DisplayList::default();
+
+        render_widget(&tree.widgets[&stack], layout, &mut list);
+
+        assert!(matches!(
+            list.commands(),
+            [DrawCommand::FillRect { width: 100., color, .. }] if *color == Color::rgb(30, 30, 40)
+        ));
+    }
+
+    #[test]
+    fn button_border_from_style() {
+        let gray = Color::rgb(90, 90, 90);
+        let button = Button::on_click(|| {}).border(2., gray).rounded(4.);
+
+        let style = button.style();
+        let two = length(2_f32);
+        assert_eq!(
+            style.0.border,
+            taffy::Rect {
+                left: two,
+                right: two,
+                top: two,
+                bottom: two,
+            }
+        );
+        assert_eq!(style.border_radius(), 4.);
+
+        let mut registry = TypeRegistry::new();
+        let mut tree =
+            WidgetTree::create_internal(&mut registry, button, PhysicalSize::new(100, 60));
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(100_f32),
+                    height: length(60_f32),
+                },
+            )
+            .unwrap();
+
+        let node = tree.taffy.child_at_index(tree.root, 0).unwrap();
+        let layout: crate::Layout = (*tree.taffy.layout(node).unwrap()).into();
+        assert_eq!(layout.border.left, 2);
+
+        let mut list = DisplayList::default();
+        render_widget(&tree.widgets[&node], layout, &mut list);
+
+        // The border, then the button within it
+        assert!(matches!(
+            list.commands(),
+            [
+                DrawCommand::Border { radius: 4., widths, color, .. },
+                DrawCommand::RoundedRect { x: 2., y: 2., radius: 2., .. },
+            ] if widths.top == 2 && *color == gray
+        ));
+    }
+
+    /// Adds up how far it was scrolled.
+    #[derive(Default)]
+    struct Probe(Rc<Cell<f32>>);
+
+    impl Widget for Probe {
+        fn event(&mut self, event: WidgetEvent) {
+            if let WidgetEvent::Scroll { delta_y, .. } = event {
+                self.0.set(self.0.get() + delta_y);
+            }
+        }
+
+        fn style(&self) -> Style {
+            let mut style = Style::default();
+            style.0.size = taffy::Size {
+                width: length(20_f32),
+                height: length(10_f32),
+            };
+
+            style
+        }
+    }
+
+    #[test]
+    fn click_hits_nested_custom_widget() {
+        let spacer = || {
+            let mut button = Button::on_click(|| {});
+            button.style_mut().0.size = taffy::Size {
+                width: length(30_f32),
+                height: length(10_f32),
+            };
+            button.style_mut().0.flex_shrink = 0.;
+            button
+        };
+
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((
+                spacer(),
+                center_y(hstack((spacer(), custom(Probe::default())))),
+            )),
+            PhysicalSize::new(200, 100),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(200_f32),
+                    height: length(100_f32),
+                },
+            )
+            .unwrap();
+
+        let kinds = |x, y| {
+            tree.hit(x, y)
+                .into_iter()
+                .map(|(node, layout)| (tree.widgets[&node].kind(), layout.location))
+                .collect::<Vec<_>>()
+        };
+
+        // Offset by both spacers, and centered in the full height
+        let hit = kinds(65, 50);
+        assert_eq!(hit[0], ("Custom", crate::Point { x: 60, y: 45 }));
+        assert!(hit[1..].iter().all(|(kind, _)| *kind != "Custom"));
+
+        assert_eq!(kinds(5, 5)[0].0, "Button");
+        assert!(kinds(65, 5).iter().all(|(kind, _)| *kind != "Custom"));
+
+        // Accumulated in one pass, the same as walking up from each node
+        for (_, node, layout) in tree.window_layouts() {
+            assert_eq!(
+                layout.location,
+                location_within(&tree.taffy, *node, tree.root)
+            );
+        }
+
+        // And reused by every hit until the tree is laid out again
+        let layouts: *const _ = tree.window_layouts();
+        kinds(5, 5);
+        assert!(std::ptr::eq(layouts, tree.window_layouts()));
+    }
+
+    #[test]
+    fn wheel_reaches_widget_under_cursor() {
+        let scrolled = Rc::new(Cell::new(0.));
+
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack(("Label", custom(Probe(scrolled.clone())))),
+            PhysicalSize::new(200, 100),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(200_f32),
+                    height: length(100_f32),
+                },
+            )
+            .unwrap();
+
+        let probe = tree
+            .window_layouts()
+            .iter()
+            .find(|(_, node, _)| tree.widgets[node].kind() == "Custom")
+            .map(|(_, _, layout)| *layout)
+            .unwrap();
+
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        let mut scroll = |x, y| {
+            let event = WidgetEvent::Scroll {
+                x,
+                y,
+                delta_x: 0.,
+                delta_y: 45.,
+            };
+
+            tree.deliver(x, y, event, &mut font_system).len()
+        };
+
+        let (x, y) = (probe.location.x + 5, probe.location.y + 5);
+        assert!(scroll(x, y) > 0);
+        assert_eq!(scrolled.get(), 45.);
+
+        // Outside of it, nothing reaches the probe
+        scroll(x, probe.location.y + probe.size.height + 5);
+        assert_eq!(scrolled.get(), 45.);
+    }
+
+    #[test]
+    fn text_is_as_tall_as_its_lines() {
+        let mut stack = hstack((
+            Text::builder().text("one").size(20.).build(),
+            Text::builder()
+                .text("one two three four five")
+                .size(20.)
+                .build(),
+            Text::builder().text("six").size(20.).build(),
+        ));
+        stack.style_mut().0.flex_direction = taffy::FlexDirection::Column;
+
+        let mut registry = TypeRegistry::new();
+        let mut tree =
+            WidgetTree::create_internal(&mut registry, stack, PhysicalSize::new(100, 300));
+
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        font_system
+            .db_mut()
+            .load_font_data(include_bytes!("../../assets/JetBrainsMono-Regular.ttf").to_vec());
+        let mut cache = RenderCache::new(font_system);
+
+        tree.compute_layout(
+            taffy::Size {
+                width: length(100_f32),
+                height: length(300_f32),
+            },
+            &mut cache,
+        );
+
+        let stack = tree.taffy.children(tree.root).unwrap()[0];
+        let texts: Vec<_> = tree
+            .taffy
+            .children(stack)
+            .unwrap()
+            .into_iter()
+            .map(|text| *tree.taffy.layout(text).unwrap())
+            .collect();
+
+        // Eight 12px wide characters fit on a line, so the words wrap over four
+        let heights: Vec<_> = texts.iter().map(|it| it.size.height).collect();
+        assert_eq!(heights, [20., 80., 20.]);
+        assert_eq!(texts[2].location.y, 100.);
+    }
+
+    #[test]
+    fn identical_text_is_measured_once() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        font_system
+            .db_mut()
+            .load_font_data(include_bytes!("../../assets/JetBrainsMono-Regular.ttf").to_vec());
+        let mut cache = RenderCache::new(font_system);
+
+        let label = || Text::builder().text("one two").size(20.).build();
+        let mut stack = hstack((label(), label(), label()));
+        stack.style_mut().0.flex_direction = taffy::FlexDirection::Column;
+
+        let mut registry = TypeRegistry::new();
+        let mut tree =
+            WidgetTree::create_internal(&mut registry, stack, PhysicalSize::new(100, 300));
+        let available = taffy::Size {
+            width: length(100_f32),
+            height: length(300_f32),
+        };
+
+        tree.compute_layout(available, &mut cache);
+        let misses = cache.measures.misses();
+        assert!(misses > 0);
+
+        // Each width the labels were measured at is shaped for the first label only
+        let mut single = hstack((label(),));
+        single.style_mut().0.flex_direction = taffy::FlexDirection::Column;
+        let mut single_tree =
+            WidgetTree::create_internal(&mut registry, single, PhysicalSize::new(100, 300));
+        let mut single_cache = RenderCache::new(FontSystem::new_with_locale_and_db(
+            "en-US".into(),
+            fontdb::Database::new(),
+        ));
+        single_cache
+            .font_system
+            .db_mut()
+            .load_font_data(include_bytes!("../../assets/JetBrainsMono-Regular.ttf").to_vec());
+        single_tree.compute_layout(available, &mut single_cache);
+        assert_eq!(single_cache.measures.misses(), misses);
+
+        // And the next frame measures nothing again
+        cache.start_frame();
+        tree.compute_layout(available, &mut cache);
+        assert_eq!(cache.measures.misses(), misses);
+    }
+
+    #[test]
+    fn texts_share_a_baseline() {
+        let sized = |size: f32| {
+            let mut text = Text::builder().text("Ag").size(size).build();
+            text.style_mut().0.size = taffy::Size {
+                width: length(100_f32),
+                height: length(size * 2.),
+            };
+
+            text
+        };
+
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((sized(40.), sized(12.))).align_baselines(),
+            PhysicalSize::new(300, 100),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(300_f32),
+                    height: length(100_f32),
+                },
+            )
+            .unwrap();
+
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        font_system
+            .db_mut()
+            .load_font_data(include_bytes!("../../assets/JetBrainsMono-Regular.ttf").to_vec());
+
+        tree.align_baselines(&mut font_system);
+
+        let lines: Vec<_> = tree
+            .window_layouts()
+            .iter()
+            .filter_map(|(_, node, layout)| {
+                let MountedWidget::Text(text) = &tree.widgets[node] else {
+                    return None;
+                };
+
+                Some((text.baseline().unwrap(), layout.location.y as f32))
+            })
+            .collect();
+
+        let [(big, big_top), (small, small_top)] = lines[..] else {
+            panic!("{lines:?}")
+        };
+
+        // Measured from each text's own top, then lined up in the window
+        assert!(big > small);
+        assert!((big_top + big - (small_top + small)).abs() <= 0.5);
+    }
+
+    /// Logs the pointer entering and leaving it.
+    struct Hover(&'static str, Rc<RefCell<Vec<String>>>);
+
+    impl Widget for Hover {
+        fn event(&mut self, event: WidgetEvent) {
+            let what = match event {
+                WidgetEvent::PointerEnter => "enter",
+                WidgetEvent::PointerLeave => "leave",
+                _ => return,
+            };
+
+            self.1.borrow_mut().push(format!("{what} {}", self.0));
+        }
+
+        fn style(&self) -> Style {
+            let mut style = Style::default();
+            style.0.size = taffy::Size {
+                width: length(20_f32),
+                height: length(10_f32),
+            };
+            style.0.flex_shrink = 0.;
+
+            style
+        }
+    }
+
+    #[test]
+    fn hover_enters_and_leaves_in_pairs() {
+        let log = Rc::new(RefCell::new(vec![]));
+        let hover = |name| custom(Hover(name, log.clone()));
+
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            // The inner stack holds b and c, at 20..60
+            hstack((hover("a"), hstack((hover("b"), hover("c"))))),
+            PhysicalSize::new(200, 100),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(200_f32),
+                    height: length(100_f32),
+                },
+            )
+            .unwrap();
+
+        let mut hovered = vec![];
+        let mut path = |points: &[(u32, u32)]| {
+            for (x, y) in points {
+                let hit = tree.hit(*x, *y);
+                hovered = tree.pointer_moved(&hovered, &hit);
+            }
+
+            log.borrow_mut().drain(..).collect::<Vec<_>>()
+        };
+
+        assert_eq!(path(&[(5, 5), (6, 5)]), ["enter a"]);
+        // Straight from a into c, skipping b
+        assert_eq!(path(&[(45, 5)]), ["leave a", "enter c"]);
+        assert_eq!(
+            path(&[(25, 5), (150, 90)]),
+            ["leave c", "enter b", "leave b"]
+        );
+    }
+    #[test]
+    fn tab_moves_focus_between_inputs() {
+        let mut registry = TypeRegistry::new();
+        let mut tree = WidgetTree::create_internal(
+            &mut registry,
+            hstack((
+                custom(Hover("a", Rc::default())),
+                text_input("b"),
+                Button::on_click(|| {}),
+                hstack(text_input("c").focused()),
+            )),
+            PhysicalSize::new(200, 100),
+        );
+        tree.taffy
+            .compute_layout(
+                tree.root,
+                taffy::Size {
+                    width: length(200_f32),
+                    height: length(100_f32),
+                },
+            )
+            .unwrap();
+
+        let focused = |tree: &WidgetTree| {
+            tree.focus_order()
+                .into_iter()
+                .filter_map(|node| match &tree.widgets[&node] {
+                    MountedWidget::TextInput(input) if input.is_focused() => {
+                        Some(input.text().to_string())
+                    }
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let [b, c] = tree.focus_order()[..] else {
+            panic!("only the inputs take focus");
+        };
+        assert_eq!(tree.initially_focused(), Some(c));
+
+        // Tab wraps around from the last input
+        let mut focus = tree.move_focus(None, Some(c));
+        assert_eq!(focused(&tree), ["c"]);
+
+        let next = tree.next_focus(focus);
+        focus = tree.move_focus(focus, next);
+        assert_eq!(focus, Some(b));
+        assert_eq!(focused(&tree), ["b"]);
+
+        assert_eq!(tree.next_focus(focus), Some(c));
+        assert_eq!(tree.next_focus(None), Some(b));
+
+        // Clicking something that can't be focused blurs the input
+        assert_eq!(tree.focusable_at(5, 5), None);
+        tree.move_focus(focus, None);
+        assert!(focused(&tree).is_empty());
+    }
+
+    #[view]
+    struct Toolbar;
+
+    impl View for Toolbar {
+        fn build(&self) -> impl Element + use<> {
+            hstack(
+                (0..20)
+                    .map(|tab| {
+                        Text::builder()
+                            .text(format!("tab {tab}"))
+                            .size(16.)
+                            .build()
+                            .background(Color::rgb(60, 60, 80))
+                            .pad(length(6.))
+                    })
+                    .collect::<Vec<_>>(),
+            )
+        }
+    }
+
+    #[test]
+    fn labels_with_backgrounds_batch_their_rects() {
+        let size = PhysicalSize::new(2000, 100);
+        let mut canvas = Canvas::recording(size.width, size.height);
+        let mut app = App::new(Toolbar, size);
+
+        app.event(AppEvent::Paint(size), &mut canvas);
+        canvas.draw();
+
+        let count = |call: Issued| {
+            canvas
+                .issued
+                .iter()
+                .filter(|&&issued| issued == call)
+                .count()
+        };
+
+        // One fill for all the backgrounds, rather than one between each label
+        assert_eq!(count(Issued::Fill), 1);
+        assert_eq!(count(Issued::Glyphs), 20);
+        assert_eq!(canvas.issued.len(), 21);
     }
 }
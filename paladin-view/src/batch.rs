@@ -0,0 +1,256 @@
+//! Batching of solid rects, so a frame with many of them takes few draw calls.
+
+use femtovg::GlyphDrawCommands;
+
+use crate::Color;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct BatchedRect {
+    x: f32,
+    y: f32,
+    width: f32,
+    height: f32,
+}
+
+impl BatchedRect {
+    fn overlaps(&self, other: &BatchedRect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// Glyphs to draw, per text color.
+pub(crate) type Glyphs = Vec<(cosmic_text::Color, GlyphDrawCommands)>;
+
+enum Pending {
+    Rects(Color, Vec<BatchedRect>),
+    /// Text drawn between rects, taking up `bounds`.
+    Glyphs {
+        bounds: BatchedRect,
+        glyphs: Glyphs,
+    },
+}
+
+impl Pending {
+    fn overlaps(&self, rect: &BatchedRect) -> bool {
+        match self {
+            Pending::Rects(_, rects) => rects.iter().any(|later| later.overlaps(rect)),
+            Pending::Glyphs { bounds, .. } => bounds.overlaps(rect),
+        }
+    }
+}
+
+/// A draw taken from a [RectBatch], in painting order.
+pub(crate) enum Batched {
+    /// Rects of one color, as one path.
+    Rects(Color, femtovg::Path),
+    Glyphs(Glyphs),
+}
+
+/// Rects waiting to be drawn, grouped into one path per color.
+/// A rect only joins an earlier group when nothing drawn after that group overlaps it, so the result looks as if each rect was drawn in order.
+/// Text waits in between, so the rects of a row of labels with backgrounds still end up in one group.
+#[derive(Default)]
+pub(crate) struct RectBatch {
+    pending: Vec<Pending>,
+}
+
+impl RectBatch {
+    pub(crate) fn push(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        let rect = BatchedRect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        let joinable = self
+            .pending
+            .iter()
+            .rposition(|pending| matches!(pending, Pending::Rects(group_color, _) if *group_color == color))
+            .filter(|group| {
+                self.pending[group + 1..]
+                    .iter()
+                    .all(|later| !later.overlaps(&rect))
+            });
+
+        match joinable {
+            Some(group) => {
+                let Pending::Rects(_, rects) = &mut self.pending[group] else {
+                    unreachable!()
+                };
+
+                rects.push(rect)
+            }
+            None => self.pending.push(Pending::Rects(color, vec![rect])),
+        }
+    }
+
+    /// Draw text after the rects pushed so far. It is never grouped, but only rects it overlaps have to wait for it.
+    pub(crate) fn push_glyphs(&mut self, glyphs: Glyphs) {
+        let quads = glyphs
+            .iter()
+            .flat_map(|(_, commands)| commands.alpha_glyphs.iter().chain(&commands.color_glyphs))
+            .flat_map(|command| &command.quads);
+
+        let Some((left, top, right, bottom)) = quads.fold(None, |bounds, quad| {
+            let (left, top, right, bottom) = bounds.unwrap_or((quad.x0, quad.y0, quad.x1, quad.y1));
+
+            Some((
+                left.min(quad.x0),
+                top.min(quad.y0),
+                right.max(quad.x1),
+                bottom.max(quad.y1),
+            ))
+        }) else {
+            // No glyphs with anything to draw
+            return;
+        };
+
+        let bounds = BatchedRect {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        };
+
+        self.pending.push(Pending::Glyphs { bounds, glyphs });
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Take the pending draws, a path per group of rects.
+    pub(crate) fn take(&mut self) -> impl Iterator<Item = Batched> {
+        std::mem::take(&mut self.pending)
+            .into_iter()
+            .map(|pending| match pending {
+                Pending::Rects(color, rects) => {
+                    let mut path = femtovg::Path::new();
+
+                    for rect in rects {
+                        path.rect(rect.x, rect.y, rect.width, rect.height);
+                    }
+
+                    Batched::Rects(color, path)
+                }
+                Pending::Glyphs { glyphs, .. } => Batched::Glyphs(glyphs),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use femtovg::{renderer::Void, DrawCommand, GlyphDrawCommands, ImageFlags, PixelFormat, Quad};
+
+    use crate::Color;
+
+    use super::{Batched, RectBatch};
+
+    /// Pushes 600 rects in a grid, colored in turn from `colors`.
+    fn grid(colors: &[Color]) -> RectBatch {
+        let mut batch = RectBatch::default();
+
+        for i in 0..600 {
+            let (x, y) = ((i % 30) as f32 * 20., (i / 30) as f32 * 20.);
+
+            batch.push(x, y, 18., 18., colors[i % colors.len()]);
+        }
+
+        batch
+    }
+
+    #[test]
+    fn hundreds_of_rects() {
+        // Each path taken is filled with one draw call
+        assert_eq!(grid(&[Color::rgb(30, 30, 30)]).take().count(), 1);
+
+        let colors = [
+            Color::rgb(200, 130, 90),
+            Color::rgb(30, 30, 30),
+            Color::rgb(60, 60, 80),
+        ];
+        let mut batch = grid(&colors);
+        let taken: Vec<_> = batch
+            .take()
+            .map(|batched| match batched {
+                Batched::Rects(color, _) => color,
+                Batched::Glyphs(_) => unreachable!(),
+            })
+            .collect();
+
+        assert_eq!(taken, colors);
+        assert!(batch.is_empty());
+    }
+
+    #[test]
+    fn keeps_overlapping_order() {
+        let (red, blue) = (Color::rgb(255, 0, 0), Color::rgb(0, 0, 255));
+
+        let mut batch = RectBatch::default();
+        batch.push(0., 0., 10., 10., red);
+        batch.push(5., 5., 10., 10., blue);
+        // Would end up below blue if merged into the first red group
+        batch.push(8., 8., 10., 10., red);
+        // Overlaps nothing after the first red group
+        batch.push(100., 100., 10., 10., red);
+
+        assert_eq!(batch.take().count(), 3);
+    }
+
+    /// Glyphs covering `x` to `x + width` and `y` to `y + height`.
+    fn glyphs(x: f32, y: f32, width: f32, height: f32) -> super::Glyphs {
+        let quad = Quad {
+            x0: x,
+            y0: y,
+            x1: x + width,
+            y1: y + height,
+            ..Default::default()
+        };
+
+        let image_id = femtovg::Canvas::new(Void)
+            .unwrap()
+            .create_image_empty(1, 1, PixelFormat::Rgba8, ImageFlags::empty())
+            .unwrap();
+
+        vec![(
+            cosmic_text::Color::rgb(255, 255, 255),
+            GlyphDrawCommands {
+                alpha_glyphs: vec![DrawCommand {
+                    image_id,
+                    quads: vec![quad],
+                }],
+                color_glyphs: vec![],
+            },
+        )]
+    }
+
+    #[test]
+    fn rects_group_around_text() {
+        let red = Color::rgb(255, 0, 0);
+
+        let mut batch = RectBatch::default();
+        batch.push(0., 0., 40., 20., red);
+        batch.push_glyphs(glyphs(4., 4., 30., 12.));
+        // Next to the text, drawn along with the first red rect
+        batch.push(50., 0., 40., 20., red);
+        batch.push_glyphs(glyphs(54., 4., 30., 12.));
+        // Over the first text, has to wait until after it
+        batch.push(10., 10., 5., 5., red);
+        // Nothing is drawn over the last group
+        batch.push(60., 8., 5., 5., red);
+
+        let taken: Vec<_> = batch
+            .take()
+            .map(|batched| match batched {
+                Batched::Rects(color, _) => Some(color),
+                Batched::Glyphs(_) => None,
+            })
+            .collect();
+
+        assert_eq!(taken, [Some(red), None, None, Some(red)]);
+    }
+}
@@ -0,0 +1,184 @@
+use std::path::PathBuf;
+
+use crate::{text::RenderCache, AntialiasMode, Canvas, PixelSnap};
+
+/// How [run_with_config](crate::run_with_config) sets up the app before its first frame.
+/// Anything left unset keeps its default, see the matching setter of [Canvas].
+/// ```
+/// # use paladin_view::prelude::*;
+/// let config = AppConfig::default()
+///     .hinting(false)
+///     .antialias(AntialiasMode::Alpha)
+///     .fallback_family("JetBrains Mono")
+///     .on_start(|canvas| println!("{:?}", canvas.list_families()));
+/// ```
+#[derive(Default)]
+pub struct AppConfig {
+    fonts: Vec<Vec<u8>>,
+    font_files: Vec<PathBuf>,
+    pixel_snapping: Option<bool>,
+    hinting: Option<bool>,
+    max_atlases: Option<usize>,
+    antialias: Option<AntialiasMode>,
+    fallback_family: Option<String>,
+    fallback_fonts: Option<Vec<String>>,
+    on_start: Option<OnStart>,
+}
+
+type OnStart = Box<dyn FnOnce(&mut Canvas)>;
+
+impl AppConfig {
+    /// Load the faces in a font file's contents, like the bold and italic faces of a family.
+    /// Text asking for a weight or style uses the matching face of its family.
+    pub fn font(mut self, data: Vec<u8>) -> Self {
+        self.fonts.push(data);
+        self
+    }
+
+    /// Like [AppConfig::font], reading the file at `path` when the app starts.
+    pub fn font_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.font_files.push(path.into());
+        self
+    }
+
+    /// See [Canvas::set_pixel_snapping].
+    pub fn pixel_snapping(mut self, enabled: bool) -> Self {
+        self.pixel_snapping = Some(enabled);
+        self
+    }
+
+    /// See [Canvas::set_hinting].
+    pub fn hinting(mut self, enabled: bool) -> Self {
+        self.hinting = Some(enabled);
+        self
+    }
+
+    /// See [Canvas::set_max_atlases].
+    pub fn max_atlases(mut self, atlases: usize) -> Self {
+        self.max_atlases = Some(atlases);
+        self
+    }
+
+    /// See [Canvas::set_antialias].
+    pub fn antialias(mut self, mode: AntialiasMode) -> Self {
+        self.antialias = Some(mode);
+        self
+    }
+
+    /// See [Canvas::set_fallback_family].
+    pub fn fallback_family(mut self, family: impl Into<String>) -> Self {
+        self.fallback_family = Some(family.into());
+        self
+    }
+
+    /// See [Canvas::set_fallback_fonts].
+    pub fn fallback_fonts(mut self, families: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.fallback_fonts = Some(families.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Called with the canvas once the rest is set up, like to pick a family from [Canvas::list_families]
+    /// or size the window contents with [Canvas::measure].
+    pub fn on_start(mut self, f: impl FnOnce(&mut Canvas) + 'static) -> Self {
+        self.on_start = Some(Box::new(f));
+        self
+    }
+
+    pub(crate) fn apply(mut self, canvas: &mut Canvas) -> crate::Result<()> {
+        let on_start = self.on_start.take();
+
+        self.apply_to(&mut canvas.text_cache, &mut canvas.snap)?;
+
+        if let Some(on_start) = on_start {
+            on_start(canvas);
+        }
+
+        Ok(())
+    }
+
+    /// Everything but [AppConfig::on_start], which needs a window.
+    fn apply_to(self, text_cache: &mut RenderCache, snap: &mut PixelSnap) -> crate::Result<()> {
+        for font in self.fonts {
+            text_cache.load_font_data(font);
+        }
+
+        for path in &self.font_files {
+            text_cache.load_font_file(path)?;
+        }
+
+        if let Some(enabled) = self.pixel_snapping {
+            snap.enabled = enabled;
+        }
+
+        if let Some(enabled) = self.hinting {
+            text_cache.set_hinting(enabled);
+        }
+
+        if let Some(atlases) = self.max_atlases {
+            text_cache.set_max_atlases(atlases);
+        }
+
+        if let Some(mode) = self.antialias {
+            text_cache.set_antialias(mode);
+        }
+
+        if let Some(family) = self.fallback_family {
+            text_cache.set_fallback_family(family);
+        }
+
+        if let Some(families) = self.fallback_fonts {
+            text_cache.set_fallback_fonts(families);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{fontdb, FontSystem};
+
+    use crate::{
+        text::{load_fonts, RenderCache},
+        AntialiasMode, PixelSnap,
+    };
+
+    use super::AppConfig;
+
+    #[test]
+    fn config_sets_up_text() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+        let mut cache = RenderCache::new(font_system);
+        let mut snap = PixelSnap::default();
+
+        assert!(!cache.list_families().contains(&"Roboto".to_string()));
+
+        AppConfig::default()
+            .font_file(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/../assets/Roboto-Regular.ttf"
+            ))
+            .pixel_snapping(false)
+            .hinting(false)
+            .max_atlases(2)
+            .antialias(AntialiasMode::Alpha)
+            .fallback_fonts(["JetBrains Mono"])
+            .apply_to(&mut cache, &mut snap)
+            .unwrap();
+
+        assert!(cache.list_families().contains(&"Roboto".to_string()));
+        assert!(!snap.enabled);
+        assert!(!cache.hinting);
+        assert_eq!(cache.max_atlases, 2);
+        assert_eq!(cache.antialias, AntialiasMode::Alpha);
+        assert_eq!(cache.fallbacks.fonts, ["JetBrains Mono"]);
+
+        // A missing file fails the start
+        assert!(AppConfig::default()
+            .font_file("missing.ttf")
+            .apply_to(&mut cache, &mut snap)
+            .is_err());
+    }
+}
@@ -0,0 +1,109 @@
+//! Coalescing bursts of messages, like requests made on every keystroke.
+
+use std::time::{Duration, Instant};
+
+/// Keeps the latest of rapid messages, releasing it once none came for `delay`.
+/// Polled, like from [Reducer::frame](crate::state::Reducer::frame) with a frame asked for at its [Debounced::deadline],
+/// since nothing wakes it up on its own.
+///
+/// ```
+/// # use paladin_view::prelude::*;
+/// # use std::time::{Duration, Instant};
+///
+/// let mut completion = Debounced::new(Duration::from_millis(300));
+/// let start = Instant::now();
+///
+/// completion.push("fn", start);
+/// completion.push("fn m", start + Duration::from_millis(100));
+///
+/// assert_eq!(completion.poll(start + Duration::from_millis(200)), None);
+/// assert_eq!(completion.poll(start + Duration::from_millis(400)), Some("fn m"));
+///
+/// ```
+#[derive(Debug, Clone)]
+pub struct Debounced<M> {
+    delay: Duration,
+    pending: Option<(M, Instant)>,
+}
+
+impl<M> Debounced<M> {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+        }
+    }
+
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Replace the pending message, restarting the wait.
+    pub fn push(&mut self, message: M, now: Instant) {
+        self.pending = Some((message, now));
+    }
+
+    /// The latest message, once `delay` has passed since it was pushed.
+    pub fn poll(&mut self, now: Instant) -> Option<M> {
+        if now < self.deadline()? {
+            return None;
+        }
+
+        self.pending.take().map(|(message, _)| message)
+    }
+
+    /// When the pending message is released, if there is one.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.as_ref().map(|(_, at)| *at + self.delay)
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Drop the pending message without releasing it.
+    pub fn cancel(&mut self) {
+        self.pending = None;
+    }
+}
+
+/// Waits for a short pause, like between words while typing.
+impl<M> Default for Debounced<M> {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(300))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::Debounced;
+
+    #[test]
+    fn rapid_messages_dispatch_once() {
+        let ms = Duration::from_millis;
+        let start = Instant::now();
+        let mut debounced = Debounced::new(ms(200));
+        let mut dispatched = vec![];
+
+        // A keystroke every 50ms, polled in between
+        for key in 0..10 {
+            let now = start + ms(50 * key);
+
+            debounced.push(key, now);
+            dispatched.extend(debounced.poll(now + ms(25)));
+        }
+
+        assert!(dispatched.is_empty());
+        assert_eq!(debounced.deadline(), Some(start + ms(650)));
+
+        // Trailing edge, after the last keystroke
+        dispatched.extend(debounced.poll(start + ms(649)));
+        dispatched.extend(debounced.poll(start + ms(650)));
+        dispatched.extend(debounced.poll(start + ms(900)));
+
+        assert_eq!(dispatched, [9]);
+        assert!(!debounced.is_pending());
+    }
+}
@@ -0,0 +1,253 @@
+//! Drawing commands recorded by [Widget::render](crate::Widget::render) and replayed onto the [Canvas] by the framework.
+
+use std::{cell::RefCell, rc::Rc};
+
+use femtovg::{renderer::Renderer, ImageId, Paint, Path, Solidity};
+
+use crate::{Canvas, Color, Layout, Rect};
+
+/// A single drawing operation.
+#[derive(Debug, Clone)]
+pub enum DrawCommand {
+    /// A solid rect, blending with what is below.
+    FillRect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color,
+    },
+    /// Shaped text with its top-left corner at `position`.
+    DrawText {
+        buffer: Rc<RefCell<cosmic_text::Buffer>>,
+        position: (f32, f32),
+    },
+    /// A solid rect with rounded corners.
+    RoundedRect {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        color: Color,
+    },
+    /// The outline of a rect, `widths` thick on each side, with rounded outer corners.
+    Border {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        widths: Rect,
+        color: Color,
+    },
+    Line {
+        from: (f32, f32),
+        to: (f32, f32),
+        width: f32,
+        color: Color,
+    },
+    /// An image stretched over a rect.
+    Image {
+        image: ImageId,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    /// Clip the following commands to a rect, until the matching [DrawCommand::PopClip].
+    PushClip {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    PopClip,
+}
+
+/// The commands a widget draws with, in painting order.
+#[derive(Debug, Default)]
+pub struct DisplayList {
+    commands: Vec<DrawCommand>,
+}
+
+impl DisplayList {
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.commands.push(DrawCommand::FillRect {
+            x,
+            y,
+            width,
+            height,
+            color,
+        });
+    }
+
+    pub fn rounded_rect(
+        &mut self,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        radius: f32,
+        color: Color,
+    ) {
+        self.commands.push(DrawCommand::RoundedRect {
+            x,
+            y,
+            width,
+            height,
+            radius,
+            color,
+        });
+    }
+
+    /// Outline `layout` within its border widths.
+    pub fn border(&mut self, layout: Layout, radius: f32, color: Color) {
+        self.commands.push(DrawCommand::Border {
+            x: layout.location.x as f32,
+            y: layout.location.y as f32,
+            width: layout.size.width as f32,
+            height: layout.size.height as f32,
+            radius,
+            widths: layout.border,
+            color,
+        });
+    }
+
+    pub fn text(&mut self, buffer: Rc<RefCell<cosmic_text::Buffer>>, position: (f32, f32)) {
+        self.commands
+            .push(DrawCommand::DrawText { buffer, position });
+    }
+
+    pub fn line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: Color) {
+        self.commands.push(DrawCommand::Line {
+            from,
+            to,
+            width,
+            color,
+        });
+    }
+
+    pub fn image(&mut self, image: ImageId, x: f32, y: f32, width: f32, height: f32) {
+        self.commands.push(DrawCommand::Image {
+            image,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    pub fn push_clip(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.commands.push(DrawCommand::PushClip {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.commands.push(DrawCommand::PopClip);
+    }
+
+    pub fn commands(&self) -> &[DrawCommand] {
+        &self.commands
+    }
+
+    /// Draw every command onto the canvas, emptying the list.
+    pub(crate) fn replay<R: Renderer>(&mut self, canvas: &mut Canvas<R>) {
+        for command in self.commands.drain(..) {
+            match command {
+                DrawCommand::FillRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    color,
+                } => canvas.fill_rect(x, y, width, height, color),
+                DrawCommand::RoundedRect {
+                    x,
+                    y,
+                    width,
+                    height,
+                    radius,
+                    color,
+                } => {
+                    let (x, y, width, height) = canvas.snap.rect(x, y, width, height);
+
+                    let mut path = Path::new();
+                    path.rounded_rect(x, y, width, height, radius);
+
+                    canvas.fill_path(&path, &Paint::color(color.into()));
+                }
+                DrawCommand::Border {
+                    x,
+                    y,
+                    width,
+                    height,
+                    radius,
+                    widths,
+                    color,
+                } => {
+                    let (left, right) = (widths.left as f32, widths.right as f32);
+                    let (top, bottom) = (widths.top as f32, widths.bottom as f32);
+
+                    let outer = canvas.snap.rect(x, y, width, height);
+                    let inner = canvas.snap.rect(
+                        x + left,
+                        y + top,
+                        (width - left - right).max(0.),
+                        (height - top - bottom).max(0.),
+                    );
+
+                    let mut path = Path::new();
+                    path.rounded_rect(outer.0, outer.1, outer.2, outer.3, radius);
+                    // The inner edge follows the outer one, less rounded by the border's width.
+                    path.rounded_rect(
+                        inner.0,
+                        inner.1,
+                        inner.2,
+                        inner.3,
+                        (radius - left.max(top)).max(0.),
+                    );
+                    path.solidity(Solidity::Hole);
+
+                    canvas.fill_path(&path, &Paint::color(color.into()));
+                }
+                DrawCommand::DrawText { buffer, position } => {
+                    canvas.draw_text(&buffer.borrow(), position)
+                }
+                DrawCommand::Line {
+                    from,
+                    to,
+                    width,
+                    color,
+                } => canvas.draw_line(from, to, width, color),
+                DrawCommand::Image {
+                    image,
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    let mut path = Path::new();
+                    path.rect(x, y, width, height);
+
+                    canvas.fill_path(&path, &Paint::image(image, x, y, width, height, 0., 1.));
+                }
+                DrawCommand::PushClip {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    let inner = canvas.draw();
+                    inner.save();
+                    inner.intersect_scissor(x, y, width, height);
+                }
+                DrawCommand::PopClip => canvas.draw().restore(),
+            }
+        }
+    }
+}
@@ -8,12 +8,26 @@ use std::{
     fmt::Debug,
     ops::{Deref, DerefMut},
 };
-use taffy::{prelude::auto, LengthPercentage};
+use taffy::{prelude::auto, Dimension, LengthPercentage, LengthPercentageAuto};
 pub use text::*;
 
+pub use any_element::*;
+pub use cached::*;
+pub use center::*;
+pub use divider::*;
+pub use effect::*;
+pub use fragment::*;
+pub use image::*;
+pub use list::*;
+pub use memoized::*;
+pub use progress::*;
+pub use scrollbar::*;
+pub use spacer::*;
+pub use text_input::*;
+
 use crate::{
-    BuildResult, Canvas, Element, InsertChildren, InsertContext, KeyEvent, Layout, RebuildChildren,
-    RebuildContext,
+    BuildResult, Color, DisplayList, Element, InsertChildren, InsertContext, KeyEvent, Layout,
+    RebuildChildren, RebuildContext,
 };
 
 /// An element that has been mounted into the tree.
@@ -23,17 +37,72 @@ pub enum MountedWidget {
     Button(Button),
     Text(Text),
     HStack(HStack),
+    View(ViewWidget),
+    Cached(CachedWidget),
+    Center(CenterWidget),
+    Image(Image),
+    Memoized(MemoizedWidget),
+    Scrollbar(Scrollbar),
+    Spinner(Spinner),
+    ProgressBar(ProgressBar),
+    Spacer(Spacer),
+    List(List),
+    Divider(Divider),
+    TextInput(TextInput),
+    Effect(Effect),
     Custom(CustomWidget),
 }
 
+impl MountedWidget {
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            MountedWidget::Button(_) => "Button",
+            MountedWidget::Text(_) => "Text",
+            MountedWidget::HStack(_) => "HStack",
+            MountedWidget::View(_) => "View",
+            MountedWidget::Cached(_) => "Cached",
+            MountedWidget::Center(_) => "Center",
+            MountedWidget::Image(_) => "Image",
+            MountedWidget::Memoized(_) => "Memoized",
+            MountedWidget::Scrollbar(_) => "Scrollbar",
+            MountedWidget::Spinner(_) => "Spinner",
+            MountedWidget::ProgressBar(_) => "ProgressBar",
+            MountedWidget::Spacer(_) => "Spacer",
+            MountedWidget::List(_) => "List",
+            MountedWidget::Divider(_) => "Divider",
+            MountedWidget::TextInput(_) => "TextInput",
+            MountedWidget::Effect(_) => "Effect",
+            MountedWidget::Custom(_) => "Custom",
+        }
+    }
+}
+
 pub struct CustomWidget(pub Box<dyn AnyWidget>);
 
+/// A mounted [View](crate::View). Views take up a node in the tree, but draw nothing themselves,
+/// their children do.
+pub struct ViewWidget(pub(crate) Box<dyn crate::View>);
+
+impl ViewWidget {
+    /// The mounted view, if it is a `V`.
+    pub fn view<V: crate::View>(&self) -> Option<&V> {
+        self.0.as_any().downcast_ref()
+    }
+}
+
+impl Widget for ViewWidget {}
+
 pub trait AnyWidget: Any {
     fn into_any(self: Box<Self>) -> Box<dyn Any>;
-    fn render(&self, layout: crate::Layout, canvas: &mut Canvas);
+    fn render(&self, layout: crate::Layout, list: &mut DisplayList);
     fn event(&mut self, event: WidgetEvent);
+    fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext);
     fn layout(&mut self, layout: Layout, font_system: &mut FontSystem);
     fn style(&self) -> Style;
+    fn take_dirty(&mut self) -> bool;
+    fn mounted(&mut self);
+    fn unmounted(&mut self);
+    fn accepts_focus(&self) -> bool;
 }
 
 impl<T: Any + Widget> AnyWidget for T {
@@ -41,14 +110,18 @@ impl<T: Any + Widget> AnyWidget for T {
         self
     }
 
-    fn render(&self, layout: crate::Layout, canvas: &mut Canvas) {
-        self.render(layout, canvas)
+    fn render(&self, layout: crate::Layout, list: &mut DisplayList) {
+        self.render(layout, list)
     }
 
     fn event(&mut self, event: WidgetEvent) {
         self.event(event);
     }
 
+    fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+        self.event_with(event, context);
+    }
+
     fn layout(&mut self, layout: Layout, font_system: &mut FontSystem) {
         self.layout(layout, font_system);
     }
@@ -56,6 +129,22 @@ impl<T: Any + Widget> AnyWidget for T {
     fn style(&self) -> Style {
         self.style()
     }
+
+    fn take_dirty(&mut self) -> bool {
+        self.take_dirty()
+    }
+
+    fn mounted(&mut self) {
+        self.mounted()
+    }
+
+    fn unmounted(&mut self) {
+        self.unmounted()
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.accepts_focus()
+    }
 }
 
 impl Widget for CustomWidget {
@@ -63,6 +152,10 @@ impl Widget for CustomWidget {
         self.0.event(event)
     }
 
+    fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+        self.0.event_with(event, context)
+    }
+
     fn style(&self) -> Style {
         self.0.style()
     }
@@ -71,8 +164,55 @@ impl Widget for CustomWidget {
         self.0.layout(layout, font_system)
     }
 
-    fn render(&self, layout: Layout, canvas: &mut Canvas) {
-        self.0.render(layout, canvas)
+    fn render(&self, layout: Layout, list: &mut DisplayList) {
+        self.0.render(layout, list)
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        self.0.take_dirty()
+    }
+
+    fn mounted(&mut self) {
+        self.0.mounted()
+    }
+
+    fn unmounted(&mut self) {
+        self.0.unmounted()
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.0.accepts_focus()
+    }
+}
+
+/// Mount a widget implemented outside the framework.
+pub fn custom(widget: impl Widget + 'static) -> CustomWidget {
+    CustomWidget(Box::new(widget))
+}
+
+impl Element for CustomWidget {
+    #[allow(refining_impl_trait)]
+    fn create(self, _: &mut TypeRegistry) -> BuildResult<crate::LeafNode> {
+        BuildResult {
+            widget: MountedWidget::Custom(self),
+            children: None,
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        "Custom"
+    }
+
+    #[allow(refining_impl_trait)]
+    fn compare_rebuild(
+        self,
+        _: MountedWidget,
+        _: &mut TypeRegistry,
+    ) -> BuildResult<crate::LeafNode> {
+        BuildResult {
+            widget: MountedWidget::Custom(self),
+            children: None,
+        }
     }
 }
 
@@ -102,11 +242,33 @@ pub trait Widget {
     #[allow(unused_variables)]
     fn event(&mut self, event: WidgetEvent) {}
 
+    /// Like [Widget::event], for widgets that measure text or hit-test while handling an event,
+    /// like placing a cursor where text was clicked. Calls [Widget::event] unless implemented.
+    fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+        let _ = context;
+
+        self.event(event)
+    }
+
     /// Return the current style of the element. This may be called up to each frame.
     fn style(&self) -> Style {
         Style::default()
     }
 
+    #[allow(unused_variables)]
+    /// The size of the widget's content, for the parts of it its style leaves to the content, like an `auto` height.
+    /// `known` holds the sizes layout already settled on, and `available` the room there is for the rest.
+    ///
+    /// Widgets that return `None`, the default, are sized by their style and children alone.
+    fn measure(
+        &mut self,
+        known: taffy::Size<Option<f32>>,
+        available: taffy::Size<taffy::AvailableSpace>,
+        font_system: &mut FontSystem,
+    ) -> Option<taffy::Size<f32>> {
+        None
+    }
+
     #[allow(unused_variables)]
     /// A function where a [Widget] can perform layout calculations within its given bounds. This is most useful to layout text paragraphs before rendering.
     /// Most widgets only paint based on some immutable data and do not need to implement this function.
@@ -125,7 +287,7 @@ pub trait Widget {
     ///         buffer.shape_until_scroll(true);
     ///     }
     ///
-    ///     fn render(&self, layout: Layout, canvas: &mut Canvas) {
+    ///     fn render(&self, layout: Layout, list: &mut DisplayList) {
     ///         // ..
     ///     }
     /// }
@@ -133,7 +295,7 @@ pub trait Widget {
     /// ```
     fn layout(&mut self, layout: Layout, font_system: &mut cosmic_text::FontSystem) {}
 
-    /// Painting.
+    /// Painting, by recording commands that the framework draws afterwards.
     /// ```
     /// # use paladin_view::prelude::*;
     ///
@@ -142,25 +304,56 @@ pub trait Widget {
     /// // Imagine we are inserted into the tree..
     ///
     /// impl Widget for FixedRect {
-    ///     fn render(&self, layout: Layout, canvas: &mut impl Canvas) {
-    ///         canvas.clear_rect(
-    ///             layout.location.x,
-    ///             layout.location.y,
-    ///             100,
-    ///             100,
-    ///             Color::rgb(200, 130, 90).into(),
+    ///     fn render(&self, layout: Layout, list: &mut DisplayList) {
+    ///         list.fill_rect(
+    ///             layout.location.x as f32,
+    ///             layout.location.y as f32,
+    ///             100.,
+    ///             100.,
+    ///             Color::rgb(200, 130, 90),
     ///         );
     ///     }
     /// }
     ///
     /// ```
     #[allow(unused_variables)]
-    fn render(&self, layout: crate::Layout, canvas: &mut Canvas) {}
+    fn render(&self, layout: crate::Layout, list: &mut DisplayList) {}
+
+    /// Whether the widget changed on its own since it was last asked, like an animation stepping
+    /// or a result arriving, and needs to be painted again. Polled and reset after every paint.
+    ///
+    /// State on views already repaints when it changes, this is for widgets that keep their own.
+    fn take_dirty(&mut self) -> bool {
+        false
+    }
+
+    /// Called once the widget and its children have been inserted into the tree.
+    /// A widget kept by a rebuild stays mounted, one replacing a different kind of widget is mounted again.
+    fn mounted(&mut self) {}
+
+    /// Called when the widget is removed from the tree, after its descendants.
+    fn unmounted(&mut self) {}
+
+    /// Whether the widget takes keys. One that does can be focused by clicking it or with Tab,
+    /// and then gets every [WidgetEvent::Key] until [WidgetEvent::Blur].
+    fn accepts_focus(&self) -> bool {
+        false
+    }
 }
 
 /// The style of a widget. Styling decides final layout (size, position) and is based on the flexbox algorithm, thanks to [taffy].
+/// Also holds the background and border, painted by the framework under whatever the widget renders.
 #[derive(Debug, Clone)]
-pub struct Style(pub taffy::Style);
+pub struct Style(pub taffy::Style, Decoration);
+
+/// What [Style] paints besides layout.
+#[derive(Debug, Clone, Copy, Default)]
+struct Decoration {
+    background: Option<Color>,
+    /// Drawn within the border widths of the taffy style.
+    border: Option<Color>,
+    radius: f32,
+}
 
 impl Style {
     pub fn with_direction(mut self, direction: taffy::FlexDirection) -> Self {
@@ -168,24 +361,87 @@ impl Style {
 
         self
     }
+
+    pub fn with_background(mut self, color: Color) -> Self {
+        self.1.background = Some(color);
+
+        self
+    }
+
+    /// The color filling the widget's layout, if any.
+    pub fn background(&self) -> Option<Color> {
+        self.1.background
+    }
+
+    /// The color of the border, if any. Its width on each side is [taffy::Style::border].
+    pub fn border_color(&self) -> Option<Color> {
+        self.1.border
+    }
+
+    /// How round the corners of the background and border are.
+    pub fn border_radius(&self) -> f32 {
+        self.1.radius
+    }
 }
 
 impl Default for Style {
     fn default() -> Self {
-        Self(taffy::Style {
-            size: taffy::Size {
-                width: taffy::Dimension::Percent(1.),
-                height: auto(),
+        Self(
+            taffy::Style {
+                size: taffy::Size {
+                    width: taffy::Dimension::Percent(1.),
+                    height: auto(),
+                },
+                ..Default::default()
             },
-            ..Default::default()
-        })
+            Decoration::default(),
+        )
     }
 }
 
 /// Any interaction with an element.
+#[derive(Clone)]
 pub enum WidgetEvent {
     Click(u32, u32),
     Key(KeyEvent),
+    /// The wheel or touchpad moved over the widget, at `x`, `y`. Deltas are in pixels,
+    /// positive scrolls down and to the right like [Scroll::scroll_by](crate::Scroll::scroll_by).
+    Scroll {
+        x: u32,
+        y: u32,
+        delta_x: f32,
+        delta_y: f32,
+    },
+    /// The pointer moved onto the widget. Always followed by a [WidgetEvent::PointerLeave] once it moves off.
+    PointerEnter,
+    PointerLeave,
+    /// The pointer moved while over the widget, to `x`, `y`. For widgets that react to where in them
+    /// the pointer is, like a tooltip over part of them.
+    PointerMove(u32, u32),
+    /// Keys go to the widget from now on, see [Widget::accepts_focus].
+    Focus,
+    /// Keys stopped going to the widget.
+    Blur,
+}
+
+/// What [Widget::event_with] gets to work with besides the event.
+pub struct EventContext<'a> {
+    /// Where the widget was last laid out.
+    pub layout: Layout,
+    font_system: &'a mut FontSystem,
+}
+
+impl<'a> EventContext<'a> {
+    pub fn new(layout: Layout, font_system: &'a mut FontSystem) -> Self {
+        Self {
+            layout,
+            font_system,
+        }
+    }
+
+    pub fn font_system(&mut self) -> &mut FontSystem {
+        self.font_system
+    }
 }
 
 /// Shorthands for styling.
@@ -203,11 +459,231 @@ pub trait Styleable: Sized {
         self
     }
 
-    // fn align(mut self, align: ) -> Self {
-    //     self.style_mut().0.ali
+    /// Padding on the left and right.
+    fn pad_x(mut self, padding: LengthPercentage) -> Self {
+        let style = &mut self.style_mut().0;
+        style.padding.left = padding;
+        style.padding.right = padding;
+
+        self
+    }
+
+    /// Padding on the top and bottom.
+    fn pad_y(mut self, padding: LengthPercentage) -> Self {
+        let style = &mut self.style_mut().0;
+        style.padding.top = padding;
+        style.padding.bottom = padding;
+
+        self
+    }
+
+    /// Space around the widget on every side, keeping its siblings away. `auto` takes up the free space.
+    fn margin(mut self, margin: LengthPercentageAuto) -> Self {
+        self.style_mut().0.margin = taffy::Rect {
+            left: margin,
+            right: margin,
+            top: margin,
+            bottom: margin,
+        };
+
+        self
+    }
+
+    /// Space to the left and right of the widget.
+    fn margin_x(mut self, margin: LengthPercentageAuto) -> Self {
+        let style = &mut self.style_mut().0;
+        style.margin.left = margin;
+        style.margin.right = margin;
+
+        self
+    }
+
+    /// Space above and below the widget.
+    fn margin_y(mut self, margin: LengthPercentageAuto) -> Self {
+        let style = &mut self.style_mut().0;
+        style.margin.top = margin;
+        style.margin.bottom = margin;
+
+        self
+    }
+
+    /// Space between the children of a stack, in rows and in columns.
+    /// ```
+    /// # use paladin_view::prelude::*;
+    /// # use paladin_view::taffy::LengthPercentage;
+    /// #[view]
+    /// struct Toolbar;
+    ///
+    /// impl View for Toolbar {
+    ///     fn build(&self) -> impl Element + use<> {
+    ///         hstack((
+    ///             Button::on_click(|| println!("Open")),
+    ///             Button::on_click(|| println!("Save")),
+    ///         ))
+    ///         .gap(LengthPercentage::Length(8.))
+    ///     }
+    /// }
+    /// ```
+    fn gap(mut self, gap: LengthPercentage) -> Self {
+        self.style_mut().0.gap = taffy::Size {
+            width: gap,
+            height: gap,
+        };
+
+        self
+    }
+
+    /// The width and height of the widget. Widgets are as wide as their parent by default.
+    fn size(mut self, width: Dimension, height: Dimension) -> Self {
+        self.style_mut().0.size = taffy::Size { width, height };
+
+        self
+    }
+
+    fn width(mut self, width: Dimension) -> Self {
+        self.style_mut().0.size.width = width;
+
+        self
+    }
+
+    fn height(mut self, height: Dimension) -> Self {
+        self.style_mut().0.size.height = height;
+
+        self
+    }
+
+    /// The narrowest the widget shrinks to, even when its width says less.
+    fn min_width(mut self, width: Dimension) -> Self {
+        self.style_mut().0.min_size.width = width;
+
+        self
+    }
+
+    /// The widest the widget grows to, even when its width says more.
+    fn max_width(mut self, width: Dimension) -> Self {
+        self.style_mut().0.max_size.width = width;
+
+        self
+    }
+
+    /// The shortest the widget shrinks to, even when its height says less.
+    fn min_height(mut self, height: Dimension) -> Self {
+        self.style_mut().0.min_size.height = height;
+
+        self
+    }
+
+    /// The tallest the widget grows to, even when its height says more.
+    fn max_height(mut self, height: Dimension) -> Self {
+        self.style_mut().0.max_size.height = height;
+
+        self
+    }
+
+    /// Fill the widget's layout with `color`, under anything it draws.
+    fn background(mut self, color: Color) -> Self {
+        self.style_mut().1.background = Some(color);
+
+        self
+    }
+
+    /// Round the corners of the background and border.
+    fn rounded(mut self, radius: f32) -> Self {
+        self.style_mut().1.radius = radius;
+
+        self
+    }
+
+    /// A border `width` pixels wide on every side. It takes up layout space, like padding.
+    fn border(mut self, width: f32, color: Color) -> Self {
+        let width = LengthPercentage::Length(width);
+
+        self.style_mut().0.border = taffy::Rect {
+            left: width,
+            right: width,
+            top: width,
+            bottom: width,
+        };
+        self.style_mut().1.border = Some(color);
+
+        self
+    }
+
+    /// Line up the first baseline of the text in each child of a row, instead of the children's tops.
+    /// Children without text sit on the line with their bottom edge.
+    fn align_baselines(mut self) -> Self {
+        self.style_mut().0.align_items = Some(taffy::AlignItems::Baseline);
+
+        self
+    }
+
+    /// Where the children of a stack go across it, like centering them vertically in a row.
+    /// ```
+    /// # use paladin_view::prelude::*;
+    /// # use paladin_view::taffy::{AlignItems, Dimension, FlexDirection, JustifyContent};
+    /// #[view]
+    /// struct Dialog;
+    ///
+    /// impl View for Dialog {
+    ///     fn build(&self) -> impl Element + use<> {
+    ///         let mut column = hstack(Button::on_click(|| println!("Ok")).width(Dimension::Length(80.)))
+    ///             .height(Dimension::Percent(1.))
+    ///             .align_items(AlignItems::Center)
+    ///             .justify_content(JustifyContent::Center);
+    ///         column.style_mut().0.flex_direction = FlexDirection::Column;
+    ///
+    ///         column
+    ///     }
+    /// }
+    /// ```
+    fn align_items(mut self, align: taffy::AlignItems) -> Self {
+        self.style_mut().0.align_items = Some(align);
+
+        self
+    }
+
+    /// Where the children of a stack go along it, and how the free space is spread between them.
+    fn justify_content(mut self, justify: taffy::JustifyContent) -> Self {
+        self.style_mut().0.justify_content = Some(justify);
+
+        self
+    }
+
+    /// Where the widget goes across its stack, instead of where the stack's `align_items` puts it.
+    fn align_self(mut self, align: taffy::AlignSelf) -> Self {
+        self.style_mut().0.align_self = Some(align);
+
+        self
+    }
+
+    /// Where the children of a grid go within their cells, along the row.
+    fn justify_items(mut self, justify: taffy::JustifyItems) -> Self {
+        self.style_mut().0.justify_items = Some(justify);
+
+        self
+    }
+
+    /// How much of the free space in its stack the widget takes, relative to its siblings.
+    fn flex_grow(mut self, grow: f32) -> Self {
+        self.style_mut().0.flex_grow = grow;
+
+        self
+    }
+
+    /// How much the widget gives up when its stack is too small, relative to its siblings.
+    /// `0.` keeps it at its size.
+    fn flex_shrink(mut self, shrink: f32) -> Self {
+        self.style_mut().0.flex_shrink = shrink;
+
+        self
+    }
+
+    /// The size the widget starts from along its stack, before growing or shrinking.
+    fn flex_basis(mut self, basis: Dimension) -> Self {
+        self.style_mut().0.flex_basis = basis;
 
-    //     self
-    // }
+        self
+    }
 }
 
 mod button {
@@ -238,8 +714,16 @@ mod button {
             }
         }
 
+        fn kind(&self) -> &'static str {
+            "Button"
+        }
+
         #[allow(refining_impl_trait)]
-        fn compare_rebuild(self, _: MountedWidget) -> crate::BuildResult<LeafNode> {
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
             crate::BuildResult {
                 widget: MountedWidget::Button(self),
                 children: None,
@@ -271,12 +755,15 @@ mod button {
                 .build()
         }
 
+        /// A button that sends `message` to some [State] when clicked.
+        /// The message can be of any type the state knows how to reduce, e.g. an editor action for a toolbar button.
+        pub fn sends<M: Clone + 'static, S: Reducer<M>>(state: &State<M, S>, message: M) -> Button {
+            Self::on_click(state.then_send(message))
+        }
+
         /// Convenience for a state reducer that only responds to button messages.
         pub fn interactions<S: Reducer<ButtonMessage>>(state: &State<ButtonMessage, S>) -> Button {
-            Self::builder()
-                .on_click(state.then_send(ButtonMessage::Clicked(0, 0)))
-                .style(Style::default())
-                .build()
+            Self::sends(state, ButtonMessage::Clicked(0, 0))
         }
     }
 
@@ -291,14 +778,25 @@ mod button {
             self.style.clone()
         }
 
-        fn render(&self, layout: Layout, canvas: &mut crate::Canvas) {
-            canvas.clear_rect(
-                layout.location.x,
-                layout.location.y,
-                layout.size.width,
-                layout.size.height,
-                Color::rgb(200, 130, 90).into(),
-            );
+        fn render(&self, layout: Layout, list: &mut crate::DisplayList) {
+            let border = layout.border;
+            let color = Color::rgb(200, 130, 90);
+
+            // Within the border, if styled with one.
+            let x = (layout.location.x + border.left) as f32;
+            let y = (layout.location.y + border.top) as f32;
+            let width = layout.size.width.saturating_sub(border.left + border.right) as f32;
+            let height = layout
+                .size
+                .height
+                .saturating_sub(border.top + border.bottom) as f32;
+            let radius = (self.style.border_radius() - border.left.max(border.top) as f32).max(0.);
+
+            if radius > 0. {
+                list.rounded_rect(x, y, width, height, radius, color);
+            } else {
+                list.fill_rect(x, y, width, height, color);
+            }
         }
     }
 
@@ -318,18 +816,31 @@ mod button {
 mod text {
     use bevy_reflect::TypeRegistry;
     use bon::bon;
-    use cosmic_text::{Attrs, AttrsList, Buffer, BufferLine, FontSystem, LineEnding, Metrics};
+    use std::{
+        cell::{Ref, RefCell},
+        rc::Rc,
+    };
 
-    use crate::{Element, LeafNode};
+    use cosmic_text::{
+        Attrs, AttrsList, Buffer, BufferLine, Cursor, FontSystem, LineEnding, Metrics,
+    };
 
-    use super::{MountedWidget, Style, Styleable, Widget};
+    use crate::{
+        text::{measured_size, with_fallback_fonts, Fallbacks, MeasureKey, RenderCache, ShapeKey},
+        Element, LeafNode, WidgetEvent,
+    };
+
+    use super::{EventContext, MountedWidget, Style, Styleable, Widget};
 
     #[derive(Debug)]
     /// Rich text.
     pub struct Text {
         unused_text: Option<Vec<(String, AttrsList)>>,
         wrap: cosmic_text::Wrap,
-        buffer: cosmic_text::Buffer,
+        /// Where in the text the last click landed.
+        clicked: Option<Cursor>,
+        /// Shared with the [DisplayList](crate::DisplayList) while drawing.
+        buffer: Rc<RefCell<Buffer>>,
         style: Style,
     }
 
@@ -342,8 +853,16 @@ mod text {
             }
         }
 
+        fn kind(&self) -> &'static str {
+            "Text"
+        }
+
         #[allow(refining_impl_trait)]
-        fn compare_rebuild(self, _: MountedWidget) -> crate::BuildResult<LeafNode> {
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
             // todo
             crate::BuildResult {
                 widget: MountedWidget::Text(self),
@@ -371,6 +890,8 @@ mod text {
         #[builder]
         /// Create a text widget.
         /// Like all widgets, uses the builder syntax from [bon].
+        /// `weight` picks the closest face of the family, but an italic `style` needs an italic face loaded,
+        /// see [run_with_fonts](crate::run_with_fonts).
         /// ```
         ///
         /// # use paladin_view::prelude::*;
@@ -385,16 +906,21 @@ mod text {
             wrap: Option<cosmic_text::Wrap>,
             font: Option<&'static str>,
             size: Option<f32>,
+            weight: Option<cosmic_text::Weight>,
+            style: Option<cosmic_text::Style>,
         ) -> Text {
             let size = size.unwrap_or(25.);
             let attrs = Attrs::new()
                 .color(color.unwrap_or_default().into())
-                .family(cosmic_text::Family::Name(font.unwrap_or("JetBrains Mono")));
+                .family(cosmic_text::Family::Name(font.unwrap_or("JetBrains Mono")))
+                .weight(weight.unwrap_or_default())
+                .style(style.unwrap_or_default());
 
             Self {
                 unused_text: Some(vec![(text.into(), AttrsList::new(attrs))]),
-                buffer: Buffer::new_empty(Metrics::new(size, size)),
+                buffer: Rc::new(RefCell::new(Buffer::new_empty(Metrics::new(size, size)))),
                 wrap: wrap.unwrap_or(cosmic_text::Wrap::Word),
+                clicked: None,
                 style: Style::default(),
             }
         }
@@ -404,70 +930,78 @@ mod text {
             Self {
                 unused_text: Some(text),
                 wrap: cosmic_text::Wrap::Word,
-                buffer: Buffer::new_empty(Metrics::new(size, size)),
+                clicked: None,
+                buffer: Rc::new(RefCell::new(Buffer::new_empty(Metrics::new(size, size)))),
                 style: Style::default(),
             }
         }
-    }
 
-    fn text(str: &'static str) -> Text {
-        let size = 25.;
-        let attrs = Attrs::new()
-            .color(crate::Color::default().into())
-            .family(cosmic_text::Family::Name("JetBrains Mono"));
+        /// The shaped text, valid after [Widget::layout].
+        pub fn buffer(&self) -> Ref<'_, cosmic_text::Buffer> {
+            self.buffer.borrow()
+        }
 
-        Text {
-            unused_text: Some(vec![(str.into(), AttrsList::new(attrs))]),
-            buffer: Buffer::new_empty(Metrics::new(size, size)),
-            wrap: cosmic_text::Wrap::Word,
-            style: Style::default(),
+        /// How far below the top of the widget the first line of text sits, once laid out.
+        pub fn baseline(&self) -> Option<f32> {
+            self.buffer
+                .borrow()
+                .layout_runs()
+                .next()
+                .map(|run| run.line_y)
         }
-    }
 
-    impl Element for &'static str {
-        #[allow(refining_impl_trait)]
-        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
-            crate::BuildResult {
-                widget: MountedWidget::Text(text(self)),
-                children: None,
-            }
+        /// Where in the text the last click landed, if it hit any.
+        pub fn clicked(&self) -> Option<Cursor> {
+            self.clicked
         }
 
-        #[allow(refining_impl_trait)]
-        fn compare_rebuild(self, _: MountedWidget) -> crate::BuildResult<LeafNode> {
-            crate::BuildResult {
-                widget: MountedWidget::Text(text(self)),
-                children: None,
+        /// Like [Widget::layout], copying the shaping of an identical text laid out this frame if there is one.
+        pub(crate) fn layout_shared(&mut self, layout: crate::Layout, cache: &mut RenderCache) {
+            let key = self.unused_text.as_deref().and_then(|text| {
+                ShapeKey::new(text, self.buffer.borrow().metrics(), self.wrap, layout.size)
+            });
+            let Some(key) = key else {
+                return self.layout_in(layout, &mut cache.font_system, &cache.fallbacks);
+            };
+
+            if let Some(shaped) = cache.shapes.get(&key) {
+                *self.buffer.borrow_mut() = shaped.clone();
+                self.unused_text = None;
+
+                return;
             }
+
+            self.layout_in(layout, &mut cache.font_system, &cache.fallbacks);
+            cache.shapes.insert(key, self.buffer.borrow().clone());
         }
-    }
 
-    impl Widget for Text {
-        fn layout(&mut self, layout: crate::Layout, font_system: &mut FontSystem) {
-            if self.wrap != self.buffer.wrap() {
-                self.buffer.set_wrap(font_system, self.wrap);
+        /// Like [Widget::layout], with what text falls back to from `fallbacks`.
+        pub(crate) fn layout_in(
+            &mut self,
+            layout: crate::Layout,
+            font_system: &mut FontSystem,
+            fallbacks: &Fallbacks,
+        ) {
+            let lines = self
+                .unused_text
+                .take()
+                .map(|text| buffer_lines(text, font_system, fallbacks));
+
+            let mut buffer = self.buffer.borrow_mut();
+
+            if self.wrap != buffer.wrap() {
+                buffer.set_wrap(font_system, self.wrap);
             }
 
-            let mut buffer = self.buffer.borrow_with(font_system);
+            let mut buffer = buffer.borrow_with(font_system);
 
             buffer.set_size(
                 Some(layout.size.width as f32),
                 Some(layout.size.height as f32),
             );
 
-            if let Some(text) = self.unused_text.take() {
-                buffer.lines.clear();
-
-                for (text, attrs) in text {
-                    buffer.lines.push(BufferLine::new(
-                        text,
-                        LineEnding::default(),
-                        attrs,
-                        // This _MUST_ be advanced for coloring to work.
-                        // Otherwise the colors appear to apply per-word instead of per-byte? Not sure, but leave as is.
-                        cosmic_text::Shaping::Advanced,
-                    ));
-                }
+            if let Some(lines) = lines {
+                buffer.lines = lines;
             }
 
             // if self.buffer_needs_refresh {
@@ -475,27 +1009,234 @@ mod text {
             // }
         }
 
-        fn render(&self, layout: crate::Layout, canvas: &mut crate::Canvas) {
-            let text_draw_cmds = canvas
-                .text_cache
-                .fill_buffer_to_draw_commands(
-                    &mut canvas.inner,
-                    &self.buffer,
-                    (layout.location.x as f32, layout.location.y as f32),
-                )
-                .unwrap();
+        /// Like [Widget::measure], reusing the size of the same text measured at the same width.
+        pub(crate) fn measure_shared(
+            &mut self,
+            known: taffy::Size<Option<f32>>,
+            available: taffy::Size<taffy::AvailableSpace>,
+            cache: &mut RenderCache,
+        ) -> Option<taffy::Size<f32>> {
+            let buffer = self.buffer.borrow();
+            let max_width = max_width(known, available);
 
-            for (color, cmds) in text_draw_cmds {
-                canvas.inner.draw_glyph_commands(
-                    cmds,
-                    &femtovg::Paint::color(femtovg::Color::rgb(color.r(), color.g(), color.b())),
-                    1.,
-                );
+            let key = match &self.unused_text {
+                Some(text) => MeasureKey::new(
+                    text.iter().map(|(line, attrs)| (line.as_str(), attrs)),
+                    &cache.fallbacks,
+                    buffer.metrics(),
+                    self.wrap,
+                    max_width,
+                ),
+                None => MeasureKey::new(
+                    buffer
+                        .lines
+                        .iter()
+                        .map(|line| (line.text(), line.attrs_list())),
+                    &cache.fallbacks,
+                    buffer.metrics(),
+                    self.wrap,
+                    max_width,
+                ),
+            };
+            drop(buffer);
+
+            let frame = cache.frame();
+            let size = match cache.measures.get(&key, frame) {
+                Some(size) => size,
+                None => {
+                    let size = self.measured(max_width, &mut cache.font_system, &cache.fallbacks);
+                    cache.measures.insert(key, size, frame);
+
+                    size
+                }
+            };
+
+            Some(taffy::Size {
+                width: known.width.unwrap_or(size.width),
+                height: known.height.unwrap_or(size.height),
+            })
+        }
+
+        /// Like [Widget::measure], with what text falls back to from `fallbacks`.
+        pub(crate) fn measure_in(
+            &mut self,
+            known: taffy::Size<Option<f32>>,
+            available: taffy::Size<taffy::AvailableSpace>,
+            font_system: &mut FontSystem,
+            fallbacks: &Fallbacks,
+        ) -> Option<taffy::Size<f32>> {
+            let size = self.measured(max_width(known, available), font_system, fallbacks);
+
+            Some(taffy::Size {
+                width: known.width.unwrap_or(size.width),
+                height: known.height.unwrap_or(size.height),
+            })
+        }
+
+        /// The size of the text wrapped to `max_width`, shaped in a copy of the buffer.
+        fn measured(
+            &self,
+            max_width: Option<f32>,
+            font_system: &mut FontSystem,
+            fallbacks: &Fallbacks,
+        ) -> taffy::Size<f32> {
+            let mut buffer = self.buffer.borrow().clone();
+            buffer.set_wrap(font_system, self.wrap);
+
+            if let Some(text) = &self.unused_text {
+                buffer.lines = buffer_lines(text.clone(), font_system, fallbacks);
             }
+
+            measured_size(font_system, &mut buffer, max_width)
         }
 
-        fn style(&self) -> Style {
-            self.style.clone()
+        /// How many spaces a tab advances to.
+        pub fn set_tab_width(&mut self, font_system: &mut FontSystem, tab_width: u16) {
+            self.buffer
+                .borrow_mut()
+                .set_tab_width(font_system, tab_width);
+        }
+    }
+
+    /// How wide the text may get, when measured with `known` and `available`.
+    fn max_width(
+        known: taffy::Size<Option<f32>>,
+        available: taffy::Size<taffy::AvailableSpace>,
+    ) -> Option<f32> {
+        known.width.or(match available.width {
+            taffy::AvailableSpace::Definite(width) => Some(width),
+            // Wrapping at every word
+            taffy::AvailableSpace::MinContent => Some(0.),
+            taffy::AvailableSpace::MaxContent => None,
+        })
+    }
+
+    /// A line of the buffer for each span of text, with the fallback fonts for what its font lacks.
+    fn buffer_lines(
+        text: Vec<(String, AttrsList)>,
+        font_system: &mut FontSystem,
+        fallbacks: &Fallbacks,
+    ) -> Vec<BufferLine> {
+        text.into_iter()
+            .map(|(text, attrs)| {
+                let attrs = with_fallback_fonts(font_system, fallbacks, &text, attrs);
+
+                BufferLine::new(
+                    text,
+                    LineEnding::default(),
+                    attrs,
+                    // This _MUST_ be advanced for coloring to work.
+                    // Otherwise the colors appear to apply per-word instead of per-byte? Not sure, but leave as is.
+                    cosmic_text::Shaping::Advanced,
+                )
+            })
+            .collect()
+    }
+
+    fn text(str: String) -> Text {
+        let size = 25.;
+        let attrs = Attrs::new()
+            .color(crate::Color::default().into())
+            .family(cosmic_text::Family::Name("JetBrains Mono"));
+
+        Text {
+            unused_text: Some(vec![(str, AttrsList::new(attrs))]),
+            buffer: Rc::new(RefCell::new(Buffer::new_empty(Metrics::new(size, size)))),
+            wrap: cosmic_text::Wrap::Word,
+            clicked: None,
+            style: Style::default(),
+        }
+    }
+
+    impl Element for &'static str {
+        #[allow(refining_impl_trait)]
+        fn create(self, registry: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            self.to_string().create(registry)
+        }
+
+        fn kind(&self) -> &'static str {
+            "Text"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            old: MountedWidget,
+            registry: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            self.to_string().compare_rebuild(old, registry)
+        }
+    }
+
+    /// Owned text, like a label made with `format!`.
+    impl Element for String {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Text(text(self)),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Text"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Text(text(self)),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Text {
+        fn layout(&mut self, layout: crate::Layout, font_system: &mut FontSystem) {
+            self.layout_in(layout, font_system, &Fallbacks::default());
+        }
+
+        /// As tall as the text is once wrapped to the width it gets, and as wide as its longest line.
+        fn measure(
+            &mut self,
+            known: taffy::Size<Option<f32>>,
+            available: taffy::Size<taffy::AvailableSpace>,
+            font_system: &mut FontSystem,
+        ) -> Option<taffy::Size<f32>> {
+            self.measure_in(known, available, font_system, &Fallbacks::default())
+        }
+
+        fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+            let WidgetEvent::Click(x, y) = event else {
+                return;
+            };
+
+            let layout = context.layout;
+
+            // Not shaped yet, when clicked before the first layout.
+            if self.unused_text.is_some() {
+                self.layout(layout, context.font_system());
+            }
+
+            self.clicked = self.buffer.borrow().hit(
+                x as f32 - layout.location.x as f32,
+                y as f32 - layout.location.y as f32,
+            );
+        }
+
+        fn render(&self, layout: crate::Layout, list: &mut crate::DisplayList) {
+            list.text(
+                self.buffer.clone(),
+                (layout.location.x as f32, layout.location.y as f32),
+            );
+        }
+
+        fn style(&self) -> Style {
+            self.style.clone()
         }
     }
 
@@ -504,111 +1245,2770 @@ mod text {
             &mut self.style
         }
     }
-}
+}
+
+mod stack {
+
+    use std::{fmt::Debug, marker::PhantomData};
+
+    use bevy_reflect::TypeRegistry;
+
+    use crate::{BuildResult, Element, InsertChildren, RebuildChildren};
+
+    use super::{ChildInsertBuilder, ChildRebuildBuilder, ChildView, Style, Styleable, Widget};
+
+    #[derive(Debug)]
+    pub struct HStack {
+        style: Style,
+    }
+
+    pub struct HStackElement<F, Children: ChildView<F>> {
+        children: Children,
+        style: Style,
+        phantom: PhantomData<F>,
+    }
+
+    pub(crate) struct HStackChildren<F, Children: ChildView<F>> {
+        children: Children,
+        phantom: PhantomData<F>,
+    }
+
+    impl<F: 'static, C: ChildView<F> + 'static> RebuildChildren for HStackChildren<F, C> {
+        fn rebuild_children(self, builder: &mut impl crate::RebuildContext) {
+            self.children.call_each(ChildRebuildBuilder { pc: builder });
+        }
+    }
+
+    impl<F: 'static, C: ChildView<F> + 'static> InsertChildren for HStackChildren<F, C> {
+        fn insert_children(self, builder: &mut impl crate::InsertContext) {
+            self.children.call_each(ChildInsertBuilder { pc: builder });
+        }
+    }
+
+    impl<F, Children: ChildView<F>> Element for HStackElement<F, Children>
+    where
+        F: 'static,
+        Children: 'static,
+    {
+        fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
+            crate::BuildResult {
+                widget: super::MountedWidget::HStack(HStack { style: self.style }),
+                children: Some(HStackChildren {
+                    children: self.children,
+                    phantom: PhantomData,
+                }),
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "HStack"
+        }
+
+        fn compare_rebuild(
+            self,
+            _: super::MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> BuildResult<impl RebuildChildren> {
+            // if !matches!(old, MountedWidget::HStack(_)) {
+            //     return CompareResult::Replace { with: self };
+            // }
+
+            // context.insert(super::MountedWidget::HStack(HStack));
+
+            // self.children.call_each(ChildRebuildBuilder { pc: context });
+            crate::BuildResult {
+                widget: super::MountedWidget::HStack(HStack { style: self.style }),
+                children: Some(HStackChildren {
+                    children: self.children,
+                    phantom: PhantomData,
+                }),
+            }
+
+            // crate::CompareResult::<Self>::Success
+        }
+    }
+
+    impl Widget for HStack {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+    }
+
+    impl<F, Children: ChildView<F>> Styleable for HStackElement<F, Children> {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+
+    /// A horizontal stack, also called a Row.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// hstack(
+    ///     (
+    ///         "Hello",
+    ///         "World !"
+    ///     )
+    /// );
+    ///
+    /// ```
+    pub fn hstack<F: 'static, CV: ChildView<F> + 'static>(child: CV) -> HStackElement<F, CV> {
+        HStackElement {
+            children: child,
+            style: Style::default().with_direction(taffy::FlexDirection::Row),
+            phantom: PhantomData,
+        }
+    }
+}
+
+mod effect {
+    use std::fmt::Debug;
+
+    use bevy_reflect::TypeRegistry;
+    use taffy::Display;
+
+    use crate::{Element, LeafNode};
+
+    use super::{MountedWidget, Style, Widget};
+
+    type Hook = Box<dyn FnOnce()>;
+
+    /// Side effects tied to where the element is mounted, like starting a timer when a view first shows.
+    /// Takes no space and draws nothing.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// fragment((
+    ///     on_mount(|| println!("Opened")).on_unmount(|| println!("Closed")),
+    ///     "Document",
+    /// ));
+    ///
+    /// ```
+    pub struct Effect {
+        on_mount: Option<Hook>,
+        on_unmount: Option<Hook>,
+    }
+
+    /// Run `f` once, when the element is mounted. Rebuilding it in place does not run it again.
+    pub fn on_mount(f: impl FnOnce() + 'static) -> Effect {
+        Effect {
+            on_mount: Some(Box::new(f)),
+            on_unmount: None,
+        }
+    }
+
+    /// Run `f` once, when the element is removed from the tree.
+    pub fn on_unmount(f: impl FnOnce() + 'static) -> Effect {
+        Effect {
+            on_mount: None,
+            on_unmount: Some(Box::new(f)),
+        }
+    }
+
+    impl Effect {
+        pub fn on_unmount(mut self, f: impl FnOnce() + 'static) -> Self {
+            self.on_unmount = Some(Box::new(f));
+            self
+        }
+    }
+
+    impl Element for Effect {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Effect(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Effect"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            mut self,
+            old: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            // Already mounted, keep the unmount hook paired with the mount that ran.
+            if let MountedWidget::Effect(old) = old {
+                self.on_mount = None;
+                self.on_unmount = old.on_unmount;
+            }
+
+            crate::BuildResult {
+                widget: MountedWidget::Effect(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Effect {
+        fn style(&self) -> Style {
+            let mut style = Style::default();
+            style.0.display = Display::None;
+
+            style
+        }
+
+        fn mounted(&mut self) {
+            if let Some(on_mount) = self.on_mount.take() {
+                on_mount();
+            }
+        }
+
+        fn unmounted(&mut self) {
+            if let Some(on_unmount) = self.on_unmount.take() {
+                on_unmount();
+            }
+        }
+    }
+
+    impl Debug for Effect {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("Effect")
+                .field("mounted", &self.on_mount.is_none())
+                .finish()
+        }
+    }
+}
+
+mod fragment {
+    use std::marker::PhantomData;
+
+    use bevy_reflect::TypeRegistry;
+
+    use crate::{BuildResult, Element, InsertContext, LeafNode, MountedWidget, RebuildContext};
+
+    use super::{
+        sealed::{ChildViewFnBuilder, Sealed},
+        ChildInsertBuilder, ChildRebuildBuilder, ChildView,
+    };
+
+    pub struct Fragment<F, Children: ChildView<F>> {
+        children: Children,
+        phantom: PhantomData<F>,
+    }
+
+    impl<F, Children: ChildView<F>> Element for Fragment<F, Children>
+    where
+        F: 'static,
+        Children: 'static,
+    {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> BuildResult<LeafNode> {
+            unreachable!("Fragments insert their children directly")
+        }
+
+        fn kind(&self) -> &'static str {
+            unreachable!("Fragments mount no widget of their own")
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(self, _: MountedWidget, _: &mut TypeRegistry) -> BuildResult<LeafNode> {
+            unreachable!("Fragments rebuild their children directly")
+        }
+
+        fn insert(self, context: &mut impl InsertContext) {
+            self.children.call_each(ChildInsertBuilder { pc: context });
+        }
+
+        fn rebuild(self, context: &mut impl RebuildContext) {
+            self.children.call_each(ChildRebuildBuilder { pc: context });
+        }
+    }
+
+    /// Several elements side by side, without a node of their own.
+    /// Useful for returning more than one element from [View::build](crate::View::build).
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// fragment(
+    ///     (
+    ///         "Hello",
+    ///         "World !"
+    ///     )
+    /// );
+    ///
+    /// ```
+    pub fn fragment<F: 'static, CV: ChildView<F> + 'static>(children: CV) -> Fragment<F, CV> {
+        Fragment {
+            children,
+            phantom: PhantomData,
+        }
+    }
+
+    /// The elements of an iterator, see [children].
+    pub struct Children<I>(I);
+
+    impl<I: IntoIterator<Item = E>, E: Element> Sealed<Children<I>> for Children<I> {}
+
+    impl<I: IntoIterator<Item = E>, E: Element> ChildView<Children<I>> for Children<I> {
+        fn call_each(self, mut f: impl ChildViewFnBuilder) {
+            let mut f = f.create_fn();
+
+            for child in self.0 {
+                f(child)
+            }
+        }
+    }
+
+    /// Each element of an iterator, side by side like a [fragment], without collecting them first.
+    /// Rebuilt by position, the first element against the first child mounted last time and so on.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// hstack((
+    ///     "Tabs:",
+    ///     children(["main.rs", "lib.rs"].into_iter().map(|name| format!("[{name}]"))),
+    /// ));
+    ///
+    /// ```
+    pub fn children<I, E>(iter: I) -> Fragment<Children<I>, Children<I>>
+    where
+        I: IntoIterator<Item = E> + 'static,
+        E: Element,
+    {
+        fragment(Children(iter))
+    }
+}
+
+mod any_element {
+    use bevy_reflect::TypeRegistry;
+
+    use crate::{
+        BuildResult, Element, InsertChildren, InsertContext, MountedWidget, RebuildChildren,
+        RebuildContext,
+    };
+
+    /// A type-erased [Element], for when elements of different types have to be stored together.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// hstack(vec![
+    ///     AnyElement::new("Hello"),
+    ///     AnyElement::new(Button::on_click(|| {})),
+    /// ]);
+    ///
+    /// ```
+    pub struct AnyElement(Box<dyn ErasedElement>);
+
+    impl AnyElement {
+        pub fn new(element: impl Element) -> Self {
+            Self(Box::new(element))
+        }
+    }
+
+    impl Element for AnyElement {
+        #[allow(refining_impl_trait)]
+        fn create(self, registry: &mut TypeRegistry) -> BuildResult<AnyInsertChildren> {
+            self.0.create_erased(registry)
+        }
+
+        fn kind(&self) -> &'static str {
+            self.0.kind_erased()
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            old: MountedWidget,
+            registry: &mut TypeRegistry,
+        ) -> BuildResult<AnyRebuildChildren> {
+            self.0.compare_rebuild_erased(old, registry)
+        }
+
+        fn insert(self, context: &mut impl InsertContext) {
+            self.0.insert_erased(context)
+        }
+
+        fn rebuild(self, context: &mut impl RebuildContext) {
+            self.0.rebuild_erased(context)
+        }
+    }
+
+    // Object safe counterparts of the element traits.
+    // The generic contexts are passed along as trait objects, and any child reached through them is erased in turn.
+
+    trait ErasedElement {
+        fn create_erased(
+            self: Box<Self>,
+            registry: &mut TypeRegistry,
+        ) -> BuildResult<AnyInsertChildren>;
+        fn kind_erased(&self) -> &'static str;
+        fn compare_rebuild_erased(
+            self: Box<Self>,
+            old: MountedWidget,
+            registry: &mut TypeRegistry,
+        ) -> BuildResult<AnyRebuildChildren>;
+        fn insert_erased(self: Box<Self>, context: &mut dyn DynInsertContext);
+        fn rebuild_erased(self: Box<Self>, context: &mut dyn DynRebuildContext);
+    }
+
+    impl<E: Element> ErasedElement for E {
+        fn create_erased(
+            self: Box<Self>,
+            registry: &mut TypeRegistry,
+        ) -> BuildResult<AnyInsertChildren> {
+            let BuildResult { widget, children } = (*self).create(registry);
+
+            BuildResult {
+                widget,
+                children: children.map(|children| AnyInsertChildren(Box::new(children))),
+            }
+        }
+
+        fn kind_erased(&self) -> &'static str {
+            self.kind()
+        }
+
+        fn compare_rebuild_erased(
+            self: Box<Self>,
+            old: MountedWidget,
+            registry: &mut TypeRegistry,
+        ) -> BuildResult<AnyRebuildChildren> {
+            let BuildResult { widget, children } = (*self).compare_rebuild(old, registry);
+
+            BuildResult {
+                widget,
+                children: children.map(|children| AnyRebuildChildren(Box::new(children))),
+            }
+        }
+
+        fn insert_erased(self: Box<Self>, context: &mut dyn DynInsertContext) {
+            (*self).insert(&mut ErasedInsertContext(context))
+        }
+
+        fn rebuild_erased(self: Box<Self>, context: &mut dyn DynRebuildContext) {
+            (*self).rebuild(&mut ErasedRebuildContext(context))
+        }
+    }
+
+    #[doc(hidden)]
+    pub struct AnyInsertChildren(Box<dyn ErasedInsertChildren>);
+
+    #[doc(hidden)]
+    pub struct AnyRebuildChildren(Box<dyn ErasedRebuildChildren>);
+
+    trait ErasedInsertChildren {
+        fn insert_children_erased(self: Box<Self>, context: &mut dyn DynInsertContext);
+    }
+
+    trait ErasedRebuildChildren {
+        fn rebuild_children_erased(self: Box<Self>, context: &mut dyn DynRebuildContext);
+    }
+
+    impl<C: InsertChildren> ErasedInsertChildren for C {
+        fn insert_children_erased(self: Box<Self>, context: &mut dyn DynInsertContext) {
+            (*self).insert_children(&mut ErasedInsertContext(context))
+        }
+    }
+
+    impl<C: RebuildChildren> ErasedRebuildChildren for C {
+        fn rebuild_children_erased(self: Box<Self>, context: &mut dyn DynRebuildContext) {
+            (*self).rebuild_children(&mut ErasedRebuildContext(context))
+        }
+    }
+
+    impl InsertChildren for AnyInsertChildren {
+        fn insert_children(self, context: &mut impl InsertContext) {
+            self.0.insert_children_erased(context)
+        }
+    }
+
+    impl RebuildChildren for AnyRebuildChildren {
+        fn rebuild_children(self, context: &mut impl RebuildContext) {
+            self.0.rebuild_children_erased(context)
+        }
+    }
+
+    trait DynInsertContext {
+        fn insert_any(&mut self, e: AnyElement);
+    }
+
+    trait DynRebuildContext {
+        fn rebuild_any(&mut self, e: AnyElement);
+        fn keep_any(&mut self);
+    }
+
+    impl<C: InsertContext> DynInsertContext for C {
+        fn insert_any(&mut self, e: AnyElement) {
+            self.insert_child(e)
+        }
+    }
+
+    impl<C: RebuildContext> DynRebuildContext for C {
+        fn rebuild_any(&mut self, e: AnyElement) {
+            self.rebuild_child(e)
+        }
+
+        fn keep_any(&mut self) {
+            self.keep_children()
+        }
+    }
+
+    struct ErasedInsertContext<'a>(&'a mut dyn DynInsertContext);
+
+    struct ErasedRebuildContext<'a>(&'a mut dyn DynRebuildContext);
+
+    impl<'a> InsertContext for ErasedInsertContext<'a> {
+        fn insert_child<E: Element>(&mut self, e: E) {
+            self.0.insert_any(AnyElement::new(e))
+        }
+    }
+
+    impl<'a> RebuildContext for ErasedRebuildContext<'a> {
+        fn rebuild_child<E: Element>(&mut self, e: E) {
+            self.0.rebuild_any(AnyElement::new(e))
+        }
+
+        fn keep_children(&mut self) {
+            self.0.keep_any()
+        }
+    }
+}
+
+mod cached {
+    use bevy_reflect::TypeRegistry;
+    use femtovg::{renderer::Renderer, ImageFlags, ImageId, PixelFormat};
+
+    use crate::{
+        BuildResult, Canvas, DisplayList, Element, InsertChildren, Layout, MountedWidget,
+        RebuildChildren, SingleChild, Size,
+    };
+
+    use super::Widget;
+
+    /// Renders its child once into an offscreen image, then draws that image until the child is rebuilt or resized.
+    /// Useful for large subtrees that rarely change.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// cached(hstack(("Hello", "World !")));
+    ///
+    /// ```
+    pub struct Cached<E> {
+        child: E,
+    }
+
+    pub fn cached<E: Element>(child: E) -> Cached<E> {
+        Cached { child }
+    }
+
+    #[derive(Debug, Default)]
+    pub struct CachedWidget {
+        image: Option<ImageId>,
+        // The size the image was rendered at
+        rendered: Option<Size>,
+        dirty: bool,
+    }
+
+    impl<E: Element> Element for Cached<E> {
+        fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
+            BuildResult {
+                widget: MountedWidget::Cached(CachedWidget::default()),
+                children: Some(SingleChild(self.child)),
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Cached"
+        }
+
+        fn compare_rebuild(
+            self,
+            old: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> BuildResult<impl RebuildChildren> {
+            // Keep the old image around, it can be drawn into again if the size is unchanged.
+            let mut widget = match old {
+                MountedWidget::Cached(old) => old,
+                _ => CachedWidget::default(),
+            };
+
+            widget.invalidate();
+
+            BuildResult {
+                widget: MountedWidget::Cached(widget),
+                children: Some(SingleChild(self.child)),
+            }
+        }
+    }
+
+    impl CachedWidget {
+        /// Whether the children must be rendered again before drawing at `size`.
+        pub(crate) fn is_stale(&self, size: Size) -> bool {
+            self.dirty || self.rendered != Some(size)
+        }
+
+        pub(crate) fn invalidate(&mut self) {
+            self.dirty = true;
+        }
+
+        pub(crate) fn rendered(&mut self, size: Size) {
+            self.rendered = Some(size);
+            self.dirty = false;
+        }
+
+        /// Get an image to render the children into, marking the cache fresh.
+        /// None if there is nothing to render.
+        pub(crate) fn render_target<R: Renderer>(
+            &mut self,
+            canvas: &mut Canvas<R>,
+            size: Size,
+        ) -> Option<ImageId> {
+            if self.rendered != Some(size) {
+                if let Some(old) = self.image.take() {
+                    canvas.draw().delete_image(old);
+                }
+            }
+
+            self.rendered(size);
+
+            if size.width == 0 || size.height == 0 {
+                return None;
+            }
+
+            if self.image.is_none() {
+                self.image = canvas
+                    .inner
+                    .create_image_empty(
+                        size.width as usize,
+                        size.height as usize,
+                        PixelFormat::Rgba8,
+                        ImageFlags::FLIP_Y | ImageFlags::PREMULTIPLIED,
+                    )
+                    .inspect_err(|err| {
+                        dbg!("WARN: ", err);
+                    })
+                    .ok();
+            }
+
+            self.image
+        }
+    }
+
+    impl Widget for CachedWidget {
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            let Some(image) = self.image else {
+                return;
+            };
+
+            list.image(
+                image,
+                layout.location.x as f32,
+                layout.location.y as f32,
+                layout.size.width as f32,
+                layout.size.height as f32,
+            );
+        }
+    }
+}
+
+mod center {
+    use bevy_reflect::TypeRegistry;
+    use taffy::{prelude::percent, AlignItems, JustifyContent};
+
+    use crate::{
+        BuildResult, Element, InsertChildren, MountedWidget, RebuildChildren, SingleChild,
+    };
+
+    use super::{Style, Styleable, Widget};
+
+    /// Fills the space it is given and centers its child in it.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// center("Hello");
+    /// center_x(Button::on_click(|| {}));
+    ///
+    /// ```
+    pub struct Center<E> {
+        child: E,
+        style: Style,
+    }
+
+    /// Center `child` horizontally and vertically.
+    pub fn center<E: Element>(child: E) -> Center<E> {
+        Center::new(child, true, true)
+    }
+
+    /// Center `child` horizontally only.
+    pub fn center_x<E: Element>(child: E) -> Center<E> {
+        Center::new(child, true, false)
+    }
+
+    /// Center `child` vertically only.
+    pub fn center_y<E: Element>(child: E) -> Center<E> {
+        Center::new(child, false, true)
+    }
+
+    impl<E> Center<E> {
+        fn new(child: E, x: bool, y: bool) -> Self {
+            let mut style = Style::default();
+            style.0.size.height = percent(1_f32);
+
+            // A row, so the main axis is horizontal.
+            if x {
+                style.0.justify_content = Some(JustifyContent::Center);
+            }
+
+            if y {
+                style.0.align_items = Some(AlignItems::Center);
+            }
+
+            Self { child, style }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct CenterWidget {
+        style: Style,
+    }
+
+    impl<E: Element> Element for Center<E> {
+        fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
+            BuildResult {
+                widget: MountedWidget::Center(CenterWidget { style: self.style }),
+                children: Some(SingleChild(self.child)),
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Center"
+        }
+
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> BuildResult<impl RebuildChildren> {
+            BuildResult {
+                widget: MountedWidget::Center(CenterWidget { style: self.style }),
+                children: Some(SingleChild(self.child)),
+            }
+        }
+    }
+
+    impl Widget for CenterWidget {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+    }
+
+    impl<E> Styleable for Center<E> {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+}
+
+mod spacer {
+    use bevy_reflect::TypeRegistry;
+    use taffy::prelude::length;
+
+    use crate::{Element, LeafNode};
+
+    use super::{MountedWidget, Style, Styleable, Widget};
+
+    /// Takes up the free space in a stack, pushing its siblings apart. Draws nothing.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// // Open on the left edge, Save on the right
+    /// hstack((
+    ///     Button::on_click(|| println!("Open")),
+    ///     spacer(),
+    ///     Button::on_click(|| println!("Save")),
+    /// ));
+    ///
+    /// ```
+    #[derive(Debug)]
+    pub struct Spacer {
+        style: Style,
+    }
+
+    pub fn spacer() -> Spacer {
+        let mut style = Style::default();
+        style.0.size.width = length(0_f32);
+        style.0.size.height = length(0_f32);
+        style.0.flex_grow = 1.;
+
+        Spacer { style }
+    }
+
+    impl Element for Spacer {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Spacer(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Spacer"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Spacer(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Spacer {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+    }
+
+    impl Styleable for Spacer {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+}
+
+mod divider {
+    use bevy_reflect::TypeRegistry;
+    use taffy::prelude::{length, percent};
+
+    use crate::{Color, DisplayList, Element, Layout, LeafNode};
+
+    use super::{MountedWidget, Style, Styleable, Widget};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Orientation {
+        /// A line across, separating what is above from what is below.
+        Horizontal,
+        /// A line down, separating what is left from what is right.
+        Vertical,
+    }
+
+    /// A thin line separating panes, as long as its parent and 1px thick in a subtle gray by default.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// // A file tree, then the buffer
+    /// hstack((
+    ///     Button::on_click(|| println!("Tree")),
+    ///     Divider::vertical().thickness(2.),
+    ///     Button::on_click(|| println!("Buffer")),
+    /// ));
+    ///
+    /// ```
+    #[derive(Debug)]
+    pub struct Divider {
+        orientation: Orientation,
+        color: Color,
+        style: Style,
+    }
+
+    impl Divider {
+        pub fn new(orientation: Orientation) -> Self {
+            let mut style = Style::default();
+            // Keeps its thickness when the stack is short on space.
+            style.0.flex_shrink = 0.;
+
+            Divider {
+                orientation,
+                color: Color::rgba(255, 255, 255, 40),
+                style,
+            }
+            .thickness(1.)
+        }
+
+        pub fn horizontal() -> Self {
+            Self::new(Orientation::Horizontal)
+        }
+
+        pub fn vertical() -> Self {
+            Self::new(Orientation::Vertical)
+        }
+
+        pub fn orientation(&self) -> Orientation {
+            self.orientation
+        }
+
+        /// Pixels across the line, it spans the whole of its parent the other way.
+        pub fn thickness(mut self, thickness: f32) -> Self {
+            let size = &mut self.style.0.size;
+
+            match self.orientation {
+                Orientation::Horizontal => {
+                    size.width = percent(1_f32);
+                    size.height = length(thickness);
+                }
+                Orientation::Vertical => {
+                    size.width = length(thickness);
+                    size.height = percent(1_f32);
+                }
+            }
+
+            self
+        }
+
+        pub fn color(mut self, color: Color) -> Self {
+            self.color = color;
+            self
+        }
+    }
+
+    impl Element for Divider {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Divider(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Divider"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Divider(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Divider {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            list.fill_rect(
+                layout.location.x as f32,
+                layout.location.y as f32,
+                layout.size.width as f32,
+                layout.size.height as f32,
+                self.color,
+            );
+        }
+    }
+
+    impl Styleable for Divider {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+}
+
+mod image {
+    use std::{
+        cell::{Ref, RefCell},
+        collections::HashMap,
+        path::{Path, PathBuf},
+        rc::Rc,
+    };
+
+    use bevy_reflect::TypeRegistry;
+    use crossbeam::channel::{Receiver, Sender, TryRecvError};
+    pub use femtovg::ImageId;
+    use femtovg::{renderer::Renderer, ImageFlags};
+    use imgref::ImgVec;
+    use rgb::RGBA8;
+    use taffy::prelude::{length, percent};
+
+    use crate::{Canvas, Color, DisplayList, Element, Layout, LeafNode};
+
+    use super::{MountedWidget, Style, Styleable, Widget};
+
+    /// How an image is sized within its node when their aspect ratios differ.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum Fit {
+        /// Scale to fit inside the node, keeping the aspect ratio. Leaves empty bars on two sides.
+        #[default]
+        Contain,
+        /// Scale to cover the whole node, keeping the aspect ratio. The overflowing part is cropped.
+        Cover,
+        /// Stretch to the size of the node.
+        Fill,
+        /// Draw at the image's own size, cropping whatever is outside the node.
+        None,
+    }
+
+    /// Where an image sits within its node when it doesn't fill it.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub enum Align {
+        TopLeft,
+        Top,
+        TopRight,
+        Left,
+        #[default]
+        Center,
+        Right,
+        BottomLeft,
+        Bottom,
+        BottomRight,
+    }
+
+    impl Align {
+        /// Fraction of the free space before the image, horizontally and vertically.
+        fn fractions(self) -> (f32, f32) {
+            match self {
+                Align::TopLeft => (0., 0.),
+                Align::Top => (0.5, 0.),
+                Align::TopRight => (1., 0.),
+                Align::Left => (0., 0.5),
+                Align::Center => (0.5, 0.5),
+                Align::Right => (1., 0.5),
+                Align::BottomLeft => (0., 1.),
+                Align::Bottom => (0.5, 1.),
+                Align::BottomRight => (1., 1.),
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct ImageRect {
+        pub x: f32,
+        pub y: f32,
+        pub width: f32,
+        pub height: f32,
+    }
+
+    /// The part of an image that is shown, in image pixels, and where it is drawn.
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub struct Placement {
+        pub source: ImageRect,
+        pub dest: ImageRect,
+    }
+
+    impl Placement {
+        /// Place an image of `size` in `bounds`.
+        pub fn new(size: (f32, f32), bounds: ImageRect, fit: Fit, align: Align) -> Self {
+            let (width, height) = size;
+            let (align_x, align_y) = align.fractions();
+
+            let (scale_x, scale_y) = match fit {
+                Fit::Contain => {
+                    let scale = (bounds.width / width).min(bounds.height / height);
+                    (scale, scale)
+                }
+                Fit::Cover => {
+                    let scale = (bounds.width / width).max(bounds.height / height);
+                    (scale, scale)
+                }
+                Fit::Fill => (bounds.width / width, bounds.height / height),
+                Fit::None => (1., 1.),
+            };
+
+            let (x, source_x, dest_width) = axis(bounds.x, bounds.width, width, scale_x, align_x);
+            let (y, source_y, dest_height) =
+                axis(bounds.y, bounds.height, height, scale_y, align_y);
+
+            Self {
+                source: ImageRect {
+                    x: source_x,
+                    y: source_y,
+                    width: dest_width / scale_x,
+                    height: dest_height / scale_y,
+                },
+                dest: ImageRect {
+                    x,
+                    y,
+                    width: dest_width,
+                    height: dest_height,
+                },
+            }
+        }
+
+        /// Where the whole image is drawn so that [Placement::source] lands on [Placement::dest].
+        fn drawn(&self, size: (f32, f32)) -> ImageRect {
+            let scale_x = self.dest.width / self.source.width;
+            let scale_y = self.dest.height / self.source.height;
+
+            ImageRect {
+                x: self.dest.x - self.source.x * scale_x,
+                y: self.dest.y - self.source.y * scale_y,
+                width: size.0 * scale_x,
+                height: size.1 * scale_y,
+            }
+        }
+    }
+
+    /// The visible start and length of one axis, plus where the visible part starts in the image.
+    fn axis(start: f32, available: f32, size: f32, scale: f32, align: f32) -> (f32, f32, f32) {
+        let drawn = size * scale;
+        let drawn_start = start + (available - drawn) * align;
+
+        let visible_start = drawn_start.max(start);
+        let visible_end = (drawn_start + drawn).min(start + available);
+
+        (
+            visible_start,
+            (visible_start - drawn_start) / scale,
+            (visible_end - visible_start).max(0.),
+        )
+    }
+
+    /// Pixels decoded off the UI thread, or why decoding failed.
+    pub type Decoded = Result<ImgVec<RGBA8>, String>;
+
+    /// Decode a PNG or JPEG.
+    pub fn decode(bytes: &[u8]) -> Decoded {
+        let decoded = ::image::load_from_memory(bytes)
+            .map_err(|err| err.to_string())?
+            .to_rgba8();
+        let (width, height) = (decoded.width() as usize, decoded.height() as usize);
+
+        let pixels = decoded
+            .into_raw()
+            .chunks_exact(4)
+            .map(|it| RGBA8::new(it[0], it[1], it[2], it[3]))
+            .collect();
+
+        Ok(ImgVec::new(pixels, width, height))
+    }
+
+    /// Read and [decode] the image file at `path`.
+    pub fn decode_file(path: &Path) -> Decoded {
+        let bytes = std::fs::read(path).map_err(|err| format!("{}: {err}", path.display()))?;
+
+        decode(&bytes)
+    }
+
+    thread_local! {
+        /// The load of every image file shown so far, by path, see [image_file].
+        static FILES: RefCell<HashMap<PathBuf, ImageLoad>> = Default::default();
+    }
+
+    /// Where an image that loads in the background is at.
+    #[derive(Debug)]
+    pub enum LoadState {
+        Pending,
+        /// Decoded, but not uploaded to the GPU until the next paint.
+        Decoded(ImgVec<RGBA8>),
+        Loaded {
+            image: ImageId,
+            width: u32,
+            height: u32,
+        },
+        Failed(String),
+    }
+
+    /// An image loading in the background, shown by [image_async].
+    /// Keep it on the view and pass a clone on every build, clones share the loaded image.
+    #[derive(Debug, Clone)]
+    pub struct ImageLoad {
+        rx: Receiver<Decoded>,
+        state: Rc<RefCell<LoadState>>,
+    }
+
+    /// Finishes an [ImageLoad], from any thread.
+    #[derive(Debug)]
+    pub struct ImageSender(Sender<Decoded>);
+
+    impl ImageSender {
+        /// Hand over the decoded image and redraw so it gets shown.
+        pub fn send(self, decoded: Decoded) {
+            if self.0.send(decoded).is_ok() {
+                crate::request_redraw();
+            }
+        }
+    }
+
+    impl ImageLoad {
+        /// Run `decode` on a new thread.
+        pub fn spawn(decode: impl FnOnce() -> Decoded + Send + 'static) -> Self {
+            let (sender, load) = Self::channel();
+
+            std::thread::spawn(move || sender.send(decode()));
+
+            load
+        }
+
+        /// Load the image file at `path`, or share the load of it started before.
+        pub fn file(path: impl Into<PathBuf>) -> Self {
+            let path = path.into();
+
+            FILES.with_borrow_mut(|files| {
+                files
+                    .entry(path.clone())
+                    .or_insert_with(|| Self::spawn(move || decode_file(&path)))
+                    .clone()
+            })
+        }
+
+        /// A load that finishes when the [ImageSender] is sent to.
+        pub fn channel() -> (ImageSender, Self) {
+            let (tx, rx) = crossbeam::channel::bounded(1);
+
+            let load = Self {
+                rx,
+                state: Rc::new(RefCell::new(LoadState::Pending)),
+            };
+
+            (ImageSender(tx), load)
+        }
+
+        pub fn state(&self) -> Ref<'_, LoadState> {
+            self.state.borrow()
+        }
+
+        /// Take the result if it has arrived. Returns whether the state changed.
+        pub fn poll(&self) -> bool {
+            let mut state = self.state.borrow_mut();
+
+            if !matches!(*state, LoadState::Pending) {
+                return false;
+            }
+
+            *state = match self.rx.try_recv() {
+                Ok(Ok(pixels)) => LoadState::Decoded(pixels),
+                Ok(Err(err)) => LoadState::Failed(err),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    LoadState::Failed("Image loader stopped without sending".into())
+                }
+            };
+
+            true
+        }
+
+        fn upload<R: Renderer>(&self, canvas: &mut Canvas<R>) {
+            let mut state = self.state.borrow_mut();
+
+            let LoadState::Decoded(pixels) = &*state else {
+                return;
+            };
+
+            *state = match canvas
+                .draw()
+                .create_image(pixels.as_ref(), ImageFlags::empty())
+            {
+                Ok(image) => LoadState::Loaded {
+                    image,
+                    width: pixels.width() as u32,
+                    height: pixels.height() as u32,
+                },
+                Err(err) => LoadState::Failed(format!("{err:?}")),
+            };
+        }
+    }
+
+    #[derive(Debug)]
+    enum Source {
+        Ready {
+            image: ImageId,
+            width: u32,
+            height: u32,
+        },
+        Loading(ImageLoad),
+    }
+
+    /// An image drawn into its node, [Fit::Contain] and centered by default.
+    /// Takes the size of the image unless styled otherwise.
+    ///
+    /// ```no_run
+    /// # use paladin_view::prelude::*;
+    /// # let id: paladin_view::ImageId = todo!();
+    ///
+    /// image(id, 1920, 1080).fit(Fit::Cover).align(Align::Top);
+    ///
+    /// ```
+    #[derive(Debug)]
+    pub struct Image {
+        source: Source,
+        fit: Fit,
+        align: Align,
+        placeholder: Color,
+        error: Color,
+        style: Style,
+    }
+
+    /// Draw `image`, which is `width` by `height` pixels.
+    pub fn image(image: ImageId, width: u32, height: u32) -> Image {
+        let mut image = Image::new(Source::Ready {
+            image,
+            width,
+            height,
+        });
+        image.style.0.size.width = length(width as f32);
+        image.style.0.size.height = length(height as f32);
+
+        image
+    }
+
+    /// Draw the image of `load` once it has loaded, and a placeholder until then.
+    /// The size isn't known up front, so it fills its parent unless styled otherwise.
+    ///
+    /// ```no_run
+    /// # use paladin_view::prelude::*;
+    /// # fn decode(path: &str) -> Decoded { todo!() }
+    ///
+    /// let load = ImageLoad::spawn(|| decode("logo.png"));
+    ///
+    /// image_async(load.clone()).placeholder(Color::rgb(40, 40, 40));
+    ///
+    /// ```
+    pub fn image_async(load: ImageLoad) -> Image {
+        let mut image = Image::new(Source::Loading(load));
+        image.style.0.size.width = percent(1_f32);
+        image.style.0.size.height = percent(1_f32);
+
+        image
+    }
+
+    /// Draw the image file at `path`, decoded in the background the first time it is shown.
+    /// Every image of the same path shares it, rebuilding doesn't decode or upload it again.
+    ///
+    /// ```no_run
+    /// # use paladin_view::prelude::*;
+    ///
+    /// image_file("assets/error.png").fit(Fit::Contain);
+    ///
+    /// ```
+    pub fn image_file(path: impl Into<PathBuf>) -> Image {
+        image_async(ImageLoad::file(path))
+    }
+
+    impl Image {
+        fn new(source: Source) -> Self {
+            Self {
+                source,
+                fit: Fit::default(),
+                align: Align::default(),
+                placeholder: Color::rgba(255, 255, 255, 20),
+                error: Color::rgba(200, 60, 60, 120),
+                style: Style::default(),
+            }
+        }
+
+        pub fn fit(mut self, fit: Fit) -> Self {
+            self.fit = fit;
+            self
+        }
+
+        pub fn align(mut self, align: Align) -> Self {
+            self.align = align;
+            self
+        }
+
+        /// Filled in while the image is loading.
+        pub fn placeholder(mut self, color: Color) -> Self {
+            self.placeholder = color;
+            self
+        }
+
+        /// Filled in when the image failed to load.
+        pub fn error(mut self, color: Color) -> Self {
+            self.error = color;
+            self
+        }
+
+        /// Where the image is drawn, or `None` while it isn't loaded.
+        pub fn placement(&self, layout: Layout) -> Option<Placement> {
+            let (_, width, height) = self.loaded()?;
+
+            let bounds = ImageRect {
+                x: layout.location.x as f32,
+                y: layout.location.y as f32,
+                width: layout.size.width as f32,
+                height: layout.size.height as f32,
+            };
+
+            Some(Placement::new(
+                (width as f32, height as f32),
+                bounds,
+                self.fit,
+                self.align,
+            ))
+        }
+
+        fn loaded(&self) -> Option<(ImageId, u32, u32)> {
+            match &self.source {
+                Source::Ready {
+                    image,
+                    width,
+                    height,
+                } => Some((*image, *width, *height)),
+                Source::Loading(load) => match *load.state() {
+                    LoadState::Loaded {
+                        image,
+                        width,
+                        height,
+                    } => Some((image, width, height)),
+                    _ => None,
+                },
+            }
+        }
+
+        /// Take in a finished load and upload it, before rendering.
+        pub(crate) fn prepare<R: Renderer>(&self, canvas: &mut Canvas<R>) {
+            if let Source::Loading(load) = &self.source {
+                load.poll();
+                load.upload(canvas);
+            }
+        }
+    }
+
+    impl Element for Image {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Image(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Image"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Image(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Image {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            let Some((image, width, height)) = self.loaded() else {
+                let color = match &self.source {
+                    Source::Loading(load) if matches!(*load.state(), LoadState::Failed(_)) => {
+                        self.error
+                    }
+                    _ => self.placeholder,
+                };
+
+                list.fill_rect(
+                    layout.location.x as f32,
+                    layout.location.y as f32,
+                    layout.size.width as f32,
+                    layout.size.height as f32,
+                    color,
+                );
+
+                return;
+            };
+
+            if width == 0 || height == 0 {
+                return;
+            }
+
+            let Some(placement) = self.placement(layout) else {
+                return;
+            };
+
+            let ImageRect {
+                x,
+                y,
+                width: dest_width,
+                height: dest_height,
+            } = placement.dest;
+
+            if dest_width <= 0. || dest_height <= 0. {
+                return;
+            }
+
+            let drawn = placement.drawn((width as f32, height as f32));
+
+            // Cover and None can draw past the node.
+            list.push_clip(x, y, dest_width, dest_height);
+            list.image(image, drawn.x, drawn.y, drawn.width, drawn.height);
+            list.pop_clip();
+        }
+    }
+
+    impl Styleable for Image {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+}
+
+mod memoized {
+    use std::any::Any;
+
+    use bevy_reflect::TypeRegistry;
+
+    use crate::{
+        BuildResult, Element, InsertChildren, KeepChildren, MountedWidget, OneOf, RebuildChildren,
+        SingleChild,
+    };
+
+    use super::Widget;
+
+    /// Only builds its child when its props change.
+    /// When an ancestor rebuilds with props equal to the previous ones, the mounted child is left as is.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// let lines = 200;
+    ///
+    /// memoized(lines, |lines| Text::builder().text(format!("{lines} lines")).build());
+    ///
+    /// ```
+    pub struct Memoized<P, F> {
+        props: P,
+        build: F,
+    }
+
+    pub fn memoized<P, E, F>(props: P, build: F) -> Memoized<P, F>
+    where
+        P: PartialEq + 'static,
+        E: Element,
+        F: FnOnce(&P) -> E + 'static,
+    {
+        Memoized { props, build }
+    }
+
+    /// The props the mounted child was built from.
+    pub struct MemoizedWidget(Box<dyn Any>);
+
+    impl<P, E, F> Element for Memoized<P, F>
+    where
+        P: PartialEq + 'static,
+        E: Element,
+        F: FnOnce(&P) -> E + 'static,
+    {
+        fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
+            let child = (self.build)(&self.props);
+
+            BuildResult {
+                widget: MountedWidget::Memoized(MemoizedWidget(Box::new(self.props))),
+                children: Some(SingleChild(child)),
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Memoized"
+        }
+
+        fn compare_rebuild(
+            self,
+            old: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> BuildResult<impl RebuildChildren> {
+            match old {
+                MountedWidget::Memoized(old) if old.0.downcast_ref::<P>() == Some(&self.props) => {
+                    return BuildResult {
+                        widget: MountedWidget::Memoized(old),
+                        children: Some(OneOf::A(KeepChildren)),
+                    };
+                }
+                _ => {}
+            }
+
+            let child = (self.build)(&self.props);
+
+            BuildResult {
+                widget: MountedWidget::Memoized(MemoizedWidget(Box::new(self.props))),
+                children: Some(OneOf::B(SingleChild(child))),
+            }
+        }
+    }
+
+    impl Widget for MemoizedWidget {}
+
+    impl std::fmt::Debug for MemoizedWidget {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("MemoizedWidget").finish_non_exhaustive()
+        }
+    }
+}
+
+mod progress {
+    use std::time::{Duration, Instant};
+
+    use bevy_reflect::TypeRegistry;
+    use taffy::prelude::{length, percent};
+
+    use crate::{Color, DisplayList, Element, Layout, LeafNode};
+
+    use super::{MountedWidget, Style, Styleable, Widget};
+
+    const SEGMENTS: usize = 8;
+    const STEP: Duration = Duration::from_millis(100);
+
+    /// A busy indicator, a ring of segments with a bright one going round.
+    /// Only redraws while it is active, once per step of the bright segment.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    /// # let indexing = true;
+    ///
+    /// spinner().active(indexing);
+    ///
+    /// ```
+    #[derive(Debug)]
+    pub struct Spinner {
+        active: bool,
+        started: Option<Instant>,
+        color: Color,
+        style: Style,
+    }
+
+    pub fn spinner() -> Spinner {
+        let mut style = Style::default();
+        style.0.size.width = length(16_f32);
+        style.0.size.height = length(16_f32);
+
+        Spinner {
+            active: true,
+            started: None,
+            color: Color::rgb(200, 200, 200),
+            style,
+        }
+    }
+
+    impl Spinner {
+        /// Draw nothing and stop redrawing when `active` is false.
+        pub fn active(mut self, active: bool) -> Self {
+            self.active = active;
+            self
+        }
+
+        pub fn color(mut self, color: Color) -> Self {
+            self.color = color;
+            self
+        }
+
+        /// When the animation started. Defaults to when the spinner was first built.
+        pub fn started_at(mut self, at: Instant) -> Self {
+            self.started = Some(at);
+            self
+        }
+
+        /// The brightest segment at `now`, moving one segment per tick.
+        pub fn phase_at(&self, now: Instant) -> usize {
+            self.ticks_at(now) as usize % SEGMENTS
+        }
+
+        /// When the next tick after `now` moves the bright segment.
+        pub fn next_step_at(&self, now: Instant) -> Instant {
+            let started = self.started.unwrap_or(now);
+
+            started + STEP * (self.ticks_at(now) + 1)
+        }
+
+        fn ticks_at(&self, now: Instant) -> u32 {
+            let started = self.started.unwrap_or(now);
+
+            (now.saturating_duration_since(started).as_millis() / STEP.as_millis()) as u32
+        }
+    }
+
+    impl Element for Spinner {
+        #[allow(refining_impl_trait)]
+        fn create(mut self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            self.started.get_or_insert_with(Instant::now);
+
+            crate::BuildResult {
+                widget: MountedWidget::Spinner(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Spinner"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            mut self,
+            old: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            // Keep spinning from where it was, instead of jumping back on every rebuild.
+            self.started = match old {
+                MountedWidget::Spinner(old) => self.started.or(old.started),
+                _ => self.started.or(Some(Instant::now())),
+            };
+
+            crate::BuildResult {
+                widget: MountedWidget::Spinner(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Spinner {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            if !self.active {
+                return;
+            }
+
+            let radius = layout.size.width.min(layout.size.height) as f32 / 2.;
+            let center_x = layout.location.x as f32 + layout.size.width as f32 / 2.;
+            let center_y = layout.location.y as f32 + layout.size.height as f32 / 2.;
+            let now = Instant::now();
+            let phase = self.phase_at(now);
+
+            for segment in 0..SEGMENTS {
+                let angle = segment as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+                let (sin, cos) = angle.sin_cos();
+
+                // Segments fade out behind the bright one.
+                let behind = (phase + SEGMENTS - segment) % SEGMENTS;
+                let alpha = 1. - behind as f32 / SEGMENTS as f32;
+
+                let mut color = self.color;
+                color.0.a *= alpha;
+
+                list.line(
+                    (center_x + cos * radius * 0.5, center_y + sin * radius * 0.5),
+                    (center_x + cos * radius, center_y + sin * radius),
+                    (radius / 4.).max(1.),
+                    color,
+                );
+            }
+
+            crate::request_redraw_at(self.next_step_at(now));
+        }
+    }
+
+    impl Styleable for Spinner {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+
+    /// A bar filled up to how far along something is.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// progress_bar(0.25);
+    ///
+    /// ```
+    #[derive(Debug)]
+    pub struct ProgressBar {
+        value: f32,
+        track: Color,
+        fill: Color,
+        style: Style,
+    }
+
+    /// A bar that is `value` full, from 0 to 1.
+    pub fn progress_bar(value: f32) -> ProgressBar {
+        let mut style = Style::default();
+        style.0.size.width = percent(1_f32);
+        style.0.size.height = length(4_f32);
+
+        ProgressBar {
+            value: value.clamp(0., 1.),
+            track: Color::rgba(255, 255, 255, 30),
+            fill: Color::rgb(80, 140, 230),
+            style,
+        }
+    }
+
+    impl ProgressBar {
+        pub fn value(&self) -> f32 {
+            self.value
+        }
+
+        pub fn track(mut self, color: Color) -> Self {
+            self.track = color;
+            self
+        }
+
+        pub fn fill(mut self, color: Color) -> Self {
+            self.fill = color;
+            self
+        }
+    }
+
+    impl Element for ProgressBar {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::ProgressBar(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "ProgressBar"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::ProgressBar(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for ProgressBar {
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+            let (width, height) = (layout.size.width as f32, layout.size.height as f32);
+
+            list.fill_rect(x, y, width, height, self.track);
+
+            if self.value > 0. {
+                list.fill_rect(x, y, width * self.value, height, self.fill);
+            }
+        }
+    }
+
+    impl Styleable for ProgressBar {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+}
+
+mod scrollbar {
+    use std::time::{Duration, Instant};
+
+    use bevy_reflect::TypeRegistry;
+    use taffy::{prelude::length, Display, Position};
+
+    use crate::{Color, DisplayList, Element, Layout, LeafNode};
+
+    use super::{MountedWidget, Style, Styleable, Widget};
+
+    const WIDTH: f32 = 10.;
+    const FADE_OUT: Duration = Duration::from_millis(200);
+
+    /// A vertical scrollbar for content taller than its viewport.
+    /// Hidden, taking no space and receiving no clicks, while the content fits.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// scrollbar(2400., 600., 300.).overlay();
+    ///
+    /// ```
+    #[derive(Debug)]
+    pub struct Scrollbar {
+        content: f32,
+        viewport: f32,
+        offset: f32,
+        overlay: bool,
+        fade_after: Option<Duration>,
+        scrolled_at: Option<Instant>,
+        style: Style,
+    }
+
+    /// A scrollbar for `content` pixels of which `viewport` are shown, scrolled down by `offset`.
+    pub fn scrollbar(content: f32, viewport: f32, offset: f32) -> Scrollbar {
+        Scrollbar {
+            content,
+            viewport,
+            offset,
+            overlay: false,
+            fade_after: None,
+            scrolled_at: None,
+            style: Style::default(),
+        }
+    }
+
+    impl Scrollbar {
+        /// Draw over the right edge of the parent instead of taking layout space.
+        pub fn overlay(mut self) -> Self {
+            self.overlay = true;
+            self
+        }
+
+        /// Only show the scrollbar while scrolling, fading out once `after` has passed since the last scroll.
+        pub fn fade_after(mut self, after: Duration) -> Self {
+            self.fade_after = Some(after);
+            self
+        }
+
+        /// When the content was last scrolled, for [Scrollbar::fade_after].
+        pub fn scrolled_at(mut self, at: Instant) -> Self {
+            self.scrolled_at = Some(at);
+            self
+        }
+
+        pub fn is_hidden(&self) -> bool {
+            self.content <= self.viewport
+        }
+
+        /// How opaque the scrollbar is at `now`, from 0 (invisible) to 1.
+        pub fn opacity_at(&self, now: Instant) -> f32 {
+            if self.is_hidden() {
+                return 0.;
+            }
+
+            let Some(fade_after) = self.fade_after else {
+                return 1.;
+            };
+
+            let Some(scrolled_at) = self.scrolled_at else {
+                return 0.;
+            };
+
+            let fading = now
+                .saturating_duration_since(scrolled_at)
+                .saturating_sub(fade_after);
+
+            1. - (fading.as_secs_f32() / FADE_OUT.as_secs_f32()).min(1.)
+        }
+
+        /// The thumb's offset from the top of the track, and its length.
+        fn thumb(&self, track: f32) -> (f32, f32) {
+            let length = (track * self.viewport / self.content).max(WIDTH).min(track);
+            let scrollable = (self.content - self.viewport).max(1.);
+            let offset = (self.offset / scrollable).clamp(0., 1.) * (track - length);
+
+            (offset, length)
+        }
+    }
+
+    impl Element for Scrollbar {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Scrollbar(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "Scrollbar"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            self,
+            _: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::Scrollbar(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for Scrollbar {
+        fn style(&self) -> Style {
+            let mut style = self.style.clone();
+
+            if self.is_hidden() {
+                style.0.display = Display::None;
+
+                return style;
+            }
+
+            style.0.size.width = length(WIDTH);
+
+            if self.overlay {
+                style.0.position = Position::Absolute;
+                style.0.inset.top = length(0_f32);
+                style.0.inset.right = length(0_f32);
+                style.0.size.height = taffy::Dimension::Percent(1.);
+            }
+
+            style
+        }
+
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            let opacity = self.opacity_at(Instant::now());
+
+            if opacity <= 0. {
+                return;
+            }
+
+            let (offset, length) = self.thumb(layout.size.height as f32);
+
+            list.fill_rect(
+                layout.location.x as f32,
+                layout.location.y as f32 + offset,
+                layout.size.width as f32,
+                length,
+                Color::rgba(200, 200, 200, (opacity * 120.) as u8),
+            );
+        }
+    }
+
+    impl Styleable for Scrollbar {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+}
+
+mod list {
+    use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+    use bevy_reflect::TypeRegistry;
+    use cosmic_text::{Attrs, FontSystem, Metrics, Shaping};
+
+    use crate::{fuzzy_score, Color, DisplayList, Element, Layout, LeafNode, Scroll};
+
+    use super::{EventContext, MountedWidget, Style, Styleable, Widget, WidgetEvent};
+
+    const FONT_SIZE: f32 = 18.;
+    const LINE_HEIGHT: f32 = 24.;
+
+    /// A line of a [List], with a dot in front of it to tell kinds of items apart.
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct ListItem {
+        text: String,
+        marker: Option<Color>,
+        header: bool,
+        indent: usize,
+    }
+
+    impl ListItem {
+        pub fn new(text: impl Into<String>) -> Self {
+            Self {
+                text: text.into(),
+                marker: None,
+                header: false,
+                indent: 0,
+            }
+        }
+
+        /// Titles the items after it, up to the next header.
+        /// Filtering keeps it as long as any of them is kept, it doesn't match by itself.
+        pub fn header(mut self) -> Self {
+            self.header = true;
+            self
+        }
+
+        pub fn marker(mut self, color: Color) -> Self {
+            self.marker = Some(color);
+            self
+        }
+
+        /// Indent the text by `indent` characters.
+        pub fn indent(mut self, indent: usize) -> Self {
+            self.indent = indent;
+            self
+        }
+
+        pub fn text(&self) -> &str {
+            &self.text
+        }
+    }
+
+    impl<T: Into<String>> From<T> for ListItem {
+        fn from(text: T) -> Self {
+            Self::new(text)
+        }
+    }
+
+    type OnSelect = Rc<dyn Fn(usize)>;
+
+    /// A scrolling list of single lines, narrowed down with [List::filter].
+    /// Rebuilding it with other items keeps it scrolled where it was.
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// list(["main.rs", "lib.rs", "app.rs"])
+    ///     .filter("ap")
+    ///     .on_select(|index| println!("Open {index}"));
+    ///
+    /// ```
+    pub struct List {
+        items: Vec<ListItem>,
+        filter: String,
+        /// Indices of the items matching the filter, in order.
+        shown: Vec<usize>,
+        on_select: Option<OnSelect>,
+        color: Color,
+        scroll: Scroll,
+        /// Shaped again once the shown items change.
+        buffer: Option<Rc<RefCell<cosmic_text::Buffer>>>,
+        /// Scrolled since the last paint.
+        dirty: bool,
+        style: Style,
+    }
+
+    pub fn list<T: Into<ListItem>>(items: impl IntoIterator<Item = T>) -> List {
+        let items: Vec<_> = items.into_iter().map(Into::into).collect();
+
+        List {
+            shown: (0..items.len()).collect(),
+            items,
+            filter: String::new(),
+            on_select: None,
+            color: Color::rgb(220, 220, 220),
+            scroll: Scroll::new(0., 0.),
+            buffer: None,
+            dirty: false,
+            style: Style::default(),
+        }
+    }
+
+    impl List {
+        /// Only show the items that [fuzzy match](fuzzy_score) `filter`, and the headers above them.
+        pub fn filter(mut self, filter: impl Into<String>) -> Self {
+            self.filter = filter.into();
+            self.shown = shown(&self.items, &self.filter);
+            self
+        }
+
+        /// Called with the index into the items of an item when it is clicked, headers included.
+        pub fn on_select(mut self, on_select: impl Fn(usize) + 'static) -> Self {
+            self.on_select = Some(Rc::new(on_select));
+            self
+        }
+
+        pub fn color(mut self, color: Color) -> Self {
+            self.color = color;
+            self
+        }
+
+        /// The items matching the filter, in order.
+        pub fn shown(&self) -> impl Iterator<Item = &ListItem> {
+            self.shown.iter().map(|index| &self.items[*index])
+        }
+
+        /// The index into the items of the one `y` pixels below the top of the list, scrolled.
+        fn item_at(&self, y: f32) -> Option<usize> {
+            let row = (y + self.scroll.offset()) / LINE_HEIGHT;
+
+            self.shown.get(row as usize).copied()
+        }
+
+        fn shape(&self, font_system: &mut FontSystem) -> cosmic_text::Buffer {
+            let text = self
+                .shown()
+                .map(|item| format!("{}{}", " ".repeat(item.indent), item.text))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let mut buffer =
+                cosmic_text::Buffer::new(font_system, Metrics::new(FONT_SIZE, LINE_HEIGHT));
+            let mut borrowed = buffer.borrow_with(font_system);
+            // A row per item, long ones are cut off rather than wrapped.
+            borrowed.set_wrap(cosmic_text::Wrap::None);
+            borrowed.set_text(
+                &text,
+                Attrs::new()
+                    .family(cosmic_text::Family::Name("JetBrains Mono"))
+                    .color(self.color.into()),
+                Shaping::Advanced,
+            );
+            borrowed.shape_until_scroll(true);
+
+            buffer
+        }
+    }
+
+    /// Indices of the items matching `filter`, with the header of each group that has any.
+    fn shown(items: &[ListItem], filter: &str) -> Vec<usize> {
+        let mut shown = vec![];
+        let mut header = None;
+
+        for (index, item) in items.iter().enumerate() {
+            if item.header {
+                header = Some(index);
+            } else if fuzzy_score(filter, &item.text).is_some() {
+                // Once per group, before its first item.
+                shown.extend(header.take());
+                shown.push(index);
+            }
+        }
+
+        shown
+    }
+
+    impl Element for List {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::List(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "List"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            mut self,
+            old: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            if let MountedWidget::List(old) = old {
+                // Stays scrolled where it was
+                self.scroll = old.scroll;
+
+                if self.shown().eq(old.shown()) && self.color == old.color {
+                    self.buffer = old.buffer;
+                }
+            }
+
+            crate::BuildResult {
+                widget: MountedWidget::List(self),
+                children: None,
+            }
+        }
+    }
+
+    impl Widget for List {
+        fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+            match event {
+                WidgetEvent::Scroll { delta_y, .. } => self.dirty |= self.scroll.scroll_by(delta_y),
+                WidgetEvent::Click(_, y) => {
+                    let y = y as f32 - context.layout.location.y as f32;
+
+                    if let (Some(index), Some(on_select)) = (self.item_at(y), &self.on_select) {
+                        on_select(index)
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn layout(&mut self, layout: Layout, font_system: &mut FontSystem) {
+            if self.buffer.is_none() {
+                self.buffer = Some(Rc::new(RefCell::new(self.shape(font_system))));
+            }
+
+            self.scroll.resize(
+                self.shown.len() as f32 * LINE_HEIGHT,
+                layout.size.height as f32,
+            );
+        }
+
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            let Some(buffer) = &self.buffer else {
+                return;
+            };
+
+            let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+            let top = y - self.scroll.offset();
+            let dot = LINE_HEIGHT / 3.;
+            let indent = dot * 2.;
+
+            list.push_clip(x, y, layout.size.width as f32, layout.size.height as f32);
+
+            for (row, item) in self.shown().enumerate() {
+                if let Some(color) = item.marker {
+                    let row_top = top + row as f32 * LINE_HEIGHT + dot;
+
+                    list.rounded_rect(x, row_top, dot, dot, dot / 2., color);
+                }
+            }
+
+            list.text(buffer.clone(), (x + indent, top));
+            list.pop_clip();
+        }
+
+        fn take_dirty(&mut self) -> bool {
+            std::mem::take(&mut self.dirty)
+        }
+
+        fn style(&self) -> Style {
+            self.style.clone()
+        }
+    }
+
+    impl Styleable for List {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
+        }
+    }
+
+    impl Debug for List {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("List")
+                .field("items", &self.items)
+                .field("filter", &self.filter)
+                .field("scroll", &self.scroll)
+                .finish()
+        }
+    }
+}
+
+mod text_input {
+    use std::{
+        cell::{Cell, RefCell},
+        fmt::Debug,
+        rc::Rc,
+        time::{Duration, Instant},
+    };
+
+    use bevy_reflect::TypeRegistry;
+    use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, Shaping};
+    use taffy::prelude::{auto, length};
+    use winit::{
+        event::ElementState,
+        keyboard::{Key, NamedKey},
+    };
+
+    use crate::{
+        state::{Reducer, State},
+        text::{with_fallback_fonts, Fallbacks},
+        Color, DisplayList, Element, Layout, LeafNode,
+    };
+
+    use super::{EventContext, MountedWidget, Style, Styleable, Widget, WidgetEvent};
+
+    /// How long the caret stays on, and then off.
+    const BLINK: Duration = Duration::from_millis(500);
+    const CARET_WIDTH: f32 = 2.;
+
+    type OnChange = Box<dyn Fn(&str)>;
+
+    /// A single line of editable text. Focused by clicking it, with Tab, or with [TextInput::focused].
+    ///
+    /// ```
+    /// # use paladin_view::prelude::*;
+    ///
+    /// text_input("").on_change(|text| println!("{text}"));
+    ///
+    /// ```
+    pub struct TextInput {
+        text: String,
+        /// What the element was built with, to tell the user's edits from the text being replaced.
+        initial: String,
+        /// A byte index into the text, always on a char boundary.
+        caret: usize,
+        focused: bool,
+        /// The caret stays on while typing, and blinks from when it last moved.
+        moved_at: Instant,
+        /// Whether the caret was drawn by the last paint, it is painted again once that changes.
+        caret_shown: Cell<bool>,
+        on_change: Option<OnChange>,
+        color: Color,
+        size: f32,
+        /// The text changed since it was last shaped.
+        reshape: bool,
+        /// Shared with the [DisplayList] while drawing.
+        buffer: Rc<RefCell<Buffer>>,
+        style: Style,
+    }
+
+    /// An input starting out with `text`. Edits are kept across rebuilds until the text passed in changes.
+    pub fn text_input(text: impl Into<String>) -> TextInput {
+        let text = text.into();
+        let size = 25.;
+
+        TextInput {
+            caret: text.len(),
+            initial: text.clone(),
+            text,
+            focused: false,
+            moved_at: Instant::now(),
+            caret_shown: Cell::new(false),
+            on_change: None,
+            color: Color::default(),
+            size,
+            reshape: true,
+            buffer: Rc::new(RefCell::new(Buffer::new_empty(Metrics::new(size, size)))),
+            style: Style::default(),
+        }
+    }
+
+    impl TextInput {
+        /// Called with the whole text after every edit.
+        pub fn on_change(mut self, on_change: impl Fn(&str) + 'static) -> Self {
+            self.on_change = Some(Box::new(on_change));
+            self
+        }
+
+        /// Send the message made from the text to some [State] after every edit.
+        pub fn sends<M: Clone + 'static, S: Reducer<M>>(
+            self,
+            state: &State<M, S>,
+            message: impl Fn(&str) -> M + 'static,
+        ) -> Self {
+            let send = state.sender();
+
+            self.on_change(move |text| send(message(text)))
+        }
+
+        /// Start out focused, taking keys without being clicked first.
+        pub fn focused(mut self) -> Self {
+            self.focused = true;
+            self
+        }
+
+        pub fn color(mut self, color: Color) -> Self {
+            self.color = color;
+            self
+        }
+
+        pub fn size(mut self, size: f32) -> Self {
+            self.size = size;
+            self.buffer = Rc::new(RefCell::new(Buffer::new_empty(Metrics::new(size, size))));
+            self
+        }
+
+        pub fn text(&self) -> &str {
+            &self.text
+        }
+
+        /// The caret's byte index into [TextInput::text].
+        pub fn caret(&self) -> usize {
+            self.caret
+        }
+
+        pub fn is_focused(&self) -> bool {
+            self.focused
+        }
+
+        /// Stop taking keys, like when something else was clicked.
+        pub fn blur(&mut self) {
+            self.focused = false;
+        }
+
+        /// Edit for a pressed `key`, where `text` is what it types, if anything.
+        /// Returns whether the key did anything. Keys are ignored while unfocused,
+        /// as are modifiers pressed on their own and control characters.
+        pub fn key(&mut self, key: &Key, text: Option<&str>) -> bool {
+            if !self.focused {
+                return false;
+            }
+
+            let before = self.text[..self.caret].chars().next_back();
+            let after = self.text[self.caret..].chars().next();
+
+            match key {
+                Key::Named(NamedKey::Backspace) => {
+                    let Some(before) = before else {
+                        return false;
+                    };
+
+                    self.caret -= before.len_utf8();
+                    self.text.remove(self.caret);
+                    self.changed();
+                }
+                Key::Named(NamedKey::Delete) => {
+                    if after.is_none() {
+                        return false;
+                    }
+
+                    self.text.remove(self.caret);
+                    self.changed();
+                }
+                Key::Named(NamedKey::ArrowLeft) => {
+                    let Some(before) = before else {
+                        return false;
+                    };
+
+                    self.move_to(self.caret - before.len_utf8());
+                }
+                Key::Named(NamedKey::ArrowRight) => {
+                    let Some(after) = after else {
+                        return false;
+                    };
+
+                    self.move_to(self.caret + after.len_utf8());
+                }
+                Key::Named(NamedKey::Home) => self.move_to(0),
+                Key::Named(NamedKey::End) => self.move_to(self.text.len()),
+                _ => {
+                    let Some(text) =
+                        text.filter(|text| !text.is_empty() && !text.chars().any(char::is_control))
+                    else {
+                        return false;
+                    };
+
+                    self.text.insert_str(self.caret, text);
+                    self.caret += text.len();
+                    self.changed();
+                }
+            }
+
+            true
+        }
+
+        fn move_to(&mut self, caret: usize) {
+            self.caret = caret;
+            self.moved_at = Instant::now();
+        }
+
+        fn changed(&mut self) {
+            self.reshape = true;
+            self.moved_at = Instant::now();
+
+            if let Some(on_change) = &self.on_change {
+                on_change(&self.text);
+            }
+        }
+
+        /// Whether the caret is drawn at `now`.
+        pub fn caret_visible_at(&self, now: Instant) -> bool {
+            let elapsed = now.saturating_duration_since(self.moved_at);
+
+            self.focused && elapsed.as_millis() % (2 * BLINK.as_millis()) < BLINK.as_millis()
+        }
+
+        /// When the caret next turns on or off after `now`, `None` while unfocused.
+        pub fn next_blink_at(&self, now: Instant) -> Option<Instant> {
+            if !self.focused {
+                return None;
+            }
+
+            let blinks =
+                now.saturating_duration_since(self.moved_at).as_millis() / BLINK.as_millis();
+
+            Some(self.moved_at + BLINK * (blinks as u32 + 1))
+        }
+
+        /// How far into the shaped text the caret is.
+        fn caret_x(&self) -> f32 {
+            let buffer = self.buffer.borrow();
+            let Some(run) = buffer.layout_runs().next() else {
+                return 0.;
+            };
+
+            run.glyphs
+                .iter()
+                .find(|glyph| glyph.start >= self.caret)
+                .map_or(run.line_w, |glyph| glyph.x)
+        }
+
+        /// Like [Widget::layout], with the fallback fonts from `fallbacks`.
+        pub(crate) fn layout_in(
+            &mut self,
+            layout: Layout,
+            font_system: &mut FontSystem,
+            fallbacks: &Fallbacks,
+        ) {
+            let mut buffer = self.buffer.borrow_mut();
+
+            if self.reshape {
+                let attrs = Attrs::new()
+                    .color(self.color.into())
+                    .family(cosmic_text::Family::Name("JetBrains Mono"));
+
+                buffer.set_text(font_system, &self.text, attrs, Shaping::Advanced);
+
+                for line in &mut buffer.lines {
+                    // Always a bundled family, so never missing
+                    let attrs = with_fallback_fonts(
+                        font_system,
+                        fallbacks,
+                        line.text(),
+                        line.attrs_list().clone(),
+                    );
+                    line.set_attrs_list(attrs);
+                }
+
+                self.reshape = false;
+            }
+
+            let mut buffer = buffer.borrow_with(font_system);
+
+            buffer.set_wrap(cosmic_text::Wrap::None);
+            buffer.set_size(
+                Some(layout.size.width as f32),
+                Some(layout.size.height as f32),
+            );
+            buffer.shape_until_scroll(true);
+        }
+    }
+
+    impl Element for TextInput {
+        #[allow(refining_impl_trait)]
+        fn create(self, _: &mut TypeRegistry) -> crate::BuildResult<LeafNode> {
+            crate::BuildResult {
+                widget: MountedWidget::TextInput(self),
+                children: None,
+            }
+        }
+
+        fn kind(&self) -> &'static str {
+            "TextInput"
+        }
+
+        #[allow(refining_impl_trait)]
+        fn compare_rebuild(
+            mut self,
+            old: MountedWidget,
+            _: &mut TypeRegistry,
+        ) -> crate::BuildResult<LeafNode> {
+            if let MountedWidget::TextInput(old) = old {
+                if old.initial == self.initial {
+                    self.text = old.text;
+                }
+
+                self.focused |= old.focused;
+                self.moved_at = old.moved_at;
+                self.caret = old.caret.min(self.text.len());
+
+                while !self.text.is_char_boundary(self.caret) {
+                    self.caret -= 1;
+                }
+            }
+
+            crate::BuildResult {
+                widget: MountedWidget::TextInput(self),
+                children: None,
+            }
+        }
+    }
 
-mod stack {
+    impl Widget for TextInput {
+        fn event(&mut self, event: WidgetEvent) {
+            match event {
+                WidgetEvent::Key(event) if event.state == ElementState::Pressed => {
+                    self.key(&event.logical_key, event.text.as_deref());
+                }
+                WidgetEvent::Focus => {
+                    self.focused = true;
+                    self.moved_at = Instant::now();
+                }
+                WidgetEvent::Blur => self.blur(),
+                _ => {}
+            }
+        }
 
-    use std::{fmt::Debug, marker::PhantomData};
+        fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+            let WidgetEvent::Click(x, y) = event else {
+                return self.event(event);
+            };
 
-    use bevy_reflect::TypeRegistry;
+            let layout = context.layout;
 
-    use crate::{BuildResult, Element, InsertChildren, RebuildChildren};
+            if self.reshape {
+                self.layout(layout, context.font_system());
+            }
 
-    use super::{ChildInsertBuilder, ChildRebuildBuilder, ChildView, Widget};
+            let hit = self.buffer.borrow().hit(
+                x as f32 - layout.location.x as f32,
+                y as f32 - layout.location.y as f32,
+            );
 
-    #[derive(Debug)]
-    pub struct HStack;
+            self.move_to(hit.map_or(self.text.len(), |cursor| cursor.index));
+        }
 
-    pub struct HStackElement<F, Children: ChildView<F>> {
-        children: Children,
-        phantom: PhantomData<F>,
-    }
+        fn layout(&mut self, layout: Layout, font_system: &mut FontSystem) {
+            self.layout_in(layout, font_system, &Fallbacks::default());
+        }
 
-    pub(crate) struct HStackChildren<F, Children: ChildView<F>> {
-        children: Children,
-        phantom: PhantomData<F>,
-    }
+        fn render(&self, layout: Layout, list: &mut DisplayList) {
+            let (x, y) = (layout.location.x as f32, layout.location.y as f32);
 
-    impl<F: 'static, C: ChildView<F> + 'static> RebuildChildren for HStackChildren<F, C> {
-        fn rebuild_children(self, builder: &mut impl crate::RebuildContext) {
-            self.children.call_each(ChildRebuildBuilder { pc: builder });
-        }
-    }
+            list.fill_rect(
+                x,
+                y,
+                layout.size.width as f32,
+                layout.size.height as f32,
+                Color::rgba(255, 255, 255, 15),
+            );
+            list.text(self.buffer.clone(), (x, y));
 
-    impl<F: 'static, C: ChildView<F> + 'static> InsertChildren for HStackChildren<F, C> {
-        fn insert_children(self, builder: &mut impl crate::InsertContext) {
-            self.children.call_each(ChildInsertBuilder { pc: builder });
-        }
-    }
+            let now = Instant::now();
+            let shown = self.caret_visible_at(now);
+            self.caret_shown.set(shown);
 
-    impl<F, Children: ChildView<F>> Element for HStackElement<F, Children>
-    where
-        F: 'static,
-        Children: 'static,
-    {
-        fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
-            crate::BuildResult {
-                widget: super::MountedWidget::HStack(HStack),
-                children: Some(HStackChildren {
-                    children: self.children,
-                    phantom: PhantomData,
-                }),
+            if shown {
+                list.fill_rect(x + self.caret_x(), y, CARET_WIDTH, self.size, self.color);
             }
-        }
 
-        fn compare_rebuild(self, _: super::MountedWidget) -> BuildResult<impl RebuildChildren> {
-            // if !matches!(old, MountedWidget::HStack(_)) {
-            //     return CompareResult::Replace { with: self };
-            // }
+            if let Some(blink) = self.next_blink_at(now) {
+                crate::request_redraw_at(blink);
+            }
+        }
 
-            // context.insert(super::MountedWidget::HStack(HStack));
+        fn style(&self) -> Style {
+            let mut style = self.style.clone();
 
-            // self.children.call_each(ChildRebuildBuilder { pc: context });
-            crate::BuildResult {
-                widget: super::MountedWidget::HStack(HStack),
-                children: Some(HStackChildren {
-                    children: self.children,
-                    phantom: PhantomData,
-                }),
+            if style.0.size.height == auto() {
+                style.0.size.height = length(self.size);
             }
 
-            // crate::CompareResult::<Self>::Success
+            style
+        }
+
+        /// Once the caret blinked since the last paint.
+        fn take_dirty(&mut self) -> bool {
+            self.caret_visible_at(Instant::now()) != self.caret_shown.get()
+        }
+
+        fn accepts_focus(&self) -> bool {
+            true
         }
     }
 
-    impl Widget for HStack {
-        fn style(&self) -> super::Style {
-            super::Style::default().with_direction(taffy::FlexDirection::Row)
+    impl Styleable for TextInput {
+        fn style_mut(&mut self) -> &mut Style {
+            &mut self.style
         }
     }
 
-    #[allow(private_bounds)]
-    /// A horizontal stack, also called a Row.
-    ///
-    /// ```
-    /// # use paladin_view::prelude::*;
-    ///
-    /// hstack(
-    ///     (
-    ///         "Hello",
-    ///         "World !"
-    ///     )
-    /// );
-    ///
-    /// ```
-    #[allow(private_interfaces)]
-    pub fn hstack<F: 'static, CV: ChildView<F> + 'static>(child: CV) -> HStackElement<F, CV> {
-        HStackElement {
-            children: child,
-            phantom: PhantomData,
+    impl Debug for TextInput {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("TextInput")
+                .field("text", &self.text)
+                .field("caret", &self.caret)
+                .field("focused", &self.focused)
+                .finish()
         }
     }
 }
 
 pub(crate) mod prelude {
+    pub use super::any_element::AnyElement;
     pub use super::button::Button;
+    pub use super::cached::{cached, Cached};
+    pub use super::center::{center, center_x, center_y, Center};
+    pub use super::custom;
+    pub use super::divider::{Divider, Orientation};
+    pub use super::effect::{on_mount, on_unmount, Effect};
+    pub use super::fragment::{children, fragment, Children, Fragment};
+    pub use super::image::{
+        decode, decode_file, image, image_async, image_file, Align, Decoded, Fit, Image, ImageLoad,
+    };
+    pub use super::list::{list, List, ListItem};
+    pub use super::memoized::{memoized, Memoized};
+    pub use super::progress::{progress_bar, spinner, ProgressBar, Spinner};
+    pub use super::scrollbar::{scrollbar, Scrollbar};
+    pub use super::spacer::{spacer, Spacer};
     pub use super::stack::{hstack, HStack};
     pub use super::text::Text;
+    pub use super::text_input::{text_input, TextInput};
     pub use super::OneOf;
     pub use super::OneOfSwizz;
     pub use super::Styleable;
@@ -670,17 +4070,28 @@ impl<A: Element, B: Element> Element for OneOf<A, B> {
         }
     }
 
-    fn compare_rebuild(self, old: MountedWidget) -> BuildResult<impl RebuildChildren> {
+    fn kind(&self) -> &'static str {
+        match self {
+            OneOf::A(a) => a.kind(),
+            OneOf::B(b) => b.kind(),
+        }
+    }
+
+    fn compare_rebuild(
+        self,
+        old: MountedWidget,
+        registry: &mut TypeRegistry,
+    ) -> BuildResult<impl RebuildChildren> {
         match self {
             OneOf::A(a) => {
-                let result = a.compare_rebuild(old);
+                let result = a.compare_rebuild(old, registry);
                 BuildResult {
                     widget: result.widget,
                     children: result.children.map(|children| OneOf::<_, _>::A(children)),
                 }
             }
             OneOf::B(b) => {
-                let result = b.compare_rebuild(old);
+                let result = b.compare_rebuild(old, registry);
 
                 BuildResult {
                     widget: result.widget,
@@ -689,6 +4100,20 @@ impl<A: Element, B: Element> Element for OneOf<A, B> {
             }
         }
     }
+
+    fn insert(self, context: &mut impl InsertContext) {
+        match self {
+            OneOf::A(a) => a.insert(context),
+            OneOf::B(b) => b.insert(context),
+        }
+    }
+
+    fn rebuild(self, context: &mut impl RebuildContext) {
+        match self {
+            OneOf::A(a) => a.rebuild(context),
+            OneOf::B(b) => b.rebuild(context),
+        }
+    }
 }
 
 impl<A: RebuildChildren, B: RebuildChildren> RebuildChildren for OneOf<A, B> {
@@ -725,10 +4150,19 @@ impl<El> OneOfSwizz<El> for El {
     }
 }
 
-pub(crate) trait ChildViewFnBuilder {
-    fn create_fn<E: Element>(&mut self) -> impl FnMut(E);
+mod sealed {
+    use super::Element;
+
+    /// Implemented only in this crate, so [ChildView](super::ChildView) is.
+    pub trait Sealed<F> {}
+
+    pub trait ChildViewFnBuilder {
+        fn create_fn<E: Element>(&mut self) -> impl FnMut(E);
+    }
 }
 
+use sealed::{ChildViewFnBuilder, Sealed};
+
 struct ChildInsertBuilder<'a, Pc: InsertContext> {
     pc: &'a mut Pc,
 }
@@ -739,44 +4173,65 @@ struct ChildRebuildBuilder<'a, Pc: RebuildContext> {
 
 impl<'a, Pc: InsertContext> ChildViewFnBuilder for ChildInsertBuilder<'a, Pc> {
     fn create_fn<E: Element>(&mut self) -> impl FnMut(E) {
-        |e| self.pc.insert_child(e)
+        |e| e.insert(self.pc)
     }
 }
 
 impl<'a, Pc: RebuildContext> ChildViewFnBuilder for ChildRebuildBuilder<'a, Pc> {
     fn create_fn<E: Element>(&mut self) -> impl FnMut(E) {
-        |e| self.pc.rebuild_child(e)
+        |e| e.rebuild(self.pc)
     }
 }
 
-pub(crate) trait ChildView<F> {
+/// The children of a stack or fragment: an element, a tuple of up to 12 of them, a `Vec` or [children].
+/// Sealed, it can't be implemented outside this crate.
+pub trait ChildView<F>: Sealed<F> {
     fn call_each(self, f: impl ChildViewFnBuilder);
 }
 
+impl<A: Element> Sealed<(A,)> for A {}
+
 impl<A: Element> ChildView<(A,)> for A {
     fn call_each(self, mut f: impl ChildViewFnBuilder) {
         f.create_fn()(self)
     }
 }
 
-impl<A: Element> ChildView<(A,)> for (A,) {
-    fn call_each(self, mut f: impl ChildViewFnBuilder) {
-        f.create_fn()(self.0)
-    }
-}
+/// Implements [ChildView] for a tuple, inserting its elements in order.
+macro_rules! tuple_child_view {
+    ($($name:ident $idx:tt),+) => {
+        impl<$($name: Element),+> Sealed<($($name,)+)> for ($($name,)+) {}
 
-impl<A: Element, B: Element> ChildView<(A, B)> for (A, B) {
-    fn call_each(self, mut f: impl ChildViewFnBuilder) {
-        f.create_fn()(self.0);
-        f.create_fn()(self.1)
-    }
+        impl<$($name: Element),+> ChildView<($($name,)+)> for ($($name,)+) {
+            fn call_each(self, mut f: impl ChildViewFnBuilder) {
+                $(f.create_fn()(self.$idx);)+
+            }
+        }
+    };
 }
 
-impl<A: Element, B: Element, C: Element> ChildView<(A, B, C)> for (A, B, C) {
+tuple_child_view!(A 0);
+tuple_child_view!(A 0, B 1);
+tuple_child_view!(A 0, B 1, C 2);
+tuple_child_view!(A 0, B 1, C 2, D 3);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+tuple_child_view!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+impl<E: Element> Sealed<Vec<E>> for Vec<E> {}
+
+impl<E: Element> ChildView<Vec<E>> for Vec<E> {
     fn call_each(self, mut f: impl ChildViewFnBuilder) {
-        f.create_fn()(self.0);
-        f.create_fn()(self.1);
-        f.create_fn()(self.2)
+        let mut f = f.create_fn();
+
+        for child in self {
+            f(child)
+        }
     }
 }
 
@@ -794,8 +4249,537 @@ impl DerefMut for Style {
     }
 }
 
+impl std::fmt::Debug for ViewWidget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ViewWidget")
+            .field(&self.0.reflect_type_path())
+            .finish()
+    }
+}
+
 impl std::fmt::Debug for CustomWidget {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_tuple("CustomWidget").finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        state::{Reducer, State, StateTrait},
+        Color, DisplayList, DrawCommand, Layout, Point, Size, Widget, WidgetEvent,
+    };
+
+    use super::{
+        image_async, progress_bar, scrollbar, spinner, text_input, Align, Button, CachedWidget,
+        Divider, EventContext, Fit, ImageLoad, ImageRect, ListItem, LoadState, Placement, Text,
+    };
+
+    // Stand-in for the editor's `Action`
+    #[derive(Clone, Debug, PartialEq)]
+    enum Action {
+        Save,
+    }
+
+    #[derive(Default)]
+    struct Received(Vec<Action>);
+
+    impl Reducer<Action> for Received {
+        fn reduce(&mut self, message: Action) {
+            self.0.push(message);
+        }
+    }
+
+    #[test]
+    fn size_setters_chain() {
+        use taffy::Dimension;
+
+        use crate::Styleable;
+
+        let mut button = Button::on_click(|| {})
+            .width(Dimension::Length(100.))
+            .max_width(Dimension::Percent(0.5))
+            .min_height(Dimension::Length(20.));
+
+        let style = &button.style_mut().0;
+        assert_eq!(style.size.width, Dimension::Length(100.));
+        // Left as it was
+        assert_eq!(style.size.height, Dimension::Auto);
+        assert_eq!(style.max_size.width, Dimension::Percent(0.5));
+        assert_eq!(style.min_size.height, Dimension::Length(20.));
+
+        let mut button = button.size(Dimension::Percent(1.), Dimension::Length(32.));
+        assert_eq!(
+            button.style_mut().0.size,
+            taffy::Size {
+                width: Dimension::Percent(1.),
+                height: Dimension::Length(32.),
+            }
+        );
+    }
+
+    #[test]
+    fn alignment_setters() {
+        use taffy::{AlignItems, AlignSelf, Dimension, JustifyContent, JustifyItems};
+
+        use crate::Styleable;
+
+        let mut button = Button::on_click(|| {})
+            .align_items(AlignItems::Center)
+            .justify_content(JustifyContent::SpaceBetween)
+            .align_self(AlignSelf::End)
+            .justify_items(JustifyItems::Start)
+            .flex_grow(2.)
+            .flex_shrink(0.)
+            .flex_basis(Dimension::Length(40.));
+
+        let style = &button.style_mut().0;
+        assert_eq!(style.align_items, Some(AlignItems::Center));
+        assert_eq!(style.justify_content, Some(JustifyContent::SpaceBetween));
+        assert_eq!(style.align_self, Some(AlignSelf::End));
+        assert_eq!(style.justify_items, Some(JustifyItems::Start));
+        assert_eq!(
+            (style.flex_grow, style.flex_shrink, style.flex_basis),
+            (2., 0., Dimension::Length(40.))
+        );
+    }
+
+    #[test]
+    fn button_sends_message() {
+        let mut state = State::<Action, Received>::default();
+        state.init();
+
+        let mut button = Button::sends(&state, Action::Save);
+        button.event(WidgetEvent::Click(0, 0));
+
+        assert!(state.is_dirty());
+
+        state.process();
+
+        assert_eq!(state.0, vec![Action::Save]);
+    }
+
+    #[derive(Clone)]
+    enum CounterMessage {
+        Increment,
+        Decrement,
+    }
+
+    #[derive(Default)]
+    struct Counter(i32);
+
+    impl Reducer<CounterMessage> for Counter {
+        fn reduce(&mut self, message: CounterMessage) {
+            match message {
+                CounterMessage::Increment => self.0 += 1,
+                CounterMessage::Decrement => self.0 -= 1,
+            }
+        }
+    }
+
+    #[test]
+    fn custom_message_button() {
+        let mut state = State::<CounterMessage, Counter>::default();
+        state.init();
+
+        let mut increment = Button::sends(&state, CounterMessage::Increment);
+        let mut decrement = Button::sends(&state, CounterMessage::Decrement);
+
+        increment.event(WidgetEvent::Click(0, 0));
+        increment.event(WidgetEvent::Click(0, 0));
+        decrement.event(WidgetEvent::Click(0, 0));
+
+        state.process();
+
+        assert_eq!(state.0, 1);
+    }
+
+    #[test]
+    fn cached_stays_fresh() {
+        let size = Size {
+            width: 100,
+            height: 50,
+        };
+
+        let mut cached = CachedWidget::default();
+        assert!(cached.is_stale(size));
+
+        cached.rendered(size);
+
+        // Two clean frames
+        assert!(!cached.is_stale(size));
+        assert!(!cached.is_stale(size));
+
+        assert!(cached.is_stale(Size {
+            width: 100,
+            height: 60
+        }));
+
+        cached.invalidate();
+        assert!(cached.is_stale(size));
+    }
+
+    #[test]
+    fn scrollbar_hides_when_content_fits() {
+        let now = std::time::Instant::now();
+
+        let fits = scrollbar(400., 600., 0.);
+        assert!(fits.is_hidden());
+        assert_eq!(fits.opacity_at(now), 0.);
+        assert_eq!(fits.style().0.display, taffy::Display::None);
+
+        let overflows = scrollbar(1200., 600., 0.);
+        assert!(!overflows.is_hidden());
+        assert_eq!(overflows.opacity_at(now), 1.);
+
+        let fading = scrollbar(1200., 600., 0.)
+            .fade_after(std::time::Duration::from_millis(500))
+            .scrolled_at(now);
+        assert_eq!(fading.opacity_at(now), 1.);
+        assert_eq!(
+            fading.opacity_at(now + std::time::Duration::from_secs(2)),
+            0.
+        );
+    }
+
+    #[test]
+    fn wide_image_in_tall_box() {
+        let bounds = ImageRect {
+            x: 0.,
+            y: 0.,
+            width: 100.,
+            height: 200.,
+        };
+        let rect = |x, y, width, height| ImageRect {
+            x,
+            y,
+            width,
+            height,
+        };
+        let place = |fit, align| Placement::new((200., 100.), bounds, fit, align);
+
+        assert_eq!(
+            place(Fit::Contain, Align::Center),
+            Placement {
+                source: rect(0., 0., 200., 100.),
+                dest: rect(0., 75., 100., 50.),
+            }
+        );
+        assert_eq!(
+            place(Fit::Cover, Align::Center),
+            Placement {
+                source: rect(75., 0., 50., 100.),
+                dest: rect(0., 0., 100., 200.),
+            }
+        );
+        assert_eq!(
+            place(Fit::Fill, Align::Center),
+            Placement {
+                source: rect(0., 0., 200., 100.),
+                dest: bounds,
+            }
+        );
+        assert_eq!(
+            place(Fit::None, Align::Center),
+            Placement {
+                source: rect(50., 0., 100., 100.),
+                dest: rect(0., 50., 100., 100.),
+            }
+        );
+
+        // Cropping keeps the aligned edge.
+        assert_eq!(
+            place(Fit::Cover, Align::TopLeft).source,
+            rect(0., 0., 50., 100.)
+        );
+        assert_eq!(
+            place(Fit::Contain, Align::Bottom).dest,
+            rect(0., 150., 100., 50.)
+        );
+    }
+
+    #[test]
+    fn async_image_shows_placeholder_until_loaded() {
+        let layout = Layout::fixed(
+            Point { x: 0, y: 0 },
+            Size {
+                width: 100,
+                height: 200,
+            },
+        );
+        let placeholder = Color::rgb(1, 2, 3);
+        let fill = |image: &super::Image| {
+            let mut list = DisplayList::default();
+            image.render(layout, &mut list);
+
+            match list.commands() {
+                [DrawCommand::FillRect { color, .. }] => Some(*color),
+                _ => None,
+            }
+        };
+
+        let (sender, load) = ImageLoad::channel();
+        let image = image_async(load.clone())
+            .placeholder(placeholder)
+            .error(Color::rgb(4, 5, 6));
+
+        assert!(!load.poll());
+        assert_eq!(fill(&image), Some(placeholder));
+
+        sender.send(Ok(imgref::ImgVec::new(
+            vec![rgb::RGBA8::default(); 200 * 100],
+            200,
+            100,
+        )));
+
+        // Decoded, and shown once the next paint has uploaded it.
+        assert!(load.poll());
+        assert!(matches!(*load.state(), LoadState::Decoded(ref pixels) if pixels.width() == 200));
+        assert_eq!(fill(&image), Some(placeholder));
+        assert!(image.placement(layout).is_none());
+
+        let (sender, load) = ImageLoad::channel();
+        let broken = image_async(load.clone()).error(Color::rgb(4, 5, 6));
+        sender.send(Err("Not a PNG".into()));
+
+        assert!(load.poll());
+        assert_eq!(fill(&broken), Some(Color::rgb(4, 5, 6)));
+    }
+
+    #[test]
+    fn image_files_load_once() {
+        let path = std::path::Path::new("missing.png");
+
+        let first = ImageLoad::file(path);
+        let started = std::time::Instant::now();
+        while !first.poll() {
+            assert!(started.elapsed() < std::time::Duration::from_secs(5));
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert!(matches!(*first.state(), LoadState::Failed(_)));
+
+        // Shares the failed load, instead of reading the file again
+        let second = ImageLoad::file(path);
+        assert!(matches!(*second.state(), LoadState::Failed(_)));
+
+        assert!(super::decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn spinner_steps_each_tick() {
+        let start = std::time::Instant::now();
+        let spinner = spinner().started_at(start);
+        let tick = std::time::Duration::from_millis(100);
+
+        let phases: Vec<_> = (0..10)
+            .map(|ticks| spinner.phase_at(start + tick * ticks))
+            .collect();
+        assert_eq!(phases, vec![0, 1, 2, 3, 4, 5, 6, 7, 0, 1]);
+
+        // Partway through a tick stays on the same segment.
+        assert_eq!(spinner.phase_at(start + tick * 3 + tick / 2), 3);
+
+        // Redrawn when the next tick moves it, not every frame
+        assert_eq!(
+            spinner.next_step_at(start + tick * 3 + tick / 2),
+            start + tick * 4
+        );
+        assert_eq!(spinner.next_step_at(start + tick * 3), start + tick * 4);
+
+        assert_eq!(progress_bar(1.5).value(), 1.);
+        assert_eq!(progress_bar(-1.).value(), 0.);
+    }
+
+    #[test]
+    fn list_filters_and_selects() {
+        use std::{cell::Cell, rc::Rc};
+
+        let selected = Rc::new(Cell::new(None));
+        let sink = selected.clone();
+        let mut list = super::list([
+            ListItem::new("src/main.rs").header(),
+            "unused x".into(),
+            "missing semicolon".into(),
+            ListItem::new("src/lib.rs").header(),
+            "unused y".into(),
+        ])
+        .filter("semi")
+        .on_select(move |index| sink.set(Some(index)));
+
+        // Headers without matching items are left out
+        let shown: Vec<_> = list.shown().map(ListItem::text).collect();
+        assert_eq!(shown, ["src/main.rs", "missing semicolon"]);
+
+        let layout = Layout::fixed(
+            Point { x: 0, y: 100 },
+            Size {
+                width: 400,
+                height: 200,
+            },
+        );
+        let mut font_system = cosmic_text::FontSystem::new_with_locale_and_db(
+            "en-US".into(),
+            cosmic_text::fontdb::Database::new(),
+        );
+        let mut context = EventContext::new(layout, &mut font_system);
+
+        // The second row shown is the third item
+        list.event_with(WidgetEvent::Click(10, 100 + 30), &mut context);
+        assert_eq!(selected.get(), Some(2));
+    }
+
+    #[test]
+    fn text_click_resolves_to_cursor() {
+        let mut font_system = cosmic_text::FontSystem::new_with_locale_and_db(
+            "en-US".into(),
+            cosmic_text::fontdb::Database::new(),
+        );
+        crate::text::load_fonts(&mut font_system, vec![]);
+
+        let layout = Layout::fixed(
+            Point { x: 10, y: 20 },
+            Size {
+                width: 400,
+                height: 100,
+            },
+        );
+
+        let mut text = Text::builder().text("hello world").size(20.).build();
+        let mut context = EventContext::new(layout, &mut font_system);
+
+        // Not laid out yet, the click shapes the text itself.
+        text.event_with(WidgetEvent::Click(10 + 2, 30), &mut context);
+        let start = text.clicked().unwrap();
+        assert_eq!((start.line, start.index), (0, 0));
+
+        let width = text.buffer().layout_runs().next().unwrap().line_w;
+        let middle = 10 + (width / 2.) as u32;
+
+        text.event_with(WidgetEvent::Click(middle, 30), &mut context);
+        let index = text.clicked().unwrap().index;
+        assert!((5..=6).contains(&index), "{index}");
+    }
+
+    #[test]
+    fn divider_fills_its_layout() {
+        let divider = Divider::vertical().thickness(2.).color(Color::rgb(1, 2, 3));
+
+        let size = divider.style().0.size;
+        assert_eq!(size.width, taffy::Dimension::Length(2.));
+        assert_eq!(size.height, taffy::Dimension::Percent(1.));
+
+        let layout = Layout::fixed(
+            Point { x: 200, y: 0 },
+            Size {
+                width: 2,
+                height: 600,
+            },
+        );
+
+        let mut list = DisplayList::default();
+        divider.render(layout, &mut list);
+
+        assert!(matches!(
+            list.commands(),
+            [DrawCommand::FillRect {
+                x: 200.,
+                y: 0.,
+                width: 2.,
+                height: 600.,
+                color,
+            }] if *color == Color::rgb(1, 2, 3)
+        ));
+    }
+
+    #[test]
+    fn button_emits_fill_rect() {
+        let layout = Layout::fixed(
+            Point { x: 10, y: 20 },
+            Size {
+                width: 100,
+                height: 30,
+            },
+        );
+
+        let mut list = DisplayList::default();
+        Button::on_click(|| {}).render(layout, &mut list);
+
+        assert!(matches!(
+            list.commands(),
+            [DrawCommand::FillRect {
+                x: 10.,
+                y: 20.,
+                width: 100.,
+                height: 30.,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn text_input_edits_with_keys() {
+        use std::{cell::RefCell, rc::Rc};
+        use winit::keyboard::{Key, NamedKey};
+
+        let changes = Rc::new(RefCell::new(vec![]));
+        let sink = changes.clone();
+        let mut input =
+            text_input("ab").on_change(move |text| sink.borrow_mut().push(text.to_string()));
+
+        // Not focused yet
+        assert!(!input.key(&Key::Character("c".into()), Some("c")));
+
+        let mut input = input.focused();
+        assert!(input.key(&Key::Character("c".into()), Some("c")));
+        assert_eq!((input.text(), input.caret()), ("abc", 3));
+
+        for _ in 0..3 {
+            assert!(input.key(&Key::Named(NamedKey::Backspace), None));
+        }
+        // Nothing left to delete
+        assert!(!input.key(&Key::Named(NamedKey::Backspace), None));
+        assert_eq!((input.text(), input.caret()), ("", 0));
+
+        input.key(&Key::Character("é".into()), Some("é"));
+        input.key(&Key::Named(NamedKey::ArrowLeft), None);
+        input.key(&Key::Named(NamedKey::Space), Some(" "));
+        assert_eq!((input.text(), input.caret()), (" é", 1));
+
+        // Modifiers on their own, and keys typing control characters
+        assert!(!input.key(&Key::Named(NamedKey::Shift), None));
+        assert!(!input.key(&Key::Named(NamedKey::Enter), Some("\r")));
+
+        input.key(&Key::Named(NamedKey::Delete), None);
+        assert_eq!(input.text(), " ");
+
+        assert_eq!(*changes.borrow(), ["abc", "ab", "a", "", "é", " é", " "]);
+    }
+
+    #[test]
+    fn text_input_paints_when_the_caret_blinks() {
+        let mut input = text_input("").focused();
+        let now = std::time::Instant::now();
+
+        let blink = input.next_blink_at(now).unwrap();
+        assert!(blink > now);
+        assert_ne!(
+            input.caret_visible_at(blink - std::time::Duration::from_millis(1)),
+            input.caret_visible_at(blink)
+        );
+
+        let layout = Layout::fixed(
+            Point { x: 0, y: 0 },
+            Size {
+                width: 100,
+                height: 30,
+            },
+        );
+        input.render(layout, &mut DisplayList::default());
+        assert!(!input.take_dirty());
+
+        // The caret drawn by the last paint went away
+        input.blur();
+        assert!(input.take_dirty());
+        assert_eq!(input.next_blink_at(now), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! Matching typed filters against the items of a list, like `dgn` finding `diagnostic_gutter.rs`.
+
+/// How well `candidate` matches `pattern`, lower is better, or `None` if it doesn't.
+///
+/// The pattern matches when its characters appear in the candidate in order, ignoring case.
+/// Each character skipped between two matched ones costs a point, so closer matches come first.
+/// An empty pattern matches everything equally.
+pub fn fuzzy_score(pattern: &str, candidate: &str) -> Option<usize> {
+    let mut pattern = pattern.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    // Skipped characters before the first match are free.
+    let mut started = false;
+
+    for c in candidate.chars().flat_map(char::to_lowercase) {
+        let Some(&next) = pattern.peek() else {
+            break;
+        };
+
+        if c == next {
+            pattern.next();
+            started = true;
+        } else if started {
+            score += 1;
+        }
+    }
+
+    pattern.peek().is_none().then_some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fuzzy_score;
+
+    #[test]
+    fn closer_matches_score_lower() {
+        assert_eq!(fuzzy_score("", "anything"), Some(0));
+        assert_eq!(fuzzy_score("main", "src/main.rs"), Some(0));
+        assert_eq!(fuzzy_score("MR", "src/main.rs"), Some(4));
+        assert_eq!(fuzzy_score("dgn", "diagnostic_gutter.rs"), Some(2));
+
+        // Out of order, or missing
+        assert_eq!(fuzzy_score("niam", "main"), None);
+        assert_eq!(fuzzy_score("mains", "main"), None);
+    }
+}
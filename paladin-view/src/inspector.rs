@@ -0,0 +1,229 @@
+//! A developer panel listing the mounted tree, toggled with F12.
+//! Hovering an entry outlines its node on screen.
+
+use std::collections::HashMap;
+
+use femtovg::{renderer::Renderer, Paint, Path};
+use taffy::NodeId;
+use winit::{
+    event::ElementState,
+    keyboard::{Key, NamedKey},
+};
+
+use crate::{
+    app::WidgetTree, Canvas, Color, DisplayList, KeyEvent, Layout, Point, Size, Style, Text, Widget,
+};
+
+const PANEL_WIDTH: u32 = 360;
+const ROW_HEIGHT: u32 = 18;
+
+/// A row of the inspector, one per mounted node.
+#[derive(Debug, Clone)]
+pub struct InspectorEntry {
+    pub node: NodeId,
+    /// How many ancestors the node has, not counting the root.
+    pub depth: usize,
+    pub kind: &'static str,
+    /// Layout of the node, in window coordinates.
+    pub layout: Layout,
+    pub style: Style,
+}
+
+/// The rows of the inspector. Depth first, so children follow their parent.
+pub fn entries(tree: &WidgetTree) -> Vec<InspectorEntry> {
+    let mut children: HashMap<NodeId, Vec<_>> = HashMap::new();
+    let mut root = None;
+
+    for (node, parent, widget, layout) in tree.nodes() {
+        root.get_or_insert(parent);
+        children
+            .entry(parent)
+            .or_default()
+            .push((node, widget.kind(), layout, widget.style()));
+    }
+
+    let Some(root) = root else {
+        return vec![];
+    };
+
+    fn walk(
+        children: &HashMap<NodeId, Vec<(NodeId, &'static str, Layout, Style)>>,
+        parent: NodeId,
+        origin: Point,
+        depth: usize,
+        entries: &mut Vec<InspectorEntry>,
+    ) {
+        for (node, kind, layout, style) in children.get(&parent).into_iter().flatten() {
+            let layout = layout.plus_location(origin);
+
+            entries.push(InspectorEntry {
+                node: *node,
+                depth,
+                kind,
+                layout,
+                style: style.clone(),
+            });
+
+            walk(children, *node, layout.location, depth + 1, entries);
+        }
+    }
+
+    let mut entries = vec![];
+    walk(&children, root, Point { x: 0, y: 0 }, 0, &mut entries);
+
+    entries
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Inspector {
+    open: bool,
+    cursor: Point,
+}
+
+impl Inspector {
+    /// Toggle the panel on F12. Returns whether the key was used.
+    pub(crate) fn key(&mut self, event: &KeyEvent) -> bool {
+        if event.state == ElementState::Pressed && event.logical_key == Key::Named(NamedKey::F12) {
+            self.open = !self.open;
+
+            return true;
+        }
+
+        false
+    }
+
+    /// Returns whether the panel needs to be redrawn.
+    pub(crate) fn cursor_moved(&mut self, at: Point) -> bool {
+        self.cursor = at;
+
+        self.open
+    }
+
+    pub(crate) fn paint<R: Renderer>(
+        &self,
+        tree: &WidgetTree,
+        window: Size,
+        canvas: &mut Canvas<R>,
+    ) {
+        if !self.open {
+            return;
+        }
+
+        let entries = entries(tree);
+        let mut list = DisplayList::default();
+        let panel_x = window.width.saturating_sub(PANEL_WIDTH);
+
+        canvas.clear_rect(
+            panel_x,
+            0,
+            window.width - panel_x,
+            window.height,
+            Color::rgb(30, 30, 30),
+        );
+
+        let hovered = (self.cursor.x >= panel_x)
+            .then(|| entries.get((self.cursor.y / ROW_HEIGHT) as usize))
+            .flatten();
+
+        for (row, entry) in entries.iter().enumerate() {
+            let row_layout = Layout::fixed(
+                Point {
+                    x: panel_x,
+                    y: row as u32 * ROW_HEIGHT,
+                },
+                Size {
+                    width: PANEL_WIDTH,
+                    height: ROW_HEIGHT,
+                },
+            );
+
+            if row_layout.location.y >= window.height {
+                break;
+            }
+
+            if hovered.is_some_and(|hovered| hovered.node == entry.node) {
+                canvas.clear_rect(
+                    row_layout.location.x,
+                    row_layout.location.y,
+                    row_layout.size.width,
+                    row_layout.size.height,
+                    Color::rgb(60, 60, 80),
+                );
+            }
+
+            let Layout {
+                location,
+                size,
+                order,
+                ..
+            } = entry.layout;
+
+            let mut text = Text::builder()
+                .text(format!(
+                    "{}{} {},{} {}x{} #{order} {:?}",
+                    "  ".repeat(entry.depth),
+                    entry.kind,
+                    location.x,
+                    location.y,
+                    size.width,
+                    size.height,
+                    entry.style.flex_direction,
+                ))
+                .size(14.)
+                .wrap(cosmic_text::Wrap::None)
+                .build();
+
+            text.layout(row_layout, canvas.font_system());
+            text.render(row_layout, &mut list);
+            list.replay(canvas);
+        }
+
+        if let Some(hovered) = hovered {
+            let Layout { location, size, .. } = hovered.layout;
+
+            let mut path = Path::new();
+            path.rect(
+                location.x as f32,
+                location.y as f32,
+                size.width as f32,
+                size.height as f32,
+            );
+
+            canvas.stroke_path(
+                &path,
+                &Paint::color(femtovg::Color::rgb(230, 80, 80)).with_line_width(2.),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_reflect::TypeRegistry;
+    use winit::dpi::PhysicalSize;
+
+    use crate::{app::WidgetTree, prelude::*};
+
+    use super::entries;
+
+    #[view]
+    struct Two;
+
+    impl View for Two {
+        fn build(&self) -> impl Element + use<> {
+            "Hello"
+        }
+    }
+
+    #[test]
+    fn two_node_tree() {
+        let tree = WidgetTree::create(&mut TypeRegistry::new(), Two, PhysicalSize::new(100, 100));
+
+        let entries = entries(&tree);
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].kind, entries[0].depth), ("View", 0));
+        assert_eq!((entries[1].kind, entries[1].depth), ("Text", 1));
+        assert_eq!(entries[1].layout.location, entries[0].layout.location);
+    }
+}
@@ -1,19 +1,51 @@
 // #![feature(type_alias_impl_trait)]
 #![feature(precise_capturing_in_traits)]
 
-use std::{fmt::Debug, hint::unreachable_unchecked};
+// Lets the macros refer to `::paladin_view` from within this crate.
+extern crate self as paladin_view;
+
+use std::{
+    fmt::Debug,
+    hint::unreachable_unchecked,
+    sync::{Mutex, OnceLock},
+    time::Instant,
+};
 
 use app::App;
 use bevy_reflect::{Reflect, TypeRegistry};
 
+/// Log a step of the render and diff pipeline. Compiles to nothing without the `trace` feature.
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        tracing::trace!($($arg)*);
+    };
+}
+
+/// Like [trace], but enters a span lasting until the end of the enclosing scope.
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "trace")]
+        let _span = tracing::trace_span!($($arg)*).entered();
+    };
+}
+
 pub mod app;
+mod batch;
+mod config;
+mod debounce;
+mod display_list;
 mod elements;
+mod fuzzy;
+pub mod inspector;
 pub mod patch;
 pub mod prelude;
 mod runner;
+mod scroll;
 
 mod start;
 mod state;
+mod task;
 mod text;
 
 mod utils;
@@ -22,10 +54,17 @@ use state::ReflectStateTrait;
 use taffy::NodeId;
 pub use utils::*;
 
+pub use config::AppConfig;
 use cosmic_text::FontSystem;
+pub use debounce::Debounced;
+pub use display_list::{DisplayList, DrawCommand};
 pub use elements::*;
+pub use fuzzy::fuzzy_score;
+pub use scroll::{wheel_pixels, Scroll, ScrollKey};
+pub use task::{task, CancelToken, Task};
+pub use text::{AntialiasMode, RenderCache};
 
-use femtovg::renderer::OpenGl;
+use femtovg::renderer::{OpenGl, Renderer};
 use runner::{Runner, Windows};
 
 pub type Result<T> = miette::Result<T>;
@@ -34,6 +73,7 @@ pub type Result<T> = miette::Result<T>;
 pub type Point = taffy::Point<u32>;
 pub type Size = taffy::Size<u32>;
 pub type Rect = taffy::Rect<u32>;
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color(femtovg::Color);
 
 pub type KeyEvent = winit::event::KeyEvent;
@@ -51,12 +91,38 @@ pub mod taffy {
 /// Run the app.
 /// Call this once with your top level view.
 pub fn run<V: View>(v: V) -> crate::Result<()> {
-    let (canvas, el, pcc, surface, window, _config) = start::create_event_loop(800, 600, "view");
+    run_with_config(v, AppConfig::default())
+}
+
+/// Run the app with extra font files, like the bold and italic faces of a family.
+/// Text asking for a weight or style uses the matching face of its family.
+pub fn run_with_fonts<V: View>(v: V, fonts: Vec<Vec<u8>>) -> crate::Result<()> {
+    run_with_config(
+        v,
+        fonts
+            .into_iter()
+            .fold(AppConfig::default(), |config, font| config.font(font)),
+    )
+}
+
+/// Run the app, set up by `config` before its first frame.
+pub fn run_with_config<V: View>(v: V, config: AppConfig) -> crate::Result<()> {
+    let (canvas, el, pcc, surface, window, _config) =
+        start::create_event_loop(800, 600, DEFAULT_TITLE);
+    let _ = PROXY.set(el.create_proxy());
 
-    let canvas = Canvas {
+    let mut canvas = Canvas {
         inner: canvas,
-        text_cache: text::init_cache(),
+        text_cache: text::init_cache(vec![]),
+        snap: PixelSnap {
+            scale_factor: window.scale_factor() as f32,
+            ..Default::default()
+        },
+        rects: Default::default(),
+        #[cfg(test)]
+        issued: vec![],
     };
+    config.apply(&mut canvas)?;
 
     let app = App::new(v, PhysicalSize::new(300, 400));
 
@@ -65,6 +131,7 @@ pub fn run<V: View>(v: V) -> crate::Result<()> {
         windows: Windows::new(window, surface),
         gl_context: pcc,
         canvas,
+        line_height: scroll::LINE,
     }
     .run(el)
 }
@@ -73,70 +140,74 @@ impl<T: View> Element for T {
     fn create(mut self, registry: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
         self.register(registry);
 
-        app::iter_fields(self.as_reflect_mut(), |_, field| {
-            if let Some(reflect_state) =
-                registry.get_type_data::<ReflectStateTrait>(field.type_id())
+        app::init_states(self.as_reflect_mut(), registry);
+
+        let built = self.build();
+
+        BuildResult {
+            widget: MountedWidget::View(ViewWidget(Box::new(self))),
+            children: Some(SingleChild(built)),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        "View"
+    }
+
+    fn compare_rebuild(
+        mut self,
+        old: MountedWidget,
+        registry: &mut TypeRegistry,
+    ) -> BuildResult<impl RebuildChildren> {
+        match old {
+            // Same props, the mounted subtree is still what build would return.
+            MountedWidget::View(old)
+                if old.0.as_any().is::<T>()
+                    && app::props_eq(self.as_reflect(), old.0.as_reflect(), registry) =>
             {
-                let Some(state) = reflect_state.get_mut(field) else {
-                    return;
+                trace!(
+                    view = self.reflect_type_path(),
+                    "props unchanged, skipping build"
+                );
+
+                return BuildResult {
+                    widget: MountedWidget::View(old),
+                    children: Some(OneOf::A(KeepChildren)),
                 };
-
-                state.init()
             }
-        });
+            // Same view as before, take over the state that has accumulated in the old one.
+            MountedWidget::View(mut old) if old.0.as_any().is::<T>() => {
+                app::reuse_states(self.as_reflect_mut(), old.0.as_reflect_mut(), registry);
+            }
+            _ => {
+                self.register(registry);
 
-        let built = self.build();
-        built.create(registry)
-
-        // let boxed = ViewWidget(Box::new(self)).into();
-
-        // let id = context.insert(boxed);
-        // context.child_work(built, id);
-
-        // mount_children(registry, tree, id, built, idx)
-    }
-
-    #[allow(refining_impl_trait)]
-    fn compare_rebuild(self, old: MountedWidget) -> BuildResult<impl RebuildChildren> {
-        // let MountedWidget::View(mut view) = old else {
-        //     return CompareResult::Replace { with: self };
-        // };
-
-        // if self.type_id() != view.0.type_id() {
-        //     return CompareResult::Replace { with: self };
-        // }
-
-        // app::iter_fields(self.as_reflect_mut(), |index, field| {
-        //     if let Some(reflect_state) = context
-        //         .registry()
-        //         .get_type_data::<ReflectStateTrait>(field.type_id())
-        //     {
-        // todo uggly
-        // if let Some(state) = reflect_state.get_mut(field) {
-        //     if let bevy_reflect::ReflectMut::Struct(st) = view.0.reflect_mut() {
-        //         state.reuse(st.field_at_mut(index).unwrap());
-        //     } else if let bevy_reflect::ReflectMut::Enum(_) = view.0.reflect_mut() {
-        //         panic!();
-        //         // state.reuse(en.field_at_mut(index).unwrap());
-        //     } else {
-        //         panic!()
-        //     }
-        // }
-        // }
-        // });
+                app::init_states(self.as_reflect_mut(), registry);
+            }
+        }
 
         let built = self.build();
 
-        built.compare_rebuild(old)
-
-        // built.compare_rebuild(old)
+        BuildResult {
+            widget: MountedWidget::View(ViewWidget(Box::new(self))),
+            children: Some(OneOf::B(SingleChild(built))),
+        }
+    }
+}
 
-        // can be optimized
-        // *view.0.as_any_mut().downcast_mut::<Self>().unwrap() = self;
+/// A single child element, inserted and rebuilt in place.
+/// Used by elements that wrap another, like [View]s wrapping whatever they built.
+pub(crate) struct SingleChild<E>(pub(crate) E);
 
-        // context.insert(MountedWidget::View(view));
+impl<E: Element> InsertChildren for SingleChild<E> {
+    fn insert_children(self, context: &mut impl InsertContext) {
+        self.0.insert(context)
+    }
+}
 
-        // context.child_work(built);
+impl<E: Element> RebuildChildren for SingleChild<E> {
+    fn rebuild_children(self, context: &mut impl RebuildContext) {
+        self.0.rebuild(context)
     }
 }
 
@@ -150,10 +221,16 @@ pub trait InsertContext {
 /// See [Element::compare_rebuild]
 pub trait RebuildContext {
     fn rebuild_child<E: Element>(&mut self, e: E);
+
+    /// Keep the children that are still mounted, rather than removing the ones not rebuilt.
+    fn keep_children(&mut self);
 }
 
 /// The result of a build.
 /// See [Element::compare_rebuild]
+///
+/// Without children, any children still mounted under the widget are removed.
+/// A widget rebuilt from an unchanged element keeps them with [KeepChildren].
 pub struct BuildResult<C> {
     pub widget: MountedWidget,
     pub children: Option<C>,
@@ -169,6 +246,16 @@ pub trait InsertChildren: 'static {
 
 pub enum LeafNode {}
 
+/// The children of a widget that would build the same as before, left mounted as they are.
+/// See [RebuildContext::keep_children]
+pub struct KeepChildren;
+
+impl RebuildChildren for KeepChildren {
+    fn rebuild_children(self, context: &mut impl RebuildContext) {
+        context.keep_children()
+    }
+}
+
 impl RebuildChildren for LeafNode {
     fn rebuild_children(self, _: &mut impl RebuildContext) {
         // Safety: Impossible to construct LeafNode
@@ -187,18 +274,43 @@ impl InsertChildren for LeafNode {
 /// Elements must also contain their own children, and perform any work the framework demands of them via [InsertContext] and [RebuildContext].
 /// In some ways Elements are the bridge between both [View]s and [Widget]s, as it will commonly be implemented by both.
 /// Usually one won't manually implement this trait (though, you can.), instead prefer to create [View]s.
-pub trait Element {
+pub trait Element: 'static {
     /// Each element is expected to create a [MountedWidget].
     /// Additionally, if the element has any children, those may additionally be specified by returning a type that knows how to create them (The InsertChildren trait).
     fn create(self, registry: &mut TypeRegistry) -> BuildResult<impl InsertChildren>;
 
+    /// The kind of [MountedWidget] this element mounts as.
+    /// When it differs from the mounted widget's, the old widget and its children are unmounted before [Element::compare_rebuild].
+    fn kind(&self) -> &'static str;
+
     /// When the element tree is rebuilt because of a dirty view, the tree must be diffed. This function is called for each new element (returned by [View::build]) down the tree from the dirty widget,
     /// and it is the responsibility of that element to:
     /// * Compare itself to old. If old is not of the same type or otherwise incompatible with self, return a [CompareResult::Replace], with self.
     /// * If old can be used to build a new MountedWidget, rebuild. Reuse any allocations or state that has accumulated in the old element.
     /// * Additionally, if the new element has any children, call [RebuildContext::child_work] once per child.
     /// * Then return [CompareResult::Success], indicating a successful rebuild and insertion.
-    fn compare_rebuild(self, old: MountedWidget) -> BuildResult<impl RebuildChildren>;
+    fn compare_rebuild(
+        self,
+        old: MountedWidget,
+        registry: &mut TypeRegistry,
+    ) -> BuildResult<impl RebuildChildren>;
+
+    /// Insert this element into its parent.
+    /// Almost every element is a single node in the tree, and should keep this default. Elements that are not (like [Fragment]) may instead insert any number of siblings.
+    fn insert(self, context: &mut impl InsertContext)
+    where
+        Self: Sized,
+    {
+        context.insert_child(self)
+    }
+
+    /// The rebuild counterpart to [Element::insert].
+    fn rebuild(self, context: &mut impl RebuildContext)
+    where
+        Self: Sized,
+    {
+        context.rebuild_child(self)
+    }
 }
 
 /// Views are the building blocks of an application. They can be used to compose widgets or other views.
@@ -241,21 +353,269 @@ pub trait View: DynView {
 #[doc(hidden)]
 pub trait DynView: Reflect {
     fn register(&self, registry: &mut TypeRegistry);
-    fn dyn_cmp(&self, child_id: NodeId, tree: &mut app::WidgetTree, registry: &mut TypeRegistry);
+    fn dyn_cmp(&self, view_id: NodeId, tree: &mut app::WidgetTree, registry: &mut TypeRegistry);
 }
 
-pub struct Canvas {
-    pub(crate) inner: femtovg::Canvas<OpenGl>,
+/// What widgets are drawn on. On screen it is backed by OpenGL, other renderers are for tests.
+pub struct Canvas<R: Renderer = OpenGl> {
+    pub(crate) inner: femtovg::Canvas<R>,
     pub(crate) text_cache: text::RenderCache,
+    pub(crate) snap: PixelSnap,
+    /// Filled rects and text not drawn yet, see [Canvas::draw].
+    rects: batch::RectBatch,
+    /// Every draw call issued to `inner`, for tests to check what a paint drew.
+    #[cfg(test)]
+    pub(crate) issued: Vec<Issued>,
+}
+
+/// A draw call a [Canvas] issued, see [Canvas::recording].
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Issued {
+    Clear {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    Fill,
+    Stroke,
+    Glyphs,
 }
 
-impl Canvas {
+/// Rounding of drawing positions to whole device pixels, so text and rects line up with the pixel grid.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PixelSnap {
+    pub(crate) enabled: bool,
+    pub(crate) scale_factor: f32,
+}
+
+impl Default for PixelSnap {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            scale_factor: 1.,
+        }
+    }
+}
+
+impl PixelSnap {
+    /// Snap a logical position to the closest device pixel.
+    pub(crate) fn apply(self, position: f32) -> f32 {
+        if !self.enabled {
+            return position;
+        }
+
+        (position * self.scale_factor).round() / self.scale_factor
+    }
+
+    /// Snap the edges of a logical rect to the closest device pixels, so its size is rounded the same way wherever it is.
+    pub(crate) fn rect(self, x: f32, y: f32, width: f32, height: f32) -> (f32, f32, f32, f32) {
+        if !self.enabled {
+            return (x, y, width, height);
+        }
+
+        let (left, top) = (self.apply(x), self.apply(y));
+        let (right, bottom) = (self.apply(x + width), self.apply(y + height));
+
+        (left, top, right - left, bottom - top)
+    }
+}
+
+impl<R: Renderer> Canvas<R> {
+    /// Round drawing positions to whole device pixels. Enabled by default.
+    /// Disabling it may look smoother for content that moves, like while scrolling.
+    pub fn set_pixel_snapping(&mut self, enabled: bool) {
+        self.snap.enabled = enabled;
+    }
+
+    /// Fit glyph outlines to the pixel grid, see [RenderCache::set_hinting](text::RenderCache::set_hinting).
+    pub fn set_hinting(&mut self, enabled: bool) {
+        self.text_cache.set_hinting(enabled);
+    }
+
+    /// Keep at most `atlases` glyph textures, see [RenderCache::set_max_atlases](text::RenderCache::set_max_atlases).
+    pub fn set_max_atlases(&mut self, atlases: usize) {
+        self.text_cache.set_max_atlases(atlases);
+    }
+
+    /// How much room `text` takes, see [RenderCache::measure](text::RenderCache::measure).
+    pub fn measure(
+        &mut self,
+        text: &str,
+        attrs: cosmic_text::Attrs,
+        size: f32,
+        max_width: Option<f32>,
+    ) -> Size {
+        self.text_cache.measure(text, attrs, size, max_width)
+    }
+
+    /// Smooth glyph edges per subpixel or in grayscale, see [RenderCache::set_antialias](text::RenderCache::set_antialias).
+    pub fn set_antialias(&mut self, mode: AntialiasMode) {
+        self.text_cache.set_antialias(mode);
+    }
+
+    /// Use `family` for text asking for one that isn't loaded, see [RenderCache::set_fallback_family](text::RenderCache::set_fallback_family).
+    pub fn set_fallback_family(&mut self, family: impl Into<String>) {
+        self.text_cache.set_fallback_family(family);
+    }
+
+    /// Use `families`, in order, for characters missing from the family text asks for, see [RenderCache::set_fallback_fonts](text::RenderCache::set_fallback_fonts).
+    pub fn set_fallback_fonts(&mut self, families: impl IntoIterator<Item = impl Into<String>>) {
+        self.text_cache.set_fallback_fonts(families);
+    }
+
+    /// Load the faces in a font file's contents, see [RenderCache::load_font_data](text::RenderCache::load_font_data).
+    pub fn load_font_data(&mut self, data: Vec<u8>) {
+        self.text_cache.load_font_data(data);
+    }
+
+    /// Load the faces in the font file at `path`.
+    pub fn load_font_file(&mut self, path: &std::path::Path) -> Result<()> {
+        self.text_cache.load_font_file(path)
+    }
+
+    /// Names of the loaded families, usable as the `font` of a [Text].
+    pub fn list_families(&self) -> Vec<String> {
+        self.text_cache.list_families()
+    }
+
     fn font_system(&mut self) -> &mut FontSystem {
         &mut self.text_cache.font_system
     }
 
-    fn clear_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color: crate::Color) {
-        self.inner.clear_rect(x, y, width, height, color.into())
+    /// The canvas to draw anything but filled rects and text on.
+    /// Those are batched, so pending ones are drawn first to keep everything in painting order.
+    pub(crate) fn draw(&mut self) -> &mut femtovg::Canvas<R> {
+        if !self.rects.is_empty() {
+            for batched in self.rects.take() {
+                match batched {
+                    batch::Batched::Rects(color, path) => {
+                        self.inner
+                            .fill_path(&path, &femtovg::Paint::color(color.into()));
+
+                        #[cfg(test)]
+                        self.issued.push(Issued::Fill);
+                    }
+                    batch::Batched::Glyphs(glyphs) => {
+                        for (color, commands) in glyphs {
+                            let color = femtovg::Color::rgb(color.r(), color.g(), color.b());
+
+                            self.inner.draw_glyph_commands(
+                                commands,
+                                &femtovg::Paint::color(color),
+                                1.,
+                            );
+
+                            #[cfg(test)]
+                            self.issued.push(Issued::Glyphs);
+                        }
+                    }
+                }
+            }
+        }
+
+        &mut self.inner
+    }
+
+    /// Fill a path, after what is waiting to be drawn.
+    pub(crate) fn fill_path(&mut self, path: &femtovg::Path, paint: &femtovg::Paint) {
+        self.draw().fill_path(path, paint);
+
+        #[cfg(test)]
+        self.issued.push(Issued::Fill);
+    }
+
+    /// Stroke a path, after what is waiting to be drawn.
+    pub(crate) fn stroke_path(&mut self, path: &femtovg::Path, paint: &femtovg::Paint) {
+        self.draw().stroke_path(path, paint);
+
+        #[cfg(test)]
+        self.issued.push(Issued::Stroke);
+    }
+
+    /// Replace everything in a rect of the render target with `color`, without blending.
+    pub(crate) fn clear_rect(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        color: crate::Color,
+    ) {
+        self.draw().clear_rect(x, y, width, height, color.into());
+
+        #[cfg(test)]
+        self.issued.push(Issued::Clear {
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    /// Fill the entire drawable with color.
+    pub fn clear(&mut self, color: crate::Color) {
+        let (width, height) = (self.inner.width(), self.inner.height());
+
+        self.clear_rect(0, 0, width, height, color)
+    }
+
+    /// Draw shaped text with its top-left corner at `position`.
+    /// It waits with the filled rects, so ones drawn next to it can still be batched with ones drawn before.
+    pub(crate) fn draw_text(&mut self, buffer: &cosmic_text::Buffer, position: (f32, f32)) {
+        let glyphs = self
+            .text_cache
+            .fill_buffer_to_draw_commands(&mut self.inner, buffer, position, self.snap)
+            .unwrap();
+
+        self.rects.push_glyphs(glyphs);
+    }
+
+    /// Stroke a straight line, blending with what is already drawn.
+    pub fn draw_line(&mut self, from: (f32, f32), to: (f32, f32), width: f32, color: crate::Color) {
+        let snap = self.snap;
+
+        let mut path = femtovg::Path::new();
+        path.move_to(snap.apply(from.0), snap.apply(from.1));
+        path.line_to(snap.apply(to.0), snap.apply(to.1));
+
+        self.stroke_path(
+            &path,
+            &femtovg::Paint::color(color.into()).with_line_width(width),
+        );
+    }
+
+    /// Fill a rectangle, blending with what is already drawn.
+    /// Unlike clearing, translucent colors let the content below show through.
+    /// Rects are batched by color, and drawn together before anything but text is.
+    pub fn fill_rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: crate::Color) {
+        let (x, y, width, height) = self.snap.rect(x, y, width, height);
+
+        self.rects.push(x, y, width, height, color);
+    }
+}
+
+#[cfg(test)]
+impl Canvas<femtovg::renderer::Void> {
+    /// A `width` by `height` canvas on a renderer that draws nothing, recording the draw calls it is given in [Canvas::issued].
+    pub(crate) fn recording(width: u32, height: u32) -> Self {
+        let mut font_system = FontSystem::new_with_locale_and_db(
+            "en-US".into(),
+            cosmic_text::fontdb::Database::new(),
+        );
+        text::load_fonts(&mut font_system, vec![]);
+
+        let mut inner = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        inner.set_size(width, height, 1.);
+
+        Self {
+            inner,
+            text_cache: text::RenderCache::new(font_system),
+            snap: PixelSnap::default(),
+            rects: Default::default(),
+            issued: vec![],
+        }
     }
 }
 
@@ -285,6 +645,36 @@ pub struct Layout {
 }
 
 impl Layout {
+    /// A node at `location` of `size`, without scrollbars, borders or padding.
+    pub(crate) fn fixed(location: Point, size: Size) -> Self {
+        let zero = Rect {
+            left: 0,
+            right: 0,
+            top: 0,
+            bottom: 0,
+        };
+
+        Self {
+            order: 0,
+            location,
+            size,
+            scrollbar_size: Size {
+                width: 0,
+                height: 0,
+            },
+            border: zero,
+            padding: zero,
+        }
+    }
+
+    /// Whether a point lies within the node's rect.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        self.location.x <= x
+            && self.location.y <= y
+            && x < self.location.x + self.size.width
+            && y < self.location.y + self.size.height
+    }
+
     pub fn plus_location(mut self, location: Point) -> Self {
         self.location = Point {
             x: self.location.x + location.x,
@@ -346,17 +736,194 @@ impl<F: Fn() + 'static> From<F> for Triggerable {
 
 #[doc(hidden)]
 pub enum GlobalEvent {
-    Dirty { hint: NodeId },
+    Dirty {
+        hint: NodeId,
+    },
+    Redraw,
+    /// New title of the root window.
+    SetTitle(String),
+    /// Messages were sent through a [Mailbox](state::Mailbox).
+    Messages,
+}
+
+static PROXY: OnceLock<winit::event_loop::EventLoopProxy<GlobalEvent>> = OnceLock::new();
+
+/// Paint again soon, from any thread. Does nothing unless the app is running.
+pub(crate) fn request_redraw() {
+    if let Some(proxy) = PROXY.get() {
+        let _ = proxy.send_event(GlobalEvent::Redraw);
+    }
+}
+
+static NEXT_FRAME: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Paint again at `at`, like for the next step of an animation. The earliest frame asked for wins.
+/// States are given the frame too, see [Reducer::frame](state::Reducer::frame).
+pub fn request_redraw_at(at: Instant) {
+    let mut next = NEXT_FRAME.lock().unwrap();
+    *next = Some(next.map_or(at, |next| next.min(at)));
+}
+
+/// When the frame asked for with [request_redraw_at] is, forgetting it once `now` reached it.
+pub(crate) fn poll_frame(now: Instant) -> Option<Instant> {
+    let mut next = NEXT_FRAME.lock().unwrap();
+    let at = (*next)?;
+
+    if at <= now {
+        *next = None;
+    }
+
+    Some(at)
+}
+
+/// Handle messages sent to states from outside the event loop. Does nothing unless the app is running.
+pub(crate) fn process_messages() {
+    if let Some(proxy) = PROXY.get() {
+        let _ = proxy.send_event(GlobalEvent::Messages);
+    }
+}
+
+/// Title of the window until [set_title] is called.
+const DEFAULT_TITLE: &str = "view";
+
+static TITLE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Change the title of the window, from any thread.
+/// It is shown once the event loop gets to it, but [title] returns it right away.
+pub fn set_title(title: impl Into<String>) {
+    let title = title.into();
+    *TITLE.lock().unwrap() = Some(title.clone());
+
+    if let Some(proxy) = PROXY.get() {
+        let _ = proxy.send_event(GlobalEvent::SetTitle(title));
+    }
+}
+
+/// The title last given to [set_title].
+pub fn title() -> String {
+    TITLE
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TITLE.into())
 }
 
 impl Color {
-    pub fn rgb(r: u8, b: u8, g: u8) -> Self {
+    pub fn rgb(r: u8, g: u8, b: u8) -> Self {
         Self(femtovg::Color::rgb(r, g, b))
     }
 
-    pub fn rgba(r: u8, b: u8, g: u8, a: u8) -> Self {
+    pub fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
         Self(femtovg::Color::rgba(r, g, b, a))
     }
+
+    /// Parse a CSS-style `#RGB`, `#RRGGBB` or `#RRGGBBAA` color.
+    pub fn hex(hex: &str) -> Result<Self> {
+        let digits = hex
+            .strip_prefix('#')
+            .filter(|digits| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .ok_or_else(|| miette::miette!("{hex:?} is not a color, expected # and hex digits"))?;
+
+        // Every digit is ASCII, so any byte is a boundary.
+        let channel = |idx: usize, len: usize| {
+            let value = u8::from_str_radix(&digits[idx * len..(idx + 1) * len], 16).unwrap();
+
+            // #RGB repeats each digit, F is FF
+            if len == 1 {
+                value * 17
+            } else {
+                value
+            }
+        };
+
+        match digits.len() {
+            3 => Ok(Self::rgb(channel(0, 1), channel(1, 1), channel(2, 1))),
+            6 => Ok(Self::rgb(channel(0, 2), channel(1, 2), channel(2, 2))),
+            8 => Ok(Self::rgba(
+                channel(0, 2),
+                channel(1, 2),
+                channel(2, 2),
+                channel(3, 2),
+            )),
+            len => Err(miette::miette!(
+                "{hex:?} is not a color, expected 3, 6 or 8 hex digits but got {len}"
+            )),
+        }
+    }
+
+    /// A color from its hue in degrees, and saturation and lightness from 0 to 1.
+    pub fn hsl(h: f32, s: f32, l: f32) -> Self {
+        Self::hsla(h, s, l, 1.)
+    }
+
+    /// Like [Color::hsl], with an alpha from 0 to 1.
+    pub fn hsla(h: f32, s: f32, l: f32, a: f32) -> Self {
+        Self(femtovg::Color::hsla(h / 360., s, l, a))
+    }
+
+    /// The color `t` of the way to `other`, with `t` clamped to 0..=1.
+    /// Mixes in linear light, so the colors halfway aren't darker than either end.
+    pub fn lerp(self, other: Color, t: f32) -> Self {
+        let t = t.clamp(0., 1.);
+
+        // Exact at the ends, which going through linear light and back isn't.
+        if t == 0. {
+            return self;
+        }
+
+        if t == 1. {
+            return other;
+        }
+
+        let (Self(from), Self(to)) = (self, other);
+        let mix = |from: f32, to: f32| {
+            let (from, to) = (srgb_to_linear(from), srgb_to_linear(to));
+
+            linear_to_srgb(from + (to - from) * t)
+        };
+
+        Self(femtovg::Color::rgbaf(
+            mix(from.r, to.r),
+            mix(from.g, to.g),
+            mix(from.b, to.b),
+            from.a + (to.a - from.a) * t,
+        ))
+    }
+
+    /// The same color with an alpha from 0 to 1.
+    pub fn with_alpha(self, a: f32) -> Self {
+        let Self(mut color) = self;
+        color.a = a.clamp(0., 1.);
+
+        Self(color)
+    }
+
+    /// Mix `amount`, from 0 to 1, of black in, keeping the alpha.
+    pub fn darken(self, amount: f32) -> Self {
+        self.lerp(Self::rgb(0, 0, 0).with_alpha(self.0.a), amount)
+    }
+
+    /// Mix `amount`, from 0 to 1, of white in, keeping the alpha.
+    pub fn lighten(self, amount: f32) -> Self {
+        self.lerp(Self::rgb(255, 255, 255).with_alpha(self.0.a), amount)
+    }
+}
+
+/// An sRGB channel, from 0 to 1, in linear light.
+fn srgb_to_linear(channel: f32) -> f32 {
+    if channel <= 0.04045 {
+        channel / 12.92
+    } else {
+        ((channel + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(channel: f32) -> f32 {
+    if channel <= 0.0031308 {
+        channel * 12.92
+    } else {
+        1.055 * channel.powf(1. / 2.4) - 0.055
+    }
 }
 
 impl Default for Color {
@@ -381,3 +948,130 @@ impl From<Color> for femtovg::Color {
         value.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{Canvas, Color, Issued, PixelSnap};
+
+    #[test]
+    fn snaps_to_device_pixels() {
+        let snap = PixelSnap {
+            enabled: true,
+            scale_factor: 2.,
+        };
+
+        // 20.6 device pixels
+        assert_eq!(snap.apply(10.3), 10.5);
+        // 20.4 device pixels
+        assert_eq!(snap.apply(10.2), 10.);
+
+        let smooth = PixelSnap {
+            enabled: false,
+            ..snap
+        };
+
+        assert_eq!(smooth.apply(10.3), 10.3);
+    }
+
+    #[test]
+    fn snaps_rects_to_device_pixels() {
+        let snap = PixelSnap {
+            enabled: true,
+            scale_factor: 2.,
+        };
+
+        let (x, y, width, height) = snap.rect(10.3, 4.1, 20.2, 7.);
+        let device = [x, y, width, height].map(|logical| logical * snap.scale_factor);
+
+        // 20.6 to 61 and 8.2 to 22.2 device pixels
+        assert_eq!(device, [21., 8., 40., 14.]);
+
+        let smooth = PixelSnap {
+            enabled: false,
+            ..snap
+        };
+
+        assert_eq!(smooth.rect(10.3, 4.1, 20.2, 7.), (10.3, 4.1, 20.2, 7.));
+    }
+
+    #[test]
+    fn clear_fills_the_surface() {
+        let mut canvas = Canvas::recording(800, 600);
+        canvas.fill_rect(10., 10., 50., 50., Color::rgb(200, 130, 90));
+
+        canvas.clear(Color::rgb(0, 0, 0));
+
+        // Rects from before are drawn first, to be cleared over
+        assert_eq!(
+            canvas.issued,
+            [
+                Issued::Fill,
+                Issued::Clear {
+                    x: 0,
+                    y: 0,
+                    width: 800,
+                    height: 600
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn rgb_channel_order() {
+        let Color(color) = Color::rgb(10, 20, 30);
+        assert_eq!(color, femtovg::Color::rgb(10, 20, 30));
+        assert_eq!((color.r * 255.).round(), 10.);
+        assert_eq!((color.g * 255.).round(), 20.);
+        assert_eq!((color.b * 255.).round(), 30.);
+
+        let Color(color) = Color::rgba(10, 20, 30, 40);
+        assert_eq!(color, femtovg::Color::rgba(10, 20, 30, 40));
+    }
+
+    #[test]
+    fn hex_and_hsl() {
+        assert_eq!(Color::hex("#1e90ff").unwrap(), Color::rgb(30, 144, 255));
+        assert_eq!(Color::hex("#F0A").unwrap(), Color::rgb(255, 0, 170));
+        assert_eq!(
+            Color::hex("#1e90ff80").unwrap(),
+            Color::rgba(30, 144, 255, 128)
+        );
+
+        for malformed in [
+            "", "#", "1e90ff", "#1e90f", "#1e90ff8", "#1g90ff", "#+e90ff", "#1é90f",
+        ] {
+            assert!(Color::hex(malformed).is_err(), "{malformed:?}");
+        }
+
+        let channels = |Color(color): Color| {
+            [color.r, color.g, color.b, color.a].map(|channel| (channel * 255.).round())
+        };
+
+        assert_eq!(channels(Color::hsl(360., 1., 0.5)), [255., 0., 0., 255.]);
+        assert_eq!(channels(Color::hsl(120., 1., 0.25)), [0., 128., 0., 255.]);
+        assert_eq!(
+            channels(Color::hsla(240., 1., 0.5, 0.5)),
+            [0., 0., 255., 128.]
+        );
+    }
+
+    #[test]
+    fn lerp_ends_and_middle() {
+        let a = Color::rgba(200, 40, 10, 255);
+        let b = Color::hsla(200., 0.5, 0.3, 0.25);
+
+        assert_eq!(a.lerp(b, 0.), a);
+        assert_eq!(a.lerp(b, 1.), b);
+        assert_eq!(a.lerp(b, -3.), a);
+        assert_eq!(a.lerp(b, 3.), b);
+
+        // Linear light puts the middle of black and white well above 128
+        let Color(gray) = Color::rgb(0, 0, 0).lerp(Color::rgb(255, 255, 255), 0.5);
+        assert_eq!((gray.r * 255.).round(), 188.);
+
+        let Color(faded) = a.with_alpha(0.5).darken(1.);
+        assert_eq!((faded.r, faded.g, faded.b, faded.a), (0., 0., 0., 0.5));
+        assert_eq!(a.lighten(0.), a);
+        assert_eq!(a.lighten(1.), Color::rgb(255, 255, 255));
+    }
+}
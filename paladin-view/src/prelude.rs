@@ -1,7 +1,9 @@
 pub use crate::utils::*;
 pub use crate::{
-    elements::prelude::*, run, state::Reducer, state::State, Canvas, Color, Element, Layout, View,
-    Widget, WidgetEvent,
+    elements::prelude::*, fuzzy_score, request_redraw_at, run, run_with_config, run_with_fonts,
+    set_title, state::Mailbox, state::Reducer, state::State, task, title, AntialiasMode, AppConfig,
+    CancelToken, Canvas, Color, Debounced, DisplayList, Element, Layout, Scroll, ScrollKey, Task,
+    View, Widget, WidgetEvent,
 };
 pub use bevy_reflect::{GetTypeRegistration, Reflect};
 pub use paladin_view_macros::*;
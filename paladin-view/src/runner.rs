@@ -5,13 +5,13 @@ use miette::IntoDiagnostic;
 use winit::{
     application::ApplicationHandler,
     event::{ElementState, WindowEvent},
-    event_loop::EventLoop,
+    event_loop::{ControlFlow, EventLoop},
     window::WindowId,
 };
 
 use crate::{
     app::{App, AppEvent},
-    Canvas, GlobalEvent, Point,
+    wheel_pixels, Canvas, Color, GlobalEvent, Point,
 };
 
 pub(crate) struct Runner {
@@ -19,6 +19,8 @@ pub(crate) struct Runner {
     pub(crate) canvas: Canvas,
     pub(crate) windows: Windows,
     pub(crate) gl_context: glutin::context::PossiblyCurrentContext,
+    /// Pixels scrolled by a notch of the mouse wheel.
+    pub(crate) line_height: f32,
 }
 
 impl Runner {
@@ -51,13 +53,13 @@ impl ApplicationHandler<GlobalEvent> for Runner {
             ref mut canvas,
             windows,
             gl_context,
+            line_height,
         } = self;
 
         let Some(WindowData {
-            window,
-            surface,
+            native: Native { window, surface },
             mouse_pos,
-            parent: _,
+            ..
         }) = windows.get_mut(&window_id)
         else {
             dbg!("Missing window");
@@ -69,21 +71,19 @@ impl ApplicationHandler<GlobalEvent> for Runner {
                 gl_context
                     .make_current(&surface)
                     .expect("Making current to work");
-                canvas.inner.clear_rect(
-                    0,
-                    0,
-                    window.inner_size().width,
-                    window.inner_size().height,
-                    femtovg::Color::black(),
-                );
+                canvas.clear(Color::rgb(0, 0, 0));
 
                 app.event(AppEvent::Paint(window.inner_size()), canvas);
 
-                canvas.inner.flush();
+                canvas.draw().flush();
 
                 surface
                     .swap_buffers(&gl_context)
                     .expect("Swapping buffer to work");
+
+                if app.take_dirty() {
+                    window.request_redraw();
+                }
             }
 
             WindowEvent::CloseRequested => event_loop.exit(),
@@ -93,6 +93,14 @@ impl ApplicationHandler<GlobalEvent> for Runner {
                     x: position.x as u32,
                     y: position.y as u32,
                 };
+
+                let inspecting = app.cursor_moved(*mouse_pos);
+                app.event(AppEvent::MouseMoved(mouse_pos.x, mouse_pos.y), canvas);
+
+                // Hovered widgets ask to be painted again.
+                if inspecting || app.take_dirty() {
+                    window.request_redraw();
+                }
             }
             WindowEvent::MouseInput {
                 state: ElementState::Pressed,
@@ -106,13 +114,19 @@ impl ApplicationHandler<GlobalEvent> for Runner {
                 window.request_redraw();
             }
             WindowEvent::MouseWheel { delta, .. } => {
-                let _pixels = match delta {
-                    winit::event::MouseScrollDelta::LineDelta(_, delta) => -delta * 45.,
-                    // TODO probably invert this too
-                    winit::event::MouseScrollDelta::PixelDelta(delta) => delta.y as f32,
-                };
+                let (delta_x, delta_y) = wheel_pixels(delta, *line_height);
+
+                app.event(
+                    AppEvent::Scroll {
+                        x: mouse_pos.x,
+                        y: mouse_pos.y,
+                        delta_x,
+                        delta_y,
+                    },
+                    canvas,
+                );
 
-                // app.main();
+                window.request_redraw();
             }
             WindowEvent::KeyboardInput { event, .. } => {
                 app.event(AppEvent::Key(event), canvas);
@@ -120,6 +134,7 @@ impl ApplicationHandler<GlobalEvent> for Runner {
             }
             WindowEvent::Resized(size) => {
                 app.event(AppEvent::Resize(size), canvas);
+                canvas.snap.scale_factor = window.scale_factor() as f32;
                 canvas
                     .inner
                     .set_size(size.width, size.height, window.scale_factor() as f32);
@@ -129,22 +144,44 @@ impl ApplicationHandler<GlobalEvent> for Runner {
         }
     }
 
+    /// Wakes up for the frames widgets asked for with [request_redraw_at](crate::request_redraw_at).
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let now = Instant::now();
+
+        match crate::poll_frame(now) {
+            Some(at) if at <= now => {
+                self.windows.root().request_redraw();
+                event_loop.set_control_flow(ControlFlow::Wait);
+            }
+            Some(at) => event_loop.set_control_flow(ControlFlow::WaitUntil(at)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+
     fn user_event(&mut self, _event_loop: &winit::event_loop::ActiveEventLoop, event: GlobalEvent) {
         match event {
             GlobalEvent::Dirty { hint } => {
                 self.app.hint_dirty(hint);
-            } // FlareEvent::LspEvent(event) => {
-              //     app.event(LspEvent(event));
+            }
+            GlobalEvent::Redraw => self.windows.root().request_redraw(),
+            GlobalEvent::Messages => {
+                self.app.dirty();
+                self.windows.root().request_redraw();
+            }
+            event @ GlobalEvent::SetTitle(_) => self.windows.apply(event),
+            // FlareEvent::LspEvent(event) => {
+            //     app.event(LspEvent(event));
 
-              //     target.set_control_flow(ControlFlow::Poll);
-              // }
+            //     target.set_control_flow(ControlFlow::Poll);
+            // }
         }
     }
 }
 
-pub(crate) struct Windows {
+/// The open windows, the first one opened being the root.
+pub(crate) struct Windows<W = Native> {
     root: WindowId,
-    map: HashMap<WindowId, WindowData>,
+    map: HashMap<WindowId, WindowData<W>>,
 }
 
 impl Windows {
@@ -153,9 +190,21 @@ impl Windows {
         surface: glutin::surface::Surface<glutin::surface::WindowSurface>,
     ) -> Self {
         let id = window.id();
+        let title = window.title();
+
+        Self::with_root(id, Native { window, surface }, title)
+    }
+
+    pub fn root(&self) -> &winit::window::Window {
+        &self.map[&self.root].native.window
+    }
+}
+
+impl<W: Retitle> Windows<W> {
+    fn with_root(id: WindowId, native: W, title: String) -> Self {
         let window_data = WindowData {
-            window,
-            surface,
+            native,
+            title,
             mouse_pos: Point { x: 0, y: 0 },
             parent: None,
         };
@@ -165,22 +214,93 @@ impl Windows {
             map: HashMap::from([(id, window_data)]),
         }
     }
-    pub fn root(&self) -> &winit::window::Window {
-        &self.map[&self.root].window
-    }
 
-    pub fn iter(&self) -> impl Iterator<Item = (&WindowId, &WindowData)> {
+    pub fn iter(&self) -> impl Iterator<Item = (&WindowId, &WindowData<W>)> {
         self.map.iter()
     }
 
-    pub fn get_mut(&mut self, id: &WindowId) -> Option<&mut WindowData> {
+    pub fn get_mut(&mut self, id: &WindowId) -> Option<&mut WindowData<W>> {
         self.map.get_mut(id)
     }
+
+    /// Retitle a window, if it is still open.
+    pub fn set_title(&mut self, id: &WindowId, title: String) {
+        let Some(data) = self.map.get_mut(id) else {
+            return;
+        };
+
+        data.native.set_title(&title);
+        data.title = title;
+    }
+
+    /// Handle a [GlobalEvent] about the windows themselves, like [GlobalEvent::SetTitle] for the root one.
+    /// Any other event is left to the app.
+    pub fn apply(&mut self, event: GlobalEvent) {
+        if let GlobalEvent::SetTitle(title) = event {
+            let root = self.root;
+            self.set_title(&root, title);
+        }
+    }
 }
 
-pub(crate) struct WindowData {
-    pub(crate) window: winit::window::Window,
-    pub(crate) surface: glutin::surface::Surface<glutin::surface::WindowSurface>,
+pub(crate) struct WindowData<W = Native> {
+    pub(crate) native: W,
+    pub(crate) title: String,
     pub(crate) mouse_pos: Point,
     pub(crate) parent: Option<WindowId>,
 }
+
+/// The window on screen and the surface drawn to it.
+pub(crate) struct Native {
+    pub(crate) window: winit::window::Window,
+    pub(crate) surface: glutin::surface::Surface<glutin::surface::WindowSurface>,
+}
+
+/// What [Windows] changes on the window on screen when its data changes.
+pub(crate) trait Retitle {
+    fn set_title(&mut self, title: &str);
+}
+
+impl Retitle for Native {
+    fn set_title(&mut self, title: &str) {
+        self.window.set_title(title);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::window::WindowId;
+
+    use crate::GlobalEvent;
+
+    use super::{Retitle, Windows};
+
+    /// Remembers the titles it was given, in place of a window on screen.
+    #[derive(Default)]
+    struct Shown(Vec<String>);
+
+    impl Retitle for Shown {
+        fn set_title(&mut self, title: &str) {
+            self.0.push(title.to_string());
+        }
+    }
+
+    #[test]
+    fn set_title_event_retitles_root() {
+        let root = WindowId::from(1);
+        let mut windows = Windows::with_root(root, Shown::default(), "view".into());
+
+        windows.apply(GlobalEvent::SetTitle("main.rs \u{2014} modified".into()));
+
+        let data = windows.get_mut(&root).unwrap();
+        assert_eq!(data.title, "main.rs \u{2014} modified");
+        assert_eq!(data.native.0, ["main.rs \u{2014} modified"]);
+
+        // Other events are for the app
+        windows.apply(GlobalEvent::Redraw);
+        assert_eq!(
+            windows.get_mut(&root).unwrap().title,
+            "main.rs \u{2014} modified"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Scroll position of content taller than its viewport, and the keys that move it.
+
+use winit::{
+    event::MouseScrollDelta,
+    keyboard::{Key, NamedKey},
+};
+
+/// How much of the previous page stays visible after paging.
+const PAGE_OVERLAP: f32 = 40.;
+/// How far the arrow keys scroll, the same as a line of the mouse wheel.
+pub(crate) const LINE: f32 = 45.;
+
+/// How far a wheel or touchpad asks to scroll, in pixels, with `line_height` pixels to a notch of the wheel.
+/// Positive scrolls down and to the right, the opposite of winit.
+pub fn wheel_pixels(delta: MouseScrollDelta, line_height: f32) -> (f32, f32) {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => (-x * line_height, -y * line_height),
+        MouseScrollDelta::PixelDelta(delta) => (-delta.x as f32, -delta.y as f32),
+    }
+}
+
+/// A key that scrolls, see [ScrollKey::from_key].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScrollKey {
+    LineUp,
+    LineDown,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+}
+
+impl ScrollKey {
+    /// PageUp/PageDown, Ctrl+Home/End, and the up and down arrows.
+    /// The arrows only scroll when `arrows` is set, a text editor uses them to move its cursor instead.
+    pub fn from_key(key: &Key, ctrl: bool, arrows: bool) -> Option<Self> {
+        let Key::Named(key) = key else {
+            return None;
+        };
+
+        Some(match key {
+            NamedKey::PageUp => Self::PageUp,
+            NamedKey::PageDown => Self::PageDown,
+            NamedKey::Home if ctrl => Self::Top,
+            NamedKey::End if ctrl => Self::Bottom,
+            NamedKey::ArrowUp if arrows => Self::LineUp,
+            NamedKey::ArrowDown if arrows => Self::LineDown,
+            _ => return None,
+        })
+    }
+}
+
+/// `viewport` pixels of `content` are shown, starting `offset` pixels down.
+/// The offset is kept within the content.
+///
+/// ```
+/// # use paladin_view::prelude::*;
+///
+/// let mut scroll = Scroll::new(2400., 600.);
+/// scroll.key(ScrollKey::PageDown);
+///
+/// scrollbar(scroll.content(), scroll.viewport(), scroll.offset());
+///
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scroll {
+    offset: f32,
+    content: f32,
+    viewport: f32,
+}
+
+impl Scroll {
+    pub fn new(content: f32, viewport: f32) -> Self {
+        Self {
+            offset: 0.,
+            content,
+            viewport,
+        }
+    }
+
+    pub fn offset(&self) -> f32 {
+        self.offset
+    }
+
+    pub fn content(&self) -> f32 {
+        self.content
+    }
+
+    pub fn viewport(&self) -> f32 {
+        self.viewport
+    }
+
+    /// The largest offset, where the bottom of the content meets the bottom of the viewport.
+    pub fn max_offset(&self) -> f32 {
+        (self.content - self.viewport).max(0.)
+    }
+
+    /// Update the sizes after a relayout, keeping the offset in bounds.
+    pub fn resize(&mut self, content: f32, viewport: f32) {
+        self.content = content;
+        self.viewport = viewport;
+        self.scroll_to(self.offset);
+    }
+
+    /// Returns whether the offset changed.
+    pub fn scroll_to(&mut self, offset: f32) -> bool {
+        let offset = offset.clamp(0., self.max_offset());
+        let changed = offset != self.offset;
+
+        self.offset = offset;
+
+        changed
+    }
+
+    pub fn scroll_by(&mut self, delta: f32) -> bool {
+        self.scroll_to(self.offset + delta)
+    }
+
+    /// Scroll as little as possible so that `top..bottom` of the content is visible.
+    /// Content taller than the viewport is aligned to its top.
+    pub fn scroll_into_view(&mut self, top: f32, bottom: f32) -> bool {
+        if top < self.offset || bottom - top > self.viewport {
+            self.scroll_to(top)
+        } else if bottom > self.offset + self.viewport {
+            self.scroll_to(bottom - self.viewport)
+        } else {
+            false
+        }
+    }
+
+    /// Returns whether the offset changed.
+    pub fn key(&mut self, key: ScrollKey) -> bool {
+        let page = (self.viewport - PAGE_OVERLAP).max(LINE);
+
+        match key {
+            ScrollKey::LineUp => self.scroll_by(-LINE),
+            ScrollKey::LineDown => self.scroll_by(LINE),
+            ScrollKey::PageUp => self.scroll_by(-page),
+            ScrollKey::PageDown => self.scroll_by(page),
+            ScrollKey::Top => self.scroll_to(0.),
+            ScrollKey::Bottom => self.scroll_to(self.max_offset()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::{
+        dpi::PhysicalPosition,
+        event::MouseScrollDelta,
+        keyboard::{Key, NamedKey},
+    };
+
+    use super::{wheel_pixels, Scroll, ScrollKey};
+
+    #[test]
+    fn page_down_scrolls_a_viewport() {
+        let mut scroll = Scroll::new(2000., 600.);
+
+        let page_down = ScrollKey::from_key(&Key::Named(NamedKey::PageDown), false, false);
+        assert_eq!(page_down, Some(ScrollKey::PageDown));
+
+        assert!(scroll.key(ScrollKey::PageDown));
+        assert_eq!(scroll.offset(), 560.);
+        scroll.key(ScrollKey::PageDown);
+        assert_eq!(scroll.offset(), 1120.);
+
+        // Clamped to the end of the content
+        scroll.key(ScrollKey::PageDown);
+        assert_eq!(scroll.offset(), 1400.);
+        assert!(!scroll.key(ScrollKey::PageDown));
+
+        scroll.key(ScrollKey::Top);
+        assert_eq!(scroll.offset(), 0.);
+
+        // Home without Ctrl is left to the editor, as are the arrows.
+        assert_eq!(
+            ScrollKey::from_key(&Key::Named(NamedKey::Home), false, false),
+            None
+        );
+        assert_eq!(
+            ScrollKey::from_key(&Key::Named(NamedKey::End), true, false),
+            Some(ScrollKey::Bottom)
+        );
+        assert_eq!(
+            ScrollKey::from_key(&Key::Named(NamedKey::ArrowDown), false, false),
+            None
+        );
+
+        assert!(scroll.scroll_into_view(900., 950.));
+        assert_eq!(scroll.offset(), 350.);
+        assert!(!scroll.scroll_into_view(400., 450.));
+    }
+
+    #[test]
+    fn wheel_notches_and_pixels() {
+        // A notch towards the user scrolls down
+        assert_eq!(
+            wheel_pixels(MouseScrollDelta::LineDelta(0., -2.), 20.),
+            (0., 40.)
+        );
+        assert_eq!(
+            wheel_pixels(
+                MouseScrollDelta::PixelDelta(PhysicalPosition::new(3., 12.)),
+                20.
+            ),
+            (-3., -12.)
+        );
+    }
+}
@@ -1,4 +1,9 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    fmt,
+    ops::{Deref, DerefMut},
+    sync::Arc,
+    time::Instant,
+};
 
 use bevy_reflect::{reflect_trait, Reflect};
 use crossbeam::channel::TryRecvError;
@@ -11,11 +16,18 @@ pub(crate) trait StateTrait {
     fn init(&mut self);
     fn reuse(&mut self, other: &mut dyn Reflect);
     fn process(&mut self);
+    fn frame(&mut self, now: Instant) -> bool;
 }
 
 /// A state reducer. It is generic over its message and is mostly used by [State] to handle a message sent to a given view.
 pub trait Reducer<M> {
     fn reduce(&mut self, message: M);
+
+    /// Called on every frame, for work waiting on time rather than a message, like a [Debounced](crate::Debounced) one.
+    /// Returns whether the state changed, which rebuilds its view. Ask for the frame with [request_redraw_at](crate::request_redraw_at).
+    fn frame(&mut self, _now: Instant) -> bool {
+        false
+    }
 }
 
 #[derive(Reflect, Debug, Clone)]
@@ -55,7 +67,48 @@ pub struct State<M: Clone + 'static, S: Reducer<M> + 'static> {
     inner: MessageInner<M>,
     #[reflect(ignore)]
     #[reflect(default = "create_state_fake")]
-    create_state: fn() -> S,
+    create_state: Create<M, S>,
+}
+
+/// How a [State] creates its reducer once its view is mounted.
+#[derive(Clone)]
+enum Create<M, S> {
+    Plain(fn() -> S),
+    WithMailbox(Arc<dyn Fn(Mailbox<M>) -> S + Send + Sync>),
+}
+
+impl<M, S> fmt::Debug for Create<M, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Create::Plain(_) => f.write_str("Plain"),
+            Create::WithMailbox(_) => f.write_str("WithMailbox"),
+        }
+    }
+}
+
+/// Sends messages to a [State] from outside its view, like a thread waiting on a language server.
+/// The view is rebuilt once the app gets to them, which it is woken up for.
+#[derive(Debug)]
+pub struct Mailbox<M> {
+    tx: crossbeam::channel::Sender<M>,
+}
+
+impl<M> Clone for Mailbox<M> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<M> Mailbox<M> {
+    pub fn send(&self, message: M) {
+        if let Err(err) = self.tx.send(message) {
+            dbg!("WARN: ", err);
+        }
+
+        crate::process_messages();
+    }
 }
 
 impl Reducer<()> for () {
@@ -66,7 +119,7 @@ pub(crate) trait Message: Clone + 'static {}
 
 impl<T: Clone + 'static> Message for T {}
 
-fn create_state_fake<S>() -> fn() -> S {
+fn create_state_fake<M, S>() -> Create<M, S> {
     panic!()
 }
 
@@ -81,14 +134,23 @@ impl<M: Message, S: Reducer<M> + 'static> StateTrait for State<M, S> {
         }
     }
 
+    fn frame(&mut self, now: Instant) -> bool {
+        self.state.as_mut().is_some_and(|state| state.frame(now))
+    }
+
     fn init(&mut self) {
-        self.state = Some((self.create_state)());
+        self.state = Some(match &self.create_state {
+            Create::Plain(create) => create(),
+            Create::WithMailbox(create) => create(self.mailbox()),
+        });
     }
 
     fn reuse(&mut self, other: &mut dyn Reflect) {
         let selfy = other.as_any_mut().downcast_mut::<Self>().unwrap();
 
         std::mem::swap(&mut self.state, &mut selfy.state);
+        // The reducer may have handed out a mailbox of the old channel.
+        std::mem::swap(&mut self.inner, &mut selfy.inner);
     }
 }
 
@@ -111,7 +173,7 @@ impl<M: Message, S: Default + Reducer<M> + 'static> Default for State<M, S> {
         Self {
             inner: MessageInner::default(),
             state: None,
-            create_state: Default::default,
+            create_state: Create::Plain(Default::default),
         }
     }
 }
@@ -134,7 +196,24 @@ impl<M: Clone + 'static, S: Reducer<M>> State<M, S> {
         Self {
             inner: MessageInner::default(),
             state: None,
-            create_state: f,
+            create_state: Create::Plain(f),
+        }
+    }
+
+    /// Like [State::create_state], handing the reducer a [Mailbox] for messages sent from elsewhere,
+    /// like the results of background work it starts. `f` may capture what the view was given, like its props.
+    pub fn create_state_with(f: impl Fn(Mailbox<M>) -> S + Send + Sync + 'static) -> Self {
+        Self {
+            inner: MessageInner::default(),
+            state: None,
+            create_state: Create::WithMailbox(Arc::new(f)),
+        }
+    }
+
+    /// Sends messages from outside the view, see [Mailbox].
+    pub fn mailbox(&self) -> Mailbox<M> {
+        Mailbox {
+            tx: self.inner.tx.clone(),
         }
     }
 
@@ -149,6 +228,17 @@ impl<M: Clone + 'static, S: Reducer<M>> State<M, S> {
         }
     }
 
+    /// Like [State::then_send], for messages only known when sending, like the text of an input.
+    pub(crate) fn sender(&self) -> impl Fn(M) + 'static {
+        let sender = self.inner.tx.clone();
+
+        move |message| {
+            if let Err(err) = sender.send(message) {
+                dbg!("WARN: ", err);
+            }
+        }
+    }
+
     fn recv(&self) -> Option<M> {
         self.inner
             .rx
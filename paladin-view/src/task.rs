@@ -0,0 +1,85 @@
+//! Background work that stops once the widget it was started for goes away.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::{on_mount, Effect};
+
+/// Checked by background work to stop early, see [Task].
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Acquire)
+    }
+
+    fn cancel(&self) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+/// A handle to background work. Aborting it, or dropping it, cancels the work's [CancelToken],
+/// which stops at its next check. Nothing is interrupted mid-way.
+#[derive(Debug, Default)]
+pub struct Task {
+    token: CancelToken,
+}
+
+impl Task {
+    /// Run `work` on a new thread.
+    pub fn spawn(work: impl FnOnce(CancelToken) + Send + 'static) -> Self {
+        let task = Self::default();
+        let token = task.token();
+
+        std::thread::spawn(move || work(token));
+
+        task
+    }
+
+    /// A token for work started elsewhere, like a request handled by another thread.
+    pub fn token(&self) -> CancelToken {
+        self.token.clone()
+    }
+
+    pub fn abort(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+}
+
+impl Drop for Task {
+    fn drop(&mut self) {
+        self.abort();
+    }
+}
+
+/// Run `work` on a new thread once mounted, cancelling it when unmounted.
+///
+/// ```
+/// # use paladin_view::prelude::*;
+///
+/// fragment((
+///     task(|token| {
+///         while !token.is_cancelled() {
+///             // Load the next chunk..
+///         }
+///     }),
+///     "Loading",
+/// ));
+///
+/// ```
+pub fn task(work: impl FnOnce(CancelToken) + Send + 'static) -> Effect {
+    let task = Task::default();
+    let token = task.token();
+
+    on_mount(move || {
+        std::thread::spawn(move || work(token));
+    })
+    .on_unmount(move || task.abort())
+}
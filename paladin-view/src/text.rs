@@ -1,9 +1,21 @@
-use cosmic_text::{CacheKey, FontSystem, SubpixelBin};
+use cosmic_text::{
+    fontdb, Attrs, AttrsList, AttrsOwned, BufferLine, CacheKey, Family, FontSystem, LineEnding,
+    Metrics, Shaping, SubpixelBin, Wrap,
+};
 use femtovg::{
     Atlas, Canvas, DrawCommand, ErrorKind, GlyphDrawCommands, ImageFlags, ImageId, ImageSource,
     Quad, Renderer,
 };
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    sync::Mutex,
+};
+
+use miette::IntoDiagnostic;
+
+use crate::PixelSnap;
 
 use imgref::{Img, ImgRef};
 use rgb::RGBA8;
@@ -14,19 +26,167 @@ use swash::zeno::{Format, Vector};
 const GLYPH_PADDING: u32 = 1;
 const GLYPH_MARGIN: u32 = 1;
 const TEXTURE_SIZE: usize = 512;
+/// Glyph atlases kept before the least recently used one is cleared for new glyphs.
+const MAX_ATLASES: usize = 8;
+
+/// Faces loaded into every font system, ahead of the ones given to [crate::run_with_fonts].
+const BUNDLED_FONTS: &[&[u8]] = &[include_bytes!("../../assets/JetBrainsMono-Regular.ttf")];
+
+/// What text falls back to, see [RenderCache::set_fallback_family] and [RenderCache::set_fallback_fonts].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub(crate) struct Fallbacks {
+    /// For text asking for a family that isn't loaded.
+    pub(crate) family: Option<String>,
+    /// Tried in order for characters the requested family has no glyph for.
+    pub(crate) fonts: Vec<String>,
+}
+
+/// Families found missing, each warned about once.
+static MISSING_FAMILIES: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// `attrs` for `text`, with families that aren't loaded replaced by the fallback family,
+/// and characters missing from their family set to the first of the fallback fonts that has them.
+pub(crate) fn with_fallback_fonts(
+    font_system: &mut FontSystem,
+    fallbacks: &Fallbacks,
+    text: &str,
+    attrs: AttrsList,
+) -> AttrsList {
+    let attrs = match &fallbacks.family {
+        Some(fallback) => with_loaded_families(font_system, fallback, attrs),
+        None => attrs,
+    };
 
-pub fn init_cache() -> RenderCache {
+    if fallbacks.fonts.is_empty() {
+        return attrs;
+    }
+
+    with_fallbacks(font_system, &fallbacks.fonts, text, attrs)
+}
+
+/// `attrs` with the families that aren't loaded replaced by `fallback`.
+fn with_loaded_families(font_system: &FontSystem, fallback: &str, attrs: AttrsList) -> AttrsList {
+    let mut with = AttrsList::new(loaded_family(font_system, fallback, attrs.defaults()));
+
+    for (range, span) in attrs.spans() {
+        with.add_span(
+            range.clone(),
+            loaded_family(font_system, fallback, span.as_attrs()),
+        );
+    }
+
+    with
+}
+
+fn loaded_family<'a>(font_system: &FontSystem, fallback: &'a str, attrs: Attrs<'a>) -> Attrs<'a> {
+    let Family::Name(name) = attrs.family else {
+        return attrs;
+    };
+
+    let is_loaded = font_system
+        .db()
+        .faces()
+        .any(|face| face.families.iter().any(|(family, _)| family == name));
+
+    if is_loaded {
+        return attrs;
+    }
+
+    if MISSING_FAMILIES.lock().unwrap().insert(name.to_string()) {
+        dbg!(
+            "WARN: font family not loaded, using the fallback",
+            name,
+            fallback
+        );
+    }
+
+    attrs.family(Family::Name(fallback))
+}
+
+/// Like [with_fallback_fonts], trying `families`.
+fn with_fallbacks(
+    font_system: &mut FontSystem,
+    families: &[String],
+    text: &str,
+    attrs: AttrsList,
+) -> AttrsList {
+    let mut faces = HashMap::new();
+    let mut has_glyph = |font_system: &mut FontSystem, attrs: Attrs, c: char| {
+        let id = *faces.entry(AttrsOwned::new(attrs)).or_insert_with(|| {
+            font_system.db().query(&fontdb::Query {
+                families: &[attrs.family],
+                weight: attrs.weight,
+                stretch: attrs.stretch,
+                style: attrs.style,
+            })
+        });
+
+        id.and_then(|id| font_system.get_font(id))
+            .is_some_and(|font| font.rustybuzz().glyph_index(c).is_some())
+    };
+
+    let mut with = attrs.clone();
+    let mut previous = None;
+
+    for (byte, c) in text.char_indices() {
+        let span = attrs.get_span(byte);
+
+        let fallback = if joins_previous(c) {
+            previous
+        } else if has_glyph(font_system, span, c) {
+            None
+        } else {
+            (0..families.len())
+                .find(|idx| has_glyph(font_system, span.family(Family::Name(&families[*idx])), c))
+        };
+
+        if let Some(idx) = fallback {
+            with.add_span(
+                byte..byte + c.len_utf8(),
+                span.family(Family::Name(&families[idx])),
+            );
+        }
+
+        previous = fallback;
+    }
+
+    with
+}
+
+/// Whether `c` is shaped together with the character before it, so must use the same font.
+/// Combining marks, joiners, variation selectors and emoji modifiers.
+fn joins_previous(c: char) -> bool {
+    matches!(
+        c,
+        '\u{300}'..='\u{36f}'
+            | '\u{200c}'
+            | '\u{200d}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{1f3fb}'..='\u{1f3ff}'
+            | '\u{e0020}'..='\u{e007f}'
+    )
+}
+
+pub fn init_cache(fonts: Vec<Vec<u8>>) -> RenderCache {
     // Text stuff
     let mut font_system = FontSystem::new();
 
-    let font = include_bytes!("../../assets/JetBrainsMono-Regular.ttf").to_vec();
-    font_system.db_mut().load_font_data(font);
+    load_fonts(&mut font_system, fonts);
+
+    RenderCache::new(font_system)
+}
+
+/// Load the bundled faces, then `fonts`. Faces of one family are told apart by their weight and style,
+/// so a bold file of the default family is picked for bold text.
+pub(crate) fn load_fonts(font_system: &mut FontSystem, fonts: Vec<Vec<u8>>) {
+    let db = font_system.db_mut();
+
+    for font in BUNDLED_FONTS {
+        db.load_font_data(font.to_vec());
+    }
 
-    RenderCache {
-        font_system,
-        scale_context: Default::default(),
-        rendered_glyphs: Default::default(),
-        glyph_textures: Default::default(),
+    for font in fonts {
+        db.load_font_data(font);
     }
 }
 
@@ -40,6 +200,8 @@ pub struct RenderedGlyph {
     atlas_x: u32,
     atlas_y: u32,
     color_glyph: bool,
+    /// The last frame the glyph was drawn in.
+    last_used: u64,
 }
 
 pub struct FontTexture {
@@ -49,17 +211,540 @@ pub struct FontTexture {
 
 pub struct RenderCache {
     scale_context: ScaleContext,
-    rendered_glyphs: HashMap<CacheKey, Option<RenderedGlyph>>,
+    rendered_glyphs: HashMap<GlyphKey, Option<RenderedGlyph>>,
     glyph_textures: Vec<FontTexture>,
+    pub(crate) shapes: ShapeCache,
+    pub(crate) measures: MeasureCache,
     pub font_system: FontSystem,
+    pub(crate) hinting: bool,
+    pub(crate) antialias: AntialiasMode,
+    /// Counts frames, to tell which glyphs were drawn recently.
+    frame: u64,
+    pub(crate) max_atlases: usize,
+    pub(crate) fallbacks: Fallbacks,
+}
+
+/// How the edges of glyphs are smoothed, see [RenderCache::set_antialias].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum AntialiasMode {
+    /// Coverage for each of the red, green and blue subpixels, sharper on LCD screens.
+    #[default]
+    Subpixel,
+    /// Grayscale, one coverage for the whole pixel. It looks the same on any display, and in screenshots.
+    Alpha,
+}
+
+/// A rasterized glyph, told apart from the same glyph rendered with different hinting or antialiasing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct GlyphKey {
+    glyph: CacheKey,
+    hinted: bool,
+    antialias: AntialiasMode,
+}
+
+/// What decides how plain text is shaped. Rich text, with spans, is not cached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ShapeKey {
+    text: u64,
+    font_size: u32,
+    line_height: u32,
+    wrap: u8,
+    width: u32,
+    height: u32,
+}
+
+impl ShapeKey {
+    pub(crate) fn new(
+        text: &[(String, AttrsList)],
+        metrics: Metrics,
+        wrap: Wrap,
+        size: crate::Size,
+    ) -> Option<Self> {
+        let mut hasher = DefaultHasher::new();
+
+        for (line, attrs) in text {
+            if !attrs.spans().is_empty() {
+                return None;
+            }
+
+            line.hash(&mut hasher);
+            AttrsOwned::new(attrs.defaults()).hash(&mut hasher);
+        }
+
+        Some(Self {
+            text: hasher.finish(),
+            font_size: metrics.font_size.to_bits(),
+            line_height: metrics.line_height.to_bits(),
+            wrap: wrap_id(wrap),
+            width: size.width,
+            height: size.height,
+        })
+    }
+}
+
+/// What decides the size of measured text, plain or rich, at the width it may take.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct MeasureKey {
+    text: u64,
+    font_size: u32,
+    line_height: u32,
+    wrap: u8,
+    max_width: Option<u32>,
+}
+
+impl MeasureKey {
+    pub(crate) fn new<'a>(
+        text: impl IntoIterator<Item = (&'a str, &'a AttrsList)>,
+        fallbacks: &Fallbacks,
+        metrics: Metrics,
+        wrap: Wrap,
+        max_width: Option<f32>,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+
+        for (line, attrs) in text {
+            line.hash(&mut hasher);
+            AttrsOwned::new(attrs.defaults()).hash(&mut hasher);
+            attrs.spans().hash(&mut hasher);
+        }
+        fallbacks.hash(&mut hasher);
+
+        Self {
+            text: hasher.finish(),
+            font_size: metrics.font_size.to_bits(),
+            line_height: metrics.line_height.to_bits(),
+            wrap: wrap_id(wrap),
+            max_width: max_width.map(f32::to_bits),
+        }
+    }
+}
+
+fn wrap_id(wrap: Wrap) -> u8 {
+    match wrap {
+        Wrap::None => 0,
+        Wrap::Glyph => 1,
+        Wrap::Word => 2,
+        Wrap::WordOrGlyph => 3,
+    }
+}
+
+/// Sizes of the text measured for layout, kept as long as it is measured every frame.
+#[derive(Default)]
+pub(crate) struct MeasureCache {
+    /// Each size, with the frame it was last measured in.
+    measured: HashMap<MeasureKey, (taffy::Size<f32>, u64)>,
+    /// Sizes measured rather than looked up, since the cache was created.
+    #[cfg(test)]
+    misses: usize,
+}
+
+impl MeasureCache {
+    pub(crate) fn get(&mut self, key: &MeasureKey, frame: u64) -> Option<taffy::Size<f32>> {
+        let (size, last_used) = self.measured.get_mut(key)?;
+        *last_used = frame;
+
+        Some(*size)
+    }
+
+    pub(crate) fn insert(&mut self, key: MeasureKey, size: taffy::Size<f32>, frame: u64) {
+        #[cfg(test)]
+        {
+            self.misses += 1;
+        }
+
+        self.measured.insert(key, (size, frame));
+    }
+
+    /// Forget the sizes not measured in `frame`.
+    fn retain_frame(&mut self, frame: u64) {
+        self.measured
+            .retain(|_, (_, last_used)| *last_used == frame);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+/// Text shaped this frame, for [Text](crate::Text) widgets showing the same thing to copy instead of shaping again.
+/// Copies, not shares, so each widget keeps its own scroll.
+#[derive(Default)]
+pub(crate) struct ShapeCache {
+    shaped: HashMap<ShapeKey, cosmic_text::Buffer>,
+    /// Buffers shaped rather than copied, since the cache was created.
+    #[cfg(test)]
+    misses: usize,
+}
+
+impl ShapeCache {
+    /// Forget the shaped text, done every frame.
+    pub(crate) fn clear(&mut self) {
+        self.shaped.clear();
+    }
+
+    pub(crate) fn get(&self, key: &ShapeKey) -> Option<&cosmic_text::Buffer> {
+        self.shaped.get(key)
+    }
+
+    pub(crate) fn insert(&mut self, key: ShapeKey, buffer: cosmic_text::Buffer) {
+        #[cfg(test)]
+        {
+            self.misses += 1;
+        }
+
+        self.shaped.insert(key, buffer);
+    }
+
+    #[cfg(test)]
+    pub(crate) fn misses(&self) -> usize {
+        self.misses
+    }
+}
+
+/// The size `buffer` takes once shaped to fit `max_width`, the widest line by the height of all of them.
+/// Both are rounded up to whole pixels, so the text fits.
+pub(crate) fn measured_size(
+    font_system: &mut FontSystem,
+    buffer: &mut cosmic_text::Buffer,
+    max_width: Option<f32>,
+) -> taffy::Size<f32> {
+    let mut buffer = buffer.borrow_with(font_system);
+    buffer.set_size(max_width, None);
+    buffer.shape_until_scroll(false);
+
+    let (width, height) = buffer
+        .layout_runs()
+        .fold((0_f32, 0_f32), |(width, height), run| {
+            (
+                width.max(run.line_w),
+                height.max(run.line_top + run.line_height),
+            )
+        });
+
+    taffy::Size {
+        width: width.ceil(),
+        height: height.ceil(),
+    }
 }
 
 impl RenderCache {
-    pub fn fill_buffer_to_draw_commands<T: Renderer>(
+    pub(crate) fn new(font_system: FontSystem) -> Self {
+        Self {
+            font_system,
+            scale_context: Default::default(),
+            rendered_glyphs: Default::default(),
+            glyph_textures: Default::default(),
+            shapes: Default::default(),
+            measures: Default::default(),
+            hinting: true,
+            antialias: AntialiasMode::default(),
+            frame: 0,
+            max_atlases: MAX_ATLASES,
+            fallbacks: Fallbacks::default(),
+        }
+    }
+
+    /// Called before drawing each frame.
+    pub(crate) fn start_frame(&mut self) {
+        // Shaped text is only shared within a frame.
+        self.shapes.clear();
+        // The layout for this frame is done, sizes it didn't need are stale.
+        self.measures.retain_frame(self.frame);
+        self.frame += 1;
+    }
+
+    /// The frame being laid out and drawn.
+    pub(crate) fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    /// Keep at most `atlases` glyph textures, each `TEXTURE_SIZE` squared RGBA, 8 by default.
+    ///
+    /// Once they are full, the one whose glyphs were drawn least recently is cleared for new glyphs,
+    /// which are rendered again when they are next drawn. Glyphs drawn in the current frame are never evicted,
+    /// a frame with more glyphs than fit still gets a new texture.
+    pub fn set_max_atlases(&mut self, atlases: usize) {
+        self.max_atlases = atlases.max(1);
+    }
+
+    /// The atlas to clear for new glyphs, if there are as many as allowed.
+    /// It is the one whose most recently drawn glyph is the oldest, as long as none was drawn in this frame.
+    fn least_recently_used_atlas(&self) -> Option<usize> {
+        if self.glyph_textures.len() < self.max_atlases {
+            return None;
+        }
+
+        let mut last_used = vec![0; self.glyph_textures.len()];
+        for rendered in self.rendered_glyphs.values().flatten() {
+            let used = &mut last_used[rendered.texture_index];
+            *used = (*used).max(rendered.last_used);
+        }
+
+        last_used
+            .into_iter()
+            .enumerate()
+            .filter(|(_, used)| *used < self.frame)
+            .min_by_key(|(_, used)| *used)
+            .map(|(index, _)| index)
+    }
+
+    /// Forget the glyphs in the atlas at `index` and make its space free again.
+    fn evict_atlas(&mut self, index: usize) {
+        self.rendered_glyphs.retain(
+            |_, rendered| !matches!(rendered, Some(rendered) if rendered.texture_index == index),
+        );
+        self.glyph_textures[index]
+            .atlas
+            .reset(TEXTURE_SIZE, TEXTURE_SIZE);
+    }
+
+    /// Fit glyph outlines to the pixel grid when rasterizing them. Enabled by default.
+    ///
+    /// Hinted text is crisper at small sizes, but its stems snap to whole pixels, which can look uneven
+    /// in large or HiDPI text. Unhinted glyphs keep the shapes of the font and are smoother at those sizes.
+    /// Glyphs are cached for each setting, so toggling it renders them again.
+    pub fn set_hinting(&mut self, enabled: bool) {
+        self.hinting = enabled;
+    }
+
+    /// How much room `text` takes when shaped with `attrs` at font `size`, like a [Text](crate::Text) of that size,
+    /// wrapping at word boundaries to fit `max_width` if there is one.
+    ///
+    /// The width is that of the widest line. Both are rounded up, so a widget of this size fits the text.
+    pub fn measure(
+        &mut self,
+        text: &str,
+        attrs: Attrs,
+        size: f32,
+        max_width: Option<f32>,
+    ) -> crate::Size {
+        let mut buffer = cosmic_text::Buffer::new_empty(Metrics::new(size, size));
+
+        for line in text.lines() {
+            let attrs = with_fallback_fonts(
+                &mut self.font_system,
+                &self.fallbacks,
+                line,
+                AttrsList::new(attrs),
+            );
+            buffer.lines.push(BufferLine::new(
+                line,
+                LineEnding::default(),
+                attrs,
+                Shaping::Advanced,
+            ));
+        }
+
+        let size = measured_size(&mut self.font_system, &mut buffer, max_width);
+
+        crate::Size {
+            width: size.width as u32,
+            height: size.height as u32,
+        }
+    }
+
+    /// Smooth glyph edges for each subpixel, the default, or with grayscale coverage.
+    /// Glyphs are cached for each mode, so switching renders them again.
+    pub fn set_antialias(&mut self, mode: AntialiasMode) {
+        self.antialias = mode;
+    }
+
+    /// Use `family` for text asking for a family that isn't loaded, warning once about each missing one.
+    ///
+    /// Without it, cosmic-text quietly picks another loaded face. It applies to the [Text](crate::Text)s
+    /// the app lays out, and to [RenderCache::measure], from their next layout.
+    pub fn set_fallback_family(&mut self, family: impl Into<String>) {
+        self.fallbacks.family = Some(family.into());
+    }
+
+    /// Use `families`, in order, for characters missing from the family text asks for.
+    ///
+    /// Without any, cosmic-text picks from the system fonts by script, which differs between machines.
+    /// A character none of them has a glyph for is still left to cosmic-text, drawing as a missing glyph box
+    /// if no system font has it either. Like [RenderCache::set_fallback_family], it applies from the next layout.
+    pub fn set_fallback_fonts(&mut self, families: impl IntoIterator<Item = impl Into<String>>) {
+        self.fallbacks.fonts = families.into_iter().map(Into::into).collect();
+    }
+
+    /// Load the faces in a font file's contents. Text can use them from its next layout.
+    pub fn load_font_data(&mut self, data: Vec<u8>) {
+        self.font_system.db_mut().load_font_data(data);
+    }
+
+    /// Like [RenderCache::load_font_data], reading the file at `path`.
+    pub fn load_font_file(&mut self, path: &Path) -> crate::Result<()> {
+        self.font_system
+            .db_mut()
+            .load_font_file(path)
+            .into_diagnostic()
+    }
+
+    /// Names of the loaded families, sorted, for the `font` of a [Text](crate::Text).
+    pub fn list_families(&self) -> Vec<String> {
+        self.font_system
+            .db()
+            .faces()
+            .flat_map(|face| face.families.iter().map(|(name, _)| name.clone()))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    }
+
+    fn glyph_key(&self, glyph: CacheKey) -> GlyphKey {
+        GlyphKey {
+            glyph,
+            hinted: self.hinting,
+            antialias: self.antialias,
+        }
+    }
+
+    /// Rasterize a glyph and upload it to an atlas, `None` if it has nothing to draw.
+    fn render_glyph<T: Renderer>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        cache_key: CacheKey,
+    ) -> Option<RenderedGlyph> {
+        // do the actual rasterization
+        // Not drawn if the face can't be read
+        let font = self.font_system.get_font(cache_key.font_id)?;
+        let mut scaler = self
+            .scale_context
+            .builder(font.as_swash())
+            .size(f32::from_bits(cache_key.font_size_bits))
+            .hint(self.hinting)
+            .build();
+
+        let offset = Vector::new(cache_key.x_bin.as_float(), cache_key.y_bin.as_float());
+
+        let rendered = Render::new(&[
+            Source::ColorOutline(0),
+            Source::ColorBitmap(StrikeWith::BestFit),
+            Source::Outline,
+        ])
+        .format(match self.antialias {
+            AntialiasMode::Subpixel => Format::Subpixel,
+            AntialiasMode::Alpha => Format::Alpha,
+        })
+        .offset(offset)
+        .render(&mut scaler, cache_key.glyph_id);
+
+        // upload it to the GPU
+        rendered.map(|rendered| {
+            // pick an atlas texture for our glyph
+            let content_w = rendered.placement.width as usize;
+            let content_h = rendered.placement.height as usize;
+            let alloc_w = rendered.placement.width + (GLYPH_MARGIN + GLYPH_PADDING) * 2;
+            let alloc_h = rendered.placement.height + (GLYPH_MARGIN + GLYPH_PADDING) * 2;
+            let used_w = rendered.placement.width + GLYPH_PADDING * 2;
+            let used_h = rendered.placement.height + GLYPH_PADDING * 2;
+            let (texture_index, atlas_alloc_x, atlas_alloc_y) =
+                self.allocate(canvas, alloc_w as usize, alloc_h as usize);
+
+            let atlas_used_x = atlas_alloc_x as u32 + GLYPH_MARGIN;
+            let atlas_used_y = atlas_alloc_y as u32 + GLYPH_MARGIN;
+            let atlas_content_x = atlas_alloc_x as u32 + GLYPH_MARGIN + GLYPH_PADDING;
+            let atlas_content_y = atlas_alloc_y as u32 + GLYPH_MARGIN + GLYPH_PADDING;
+
+            // Both kinds of masks are drawn as alpha masks, which take their coverage from the red channel.
+            // Grayscale masks have one byte per pixel, subpixel ones and color glyphs four.
+            let mut src_buf = Vec::with_capacity(content_w * content_h);
+            match rendered.content {
+                Content::Mask => {
+                    for chunk in rendered.data.chunks_exact(1) {
+                        src_buf.push(RGBA8::new(chunk[0], 0, 0, 0));
+                    }
+                }
+                Content::Color | Content::SubpixelMask => {
+                    for chunk in rendered.data.chunks_exact(4) {
+                        src_buf.push(RGBA8::new(chunk[0], chunk[1], chunk[2], chunk[3]));
+                    }
+                }
+            }
+            canvas
+                .update_image::<ImageSource>(
+                    self.glyph_textures[texture_index].image_id,
+                    ImgRef::new(&src_buf, content_w, content_h).into(),
+                    atlas_content_x as usize,
+                    atlas_content_y as usize,
+                )
+                .unwrap();
+
+            RenderedGlyph {
+                texture_index,
+                width: used_w,
+                height: used_h,
+                offset_x: rendered.placement.left,
+                offset_y: rendered.placement.top,
+                atlas_x: atlas_used_x,
+                atlas_y: atlas_used_y,
+                color_glyph: matches!(rendered.content, Content::Color),
+                last_used: self.frame,
+            }
+        })
+    }
+
+    /// Find room for a `width` by `height` rect in an atlas.
+    /// When they are all full, the least recently used one is cleared, or a new one is made if none can be.
+    fn allocate<T: Renderer>(
+        &mut self,
+        canvas: &mut Canvas<T>,
+        width: usize,
+        height: usize,
+    ) -> (usize, usize, usize) {
+        let free = self
+            .glyph_textures
+            .iter_mut()
+            .enumerate()
+            .find_map(|(index, texture)| {
+                let (x, y) = texture.atlas.add_rect(width, height)?;
+                Some((index, x, y))
+            });
+        if let Some(free) = free {
+            return free;
+        }
+
+        let empty = vec![RGBA8::new(0, 0, 0, 0); TEXTURE_SIZE * TEXTURE_SIZE];
+        // TODO error handling
+        let texture_index = if let Some(index) = self.least_recently_used_atlas() {
+            self.evict_atlas(index);
+            // The padding around glyphs is drawn too, so the old ones can't be left behind
+            canvas
+                .update_image::<ImageSource>(
+                    self.glyph_textures[index].image_id,
+                    ImgRef::new(&empty, TEXTURE_SIZE, TEXTURE_SIZE).into(),
+                    0,
+                    0,
+                )
+                .unwrap();
+            index
+        } else {
+            let image_id = canvas
+                .create_image(
+                    Img::new(empty, TEXTURE_SIZE, TEXTURE_SIZE).as_ref(),
+                    ImageFlags::empty(),
+                )
+                .unwrap();
+            self.glyph_textures.push(FontTexture {
+                atlas: Atlas::new(TEXTURE_SIZE, TEXTURE_SIZE),
+                image_id,
+            });
+            self.glyph_textures.len() - 1
+        };
+
+        let (x, y) = self.glyph_textures[texture_index]
+            .atlas
+            .add_rect(width, height)
+            .unwrap();
+        (texture_index, x, y)
+    }
+
+    pub(crate) fn fill_buffer_to_draw_commands<T: Renderer>(
         &mut self,
         canvas: &mut Canvas<T>,
         buffer: &cosmic_text::Buffer,
         position: (f32, f32),
+        snap: PixelSnap,
     ) -> Result<Vec<(cosmic_text::Color, GlyphDrawCommands)>, ErrorKind> {
         let mut alpha_cmd_map: HashMap<cosmic_text::Color, HashMap<usize, DrawCommand>> =
             HashMap::default();
@@ -72,134 +757,37 @@ impl RenderCache {
                 let position_x = position.0 + cache_key.x_bin.as_float();
                 let position_y = position.1 + cache_key.y_bin.as_float();
 
-                let (position_x, subpixel_x) = SubpixelBin::new(position_x);
-                let (position_y, subpixel_y) = SubpixelBin::new(position_y);
+                // Snapped glyphs are drawn at whole pixels, so there is no subpixel offset to render
+                let ((position_x, subpixel_x), (position_y, subpixel_y)) = if snap.enabled {
+                    (
+                        (snap.apply(position_x), SubpixelBin::Zero),
+                        (snap.apply(position_y), SubpixelBin::Zero),
+                    )
+                } else {
+                    let (x, subpixel_x) = SubpixelBin::new(position_x);
+                    let (y, subpixel_y) = SubpixelBin::new(position_y);
+
+                    ((x as f32, subpixel_x), (y as f32, subpixel_y))
+                };
 
                 cache_key.x_bin = subpixel_x;
                 cache_key.y_bin = subpixel_y;
-                // perform cache lookup for rendered glyph
-                let Some(rendered) = self.rendered_glyphs.entry(cache_key).or_insert_with(|| {
-                    // ...or insert it
-
-                    // do the actual rasterization
-                    let font = self
-                        .font_system
-                        .get_font(cache_key.font_id)
-                        .expect("Somehow shaped a font that doesn't exist");
-                    let mut scaler = self
-                        .scale_context
-                        .builder(font.as_swash())
-                        .size(f32::from_bits(cache_key.font_size_bits))
-                        .hint(true)
-                        .build();
-
-                    let offset =
-                        Vector::new(cache_key.x_bin.as_float(), cache_key.y_bin.as_float());
-
-                    let rendered = Render::new(&[
-                        Source::ColorOutline(0),
-                        Source::ColorBitmap(StrikeWith::BestFit),
-                        Source::Outline,
-                    ])
-                    // TODO
-                    .format(if true {
-                        Format::Subpixel
-                    } else {
-                        Format::Alpha
-                    })
-                    .offset(offset)
-                    .render(&mut scaler, cache_key.glyph_id);
-
-                    // upload it to the GPU
-                    rendered.map(|rendered| {
-                        // pick an atlas texture for our glyph
-                        let content_w = rendered.placement.width as usize;
-                        let content_h = rendered.placement.height as usize;
-                        let alloc_w = rendered.placement.width + (GLYPH_MARGIN + GLYPH_PADDING) * 2;
-                        let alloc_h =
-                            rendered.placement.height + (GLYPH_MARGIN + GLYPH_PADDING) * 2;
-                        let used_w = rendered.placement.width + GLYPH_PADDING * 2;
-                        let used_h = rendered.placement.height + GLYPH_PADDING * 2;
-                        let mut found = None;
-                        for (texture_index, glyph_atlas) in
-                            self.glyph_textures.iter_mut().enumerate()
-                        {
-                            if let Some((x, y)) = glyph_atlas
-                                .atlas
-                                .add_rect(alloc_w as usize, alloc_h as usize)
-                            {
-                                found = Some((texture_index, x, y));
-                                break;
-                            }
-                        }
-                        let (texture_index, atlas_alloc_x, atlas_alloc_y) =
-                            found.unwrap_or_else(|| {
-                                // if no atlas could fit the texture, make a new atlas tyvm
-                                // TODO error handling
-                                let mut atlas = Atlas::new(TEXTURE_SIZE, TEXTURE_SIZE);
-                                let image_id = canvas
-                                    .create_image(
-                                        Img::new(
-                                            vec![
-                                                RGBA8::new(0, 0, 0, 0);
-                                                TEXTURE_SIZE * TEXTURE_SIZE
-                                            ],
-                                            TEXTURE_SIZE,
-                                            TEXTURE_SIZE,
-                                        )
-                                        .as_ref(),
-                                        ImageFlags::empty(),
-                                    )
-                                    .unwrap();
-                                let texture_index = self.glyph_textures.len();
-                                let (x, y) =
-                                    atlas.add_rect(alloc_w as usize, alloc_h as usize).unwrap();
-                                self.glyph_textures.push(FontTexture { atlas, image_id });
-                                (texture_index, x, y)
-                            });
-
-                        let atlas_used_x = atlas_alloc_x as u32 + GLYPH_MARGIN;
-                        let atlas_used_y = atlas_alloc_y as u32 + GLYPH_MARGIN;
-                        let atlas_content_x = atlas_alloc_x as u32 + GLYPH_MARGIN + GLYPH_PADDING;
-                        let atlas_content_y = atlas_alloc_y as u32 + GLYPH_MARGIN + GLYPH_PADDING;
-
-                        let mut src_buf = Vec::with_capacity(content_w * content_h);
-                        match rendered.content {
-                            Content::Mask => {
-                                for chunk in rendered.data.chunks_exact(1) {
-                                    src_buf.push(RGBA8::new(chunk[0], 0, 0, 0));
-                                }
-                            }
-                            Content::Color | Content::SubpixelMask => {
-                                for chunk in rendered.data.chunks_exact(4) {
-                                    src_buf
-                                        .push(RGBA8::new(chunk[0], chunk[1], chunk[2], chunk[3]));
-                                }
-                            }
-                        }
-                        canvas
-                            .update_image::<ImageSource>(
-                                self.glyph_textures[texture_index].image_id,
-                                ImgRef::new(&src_buf, content_w, content_h).into(),
-                                atlas_content_x as usize,
-                                atlas_content_y as usize,
-                            )
-                            .unwrap();
-
-                        RenderedGlyph {
-                            texture_index,
-                            width: used_w,
-                            height: used_h,
-                            offset_x: rendered.placement.left,
-                            offset_y: rendered.placement.top,
-                            atlas_x: atlas_used_x,
-                            atlas_y: atlas_used_y,
-                            color_glyph: matches!(rendered.content, Content::Color),
-                        }
-                    })
-                }) else {
+                let glyph_key = self.glyph_key(cache_key);
+
+                // perform cache lookup for rendered glyph, or render it
+                if !self.rendered_glyphs.contains_key(&glyph_key) {
+                    let rendered = self.render_glyph(canvas, cache_key);
+                    self.rendered_glyphs.insert(glyph_key, rendered);
+                }
+                let Some(rendered) = self
+                    .rendered_glyphs
+                    .get_mut(&glyph_key)
+                    .and_then(Option::as_mut)
+                else {
                     continue;
                 };
+                rendered.last_used = self.frame;
+                let rendered = *rendered;
 
                 let cmd_map = if rendered.color_glyph {
                     &mut color_cmd_map
@@ -224,10 +812,11 @@ impl RenderCache {
                 let it = 1.0 / TEXTURE_SIZE as f32;
 
                 q.x0 =
-                    (position_x + glyph.x as i32 + rendered.offset_x - GLYPH_PADDING as i32) as f32;
-                q.y0 = (position_y + run.line_y as i32 + glyph.y as i32
-                    - rendered.offset_y
-                    - GLYPH_PADDING as i32) as f32;
+                    position_x + (glyph.x as i32 + rendered.offset_x - GLYPH_PADDING as i32) as f32;
+                q.y0 = position_y
+                    + (run.line_y as i32 + glyph.y as i32
+                        - rendered.offset_y
+                        - GLYPH_PADDING as i32) as f32;
                 q.x1 = q.x0 + rendered.width as f32;
                 q.y1 = q.y0 + rendered.height as f32;
 
@@ -264,3 +853,386 @@ impl RenderCache {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{
+        fontdb, Attrs, AttrsList, Buffer, Family, FontSystem, Metrics, Shaping, Style, Weight,
+    };
+
+    use crate::{Layout, PixelSnap, Point, Size, Text, Widget};
+
+    use super::{
+        load_fonts, with_fallbacks, with_loaded_families, AntialiasMode, RenderCache,
+        BUNDLED_FONTS, MISSING_FAMILIES,
+    };
+
+    /// Where the OS/2 table of `font` starts.
+    fn os2_offset(font: &[u8]) -> usize {
+        let tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+
+        (0..tables)
+            .map(|idx| 12 + idx * 16)
+            .find(|record| &font[*record..*record + 4] == b"OS/2")
+            .map(|record| u32::from_be_bytes(font[record + 8..record + 12].try_into().unwrap()))
+            .unwrap() as usize
+    }
+
+    /// The bundled regular face, declaring `weight` in its OS/2 table.
+    fn reweighted(weight: u16) -> Vec<u8> {
+        let mut font = BUNDLED_FONTS[0].to_vec();
+        let os2 = os2_offset(&font);
+
+        // usWeightClass follows the version and average char width.
+        font[os2 + 4..os2 + 6].copy_from_slice(&weight.to_be_bytes());
+
+        font
+    }
+
+    #[test]
+    fn bold_face_is_selected() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![reweighted(700)]);
+
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(16., 20.));
+        let attrs = Attrs::new()
+            .family(Family::Name("JetBrains Mono"))
+            .weight(Weight::BOLD);
+        buffer.set_text(&mut font_system, "fn", attrs, Shaping::Advanced);
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let run = buffer.layout_runs().next().unwrap();
+        let face = font_system.db().face(run.glyphs[0].font_id).unwrap();
+
+        assert_eq!(face.weight, Weight::BOLD);
+    }
+
+    #[test]
+    fn bold_italic_text() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+
+        // fsSelection, marking the face italic
+        let mut italic = reweighted(700);
+        let os2 = os2_offset(&italic);
+        italic[os2 + 63] |= 1;
+        load_fonts(&mut font_system, vec![italic]);
+
+        let layout = Layout::fixed(
+            Point { x: 0, y: 0 },
+            Size {
+                width: 200,
+                height: 40,
+            },
+        );
+
+        let mut text = Text::builder()
+            .text("fn")
+            .weight(Weight::BOLD)
+            .style(Style::Italic)
+            .build();
+        text.layout(layout, &mut font_system);
+
+        let buffer = text.buffer();
+        let run = buffer.layout_runs().next().unwrap();
+        let face = font_system.db().face(run.glyphs[0].font_id).unwrap();
+
+        assert_eq!((face.weight, face.style), (Weight::BOLD, Style::Italic));
+    }
+
+    #[test]
+    fn identical_text_is_shaped_once() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+        let mut cache = RenderCache::new(font_system);
+
+        let layout = Layout::fixed(
+            Point { x: 0, y: 0 },
+            Size {
+                width: 200,
+                height: 40,
+            },
+        );
+
+        let label = || Text::builder().text("2").size(20.).build();
+        let mut labels = [label(), label(), label()];
+
+        for label in &mut labels {
+            label.layout_shared(layout, &mut cache);
+        }
+
+        assert_eq!(cache.shapes.misses(), 1);
+        assert!(labels.iter().all(|label| label
+            .buffer()
+            .layout_runs()
+            .next()
+            .unwrap()
+            .glyphs
+            .len()
+            == 1));
+
+        // Different text is shaped on its own
+        Text::builder()
+            .text("3")
+            .size(20.)
+            .build()
+            .layout_shared(layout, &mut cache);
+        assert_eq!(cache.shapes.misses(), 2);
+    }
+
+    #[test]
+    fn missing_glyphs_come_from_the_fallbacks() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(
+            &mut font_system,
+            vec![include_bytes!("../../assets/Roboto-Regular.ttf").to_vec()],
+        );
+
+        // No CJK face is bundled, so a box drawing character stands in for one Roboto lacks.
+        let text = "a─字";
+        let families = ["Not Installed".to_string(), "JetBrains Mono".into()];
+        let attrs = with_fallbacks(
+            &mut font_system,
+            &families,
+            text,
+            AttrsList::new(Attrs::new().family(Family::Name("Roboto"))),
+        );
+
+        assert_eq!(attrs.get_span(0).family, Family::Name("Roboto"));
+        assert_eq!(attrs.get_span(1).family, Family::Name("JetBrains Mono"));
+        // Nothing has it, so it is left to cosmic-text
+        assert_eq!(attrs.get_span(4).family, Family::Name("Roboto"));
+
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(16., 20.));
+        buffer.set_text(&mut font_system, text, Attrs::new(), Shaping::Advanced);
+        buffer.lines[0].set_attrs_list(attrs);
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let glyphs = &buffer.layout_runs().next().unwrap().glyphs;
+        let family = |idx: usize| {
+            let face = font_system.db().face(glyphs[idx].font_id).unwrap();
+            face.families[0].0.clone()
+        };
+
+        assert_eq!(family(0), "Roboto");
+        assert_eq!(family(1), "JetBrains Mono");
+        // Drawn as the missing glyph box
+        assert_eq!(glyphs[2].glyph_id, 0);
+    }
+
+    #[test]
+    fn fonts_load_at_runtime() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+        let mut cache = RenderCache::new(font_system);
+
+        assert_eq!(cache.list_families(), ["JetBrains Mono"]);
+
+        let roboto = include_bytes!("../../assets/Roboto-Regular.ttf");
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/Roboto-Regular.ttf");
+        cache.load_font_file(path.as_ref()).unwrap();
+        assert_eq!(cache.list_families(), ["JetBrains Mono", "Roboto"]);
+
+        // Another face of a loaded family isn't listed twice
+        cache.load_font_data(roboto.to_vec());
+        assert_eq!(cache.list_families(), ["JetBrains Mono", "Roboto"]);
+
+        assert!(cache.load_font_file("missing.ttf".as_ref()).is_err());
+    }
+
+    #[test]
+    fn missing_family_uses_the_fallback() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+
+        let mut attrs = AttrsList::new(Attrs::new().family(Family::Name("Not Loaded Sans")));
+        attrs.add_span(0..1, Attrs::new().family(Family::Name("JetBrains Mono")));
+        attrs.add_span(1..2, Attrs::new().family(Family::Monospace));
+
+        let loaded = with_loaded_families(&font_system, "JetBrains Mono", attrs);
+
+        assert_eq!(loaded.defaults().family, Family::Name("JetBrains Mono"));
+        assert_eq!(loaded.get_span(0).family, Family::Name("JetBrains Mono"));
+        // Generic families are left to cosmic-text
+        assert_eq!(loaded.get_span(1).family, Family::Monospace);
+        assert!(MISSING_FAMILIES.lock().unwrap().contains("Not Loaded Sans"));
+    }
+
+    #[test]
+    fn fallback_family_is_per_cache() {
+        let cache = |fallback: &str| {
+            let mut font_system =
+                FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+            load_fonts(
+                &mut font_system,
+                vec![include_bytes!("../../assets/Roboto-Regular.ttf").to_vec()],
+            );
+            let mut cache = RenderCache::new(font_system);
+            cache.set_fallback_family(fallback);
+
+            cache
+        };
+
+        let layout = Layout::fixed(
+            Point { x: 0, y: 0 },
+            Size {
+                width: 200,
+                height: 40,
+            },
+        );
+
+        let family = |mut cache: RenderCache| {
+            let mut text = Text::builder().text("fn").font("Not Loaded Serif").build();
+            text.layout_shared(layout, &mut cache);
+
+            let buffer = text.buffer();
+            let id = buffer.layout_runs().next().unwrap().glyphs[0].font_id;
+
+            cache.font_system.db().face(id).unwrap().families[0]
+                .0
+                .clone()
+        };
+
+        assert_eq!(family(cache("Roboto")), "Roboto");
+        assert_eq!(family(cache("JetBrains Mono")), "JetBrains Mono");
+    }
+
+    #[test]
+    fn fallback_fonts_are_per_cache() {
+        let cache = |fallbacks: &[&str]| {
+            let mut font_system =
+                FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+            load_fonts(
+                &mut font_system,
+                vec![include_bytes!("../../assets/Roboto-Regular.ttf").to_vec()],
+            );
+            let mut cache = RenderCache::new(font_system);
+            cache.set_fallback_fonts(fallbacks.iter().copied());
+
+            cache
+        };
+
+        let layout = Layout::fixed(
+            Point { x: 0, y: 0 },
+            Size {
+                width: 200,
+                height: 40,
+            },
+        );
+
+        // Roboto has no box drawing characters
+        let family = |mut cache: RenderCache| {
+            let mut text = Text::builder().text("a─").font("Roboto").build();
+            text.layout_shared(layout, &mut cache);
+
+            let buffer = text.buffer();
+            let family = buffer.lines[0].attrs_list().get_span(1).family;
+
+            format!("{family:?}")
+        };
+
+        assert_eq!(
+            family(cache(&["JetBrains Mono"])),
+            format!("{:?}", Family::Name("JetBrains Mono"))
+        );
+        assert_eq!(family(cache(&[])), format!("{:?}", Family::Name("Roboto")));
+    }
+
+    #[test]
+    fn hinting_keys_glyphs_apart() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(16., 20.));
+        let attrs = Attrs::new().family(Family::Name("JetBrains Mono"));
+        buffer.set_text(&mut font_system, "a", attrs, Shaping::Advanced);
+        buffer.shape_until_scroll(&mut font_system, false);
+
+        let glyph = buffer.layout_runs().next().unwrap().glyphs[0]
+            .physical((0., 0.), 1.)
+            .cache_key;
+
+        let mut cache = RenderCache::new(font_system);
+        assert!(cache.hinting);
+        let hinted = cache.glyph_key(glyph);
+
+        cache.set_hinting(false);
+        assert_ne!(cache.glyph_key(glyph), hinted);
+
+        cache.set_hinting(true);
+        assert_eq!(cache.glyph_key(glyph), hinted);
+
+        cache.set_antialias(AntialiasMode::Alpha);
+        assert_ne!(cache.glyph_key(glyph), hinted);
+
+        // Both modes render and upload
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+        let grayscale = cache.render_glyph(&mut canvas, glyph).unwrap();
+        cache.set_antialias(AntialiasMode::Subpixel);
+        let subpixel = cache.render_glyph(&mut canvas, glyph).unwrap();
+        assert_eq!(cache.glyph_key(glyph), hinted);
+        assert_eq!(
+            (grayscale.width, grayscale.height),
+            (subpixel.width, subpixel.height)
+        );
+    }
+
+    #[test]
+    fn measure_wraps_to_the_width() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+        let mut cache = RenderCache::new(font_system);
+        let attrs = Attrs::new().family(Family::Name("JetBrains Mono"));
+
+        // Advances are 0.6 of the size in a monospace font
+        let line = cache.measure("abc", attrs, 20., None);
+        assert_eq!((line.width, line.height), (36, 20));
+
+        let wrapped = cache.measure("aa aa\nb", attrs, 20., Some(50.));
+        assert_eq!(wrapped.height, 60);
+        assert!(wrapped.width <= 50);
+    }
+
+    #[test]
+    fn least_recently_used_atlas_is_reused() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        load_fonts(&mut font_system, vec![]);
+        let mut cache = RenderCache::new(font_system);
+        cache.set_max_atlases(1);
+        let mut canvas = femtovg::Canvas::new(femtovg::renderer::Void).unwrap();
+
+        // Each glyph is big enough to fill most of an atlas
+        let mut draw = |cache: &mut RenderCache, text: &str| {
+            let mut buffer = Buffer::new(&mut cache.font_system, Metrics::new(600., 700.));
+            {
+                let mut buffer = buffer.borrow_with(&mut cache.font_system);
+                buffer.set_size(None, None);
+                buffer.set_text(text, Attrs::new(), Shaping::Advanced);
+                buffer.shape_until_scroll(true);
+            }
+
+            cache.start_frame();
+            cache
+                .fill_buffer_to_draw_commands(&mut canvas, &buffer, (0., 0.), PixelSnap::default())
+                .unwrap();
+        };
+
+        draw(&mut cache, "A");
+        draw(&mut cache, "B");
+        assert_eq!(cache.glyph_textures.len(), 1);
+        assert_eq!(cache.rendered_glyphs.values().flatten().count(), 1);
+
+        // Glyphs drawn in the same frame are kept, even over the budget
+        draw(&mut cache, "AB");
+        assert_eq!(cache.glyph_textures.len(), 2);
+        assert_eq!(cache.rendered_glyphs.values().flatten().count(), 2);
+    }
+}
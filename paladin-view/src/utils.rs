@@ -1,5 +1,7 @@
 use bevy_reflect::Reflect;
 
+/// The message sent by [Button::interactions](crate::Button::interactions).
+/// Buttons aren't limited to this message, see [Button::sends](crate::Button::sends).
 #[derive(Clone, Copy, Reflect, Debug)]
 pub enum ButtonMessage {
     Clicked(u32, u32),
@@ -0,0 +1,82 @@
+//! A panel listing the diagnostics of every open file, grouped by file and severity.
+
+use std::path::PathBuf;
+
+use lsp_types::{DiagnosticSeverity, Position};
+use paladin_view::{prelude::*, taffy::FlexDirection, Styleable};
+use paladinc::problems::{self, Problems};
+
+use crate::diagnostic_gutter;
+
+/// What the listed problems are narrowed down to, typed above them.
+#[derive(Reflect, Default, Debug)]
+pub struct ProblemsFilter(String);
+
+impl Reducer<String> for ProblemsFilter {
+    fn reduce(&mut self, message: String) {
+        self.0 = message;
+    }
+}
+
+fn severity_name(severity: DiagnosticSeverity) -> &'static str {
+    match severity {
+        DiagnosticSeverity::ERROR => "errors",
+        DiagnosticSeverity::WARNING => "warnings",
+        DiagnosticSeverity::INFORMATION => "information",
+        _ => "hints",
+    }
+}
+
+/// A header for each group, followed by its problems, with the location of each problem.
+fn rows(problems: &Problems) -> (Vec<ListItem>, Vec<Option<(PathBuf, Position)>>) {
+    let mut items = vec![];
+    let mut locations = vec![];
+
+    for group in problems.groups() {
+        let header = format!(
+            "{} \u{2014} {} {}",
+            group.path.display(),
+            group.diagnostics.len(),
+            severity_name(group.severity)
+        );
+
+        items.push(
+            ListItem::new(header)
+                .header()
+                .marker(diagnostic_gutter::color(group.severity)),
+        );
+        locations.push(None);
+
+        for diagnostic in group.diagnostics {
+            items.push(ListItem::new(problems::label(group.path, diagnostic)).indent(4));
+            locations.push(Some((group.path.to_owned(), diagnostic.range.start)));
+        }
+    }
+
+    (items, locations)
+}
+
+/// Lists the `problems` that fuzzy match the filter typed above them,
+/// and calls back with the location of a problem when it is clicked.
+pub fn problems_panel(
+    problems: &Problems,
+    filter: &State<String, ProblemsFilter>,
+    on_jump: impl Fn(PathBuf, Position) + 'static,
+) -> impl Element {
+    let (items, locations) = rows(problems);
+
+    let mut panel = hstack((
+        text_input(filter.0.clone()).sends(filter, str::to_string),
+        list(items)
+            .filter(filter.0.clone())
+            .on_select(move |index| {
+                if let Some((path, position)) = &locations[index] {
+                    on_jump(path.clone(), *position)
+                }
+            })
+            .flex_grow(1.),
+    ));
+    panel.style_mut().0.flex_direction = FlexDirection::Column;
+
+    panel
+}
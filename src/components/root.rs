@@ -1,14 +1,25 @@
 use paladin_view::{prelude::*, taffy::LengthPercentage};
+use paladinc::lsp::LspConfig;
 
-use crate::BufferElement;
+use crate::{
+    components::problems::{problems_panel, ProblemsFilter},
+    BufferElement, BufferMessage, BufferState,
+};
+
+/// The file opened at startup.
+const OPENED: &str = "src/main.rs";
 
 #[view]
-pub struct Root;
+pub struct Root {
+    /// Started for the opened file if it serves the file's language, `None` to start none.
+    pub lsp: Option<LspConfig>,
+}
 
 impl View for Root {
     fn build(&self) -> impl Element + use<> {
         MyView {
             state: State::create_state(|| MyViewState { data: 0 }),
+            lsp: self.lsp.clone(),
         }
     }
 }
@@ -16,18 +27,52 @@ impl View for Root {
 #[view]
 struct MyView {
     state: State<ButtonMessage, MyViewState>,
+    lsp: Option<LspConfig>,
 }
 
 impl View for MyView {
     fn build(&self) -> impl Element + use<> {
+        let lsp = self.lsp.clone();
+
         // "Some beautiful text"
         hstack((
-            BufferElement::new("src/main.rs").pad(LengthPercentage::Percent(0.5)),
+            Editor {
+                buffer: State::create_state_with(move |mailbox| {
+                    BufferState::serve(OPENED, lsp.clone(), mailbox).unwrap_or_else(|err| {
+                        dbg!("WARN: ", err);
+                        BufferState::default()
+                    })
+                }),
+                filter: State::default(),
+            },
             MySecondView::default(),
         ))
     }
 }
 
+/// The open buffer, with the problems of the workspace next to it.
+#[view]
+struct Editor {
+    buffer: State<BufferMessage, BufferState>,
+    filter: State<String, ProblemsFilter>,
+}
+
+impl View for Editor {
+    fn build(&self) -> impl Element + use<> {
+        let mailbox = self.buffer.mailbox();
+
+        hstack((
+            BufferElement::new(&self.buffer).pad(LengthPercentage::Percent(0.5)),
+            Divider::vertical(),
+            problems_panel(
+                self.buffer.problems(),
+                &self.filter,
+                move |path, position| mailbox.send(BufferMessage::Jump(path, position)),
+            ),
+        ))
+    }
+}
+
 #[derive(Reflect, Debug, Clone)]
 struct MyViewState {
     data: u32,
@@ -0,0 +1,133 @@
+//! Visible placeholders for characters that draw as nothing, or as an ordinary space.
+//! Only the shaped text is changed, the buffer keeps the original characters.
+
+use std::ops::Range;
+
+/// Color of the placeholders, apart from any highlighting.
+pub const PLACEHOLDER_COLOR: cosmic_text::Color = cosmic_text::Color::rgb(224, 108, 117);
+
+/// The stand-in drawn for `c`, if it is hidden.
+/// Tabs and line breaks are left alone, they already show as what they are.
+pub fn placeholder(c: char) -> Option<char> {
+    match c {
+        '\t' | '\n' | '\r' => None,
+        // Control Pictures, ␀ to ␟
+        '\u{0}'..='\u{1f}' => char::from_u32(0x2400 + c as u32),
+        '\u{7f}' => Some('␡'),
+        '\u{80}'..='\u{9f}' => Some('⍰'),
+        '\u{a0}' | '\u{2007}' | '\u{202f}' => Some('⍽'),
+        '\u{ad}' | '\u{200b}'..='\u{200d}' | '\u{2060}' | '\u{feff}' => Some('¦'),
+        _ => None,
+    }
+}
+
+/// A line with its hidden characters replaced, see [substitute].
+#[derive(Clone, Debug, Default)]
+pub struct Substituted {
+    pub text: String,
+    /// Where the placeholders are in `text`.
+    pub placeholders: Vec<Range<usize>>,
+    /// How far offsets at or past a byte of the original line moved, in order.
+    shifts: Vec<(usize, isize)>,
+}
+
+impl Substituted {
+    /// Where byte `byte` of the original line is in `text`, to move spans like highlights over.
+    pub fn map(&self, byte: usize) -> usize {
+        let shift = self
+            .shifts
+            .iter()
+            .take_while(|(at, _)| *at <= byte)
+            .last()
+            .map_or(0, |(_, shift)| *shift);
+
+        byte.saturating_add_signed(shift)
+    }
+}
+
+/// Replace the hidden characters of `line` with their [placeholder].
+///
+/// Combining marks are never replaced, so one following a placeholder stays on it.
+/// Joiners between two non-ASCII characters are kept, they are part of an emoji or a script's cluster.
+pub fn substitute(line: &str) -> Substituted {
+    let mut substituted = Substituted {
+        text: String::with_capacity(line.len()),
+        ..Default::default()
+    };
+    let mut shift = 0_isize;
+    let mut chars = line.char_indices().peekable();
+    let mut previous = None;
+
+    while let Some((byte, c)) = chars.next() {
+        let next = chars.peek().map(|(_, next)| *next);
+        let joins = matches!(c, '\u{200c}' | '\u{200d}')
+            && previous.is_some_and(|it: char| !it.is_ascii())
+            && next.is_some_and(|it| !it.is_ascii());
+
+        previous = Some(c);
+
+        let Some(placeholder) = placeholder(c).filter(|_| !joins) else {
+            substituted.text.push(c);
+            continue;
+        };
+
+        let start = substituted.text.len();
+        substituted.text.push(placeholder);
+        substituted.placeholders.push(start..substituted.text.len());
+
+        shift += placeholder.len_utf8() as isize - c.len_utf8() as isize;
+        substituted.shifts.push((byte + c.len_utf8(), shift));
+    }
+
+    substituted
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{fontdb, Attrs, Buffer, Family, FontSystem, Metrics, Shaping};
+
+    use super::substitute;
+
+    #[test]
+    fn nbsp_gets_a_placeholder() {
+        // 'é' as 'e' and a combining acute, then a nbsp and a zero-width space
+        let line = "let e\u{301}\u{a0}= 1;\u{200b}\n";
+        let substituted = substitute(line);
+
+        assert_eq!(substituted.text, "let e\u{301}⍽= 1;¦\n");
+        assert_eq!(substituted.placeholders, [7..10, 14..16]);
+
+        // Offsets past the nbsp move by a byte, past the zero-width space by one less.
+        assert_eq!(substituted.map(4), 4);
+        assert_eq!(substituted.map(9), 10);
+        assert_eq!(substituted.map(line.len()), substituted.text.len());
+
+        // The joiner in an emoji sequence stays hidden.
+        assert_eq!(substitute("👩\u{200d}💻").text, "👩\u{200d}💻");
+
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        font_system
+            .db_mut()
+            .load_font_data(include_bytes!("../assets/JetBrainsMono-Regular.ttf").to_vec());
+
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(10., 20.));
+        let mut buffer = buffer.borrow_with(&mut font_system);
+        buffer.set_text(
+            &substituted.text,
+            Attrs::new().family(Family::Name("JetBrains Mono")),
+            Shaping::Advanced,
+        );
+        buffer.shape_until_scroll(true);
+
+        let run = buffer.layout_runs().next().unwrap();
+        let nbsp = run
+            .glyphs
+            .iter()
+            .find(|glyph| glyph.start == 7)
+            .expect("a glyph for the placeholder");
+
+        assert_eq!(nbsp.end, 10);
+        assert!(nbsp.w > 0.);
+    }
+}
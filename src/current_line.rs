@@ -0,0 +1,54 @@
+//! Background behind the lines holding a cursor.
+
+use paladin_view::{Color, DisplayList, Layout};
+
+/// Vertical extents (top, height) of every visual line that `line` wraps into, relative to the buffer.
+pub fn line_rects(buffer: &cosmic_text::Buffer, line: usize) -> Vec<(f32, f32)> {
+    buffer
+        .layout_runs()
+        .filter(|run| run.line_i == line)
+        .map(|run| (run.line_top, run.line_height))
+        .collect()
+}
+
+/// Paint a full-width background behind each of `lines`. Call before rendering the glyphs.
+pub(crate) fn paint(
+    color: Color,
+    lines: impl IntoIterator<Item = usize>,
+    buffer: &cosmic_text::Buffer,
+    layout: Layout,
+    list: &mut DisplayList,
+) {
+    let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+
+    for line in lines {
+        for (top, height) in line_rects(buffer, line) {
+            list.fill_rect(x, y + top, layout.size.width as f32, height, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, Shaping};
+
+    use super::line_rects;
+
+    #[test]
+    fn rect_of_cursor_line() {
+        let mut font_system = FontSystem::new();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(16., 20.));
+
+        let mut buffer = buffer.borrow_with(&mut font_system);
+        buffer.set_size(Some(400.), Some(400.));
+        buffer.set_text(
+            "fn main() {\n    let x = 1;\n}",
+            Attrs::new(),
+            Shaping::Advanced,
+        );
+        buffer.shape_until_scroll(true);
+
+        assert_eq!(line_rects(&buffer, 1), vec![(20., 20.)]);
+        assert_eq!(line_rects(&buffer, 5), vec![]);
+    }
+}
@@ -0,0 +1,201 @@
+//! Markers left of the text on lines with diagnostics, colored by the worst of them, and their messages on hover.
+
+use std::{cell::RefCell, rc::Rc};
+
+use cosmic_text::{Attrs, Family, FontSystem, Metrics, Shaping};
+use lsp_types::DiagnosticSeverity;
+use paladin_view::{Color, DisplayList, Layout};
+
+use crate::current_line::line_rects;
+
+/// Width of the gutter, the text starts right of it.
+pub const WIDTH: u32 = 16;
+
+/// A line with diagnostics.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Marker {
+    pub line: usize,
+    pub severity: DiagnosticSeverity,
+    /// Vertical extents of the line's first visual line, relative to the buffer.
+    pub top: f32,
+    pub height: f32,
+}
+
+/// The messages of a hovered marker, shaped to be drawn below it.
+pub struct Tooltip {
+    pub line: usize,
+    buffer: Rc<RefCell<cosmic_text::Buffer>>,
+    size: (f32, f32),
+}
+
+pub fn color(severity: DiagnosticSeverity) -> Color {
+    match severity {
+        DiagnosticSeverity::ERROR => Color::rgb(224, 82, 82),
+        DiagnosticSeverity::WARNING => Color::rgb(229, 192, 123),
+        DiagnosticSeverity::INFORMATION => Color::rgb(97, 175, 239),
+        _ => Color::rgb(150, 150, 150),
+    }
+}
+
+/// A marker for each line of `editor` with diagnostics that is shaped in `buffer`.
+pub fn markers(editor: &paladinc::Buffer, buffer: &cosmic_text::Buffer) -> Vec<Marker> {
+    let mut lines: Vec<_> = editor
+        .diagnostics()
+        .iter()
+        .map(|it| it.range.start.line as usize)
+        .collect();
+    lines.dedup();
+
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            let severity = editor.line_severity(line)?;
+            // Shaped lines skip the folded ones.
+            let display = editor.folds().display_line(line)?;
+            let (top, height) = *line_rects(buffer, display).first()?;
+
+            Some(Marker {
+                line,
+                severity,
+                top,
+                height,
+            })
+        })
+        .collect()
+}
+
+/// The marker at `x`, `y` relative to the widget, if the point is in the gutter.
+pub fn at(markers: &[Marker], x: f32, y: f32) -> Option<&Marker> {
+    if !(0. ..WIDTH as f32).contains(&x) {
+        return None;
+    }
+
+    markers
+        .iter()
+        .find(|it| (it.top..it.top + it.height).contains(&y))
+}
+
+impl Tooltip {
+    /// The messages of the diagnostics on the marker's line, one per line.
+    pub fn new(editor: &paladinc::Buffer, line: usize, font_system: &mut FontSystem) -> Self {
+        let messages = editor
+            .line_diagnostics(line)
+            .map(|it| it.message.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut buffer = cosmic_text::Buffer::new(font_system, Metrics::new(18., 24.));
+        let mut borrowed = buffer.borrow_with(font_system);
+        borrowed.set_size(Some(800.), None);
+        borrowed.set_text(
+            &messages,
+            Attrs::new()
+                .family(Family::Name("JetBrains Mono"))
+                .color(cosmic_text::Color::rgb(220, 220, 220)),
+            Shaping::Advanced,
+        );
+        borrowed.shape_until_scroll(true);
+
+        let size = borrowed.layout_runs().fold((0_f32, 0_f32), |(w, h), run| {
+            (w.max(run.line_w), h + run.line_height)
+        });
+
+        Self {
+            line,
+            buffer: Rc::new(RefCell::new(buffer)),
+            size,
+        }
+    }
+}
+
+/// Paint a dot for each marker in the gutter of `layout`, and the tooltip below its marker.
+pub(crate) fn paint(
+    markers: &[Marker],
+    tooltip: Option<&Tooltip>,
+    layout: Layout,
+    list: &mut DisplayList,
+) {
+    let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+    let size = WIDTH as f32 / 2.;
+
+    for marker in markers {
+        let top = y + marker.top + (marker.height - size) / 2.;
+
+        list.rounded_rect(
+            x + size / 2.,
+            top,
+            size,
+            size,
+            size / 2.,
+            color(marker.severity),
+        );
+    }
+
+    let Some(tooltip) = tooltip else {
+        return;
+    };
+
+    let Some(marker) = markers.iter().find(|it| it.line == tooltip.line) else {
+        return;
+    };
+
+    let padding = 6.;
+    let (left, top) = (x + WIDTH as f32, y + marker.top + marker.height);
+    let (width, height) = tooltip.size;
+
+    list.rounded_rect(
+        left,
+        top,
+        width + padding * 2.,
+        height + padding * 2.,
+        4.,
+        Color::rgb(40, 40, 46),
+    );
+    list.text(tooltip.buffer.clone(), (left + padding, top + padding));
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{Attrs, Buffer, FontSystem, Metrics, Shaping};
+    use lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range};
+
+    use super::{at, markers};
+
+    #[test]
+    fn marker_in_worst_severity() {
+        let diagnostic = |line, severity| Diagnostic {
+            range: Range::new(Position::new(line, 4), Position::new(line, 5)),
+            severity: Some(severity),
+            ..Default::default()
+        };
+
+        let mut editor = paladinc::Buffer::scratch(paladinc::SimpleBuffer::from_str(
+            "fn main() {\n    let x = 1;\n}",
+            None,
+        ));
+        editor.set_diagnostics(vec![
+            diagnostic(1, DiagnosticSeverity::WARNING),
+            diagnostic(1, DiagnosticSeverity::ERROR),
+        ]);
+
+        let mut font_system = FontSystem::new();
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(16., 20.));
+
+        let mut buffer = buffer.borrow_with(&mut font_system);
+        buffer.set_size(Some(400.), Some(400.));
+        buffer.set_text(&editor.text(), Attrs::new(), Shaping::Advanced);
+        buffer.shape_until_scroll(true);
+
+        let found = markers(&editor, &buffer);
+        assert_eq!(found.len(), 1);
+        assert_eq!(
+            (found[0].line, found[0].severity, found[0].top),
+            (1, DiagnosticSeverity::ERROR, 20.)
+        );
+
+        assert_eq!(at(&found, 4., 30.).map(|it| it.line), Some(1));
+        // Over the text, or on a line without diagnostics
+        assert!(at(&found, 40., 30.).is_none());
+        assert!(at(&found, 4., 10.).is_none());
+    }
+}
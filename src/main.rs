@@ -1,126 +1,578 @@
 #![feature(precise_capturing_in_traits)]
 
-use std::{io, path::PathBuf};
+use std::{
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
 
-use bevy_reflect::TypeRegistry;
+use bevy_reflect::{TypePath, TypeRegistry};
 use components::root::Root;
 
 use cosmic_text::FontSystem;
 use miette::IntoDiagnostic;
 use paladin_view::{
-    prelude::*, BuildResult, CustomWidget, InsertChildren, LeafNode, RebuildChildren, Style,
-    Styleable,
+    prelude::*, BuildResult, CustomWidget, EventContext, InsertChildren, LeafNode, RebuildChildren,
+    Style, Styleable,
+};
+use paladinc::{
+    lsp::{LspConfig, LspNotification, LspResponse, LspResponseTransmitter},
+    problems::Problems,
+    ts::{self, highlight, HighlightStyle, QueryKind, Theme},
+    Action,
 };
-use paladinc::{lsp::LspResponseTransmitter, ts::highlight};
 mod components;
+mod control_chars;
+mod current_line;
+mod diagnostic_gutter;
+mod whitespace;
+mod word_highlight;
+mod wrap_indicator;
+
+use whitespace::WhitespaceOptions;
 
 fn main() -> paladin_view::Result<()> {
-    run(Root)
+    run(Root {
+        lsp: Some(LspConfig::rust_analyzer()),
+    })
 }
 
+/// Lines of the buffer that are shaped and drawn.
+const VISIBLE_LINES: usize = 149;
+
+/// Draws the buffer of a [BufferState], which edits it.
 pub struct BufferElement {
-    path: String,
+    buffer: Option<SharedBuffer>,
     style: Style,
+    whitespace: WhitespaceOptions,
+    current_line: Option<Color>,
+    wrap_indicator: bool,
+    control_characters: bool,
+    theme: Theme,
 }
 
 struct BufferWidget {
-    buffer: paladinc::Buffer,
+    buffer: Option<SharedBuffer>,
     text: paladin_view::Text,
     qc: tree_sitter::QueryCursor,
-    query: tree_sitter::Query,
+    theme: Theme,
     style: Style,
+    whitespace: WhitespaceOptions,
+    current_line: Option<Color>,
+    wrap_indicator: bool,
+    control_characters: bool,
+    words: paladinc::WordHighlights,
+    /// Lines with diagnostics, found once the text is laid out.
+    markers: Vec<diagnostic_gutter::Marker>,
+    tooltip: Option<diagnostic_gutter::Tooltip>,
+    /// The hovered marker changed since the last paint.
+    dirty: bool,
 }
 
 impl BufferElement {
-    pub fn new(path: impl Into<String>) -> Self {
+    pub fn new(state: &BufferState) -> Self {
         Self {
-            path: path.into(),
+            buffer: state.buffer.clone(),
             style: Default::default(),
+            whitespace: Default::default(),
+            current_line: Some(Color::rgba(255, 255, 255, 12)),
+            wrap_indicator: false,
+            control_characters: false,
+            theme: Theme::default(),
         }
     }
 
-    fn create_buffer() -> paladinc::Result<paladinc::Buffer> {
-        let simple = paladinc::SimpleBuffer::open("src/main.rs".into())?;
+    /// Styles of the syntax highlighting captures.
+    pub fn theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Color painted behind the line holding the cursor, or `None` to disable.
+    /// Keep it translucent so it blends with the background.
+    pub fn current_line(mut self, color: Option<Color>) -> Self {
+        self.current_line = color;
+        self
+    }
+
+    /// Mark the visual lines that continue a wrapped line, in the left margin. Off by default.
+    pub fn wrap_indicator(mut self, enabled: bool) -> Self {
+        self.wrap_indicator = enabled;
+        self
+    }
+
+    /// Draw placeholders for hidden characters, like a non-breaking or zero-width space,
+    /// in a color of their own. Off by default.
+    pub fn control_characters(mut self, enabled: bool) -> Self {
+        self.control_characters = enabled;
+        self
+    }
+
+    /// Draw a vertical guide at each indent level.
+    pub fn indent_guides(mut self, enabled: bool) -> Self {
+        self.whitespace.indent_guides = enabled;
+        self
+    }
+
+    /// Mark trailing spaces and tabs with faint dots.
+    pub fn trailing_whitespace(mut self, enabled: bool) -> Self {
+        self.whitespace.trailing_whitespace = enabled;
+        self
+    }
+
+    pub fn tab_width(mut self, tab_width: u16) -> Self {
+        self.whitespace.tab_width = tab_width;
+        self
+    }
+}
 
-        #[derive(Clone)]
-        struct Fake;
+/// The buffer a [BufferState] edits and its [BufferElement] draws.
+type SharedBuffer = Arc<Mutex<paladinc::Buffer>>;
 
-        impl LspResponseTransmitter for Fake {
-            type Error = io::Error;
+/// Hands the diagnostics a language server publishes to the [BufferState] that started it.
+#[derive(Clone)]
+struct Forward(Option<Mailbox<BufferMessage>>);
 
-            fn send(&self, event: paladinc::lsp::LspResponse) -> Result<(), Self::Error> {
-                // dbg!(event);
+impl LspResponseTransmitter for Forward {
+    type Error = io::Error;
+
+    fn send(&self, event: paladinc::lsp::LspResponse) -> Result<(), Self::Error> {
+        if let (Some(mailbox), LspResponse::Notification(LspNotification::Diagnostics(params))) =
+            (&self.0, event)
+        {
+            mailbox.send(BufferMessage::Diagnostics(params));
+        }
+
+        Ok(())
+    }
+}
+
+/// What a [BufferState] is sent, by keys and commands or by its language server.
+#[derive(Debug, Clone, TypePath)]
+pub enum BufferMessage {
+    Action(Action),
+    /// Diagnostics the language server published, for any file of the workspace.
+    Diagnostics(lsp_types::PublishDiagnosticsParams),
+    /// Move to a location, like a problem's, see [BufferState::jump].
+    Jump(PathBuf, lsp_types::Position),
+}
+
+/// State holding an editor buffer, driven by [Action]s.
+/// Sending an action through [State::then_send] edits the buffer and re-renders the owning view.
+#[derive(Reflect, Default)]
+pub struct BufferState {
+    #[reflect(ignore)]
+    buffer: Option<SharedBuffer>,
+    /// Completion is requested once typing pauses, not on every keystroke.
+    #[reflect(ignore)]
+    completion: Debounced<Action>,
+    /// The revision of the buffer when it was opened or last saved, it is modified at any other.
+    saved: u64,
+    /// Diagnostics of every file the language server published for.
+    #[reflect(ignore)]
+    problems: Problems,
+    /// Started for each file that is opened, if it serves the file's language.
+    #[reflect(ignore)]
+    lsp: Option<LspConfig>,
+    #[reflect(ignore)]
+    mailbox: Option<Mailbox<BufferMessage>>,
+}
+
+impl BufferState {
+    /// Open `path` without a language server.
+    pub fn open(path: impl Into<PathBuf>) -> paladinc::Result<Self> {
+        Self::create(path.into(), None, None)
+    }
 
-                Ok(())
+    /// Open `path` with the language server in `lsp`, which publishes its diagnostics to `mailbox`.
+    pub fn serve(
+        path: impl Into<PathBuf>,
+        lsp: Option<LspConfig>,
+        mailbox: Mailbox<BufferMessage>,
+    ) -> paladinc::Result<Self> {
+        Self::create(path.into(), lsp, Some(mailbox))
+    }
+
+    fn create(
+        path: PathBuf,
+        lsp: Option<LspConfig>,
+        mailbox: Option<Mailbox<BufferMessage>>,
+    ) -> paladinc::Result<Self> {
+        let simple = paladinc::SimpleBuffer::open(path)?;
+        let buffer =
+            paladinc::Buffer::create(simple, ".".into(), lsp.clone(), Forward(mailbox.clone()))?;
+
+        let state = Self {
+            saved: buffer.buffer.revision(),
+            buffer: Some(Arc::new(Mutex::new(buffer))),
+            completion: Debounced::default(),
+            problems: Problems::default(),
+            lsp,
+            mailbox,
+        };
+        set_title(state.title());
+
+        Ok(state)
+    }
+
+    pub fn buffer(&self) -> Option<MutexGuard<'_, paladinc::Buffer>> {
+        self.buffer.as_ref().map(|it| it.lock().unwrap())
+    }
+
+    /// Edited since it was opened or last saved, undoing back to the saved text counts as unedited.
+    pub fn modified(&self) -> bool {
+        self.buffer()
+            .is_some_and(|buffer| buffer.buffer.revision() != self.saved)
+    }
+
+    pub fn problems(&self) -> &Problems {
+        &self.problems
+    }
+
+    /// Move the cursor to `position` in `path`, like a problem's location,
+    /// opening the file in place of the current buffer unless it is already open.
+    pub fn jump(&mut self, path: PathBuf, position: lsp_types::Position) -> paladinc::Result<()> {
+        let current = self
+            .buffer()
+            .and_then(|it| it.buffer.path().map(PathBuf::from));
+
+        if current.as_ref() != Some(&path) {
+            if self.modified() {
+                return Err(miette::miette!("{} has unsaved changes", self.title()));
+            }
+
+            let problems = std::mem::take(&mut self.problems);
+            *self = Self::create(path.clone(), self.lsp.clone(), self.mailbox.clone())?;
+
+            if let Some(mut buffer) = self.buffer() {
+                buffer.set_diagnostics(problems.diagnostics(&path).to_vec());
+            }
+            self.problems = problems;
+        }
+
+        if let Some(mut buffer) = self.buffer() {
+            buffer.goto_position(position);
+        }
+
+        Ok(())
+    }
+
+    /// Keep the diagnostics of every file, and show those of the open one in its gutter.
+    fn publish(&mut self, params: lsp_types::PublishDiagnosticsParams) {
+        let path = params.uri.to_file_path().ok();
+
+        if let Some(mut buffer) = self.buffer() {
+            if path.is_some() && buffer.buffer.path() == path.as_deref() {
+                buffer.set_diagnostics(params.diagnostics.clone());
             }
         }
 
-        paladinc::Buffer::create(simple, ".".into(), Fake)
+        self.problems.publish(params);
+    }
+
+    /// Apply `message` as if it arrived at `now`.
+    fn apply(&mut self, message: Action, now: Instant) {
+        let Some(mut buffer) = self.buffer() else {
+            return;
+        };
+
+        let modified = buffer.buffer.revision() != self.saved;
+        paladinc::action(&mut buffer, message);
+        let revision = buffer.buffer.revision();
+        drop(buffer);
+
+        if matches!(message, Action::NewLine | Action::Back) {
+            self.completion.push(Action::Complete, now);
+
+            if let Some(deadline) = self.completion.deadline() {
+                request_redraw_at(deadline);
+            }
+        }
+
+        if matches!(message, Action::Save) {
+            self.saved = revision;
+        }
+
+        if self.modified() != modified {
+            set_title(self.title());
+        }
+    }
+
+    /// The window title for this buffer, `path — modified` once edited.
+    pub fn title(&self) -> String {
+        let Some(buffer) = self.buffer() else {
+            return String::new();
+        };
+
+        let path = buffer
+            .buffer
+            .path()
+            .map_or("untitled".into(), |path| path.display().to_string());
+        drop(buffer);
+
+        if self.modified() {
+            format!("{path} \u{2014} modified")
+        } else {
+            path.to_string()
+        }
+    }
+
+    /// Send requests that were waiting for typing to pause, returns whether any were sent.
+    /// Called on each frame, see [Reducer::frame], and a frame is asked for when they are due.
+    pub fn tick(&mut self, now: Instant) -> bool {
+        let Some(mut buffer) = self.buffer.as_ref().map(|it| it.lock().unwrap()) else {
+            return false;
+        };
+
+        let Some(request) = self.completion.poll(now) else {
+            return false;
+        };
+
+        paladinc::action(&mut buffer, request);
+
+        true
+    }
+
+    pub fn is_waiting(&self) -> bool {
+        self.completion.is_pending()
     }
 }
 
-impl Widget for BufferWidget {
-    fn layout(&mut self, layout: Layout, font_system: &mut FontSystem) {
-        self.text.layout(layout, font_system);
+impl Reducer<Action> for BufferState {
+    fn reduce(&mut self, message: Action) {
+        self.apply(message, Instant::now());
     }
 
-    fn render(&self, layout: Layout, canvas: &mut Canvas) {
-        self.text.render(layout, canvas)
+    fn frame(&mut self, now: Instant) -> bool {
+        // Nothing is shown until the server answers.
+        self.tick(now);
+
+        false
     }
+}
 
-    fn style(&self) -> Style {
-        self.style.clone()
+impl Reducer<BufferMessage> for BufferState {
+    fn reduce(&mut self, message: BufferMessage) {
+        match message {
+            BufferMessage::Action(action) => self.reduce(action),
+            BufferMessage::Diagnostics(params) => self.publish(params),
+            BufferMessage::Jump(path, position) => {
+                if let Err(err) = self.jump(path, position) {
+                    dbg!("WARN: ", err);
+                }
+            }
+        }
+    }
+
+    fn frame(&mut self, now: Instant) -> bool {
+        Reducer::<Action>::frame(self, now)
     }
 }
 
-impl Element for BufferElement {
-    fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
-        let mut qc = tree_sitter::QueryCursor::new();
-        let query = tree_sitter::Query::new(
-            &tree_sitter_rust::language(),
-            tree_sitter_rust::HIGHLIGHT_QUERY,
-        )
-        .unwrap();
+impl BufferWidget {
+    /// Where the text goes, right of the diagnostics gutter.
+    fn text_layout(layout: Layout) -> Layout {
+        let mut text = layout;
+        text.location.x += diagnostic_gutter::WIDTH;
+        text.size.width = text.size.width.saturating_sub(diagnostic_gutter::WIDTH);
 
-        let buffer = Self::create_buffer().unwrap();
+        text
+    }
 
-        let content = get_rich_text_content(&buffer, 0, 149, &mut qc, &query);
+    /// Show the messages of the marker the pointer is over, if any.
+    fn hover(&mut self, marker: Option<usize>, font_system: &mut FontSystem) {
+        if self.tooltip.as_ref().map(|it| it.line) == marker {
+            return;
+        }
 
-        let text = Text::rich().text(content).size(32.0).call();
+        let Some(buffer) = &self.buffer else {
+            return;
+        };
+
+        self.tooltip = marker.map(|line| {
+            diagnostic_gutter::Tooltip::new(&buffer.lock().unwrap(), line, font_system)
+        });
+        self.dirty = true;
+    }
 
-        let widget = BufferWidget {
-            buffer,
-            text,
-            qc,
-            query,
+    /// Highlight the visible lines of the buffer again, since it may have been edited.
+    fn refresh(&mut self) {
+        let content = match &self.buffer {
+            Some(buffer) => {
+                let buffer = buffer.lock().unwrap();
+                let query = ts::shared(buffer.language(), QueryKind::Highlights);
+
+                get_rich_text_content(
+                    &buffer,
+                    0,
+                    VISIBLE_LINES,
+                    &mut self.qc,
+                    query.as_deref(),
+                    &self.theme,
+                    self.control_characters,
+                )
+            }
+            None => vec![],
+        };
+
+        self.text = Text::rich().text(content).size(32.0).call();
+    }
+}
+
+impl Widget for BufferWidget {
+    fn event_with(&mut self, event: WidgetEvent, context: &mut EventContext) {
+        let marker = match event {
+            WidgetEvent::PointerMove(x, y) => {
+                let layout = context.layout;
+                let x = x as f32 - layout.location.x as f32;
+                let y = y as f32 - layout.location.y as f32;
+
+                diagnostic_gutter::at(&self.markers, x, y).map(|it| it.line)
+            }
+            WidgetEvent::PointerLeave => None,
+            _ => return,
+        };
+
+        self.hover(marker, context.font_system());
+    }
+
+    fn layout(&mut self, layout: Layout, font_system: &mut FontSystem) {
+        if self.text.buffer().tab_width() != self.whitespace.tab_width {
+            self.text
+                .set_tab_width(font_system, self.whitespace.tab_width);
+        }
+
+        self.text.layout(Self::text_layout(layout), font_system);
+
+        if let Some(buffer) = &self.buffer {
+            let buffer = buffer.lock().unwrap();
+
+            self.markers = diagnostic_gutter::markers(&buffer, &self.text.buffer());
+            self.words
+                .update(&buffer, 0..VISIBLE_LINES, std::time::Instant::now());
+        }
+    }
+
+    fn render(&self, layout: Layout, list: &mut DisplayList) {
+        let Some(editor) = &self.buffer else {
+            return;
+        };
+        let editor = editor.lock().unwrap();
+
+        let gutter = layout;
+        let layout = Self::text_layout(layout);
+        let buffer = self.text.buffer();
+        // Shaped lines skip the folded ones.
+        let folds = editor.folds();
+
+        if let Some(color) = self.current_line {
+            let line = folds.display_line(editor.cursor().line);
+
+            current_line::paint(color, line, &buffer, layout, list);
+        }
+
+        let words: Vec<_> = self
+            .words
+            .matches()
+            .iter()
+            .filter_map(|(line, range)| Some((folds.display_line(*line)?, range.clone())))
+            .collect();
+
+        word_highlight::paint(
+            Color::rgba(255, 255, 255, 24),
+            &words,
+            &buffer,
+            layout,
+            list,
+        );
+
+        self.text.render(layout, list);
+
+        whitespace::paint(self.whitespace, &buffer, layout, list);
+
+        if self.wrap_indicator {
+            wrap_indicator::paint(&buffer, layout, list);
+        }
+
+        diagnostic_gutter::paint(&self.markers, self.tooltip.as_ref(), gutter, list);
+    }
+
+    fn take_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn style(&self) -> Style {
+        self.style.clone()
+    }
+}
+
+impl BufferElement {
+    fn widget(self) -> BufferWidget {
+        let mut widget = BufferWidget {
+            buffer: self.buffer,
+            text: Text::rich().text(vec![]).size(32.0).call(),
+            qc: tree_sitter::QueryCursor::new(),
+            theme: self.theme,
             style: self.style,
+            whitespace: self.whitespace,
+            current_line: self.current_line,
+            wrap_indicator: self.wrap_indicator,
+            control_characters: self.control_characters,
+            words: paladinc::WordHighlights::new(std::time::Duration::from_millis(150)),
+            markers: vec![],
+            tooltip: None,
+            dirty: false,
         };
+        widget.refresh();
 
+        widget
+    }
+}
+
+impl Element for BufferElement {
+    fn create(self, _: &mut TypeRegistry) -> BuildResult<impl InsertChildren> {
         BuildResult {
-            widget: paladin_view::MountedWidget::Custom(CustomWidget(Box::new(widget))),
+            widget: paladin_view::MountedWidget::Custom(CustomWidget(Box::new(self.widget()))),
             children: None::<LeafNode>,
         }
     }
 
+    fn kind(&self) -> &'static str {
+        "Custom"
+    }
+
     fn compare_rebuild(
         self,
         old: paladin_view::MountedWidget,
+        _: &mut TypeRegistry,
     ) -> paladin_view::BuildResult<impl RebuildChildren> {
-        let paladin_view::MountedWidget::Custom(CustomWidget(custom)) = old else {
-            panic!()
+        let old = match old {
+            paladin_view::MountedWidget::Custom(CustomWidget(custom)) => {
+                custom.into_any().downcast::<BufferWidget>().ok()
+            }
+            _ => None,
         };
 
-        let Ok(old) = custom.into_any().downcast::<BufferWidget>() else {
-            panic!()
+        let widget = match old {
+            // Keeps the hovered marker and the highlighted words
+            Some(mut old) => {
+                old.buffer = self.buffer;
+                old.style = self.style;
+                old.whitespace = self.whitespace;
+                old.current_line = self.current_line;
+                old.wrap_indicator = self.wrap_indicator;
+                old.control_characters = self.control_characters;
+                old.theme = self.theme;
+                old.refresh();
+
+                old
+            }
+            None => Box::new(self.widget()),
         };
 
-        // if old.buffer.buffer.path.to_str() != Some(&self.path) {
-        //     panic!("New path")
-        // }
-
-        // no need to replace
         BuildResult {
-            widget: paladin_view::MountedWidget::Custom(CustomWidget(old)),
+            widget: paladin_view::MountedWidget::Custom(CustomWidget(widget)),
             children: None::<LeafNode>,
         }
     }
@@ -137,21 +589,26 @@ fn get_rich_text_content(
     start_line: usize,
     length: usize,
     ts_cursor: &mut tree_sitter::QueryCursor,
-    query: &tree_sitter::Query,
+    query: Option<&tree_sitter::Query>,
+    theme: &Theme,
+    control_characters: bool,
 ) -> Vec<(String, cosmic_text::AttrsList)> {
     let now = std::time::Instant::now();
     let attrs = cosmic_text::Attrs::new().family(cosmic_text::Family::Name("JetBrains Mono"));
 
-    let mut highlights = editor_buffer.highlight(ts_cursor, query, start_line..start_line + 80);
+    let mut highlights = query.and_then(|query| {
+        editor_buffer.highlight(ts_cursor, query, theme, start_line..start_line + 80)
+    });
 
+    // Highlights are of the buffer's text, `map` moves them over any placeholders.
     let add_span = |list: &mut cosmic_text::AttrsList,
-                    highlight: Option<highlight::LineHighlight>| {
+                    highlight: Option<highlight::LineHighlight>,
+                    map: &dyn Fn(usize) -> usize| {
         list.clear_spans();
 
         if let Some(highlight) = highlight {
-            for e in highlight.into_iter() {
-                let color = cosmic_text::Color::rgba(e.0.r, e.0.g, e.0.b, e.0.a);
-                list.add_span(e.1.clone(), attrs.color(color));
+            for (style, range) in highlight.into_iter() {
+                list.add_span(map(range.start)..map(range.end), span_attrs(attrs, style));
             }
         }
     };
@@ -159,27 +616,52 @@ fn get_rich_text_content(
     let mut vec = vec![];
 
     for line in start_line..(start_line + length).min(editor_buffer.line_len()) {
-        let mut attrs_list = cosmic_text::AttrsList::new(attrs);
+        if editor_buffer.folds().is_hidden(line) {
+            continue;
+        }
 
-        match highlights.current.cmp(&line) {
-            // Trying to highlight a line that is before the text we are drawing now.
-            std::cmp::Ordering::Less => {
-                // Consume all the lines until we are where we want to be
-                while highlights.current < line {
-                    if let Some(highlight) = highlights.next_line() {
-                        highlight.consume();
-                    } else {
-                        break;
+        let mut attrs_list = cosmic_text::AttrsList::new(attrs);
+        let text = editor_buffer.line(line).to_string();
+        let substituted = control_characters.then(|| control_chars::substitute(&text));
+        let map = |byte| substituted.as_ref().map_or(byte, |it| it.map(byte));
+
+        // Languages without a grammar are drawn plain.
+        if let Some(highlights) = &mut highlights {
+            match highlights.current.cmp(&line) {
+                // Trying to highlight a line that is before the text we are drawing now.
+                std::cmp::Ordering::Less => {
+                    // Consume all the lines until we are where we want to be
+                    while highlights.current < line {
+                        if let Some(highlight) = highlights.next_line() {
+                            highlight.consume();
+                        } else {
+                            break;
+                        }
                     }
+
+                    add_span(&mut attrs_list, highlights.next_line(), &map);
                 }
+                std::cmp::Ordering::Equal => {
+                    add_span(&mut attrs_list, highlights.next_line(), &map)
+                }
+                std::cmp::Ordering::Greater => {}
+            };
+        }
 
-                add_span(&mut attrs_list, highlights.next_line());
+        let mut text = match substituted {
+            Some(substituted) => {
+                for range in substituted.placeholders {
+                    attrs_list.add_span(range, attrs.color(control_chars::PLACEHOLDER_COLOR));
+                }
+
+                substituted.text
             }
-            std::cmp::Ordering::Equal => add_span(&mut attrs_list, highlights.next_line()),
-            std::cmp::Ordering::Greater => {}
+            None => text,
         };
 
-        let text = editor_buffer.line(line).to_string();
+        if editor_buffer.folds().is_folded(line) {
+            text.push_str(" ⋯");
+        }
 
         vec.push((text, attrs_list));
     }
@@ -189,6 +671,23 @@ fn get_rich_text_content(
     vec
 }
 
+/// Attributes of text highlighted with `style`.
+/// Bold and italic fall back to the regular face until those faces are loaded.
+fn span_attrs(attrs: cosmic_text::Attrs, style: HighlightStyle) -> cosmic_text::Attrs {
+    let color = style.color;
+    let mut attrs = attrs.color(cosmic_text::Color::rgba(color.r, color.g, color.b, color.a));
+
+    if style.bold {
+        attrs = attrs.weight(cosmic_text::Weight::BOLD);
+    }
+
+    if style.italic {
+        attrs = attrs.style(cosmic_text::Style::Italic);
+    }
+
+    attrs
+}
+
 pub struct InitResult {
     pub workspace: PathBuf,
     pub file: Option<PathBuf>,
@@ -207,3 +706,218 @@ pub fn initial_workspace() -> miette::Result<InitResult> {
         file: file.map(Into::into),
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::RefCell,
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
+
+    use tempfile::TempDir;
+
+    use bevy_reflect::TypeRegistry;
+    use paladin_view::{app::WidgetTree, prelude::*, MountedWidget};
+    use paladinc::{ts::Color, Action};
+    use winit::dpi::PhysicalSize;
+
+    use crate::{components::root::Root, span_attrs, BufferElement, BufferState, HighlightStyle};
+
+    /// `text` written to `name` in a directory of its own, which is removed with the returned [TempDir].
+    fn fixture(name: &str, text: &str) -> (TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(name);
+        std::fs::write(&path, text).unwrap();
+
+        (dir, path)
+    }
+
+    #[view]
+    struct Edited {
+        buffer: State<Action, BufferState>,
+    }
+
+    impl View for Edited {
+        fn build(&self) -> impl Element + use<> {
+            BufferElement::new(&self.buffer)
+        }
+    }
+
+    fn edited(tree: &WidgetTree) -> &Edited {
+        tree.nodes()
+            .find_map(|(_, _, widget, _)| match widget {
+                MountedWidget::View(view) => view.view::<Edited>(),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn reduce_new_line() {
+        thread_local! {
+            static PATH: RefCell<PathBuf> = RefCell::default();
+        }
+
+        let (_dir, path) = fixture("reduce_new_line.txt", "fn main() {}");
+        PATH.set(path);
+
+        let mut registry = TypeRegistry::new();
+        let view = Edited {
+            buffer: State::create_state(|| BufferState::open(PATH.take()).unwrap()),
+        };
+        let mut tree = WidgetTree::create(&mut registry, view, PhysicalSize::new(800, 600));
+
+        let buffer = &edited(&tree).buffer;
+        let lines = buffer.buffer().unwrap().line_len();
+        buffer.then_send(Action::NewLine).trigger();
+
+        tree.update(&mut registry);
+
+        assert_eq!(edited(&tree).buffer.buffer().unwrap().line_len(), lines + 1);
+    }
+
+    #[test]
+    fn completion_waits_for_typing_to_pause() {
+        let (_dir, path) = fixture("completion_waits.txt", "fn main() {}");
+
+        let mut state = BufferState::open(&path).unwrap();
+        let start = Instant::now();
+        let delay = state.completion.delay();
+
+        state.apply(Action::NewLine, start);
+        state.apply(Action::Back, start + delay / 2);
+
+        assert!(!state.tick(start + delay));
+        assert!(state.tick(start + delay / 2 + delay));
+        assert!(!state.tick(start + delay * 3));
+        assert!(!state.is_waiting());
+    }
+
+    #[test]
+    fn frames_send_completion_once_typing_pauses() {
+        thread_local! {
+            static PATH: RefCell<PathBuf> = RefCell::default();
+        }
+
+        let (_dir, path) = fixture("completion_frames.txt", "fn main() {}");
+        PATH.set(path);
+
+        let mut registry = TypeRegistry::new();
+        let view = Edited {
+            buffer: State::create_state(|| BufferState::open(PATH.take()).unwrap()),
+        };
+        let mut tree = WidgetTree::create(&mut registry, view, PhysicalSize::new(800, 600));
+
+        edited(&tree).buffer.then_send(Action::NewLine).trigger();
+        tree.update(&mut registry);
+
+        let buffer = &edited(&tree).buffer;
+        let deadline = buffer.completion.deadline().unwrap();
+        assert!(buffer.is_waiting());
+
+        // Frames before typing pauses keep it waiting
+        tree.frame(&mut registry, deadline - Duration::from_millis(1));
+        assert!(edited(&tree).buffer.is_waiting());
+
+        // The frame asked for at the deadline sends it
+        tree.frame(&mut registry, deadline);
+        assert!(!edited(&tree).buffer.is_waiting());
+    }
+
+    #[test]
+    fn title_shows_modified_until_saved() {
+        let (_dir, path) = fixture("title_modified.txt", "fn main() {}");
+
+        let mut state = BufferState::open(&path).unwrap();
+        assert_eq!(state.title(), path.display().to_string());
+
+        state.reduce(Action::NewLine);
+        assert_eq!(
+            state.title(),
+            format!("{} \u{2014} modified", path.display())
+        );
+
+        state.reduce(Action::Save);
+        assert_eq!(state.title(), path.display().to_string());
+
+        // Undoing back to the saved text
+        state.reduce(Action::NewLine);
+        assert!(state.modified());
+        state.reduce(Action::Undo);
+        assert!(!state.modified());
+        assert_eq!(state.title(), path.display().to_string());
+    }
+
+    #[test]
+    fn goto_line_clamps() {
+        let (_dir, path) = fixture("goto_line_clamps.txt", "fn main() {\n    let x = 1;\n}");
+
+        let mut state = BufferState::open(&path).unwrap();
+        let last = state.buffer().unwrap().line_len() - 1;
+
+        state.reduce(Action::goto_line("1000").unwrap());
+        assert_eq!(state.buffer().unwrap().cursor().line, last);
+
+        assert!(Action::goto_line("twelve").is_none());
+
+        state.reduce(Action::goto_line(" 2 ").unwrap());
+        assert_eq!(state.buffer().unwrap().cursor().line, 1);
+    }
+
+    #[test]
+    fn jump_opens_the_file() {
+        let (_first_dir, first) = fixture("jump_first.txt", "fn main() {}");
+        let (_second_dir, second) = fixture("jump_second.txt", "fn main() {\n    let x = 1;\n}");
+
+        let mut state = BufferState::open(&first).unwrap();
+        let position = lsp_types::Position::new(1, 8);
+
+        state.jump(second.clone(), position).unwrap();
+        let buffer = state.buffer().unwrap();
+        assert_eq!(buffer.buffer.path(), Some(second.as_path()));
+        assert_eq!(buffer.cursor(), paladinc::Cursor::from_line_byte(1, 8));
+        drop(buffer);
+
+        // Edits aren't dropped for another file
+        state.reduce(Action::NewLine);
+        assert!(state.jump(first, position).is_err());
+    }
+
+    #[test]
+    fn italic_capture_attrs() {
+        let attrs = cosmic_text::Attrs::new();
+        let comment = HighlightStyle::plain(Color::rgb(128, 128, 128));
+
+        let plain = span_attrs(attrs, comment);
+        assert_eq!(plain.style, cosmic_text::Style::Normal);
+        assert_eq!(plain.weight, cosmic_text::Weight::NORMAL);
+
+        let italic = span_attrs(attrs, comment.italic());
+        assert_eq!(italic.style, cosmic_text::Style::Italic);
+        assert_eq!(
+            italic.color_opt,
+            Some(cosmic_text::Color::rgb(128, 128, 128))
+        );
+    }
+
+    #[test]
+    fn enumerate_root() {
+        // No language server is started
+        let root = Root { lsp: None };
+        let tree = WidgetTree::create(&mut TypeRegistry::new(), root, PhysicalSize::new(800, 600));
+
+        let count = |f: fn(&MountedWidget) -> bool| {
+            tree.nodes().filter(|(_, _, widget, _)| f(widget)).count()
+        };
+
+        assert_eq!(count(|it| matches!(it, MountedWidget::View(_))), 5);
+        assert_eq!(count(|it| matches!(it, MountedWidget::HStack(_))), 5);
+        assert_eq!(count(|it| matches!(it, MountedWidget::Custom(_))), 1);
+        assert_eq!(count(|it| matches!(it, MountedWidget::List(_))), 1);
+        assert_eq!(count(|it| matches!(it, MountedWidget::TextInput(_))), 1);
+        assert_eq!(count(|it| matches!(it, MountedWidget::Divider(_))), 1);
+        assert_eq!(count(|it| matches!(it, MountedWidget::Button(_))), 1);
+        assert_eq!(count(|it| matches!(it, MountedWidget::Text(_))), 4);
+    }
+}
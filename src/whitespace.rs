@@ -0,0 +1,123 @@
+//! Whitespace markers drawn over the buffer: indent guides and trailing whitespace.
+
+use paladin_view::{Color, DisplayList, Layout};
+
+#[derive(Clone, Copy, Debug)]
+pub struct WhitespaceOptions {
+    pub indent_guides: bool,
+    pub trailing_whitespace: bool,
+    /// Columns per indent level, and how far a tab advances.
+    pub tab_width: u16,
+}
+
+impl Default for WhitespaceOptions {
+    fn default() -> Self {
+        Self {
+            indent_guides: true,
+            trailing_whitespace: true,
+            tab_width: 4,
+        }
+    }
+}
+
+/// Width of the leading whitespace in columns. A tab advances to the next tab stop.
+pub fn indent_columns(line: &str, tab_width: u16) -> usize {
+    let tab_width = tab_width.max(1) as usize;
+
+    line.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .fold(0, |column, c| match c {
+            '\t' => (column / tab_width + 1) * tab_width,
+            _ => column + 1,
+        })
+}
+
+/// Horizontal offsets of the guides for a line, one at the start of each full indent level.
+pub fn indent_guides(line: &str, tab_width: u16, cell_width: f32) -> Vec<f32> {
+    let tab_width = tab_width.max(1) as usize;
+    let levels = indent_columns(line, tab_width as u16) / tab_width;
+
+    (0..levels)
+        .map(|level| (level * tab_width) as f32 * cell_width)
+        .collect()
+}
+
+/// Byte offset where trailing whitespace starts, if the line has any.
+pub fn trailing_whitespace(line: &str) -> Option<usize> {
+    let trimmed = line.trim_end_matches([' ', '\t']).len();
+
+    (trimmed < line.len()).then_some(trimmed)
+}
+
+/// Paint the enabled markers over the shaped text of the buffer.
+pub(crate) fn paint(
+    options: WhitespaceOptions,
+    buffer: &cosmic_text::Buffer,
+    layout: Layout,
+    list: &mut DisplayList,
+) {
+    let color = || Color::rgba(255, 255, 255, 40);
+    let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+
+    for run in buffer.layout_runs() {
+        let line = buffer.lines[run.line_i].text();
+        let first = run.glyphs.first();
+
+        // Only the first run of a wrapped line starts with the indentation.
+        if options.indent_guides && first.is_none_or(|glyph| glyph.start == 0) {
+            // Monospace, so any glyph but a tab is one cell wide.
+            let cell_width = run
+                .glyphs
+                .iter()
+                .find(|glyph| !line[glyph.start..glyph.end].contains('\t'))
+                .map_or(run.line_height / 2., |glyph| glyph.w);
+
+            for guide in indent_guides(line, options.tab_width, cell_width) {
+                list.line(
+                    (x + guide + 0.5, y + run.line_top),
+                    (x + guide + 0.5, y + run.line_top + run.line_height),
+                    1.,
+                    color(),
+                );
+            }
+        }
+
+        if !options.trailing_whitespace {
+            continue;
+        }
+
+        let Some(start) = trailing_whitespace(line) else {
+            continue;
+        };
+
+        for glyph in run.glyphs.iter().filter(|glyph| glyph.start >= start) {
+            list.fill_rect(
+                x + glyph.x + glyph.w / 2. - 1.,
+                y + run.line_y - run.line_height / 4. - 1.,
+                2.,
+                2.,
+                color(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{indent_columns, indent_guides, trailing_whitespace};
+
+    #[test]
+    fn two_level_guides() {
+        assert_eq!(indent_guides("        let x = 1;", 4, 10.), vec![0., 40.]);
+        assert_eq!(indent_guides("\t\tlet x = 1;", 4, 10.), vec![0., 40.]);
+        assert_eq!(indent_guides("  \tlet x = 1;", 4, 10.), vec![0.]);
+        assert_eq!(indent_columns("  \tx", 4), 4);
+    }
+
+    #[test]
+    fn trailing() {
+        assert_eq!(trailing_whitespace("let x = 1;  "), Some(10));
+        assert_eq!(trailing_whitespace("let x = 1;"), None);
+        assert_eq!(trailing_whitespace(" \t"), Some(0));
+    }
+}
@@ -0,0 +1,34 @@
+//! Background behind every occurrence of the word under the cursor.
+
+use std::ops::Range;
+
+use paladin_view::{Color, DisplayList, Layout};
+
+/// Paint a background behind the glyphs of each match. Call before rendering the glyphs.
+pub(crate) fn paint(
+    color: Color,
+    matches: &[(usize, Range<usize>)],
+    buffer: &cosmic_text::Buffer,
+    layout: Layout,
+    list: &mut DisplayList,
+) {
+    let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+
+    for run in buffer.layout_runs() {
+        for (_, range) in matches.iter().filter(|(line, _)| *line == run.line_i) {
+            for glyph in run
+                .glyphs
+                .iter()
+                .filter(|glyph| range.contains(&glyph.start))
+            {
+                list.fill_rect(
+                    x + glyph.x,
+                    y + run.line_top,
+                    glyph.w,
+                    run.line_height,
+                    color,
+                );
+            }
+        }
+    }
+}
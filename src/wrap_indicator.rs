@@ -0,0 +1,92 @@
+//! A marker at the start of visual lines that continue a wrapped line, telling wraps apart from newlines.
+
+use paladin_view::{Color, DisplayList, Layout};
+
+/// A visual line continuing the line before it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Continuation {
+    pub line: usize,
+    /// Byte offset into the line where it was wrapped.
+    pub byte: usize,
+    /// Vertical extents of the visual line, relative to the buffer.
+    pub top: f32,
+    pub height: f32,
+}
+
+/// Every visual line after the first of a wrapped line, found where a run's line index repeats.
+pub fn continuations(buffer: &cosmic_text::Buffer) -> Vec<Continuation> {
+    let mut previous = None;
+    let mut continuations = vec![];
+
+    for run in buffer.layout_runs() {
+        if previous == Some(run.line_i) {
+            continuations.push(Continuation {
+                line: run.line_i,
+                byte: run.glyphs.first().map_or(0, |glyph| glyph.start),
+                top: run.line_top,
+                height: run.line_height,
+            });
+        }
+
+        previous = Some(run.line_i);
+    }
+
+    continuations
+}
+
+/// Paint a small `↪` hook in the left margin of each continued line.
+pub(crate) fn paint(buffer: &cosmic_text::Buffer, layout: Layout, list: &mut DisplayList) {
+    let color = Color::rgba(255, 255, 255, 60);
+    let (x, y) = (layout.location.x as f32, layout.location.y as f32);
+
+    for continuation in continuations(buffer) {
+        let size = continuation.height / 3.;
+        let top = y + continuation.top + size;
+        let left = x - size * 1.5;
+
+        // Down, then right into the text.
+        list.line((left, top), (left, top + size), 1., color);
+        list.line((left, top + size), (left + size, top + size), 1., color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmic_text::{fontdb, Attrs, Buffer, Family, FontSystem, Metrics, Shaping};
+
+    use super::continuations;
+
+    #[test]
+    fn markers_at_wrap_boundaries() {
+        let mut font_system =
+            FontSystem::new_with_locale_and_db("en-US".into(), fontdb::Database::new());
+        font_system
+            .db_mut()
+            .load_font_data(include_bytes!("../assets/JetBrainsMono-Regular.ttf").to_vec());
+
+        let mut buffer = Buffer::new(&mut font_system, Metrics::new(10., 20.));
+        let mut buffer = buffer.borrow_with(&mut font_system);
+
+        // Room for about 16 of the monospace cells.
+        buffer.set_size(Some(100.), Some(400.));
+        buffer.set_text(
+            "short\nwrapped across three lines of text\nend",
+            Attrs::new().family(Family::Name("JetBrains Mono")),
+            Shaping::Advanced,
+        );
+        buffer.shape_until_scroll(true);
+
+        let found = continuations(&buffer);
+
+        // "wrapped across " | "three lines of " | "text"
+        assert_eq!(
+            found
+                .iter()
+                .map(|it| (it.line, it.byte))
+                .collect::<Vec<_>>(),
+            vec![(1, 15), (1, 30)]
+        );
+        assert_eq!(found[0].top, 40.);
+        assert_eq!(found[1].top, 60.);
+    }
+}